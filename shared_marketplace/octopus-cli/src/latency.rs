@@ -0,0 +1,178 @@
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use octopus_common::{
+    money::{Price, Quantity},
+    types::Side,
+    wire::{Request, Response},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+/// A connection to the binary order entry listener of the trading platform
+pub struct WireClient {
+    stream: TcpStream,
+    buffer: Vec<u8>,
+    next_request_id: u64,
+}
+
+impl WireClient {
+    pub async fn connect(address: &str) -> Result<Self, String> {
+        let stream = TcpStream::connect(address)
+            .await
+            .map_err(|e| format!("Couldn't connect to {}: {}", address, e))?;
+        stream.set_nodelay(true).map_err(|e| e.to_string())?;
+        Ok(WireClient {
+            stream,
+            buffer: vec![],
+            next_request_id: 1,
+        })
+    }
+
+    /// Binds the connection to `user`, which has to happen before any order is sent
+    pub async fn logon(&mut self, user: &str, password: &str) -> Result<(), String> {
+        let response = self
+            .request(|request_id| Request::Logon {
+                request_id,
+                user: user.to_string(),
+                password: password.to_string(),
+            })
+            .await?;
+        match response {
+            Response::Ack { .. } => Ok(()),
+            rejected => Err(format!("The logon was rejected: {:?}", rejected)),
+        }
+    }
+
+    /// Sends a request and waits for its answer. Fills that arrive in the meantime are skipped.
+    pub async fn request(
+        &mut self,
+        request: impl FnOnce(u64) -> Request,
+    ) -> Result<Response, String> {
+        let request_id = self.next_request_id;
+        self.next_request_id += 1;
+        let mut frame = vec![];
+        request(request_id)
+            .encode(&mut frame)
+            .map_err(|e| e.to_string())?;
+        self.stream
+            .write_all(&frame)
+            .await
+            .map_err(|e| e.to_string())?;
+        loop {
+            match self.next().await? {
+                response @ (Response::Ack { request_id: id, .. }
+                | Response::Reject { request_id: id, .. })
+                    if id == request_id =>
+                {
+                    return Ok(response)
+                }
+                _ => {}
+            }
+        }
+    }
+
+    async fn next(&mut self) -> Result<Response, String> {
+        loop {
+            if let Some((response, len)) =
+                Response::decode(&self.buffer).map_err(|e| e.to_string())?
+            {
+                self.buffer.drain(..len);
+                return Ok(response);
+            }
+            let mut chunk = [0u8; 4096];
+            let read = self
+                .stream
+                .read(&mut chunk)
+                .await
+                .map_err(|e| e.to_string())?;
+            if read == 0 {
+                return Err("The server closed the connection".to_string());
+            }
+            self.buffer.extend_from_slice(&chunk[..read]);
+        }
+    }
+}
+
+/// Round trip times of a latency measurement
+#[derive(Debug)]
+pub struct LatencyReport {
+    samples: Vec<Duration>,
+}
+
+impl LatencyReport {
+    /// The round trip time that `percent` of the samples don't exceed
+    pub fn percentile(&self, percent: usize) -> Duration {
+        let index = (self.samples.len() * percent).div_ceil(100).max(1) - 1;
+        self.samples[index.min(self.samples.len() - 1)]
+    }
+}
+
+impl fmt::Display for LatencyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total: Duration = self.samples.iter().sum();
+        writeln!(f, "Round trips: {}", self.samples.len())?;
+        writeln!(f, "Mean:        {:?}", total / self.samples.len() as u32)?;
+        writeln!(f, "Min:         {:?}", self.samples[0])?;
+        writeln!(f, "p50:         {:?}", self.percentile(50))?;
+        writeln!(f, "p99:         {:?}", self.percentile(99))?;
+        write!(f, "Max:         {:?}", self.samples[self.samples.len() - 1])
+    }
+}
+
+/// Logs on as `user` and places and cancels a buy order of one unit at `price` for `signer` `count` times and measures
+/// the time until each request is acknowledged. Pick a price below the best ask, so the orders rest instead of trading.
+pub async fn measure(
+    address: &str,
+    user: &str,
+    password: &str,
+    signer: &str,
+    price: Price,
+    count: usize,
+) -> Result<LatencyReport, String> {
+    let mut client = WireClient::connect(address).await?;
+    client.logon(user, password).await?;
+    let mut samples = Vec::with_capacity(count * 2);
+    for _ in 0..count {
+        let start = Instant::now();
+        let ordinal = match client
+            .request(|request_id| Request::NewOrder {
+                request_id,
+                side: Side::Buy,
+                price,
+                quantity: Quantity(1),
+                signer: signer.to_string(),
+            })
+            .await?
+        {
+            Response::Ack { ordinal, .. } => ordinal,
+            rejected => return Err(format!("The order was rejected: {:?}", rejected)),
+        };
+        samples.push(start.elapsed());
+
+        let start = Instant::now();
+        let cancelled = client
+            .request(|request_id| Request::Cancel {
+                request_id,
+                ordinal,
+                signer: signer.to_string(),
+            })
+            .await?;
+        if let Response::Reject { message, .. } = cancelled {
+            return Err(format!(
+                "The order was filled or couldn't be cancelled: {}",
+                message
+            ));
+        }
+        samples.push(start.elapsed());
+    }
+    if samples.is_empty() {
+        return Err("At least one round trip is needed".to_string());
+    }
+    samples.sort();
+    Ok(LatencyReport { samples })
+}
@@ -1,9 +1,14 @@
+mod latency;
+
 use octopus_common::{
-    tx::Tx,
-    types::{AccountUpdateRequest, ErrorMessage, Order, PartialOrder, Receipt, SendRequest, Side},
+    money::{Notional, Price, Quantity},
+    types::{
+        AccountOpenRequest, AccountRequest, AccountUpdateRequest, AccountView, CancelRequest,
+        ErrorMessage, Order, SendRequest, Side,
+    },
 };
-use reqwest::Url;
-use std::{env, io, num::ParseIntError};
+use reqwest::{Certificate, Identity, Url};
+use std::{env, fs, io, num::ParseIntError};
 
 fn read_order_parameters() -> Result<Order, String> {
     let account = read_from_stdin("Account:");
@@ -15,10 +20,9 @@ fn read_order_parameters() -> Result<Order, String> {
 
     let amount = read_from_stdin("Amount:")
         .parse()
+        .map(Quantity)
         .map_err(|e: ParseIntError| e.to_string())?;
-    let price = read_from_stdin("Price:")
-        .parse()
-        .map_err(|e: ParseIntError| e.to_string())?;
+    let price: Price = read_from_stdin("Price:").parse()?;
     Ok(Order {
         price,
         amount,
@@ -27,6 +31,98 @@ fn read_order_parameters() -> Result<Order, String> {
     })
 }
 
+/// The body of a successful response, or the error the service answered with
+async fn read_response(response: reqwest::Response) -> Result<serde_json::Value, String> {
+    if response.status().is_success() {
+        response.json().await.map_err(|e| e.to_string())
+    } else {
+        let status = response.status();
+        match response.json::<ErrorMessage>().await {
+            Ok(error) => Err(error.to_string()),
+            Err(_) => Err(status.to_string()),
+        }
+    }
+}
+
+/// The options following the service path
+#[derive(Default)]
+struct Options<'a> {
+    ca_cert: Option<&'a String>,
+    client_cert: Option<&'a String>,
+    client_key: Option<&'a String>,
+    /// The service's admin interface, where operators e.g. close accounts
+    admin: Option<&'a String>,
+    /// Name of the operator using the admin interface
+    operator: Option<&'a String>,
+}
+
+fn parse_options(options: &[String]) -> Result<Options<'_>, String> {
+    let mut parsed = Options::default();
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        let value = options
+            .next()
+            .ok_or_else(|| format!("{} requires a value", option));
+        match option.as_str() {
+            "--ca-cert" => parsed.ca_cert = Some(value?),
+            "--client-cert" => parsed.client_cert = Some(value?),
+            "--client-key" => parsed.client_key = Some(value?),
+            "--admin" => parsed.admin = Some(value?),
+            "--operator" => parsed.operator = Some(value?),
+            _ => return Err(format!("Unknown option {}", option)),
+        }
+    }
+    Ok(parsed)
+}
+
+/// A client that also trusts the CAs of `--ca-cert`, e.g. of a self-signed service, and presents the certificate of
+/// `--client-cert` and `--client-key` to services requiring one
+fn build_client(options: &Options) -> Result<reqwest::Client, String> {
+    let Options {
+        ca_cert,
+        client_cert,
+        client_key,
+        ..
+    } = *options;
+    let read = |path: &String| fs::read(path).map_err(|e| format!("Couldn't read {}: {}", path, e));
+
+    // The service terminates TLS with rustls as well
+    let mut builder = reqwest::Client::builder().use_rustls_tls();
+    if let Some(path) = ca_cert {
+        let certificate = Certificate::from_pem(&read(path)?)
+            .map_err(|e| format!("{} isn't a PEM CA bundle: {}", path, e))?;
+        builder = builder.add_root_certificate(certificate);
+    }
+    match (client_cert, client_key) {
+        (Some(cert), Some(key)) => {
+            let mut pem = read(cert)?;
+            pem.extend(read(key)?);
+            let identity = Identity::from_pem(&pem).map_err(|e| {
+                format!(
+                    "{} and {} aren't a PEM certificate and key: {}",
+                    cert, key, e
+                )
+            })?;
+            builder = builder.identity(identity);
+        }
+        (None, None) => {}
+        _ => return Err("--client-cert and --client-key have to be given together".to_string()),
+    }
+    builder.build().map_err(|e| e.to_string())
+}
+
+/// The route of an account's view. The signer is percent-encoded as a single path segment, so signers containing e.g.
+/// `/` or `?` don't end up on another route.
+fn account_url(service_path: &str, signer: &str) -> Result<Url, String> {
+    let mut url = Url::parse(service_path).map_err(|e| e.to_string())?;
+    url.path_segments_mut()
+        .map_err(|_| format!("{} can't have a path", service_path))?
+        .pop_if_empty()
+        .extend(["v1", "account"])
+        .push(signer);
+    Ok(url)
+}
+
 fn read_from_stdin(label: &str) -> String {
     let mut buffer = String::new();
     println!("{}", label);
@@ -38,11 +134,11 @@ fn read_from_stdin(label: &str) -> String {
 #[tokio::main]
 async fn main() {
     let args: Vec<String> = env::args().collect();
-    let client = reqwest::Client::new();
     let service_path = args.get(1);
 
     if service_path.is_none() {
         eprintln!("Please specify the service path to connect to");
+        eprintln!("Usage: octopus-cli <service path> [--ca-cert <file>] [--client-cert <file> --client-key <file>] [--admin <admin path> --operator <name>]");
         return;
     }
 
@@ -51,28 +147,108 @@ async fn main() {
         eprintln!("Please specify the serice path to connect to");
         return;
     }
+    let options = match parse_options(&args[2..]) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
+    let client = match build_client(&options) {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
 
     loop {
         let input = read_from_stdin(
-            "Choose operation [deposit, withdraw, send, history, order, orderbook, quit], confirm with return:",
+            "Choose operation [open, close, balance, deposit, withdraw, send, history, order, cancel, orderbook, latency, quit], confirm with return:",
         );
         match input.as_str() {
+            "open" => {
+                let account = read_from_stdin("Account:");
+                let owner = read_from_stdin("Owner:");
+                let response = client
+                    .post(format!("{}/v1/account/open", service_path))
+                    .json(&AccountOpenRequest {
+                        signer: account,
+                        owner,
+                    })
+                    .send()
+                    .await
+                    .expect("The open request should be directed to the trading platform service");
+                let response = read_response(response).await;
+                match response {
+                    Ok(metadata) => {
+                        println!("{:#?}", metadata)
+                    }
+                    Err(inner) => eprintln!("Error occured: {}", inner),
+                }
+            }
+            "close" => {
+                // Only operators close accounts, on the admin interface
+                let (Some(admin), Some(operator)) = (options.admin, options.operator) else {
+                    eprintln!("Closing accounts requires --admin and --operator");
+                    continue;
+                };
+                let account = read_from_stdin("Account:");
+                let password = read_from_stdin("Operator password:");
+                let response = client
+                    .post(format!("{}/v1/account/close", admin))
+                    .basic_auth(operator, Some(password))
+                    .json(&AccountRequest { signer: account })
+                    .send()
+                    .await
+                    .expect("The close request should be directed to the trading platform service");
+                let response = read_response(response).await;
+                match response {
+                    Ok(metadata) => {
+                        println!("{:#?}", metadata)
+                    }
+                    Err(inner) => eprintln!("Error occured: {}", inner),
+                }
+            }
+            "balance" => {
+                let account = read_from_stdin("Account:");
+                let url = match account_url(service_path, &account) {
+                    Ok(url) => url,
+                    Err(inner) => {
+                        eprintln!("Error occured: {}", inner);
+                        continue;
+                    }
+                };
+                let response = client.get(url).send().await.expect(
+                    "The balance request should be directed to the trading platform service",
+                );
+                let response = read_response(response).await.and_then(|body| {
+                    serde_json::from_value::<AccountView>(body).map_err(|e| e.to_string())
+                });
+                match response {
+                    Ok(balance) => {
+                        println!("{:#?}", balance)
+                    }
+                    Err(inner) => eprintln!("Error occured: {}", inner),
+                }
+            }
             "deposit" => {
                 let account = read_from_stdin("Account:");
 
-                let raw_amount = read_from_stdin("Amount:").parse();
+                let raw_amount = read_from_stdin("Amount:")
+                    .parse::<Notional>()
+                    .map(u64::from);
                 if let Ok(amount) = raw_amount {
                     let deposit = client
-                        .post(format!("{}/account/deposit", service_path))
+                        .post(format!("{}/v1/account/deposit", service_path))
                         .json(&AccountUpdateRequest {
                             signer: account,
                             amount,
                         })
                         .send()
                         .await
-                        .expect("The deposit request should be directed to the trading platform service")
-                        .json::<serde_json::Value>()
-                        .await;
+                        .expect("The deposit request should be directed to the trading platform service");
+                    let deposit = read_response(deposit).await;
                     match deposit {
                         Ok(deposit) => {
                             // if let Tx::Deposit {
@@ -92,19 +268,20 @@ async fn main() {
             }
             "withdraw" => {
                 let account = read_from_stdin("Account:");
-                let raw_amount = read_from_stdin("Amount:").parse();
+                let raw_amount = read_from_stdin("Amount:")
+                    .parse::<Notional>()
+                    .map(u64::from);
                 if let Ok(amount) = raw_amount {
                     let withdraw = client
-                        .post(format!("{}/account/withdraw", service_path))
+                        .post(format!("{}/v1/account/withdraw", service_path))
                         .json(&AccountUpdateRequest {
                             signer:account,
                             amount,
                         })
                         .send()
                         .await
-                        .expect("The withdraw request should be directed to the trading platform service")
-                        .json::<serde_json::Value>()
-                        .await;
+                        .expect("The withdraw request should be directed to the trading platform service");
+                    let withdraw = read_response(withdraw).await;
                     match withdraw {
                         Ok(withdraw) => {
                             // if let Tx::Withdraw {
@@ -125,10 +302,12 @@ async fn main() {
             "send" => {
                 let sender = read_from_stdin("Sender Account:");
                 let recipient = read_from_stdin("Recipient Account:");
-                let raw_amount = read_from_stdin("Amount:").parse();
+                let raw_amount = read_from_stdin("Amount:")
+                    .parse::<Notional>()
+                    .map(u64::from);
                 if let Ok(amount) = raw_amount {
                     let response = client
-                        .post(format!("{}/account/send", service_path))
+                        .post(format!("{}/v1/account/send", service_path))
                         .json(&SendRequest {
                             sender,
                             recipient,
@@ -136,9 +315,8 @@ async fn main() {
                         })
                         .send()
                         .await
-                        .expect("The withdraw request should be directed to the trading platform service")
-                        .json::<serde_json::Value>()
-                        .await;
+                        .expect("The withdraw request should be directed to the trading platform service");
+                    let response = read_response(response).await;
 
                     match response {
                         Ok(send_response) => {
@@ -169,13 +347,12 @@ async fn main() {
                 match read_order_parameters() {
                     Ok(order) => {
                         let response = client
-                        .post(format!("{}/order", service_path))
+                        .post(format!("{}/v1/order", service_path))
                         .json(&order)
                         .send()
                         .await
-                        .expect("The order request should be directed to the trading platform service")
-                        .json::<serde_json::Value>()
-                        .await;
+                        .expect("The order request should be directed to the trading platform service");
+                        let response = read_response(response).await;
 
                         match response {
                             Ok(receipt) => {
@@ -191,16 +368,40 @@ async fn main() {
                 }
             }
 
+            "cancel" => {
+                let account = read_from_stdin("Account:");
+                match read_from_stdin("Ordinal:").parse() {
+                    Ok(ordinal) => {
+                        let response = client
+                            .post(format!("{}/v1/order/cancel", service_path))
+                            .json(&CancelRequest {
+                                signer: account,
+                                ordinal,
+                            })
+                            .send()
+                            .await
+                            .expect("The cancel request should be directed to the trading platform service");
+                        let response = read_response(response).await;
+                        match response {
+                            Ok(order) => {
+                                println!("{:#?}", order)
+                            }
+                            Err(inner) => eprintln!("Error occured: {}", inner),
+                        }
+                    }
+                    Err(msg) => eprintln!("Invalid ordinal: '{:?}'", msg),
+                }
+            }
+
             "history" => {
                 let response = client
-                    .get(format!("{}/order/history", service_path))
+                    .get(format!("{}/v1/order/history", service_path))
                     .send()
                     .await
                     .expect(
                         "The history request should be directed to the trading platform service",
-                    )
-                    .json::<serde_json::Value>()
-                    .await;
+                    );
+                let response = read_response(response).await;
                 match response {
                     Ok(history) => {
                         // println!("Current orderbook : {:#?}", orderbook);
@@ -212,14 +413,13 @@ async fn main() {
             }
             "orderbook" => {
                 let response = client
-                    .get(format!("{}/orderbook", service_path))
+                    .get(format!("{}/v1/orderbook", service_path))
                     .send()
                     .await
                     .expect(
                         "The orderbook request should be directed to the trading platform service",
-                    )
-                    .json::<serde_json::Value>()
-                    .await;
+                    );
+                let response = read_response(response).await;
                 match response {
                     Ok(orderbook) => {
                         // println!("Current orderbook : {:#?}", orderbook);
@@ -229,6 +429,27 @@ async fn main() {
                     Err(inner) => eprintln!("Error occured: {}", inner),
                 }
             }
+            "latency" => {
+                let address = read_from_stdin("Binary order entry address (host:port):");
+                let user = read_from_stdin("User:");
+                let password = read_from_stdin("Password:");
+                let account = read_from_stdin("Account:");
+                let price = read_from_stdin("Price (below the best ask):").parse::<Price>();
+                let count = read_from_stdin("Round trips:").parse::<usize>();
+                match (price, count) {
+                    (Ok(price), Ok(count)) => {
+                        match latency::measure(&address, &user, &password, &account, price, count)
+                            .await
+                        {
+                            Ok(report) => println!("{}", report),
+                            Err(inner) => eprintln!("Error occured: {}", inner),
+                        }
+                    }
+                    (price, count) => {
+                        eprintln!("Invalid price or count: '{:?}', '{:?}'", price, count)
+                    }
+                }
+            }
             // "txlog" => {
             //     println!("The TX log: {:#?}", ledger.transactions);
             // }
@@ -1,3 +1,13 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    money::{Notional, Price},
+    types::MarketState,
+};
+
 /// An application-specific error type
 #[derive(Debug, PartialEq, Eq)]
 pub enum ApplicationError {
@@ -9,4 +19,280 @@ pub enum ApplicationError {
 
     /// Too much currency in the account (overflow)
     AccountOverFunded(String, u64),
+
+    /// An account with this signer was opened before
+    AccountAlreadyExists(String),
+
+    /// The account is frozen and can't withdraw or trade
+    AccountFrozen(String),
+
+    /// The account was closed
+    AccountClosed(String),
+
+    /// Only accounts with a zero balance can be closed
+    AccountNotEmpty(String, u64),
+
+    /// Price times amount doesn't fit into the currency type
+    NotionalOverflow(u64, u64),
+
+    /// The price isn't a positive multiple of the market's tick size (price, tick size)
+    InvalidPrice(u64, u64),
+
+    /// The quantity isn't a multiple of the market's lot size (quantity, lot size)
+    InvalidLotSize(u64, u64),
+
+    /// The quantity is outside of the market's limits (quantity, min, max)
+    QuantityOutOfRange(u64, u64, u64),
+
+    /// The order's value is below the market's minimum (notional, min)
+    NotionalTooSmall(u64, u64),
+
+    /// The order quantity exceeds the signer's limit (quantity, max)
+    OrderSizeLimitExceeded(u64, u64),
+
+    /// The order value exceeds the signer's limit (notional, max)
+    NotionalLimitExceeded(u64, u64),
+
+    /// The signer has too many orders in the book (open orders, max)
+    OpenOrderLimitExceeded(u64, u64),
+
+    /// The price is too far from the reference price (price, lowest, highest)
+    PriceOutsideCollar(u64, u64, u64),
+
+    /// The signer sent too many orders within the rate window (signer, max)
+    RateLimitExceeded(String, u64),
+
+    /// Trading in the market is halted
+    MarketHalted,
+
+    /// The market is closed
+    MarketClosed,
+
+    /// The operation requires a running call auction
+    NoAuction,
+
+    /// The market can't change from its current state to the requested one (current, requested)
+    InvalidMarketTransition(MarketState, MarketState),
+
+    /// The matching engine doesn't accept commands anymore
+    EngineUnavailable,
+
+    /// The signer has no resting order with this ordinal (ordinal)
+    OrderNotFound(u64),
+
+    /// The sum of all ledger balances isn't zero
+    LedgerImbalance(i128),
+}
+
+/// Stable, machine-readable reason of a failed request. New codes may be added, existing ones don't change.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, ToSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    AccountNotFound,
+    AccountUnderFunded,
+    AccountOverFunded,
+    AccountAlreadyExists,
+    AccountFrozen,
+    AccountClosed,
+    AccountNotEmpty,
+    NotionalOverflow,
+    InvalidPrice,
+    InvalidLotSize,
+    QuantityOutOfRange,
+    NotionalTooSmall,
+    OrderSizeLimitExceeded,
+    NotionalLimitExceeded,
+    OpenOrderLimitExceeded,
+    PriceOutsideCollar,
+    RateLimitExceeded,
+    MarketHalted,
+    MarketClosed,
+    NoAuction,
+    InvalidMarketTransition,
+    EngineUnavailable,
+    OrderNotFound,
+    LedgerImbalance,
+    /// The request body isn't valid JSON or doesn't have the expected fields
+    InvalidBody,
+    /// The query string doesn't have the expected parameters
+    InvalidQuery,
+    /// There is no route for the path
+    NotFound,
+    /// The route doesn't support the method
+    MethodNotAllowed,
+    /// The request doesn't carry the credentials of an operator
+    Unauthorized,
+    /// An unexpected failure of the service
+    Internal,
+}
+
+/// Formats the code as it's serialized, e.g. `ACCOUNT_NOT_FOUND`
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, c) in format!("{:?}", self).chars().enumerate() {
+            if i > 0 && c.is_ascii_uppercase() {
+                write!(f, "_")?;
+            }
+            write!(f, "{}", c.to_ascii_uppercase())?;
+        }
+        Ok(())
+    }
+}
+
+impl ApplicationError {
+    /// The [`ErrorCode`] of this error
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            ApplicationError::AccountNotFound(_) => ErrorCode::AccountNotFound,
+            ApplicationError::AccountUnderFunded(..) => ErrorCode::AccountUnderFunded,
+            ApplicationError::AccountOverFunded(..) => ErrorCode::AccountOverFunded,
+            ApplicationError::AccountAlreadyExists(_) => ErrorCode::AccountAlreadyExists,
+            ApplicationError::AccountFrozen(_) => ErrorCode::AccountFrozen,
+            ApplicationError::AccountClosed(_) => ErrorCode::AccountClosed,
+            ApplicationError::AccountNotEmpty(..) => ErrorCode::AccountNotEmpty,
+            ApplicationError::NotionalOverflow(..) => ErrorCode::NotionalOverflow,
+            ApplicationError::InvalidPrice(..) => ErrorCode::InvalidPrice,
+            ApplicationError::InvalidLotSize(..) => ErrorCode::InvalidLotSize,
+            ApplicationError::QuantityOutOfRange(..) => ErrorCode::QuantityOutOfRange,
+            ApplicationError::NotionalTooSmall(..) => ErrorCode::NotionalTooSmall,
+            ApplicationError::OrderSizeLimitExceeded(..) => ErrorCode::OrderSizeLimitExceeded,
+            ApplicationError::NotionalLimitExceeded(..) => ErrorCode::NotionalLimitExceeded,
+            ApplicationError::OpenOrderLimitExceeded(..) => ErrorCode::OpenOrderLimitExceeded,
+            ApplicationError::PriceOutsideCollar(..) => ErrorCode::PriceOutsideCollar,
+            ApplicationError::RateLimitExceeded(..) => ErrorCode::RateLimitExceeded,
+            ApplicationError::MarketHalted => ErrorCode::MarketHalted,
+            ApplicationError::MarketClosed => ErrorCode::MarketClosed,
+            ApplicationError::NoAuction => ErrorCode::NoAuction,
+            ApplicationError::InvalidMarketTransition(..) => ErrorCode::InvalidMarketTransition,
+            ApplicationError::EngineUnavailable => ErrorCode::EngineUnavailable,
+            ApplicationError::OrderNotFound(_) => ErrorCode::OrderNotFound,
+            ApplicationError::LedgerImbalance(_) => ErrorCode::LedgerImbalance,
+        }
+    }
+}
+
+impl fmt::Display for ApplicationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApplicationError::AccountNotFound(signer) => {
+                write!(f, "Cannot find account {}", signer)
+            }
+            ApplicationError::AccountUnderFunded(signer, amount) => {
+                write!(f, "Account {} doesn't have {} available", signer, amount)
+            }
+            ApplicationError::AccountOverFunded(signer, amount) => write!(
+                f,
+                "Adding {} to account {} would exceed the maximum balance",
+                amount, signer
+            ),
+            ApplicationError::AccountAlreadyExists(signer) => {
+                write!(f, "Account {} already exists", signer)
+            }
+            ApplicationError::AccountFrozen(signer) => write!(f, "Account {} is frozen", signer),
+            ApplicationError::AccountClosed(signer) => write!(f, "Account {} is closed", signer),
+            ApplicationError::AccountNotEmpty(signer, balance) => write!(
+                f,
+                "Cannot close account {} with a balance of {}",
+                signer, balance
+            ),
+            ApplicationError::NotionalOverflow(price, amount) => write!(
+                f,
+                "Notional of {} units at a price of {} is too large",
+                amount,
+                Price(*price)
+            ),
+            ApplicationError::InvalidPrice(price, tick) => write!(
+                f,
+                "Price {} is not a positive multiple of {}",
+                Price(*price),
+                Price(*tick)
+            ),
+            ApplicationError::InvalidLotSize(amount, lot_size) => write!(
+                f,
+                "Amount {} is not a multiple of the lot size {}",
+                amount, lot_size
+            ),
+            ApplicationError::QuantityOutOfRange(amount, min, max) => {
+                write!(f, "Amount {} is not between {} and {}", amount, min, max)
+            }
+            ApplicationError::NotionalTooSmall(notional, min) => write!(
+                f,
+                "Order value {} is below the minimum of {}",
+                Notional(*notional),
+                Notional(*min)
+            ),
+            ApplicationError::OrderSizeLimitExceeded(amount, max) => {
+                write!(f, "Order size {} exceeds the limit of {}", amount, max)
+            }
+            ApplicationError::NotionalLimitExceeded(notional, max) => write!(
+                f,
+                "Order value {} exceeds the limit of {}",
+                Notional(*notional),
+                Notional(*max)
+            ),
+            ApplicationError::OpenOrderLimitExceeded(open, max) => {
+                write!(f, "{} open orders reached the limit of {}", open, max)
+            }
+            ApplicationError::PriceOutsideCollar(price, low, high) => write!(
+                f,
+                "Price {} is outside of the allowed range {} to {}",
+                Price(*price),
+                Price(*low),
+                Price(*high)
+            ),
+            ApplicationError::RateLimitExceeded(signer, max) => write!(
+                f,
+                "Account {} exceeded the limit of {} orders per window",
+                signer, max
+            ),
+            ApplicationError::MarketHalted => write!(f, "Trading in the market is halted"),
+            ApplicationError::MarketClosed => write!(f, "The market is closed"),
+            ApplicationError::NoAuction => write!(f, "There is no auction running"),
+            ApplicationError::InvalidMarketTransition(current, requested) => write!(
+                f,
+                "The market can't go from {:?} to {:?}",
+                current, requested
+            ),
+            ApplicationError::EngineUnavailable => {
+                write!(f, "The matching engine is unavailable")
+            }
+            ApplicationError::OrderNotFound(ordinal) => {
+                write!(f, "No resting order {} for this signer", ordinal)
+            }
+            ApplicationError::LedgerImbalance(sum) => {
+                write!(f, "Ledger balances don't sum up to zero but {}", sum)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_code_is_screaming_snake_case() {
+        let error = ApplicationError::AccountUnderFunded("ALICE".to_string(), 5);
+        assert_eq!(
+            serde_json::to_string(&error.code()).unwrap(),
+            "\"ACCOUNT_UNDER_FUNDED\""
+        );
+        for code in [
+            ErrorCode::AccountNotFound,
+            ErrorCode::InvalidBody,
+            ErrorCode::Internal,
+        ] {
+            let serialized = serde_json::to_string(&code).unwrap();
+            assert_eq!(serialized, format!("\"{}\"", code));
+        }
+    }
+
+    #[test]
+    fn test_error_display_formats_prices() {
+        let error = ApplicationError::InvalidPrice(1_005, 10);
+        assert_eq!(
+            error.to_string(),
+            "Price 10.05 is not a positive multiple of 0.10"
+        );
+    }
 }
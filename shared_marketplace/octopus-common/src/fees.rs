@@ -0,0 +1,113 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::money::Notional;
+
+/// Basis points in 100%
+pub const BPS_PER_UNIT: u32 = 10_000;
+
+/// The window for which traded volume counts towards a [`FeeTier`], in milliseconds
+pub const VOLUME_WINDOW_MILLIS: u64 = 30 * 24 * 60 * 60 * 1_000;
+
+/// Reduced (or increased) rates for signers with a trailing 30-day volume of at least `min_volume`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+pub struct FeeTier {
+    /// Traded notional over the last 30 days required for this tier
+    pub min_volume: Notional,
+    /// Fee for resting orders in basis points
+    pub maker_bps: u32,
+    /// Fee for incoming orders in basis points
+    pub taker_bps: u32,
+}
+
+/// Fees charged on every fill, paid by both sides into the platform's fee account
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default, ToSchema)]
+pub struct FeeSchedule {
+    /// Fee for resting orders in basis points
+    pub maker_bps: u32,
+    /// Fee for incoming orders in basis points
+    pub taker_bps: u32,
+    /// Volume-based tiers overriding the base rates
+    #[serde(default)]
+    pub tiers: Vec<FeeTier>,
+}
+
+impl FeeSchedule {
+    /// Checks whether all rates are between 0 and 100%.
+    ///
+    /// # Errors
+    /// A description of the first invalid rate
+    pub fn check(&self) -> Result<(), String> {
+        let rates = [(self.maker_bps, self.taker_bps)]
+            .into_iter()
+            .chain(self.tiers.iter().map(|t| (t.maker_bps, t.taker_bps)));
+        for (maker_bps, taker_bps) in rates {
+            if maker_bps > BPS_PER_UNIT || taker_bps > BPS_PER_UNIT {
+                return Err(format!(
+                    "fee rates must be at most {} bps, got {}/{}",
+                    BPS_PER_UNIT, maker_bps, taker_bps
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// The (maker, taker) rates in basis points for a signer with the given trailing volume
+    pub fn rates_for(&self, volume: Notional) -> (u32, u32) {
+        self.tiers
+            .iter()
+            .filter(|t| t.min_volume <= volume)
+            .max_by_key(|t| t.min_volume)
+            .map_or((self.maker_bps, self.taker_bps), |t| {
+                (t.maker_bps, t.taker_bps)
+            })
+    }
+
+    /// The highest maker rate of the base rates and all tiers, i.e. the most a resting order can be charged
+    pub fn max_maker_bps(&self) -> u32 {
+        self.tiers
+            .iter()
+            .map(|t| t.maker_bps)
+            .fold(self.maker_bps, u32::max)
+    }
+
+    /// Computes the fee for a `notional` at `bps`, rounded down to the smallest currency unit
+    pub fn fee(notional: Notional, bps: u32) -> Notional {
+        // Only rates above 100% can exceed the notional, those saturate
+        let fee = notional.0 as u128 * bps as u128 / BPS_PER_UNIT as u128;
+        Notional(u64::try_from(fee).unwrap_or(u64::MAX))
+    }
+}
+
+/// The fees charged for a single fill, reported in a [`crate::types::Receipt`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, ToSchema)]
+pub struct FillFee {
+    /// Ordinal of the resting order that was matched
+    pub ordinal: u64,
+    /// Fee paid by the signer of the resting order
+    pub maker_fee: Notional,
+    /// Fee paid by the signer of the incoming order
+    pub taker_fee: Notional,
+}
+
+#[cfg(test)]
+mod tests {
+    // reduce the warnings for naming tests
+    #![allow(non_snake_case)]
+
+    use super::*;
+
+    #[test]
+    fn test_FeeSchedule_fee_rounds_down_and_saturates() {
+        assert_eq!(FeeSchedule::fee(Notional(999), 10), Notional(0));
+        assert_eq!(FeeSchedule::fee(Notional(1_000), 10), Notional(1));
+        assert_eq!(
+            FeeSchedule::fee(Notional(u64::MAX), BPS_PER_UNIT),
+            Notional(u64::MAX)
+        );
+        assert_eq!(
+            FeeSchedule::fee(Notional(u64::MAX), u32::MAX),
+            Notional(u64::MAX)
+        );
+    }
+}
@@ -0,0 +1,98 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    errors::ApplicationError,
+    money::{Notional, Price, Quantity},
+    types::Order,
+};
+
+/// Trading rules of a market. Orders that don't follow them are rejected before matching.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+pub struct InstrumentSpec {
+    /// Name of the market
+    pub symbol: String,
+    /// Prices have to be a multiple of this, e.g. `"0.05"`. Prices are in the smallest currency unit, so the finest tick
+    /// is one of those (see [`CURRENCY_DECIMALS`](crate::money::CURRENCY_DECIMALS)).
+    pub tick_size: Price,
+    /// Quantities have to be a multiple of this
+    pub lot_size: Quantity,
+    /// Smallest quantity per order
+    pub min_quantity: Quantity,
+    /// Largest quantity per order
+    pub max_quantity: Quantity,
+    /// Smallest value per order
+    pub min_notional: Notional,
+}
+
+impl Default for InstrumentSpec {
+    fn default() -> Self {
+        InstrumentSpec {
+            symbol: "OCTO".to_string(),
+            tick_size: Price(1),
+            lot_size: Quantity(1),
+            min_quantity: Quantity(1),
+            max_quantity: Quantity(u64::MAX),
+            min_notional: Notional::ZERO,
+        }
+    }
+}
+
+impl InstrumentSpec {
+    /// Checks whether the spec itself is consistent.
+    ///
+    /// # Errors
+    /// A description of the first inconsistency
+    pub fn check(&self) -> Result<(), String> {
+        if self.symbol.is_empty() {
+            return Err("symbol must not be empty".to_string());
+        }
+        if self.tick_size.0 == 0 {
+            return Err(format!("{}: tick_size must be positive", self.symbol));
+        }
+        if self.lot_size.is_zero() {
+            return Err(format!("{}: lot_size must be positive", self.symbol));
+        }
+        if self.min_quantity.is_zero() || self.min_quantity > self.max_quantity {
+            return Err(format!(
+                "{}: min_quantity {} must be positive and at most max_quantity {}",
+                self.symbol, self.min_quantity, self.max_quantity
+            ));
+        }
+        Ok(())
+    }
+
+    /// Validates an [`Order`] against the price, quantity and notional rules of this market.
+    ///
+    /// # Errors
+    /// - The price isn't a positive multiple of the tick size
+    /// - The quantity isn't a multiple of the lot size or outside the allowed range
+    /// - The notional overflows or is below the minimum
+    pub fn validate(&self, order: &Order) -> Result<(), ApplicationError> {
+        let tick = self.tick_size;
+        if order.price.0 == 0 || !order.price.0.is_multiple_of(tick.0) {
+            return Err(ApplicationError::InvalidPrice(order.price.0, tick.0));
+        }
+        if !order.amount.0.is_multiple_of(self.lot_size.0) {
+            return Err(ApplicationError::InvalidLotSize(
+                order.amount.0,
+                self.lot_size.0,
+            ));
+        }
+        if order.amount < self.min_quantity || order.amount > self.max_quantity {
+            return Err(ApplicationError::QuantityOutOfRange(
+                order.amount.0,
+                self.min_quantity.0,
+                self.max_quantity.0,
+            ));
+        }
+        let notional = order.price.notional(order.amount)?;
+        if notional < self.min_notional {
+            return Err(ApplicationError::NotionalTooSmall(
+                notional.0,
+                self.min_notional.0,
+            ));
+        }
+        Ok(())
+    }
+}
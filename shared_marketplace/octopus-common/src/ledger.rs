@@ -0,0 +1,69 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Accounts owned by the platform itself. Their balances may become negative.
+#[derive(
+    Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, ToSchema,
+)]
+pub enum SystemAccount {
+    /// Counterpart for cash entering or leaving the platform
+    External,
+    /// Collected trading fees
+    Fees,
+    /// The exchange's own funds
+    Exchange,
+}
+
+/// Any account that can appear on either side of a [`Posting`]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, ToSchema)]
+pub enum LedgerAccount {
+    /// A customer account identified by its signer
+    Customer(String),
+    /// One of the platform's [`SystemAccount`]s
+    System(SystemAccount),
+}
+
+/// A journal entry that moves `amount` from the `debit` to the `credit` account
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct Posting {
+    /// Sequence number of the posting
+    pub id: u64,
+    /// The account the amount is taken from
+    pub debit: LedgerAccount,
+    /// The account the amount is given to
+    pub credit: LedgerAccount,
+    /// The amount moved
+    pub amount: u64,
+}
+
+/// Totals of a single account in a [`TrialBalance`]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, ToSchema)]
+pub struct TrialBalanceLine {
+    pub account: LedgerAccount,
+    /// Sum of all postings debiting this account
+    #[serde(with = "crate::money::decimal")]
+    #[schema(value_type = String, example = "12.34")]
+    pub debits: u128,
+    /// Sum of all postings crediting this account
+    #[serde(with = "crate::money::decimal")]
+    #[schema(value_type = String, example = "12.34")]
+    pub credits: u128,
+    /// Credits minus debits
+    #[serde(with = "crate::money::decimal")]
+    #[schema(value_type = String, example = "-12.34")]
+    pub balance: i128,
+}
+
+/// A summary of the journal with one line per account. The ledger is consistent if the total debits equal the total credits.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, ToSchema)]
+pub struct TrialBalance {
+    pub lines: Vec<TrialBalanceLine>,
+    #[serde(with = "crate::money::decimal")]
+    #[schema(value_type = String, example = "12.34")]
+    pub total_debits: u128,
+    #[serde(with = "crate::money::decimal")]
+    #[schema(value_type = String, example = "12.34")]
+    pub total_credits: u128,
+    /// Whether debits and credits (and therefore all balances) sum up to zero
+    pub balanced: bool,
+}
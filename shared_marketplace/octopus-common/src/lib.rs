@@ -1,3 +1,9 @@
 pub mod errors;
+pub mod fees;
+pub mod instrument;
+pub mod ledger;
+pub mod money;
+pub mod risk;
 pub mod tx;
 pub mod types;
+pub mod wire;
@@ -0,0 +1,376 @@
+use std::{collections::BTreeMap, fmt, iter::Sum, str::FromStr};
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use utoipa::ToSchema;
+
+use crate::errors::ApplicationError;
+
+/// Number of decimals of the smallest currency unit, e.g. 2 for cents. All amounts of money, prices included, are kept
+/// in that unit and serialized as decimal strings in the major unit, so no price can be finer than one cent.
+pub const CURRENCY_DECIMALS: u32 = 2;
+
+/// Price per unit in the smallest currency unit. Serialized as a decimal string in the major unit, e.g. `Price(1234)` is `"12.34"`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, ToSchema)]
+#[schema(value_type = String, pattern = r"^\d+(\.\d{1,2})?$", example = "12.34")]
+pub struct Price(pub u64);
+
+/// A number of units to trade
+#[derive(
+    Serialize,
+    Deserialize,
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    ToSchema,
+)]
+#[serde(transparent)]
+pub struct Quantity(pub u64);
+
+/// The value of a [`Quantity`] at a [`Price`] in the smallest currency unit. Serialized as a decimal string like [`Price`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, ToSchema)]
+#[schema(value_type = String, pattern = r"^\d+(\.\d{1,2})?$", example = "12.34")]
+pub struct Notional(pub u64);
+
+impl Price {
+    pub const MIN: Price = Price(u64::MIN);
+    pub const MAX: Price = Price(u64::MAX);
+
+    /// Computes the [`Notional`] of trading `quantity` at this price.
+    ///
+    /// # Errors
+    /// The result doesn't fit into a `u64`
+    pub fn notional(self, quantity: Quantity) -> Result<Notional, ApplicationError> {
+        self.0
+            .checked_mul(quantity.0)
+            .map(Notional)
+            .ok_or(ApplicationError::NotionalOverflow(self.0, quantity.0))
+    }
+}
+
+impl Quantity {
+    pub const ZERO: Quantity = Quantity(0);
+
+    /// Adds two quantities, returning `None` on overflow
+    pub fn checked_add(self, other: Quantity) -> Option<Quantity> {
+        self.0.checked_add(other.0).map(Quantity)
+    }
+
+    /// Subtracts `other`, returning `None` if it's larger than `self`
+    pub fn checked_sub(self, other: Quantity) -> Option<Quantity> {
+        self.0.checked_sub(other.0).map(Quantity)
+    }
+
+    /// Subtracts `other`, stopping at zero
+    pub fn saturating_sub(self, other: Quantity) -> Quantity {
+        Quantity(self.0.saturating_sub(other.0))
+    }
+
+    /// Whether there is nothing left to trade
+    pub fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl Notional {
+    pub const ZERO: Notional = Notional(0);
+
+    /// Adds two notionals, returning `None` on overflow
+    pub fn checked_add(self, other: Notional) -> Option<Notional> {
+        self.0.checked_add(other.0).map(Notional)
+    }
+}
+
+impl From<u64> for Price {
+    fn from(value: u64) -> Self {
+        Price(value)
+    }
+}
+
+impl From<u64> for Quantity {
+    fn from(value: u64) -> Self {
+        Quantity(value)
+    }
+}
+
+impl From<Notional> for u64 {
+    fn from(value: Notional) -> Self {
+        value.0
+    }
+}
+
+/// Sums up quantities. Like the sum of `u64`s this panics on overflow in debug builds, so only use it on quantities that are part of one order.
+impl Sum for Quantity {
+    fn sum<I: Iterator<Item = Quantity>>(iter: I) -> Self {
+        Quantity(iter.map(|q| q.0).sum())
+    }
+}
+
+/// Formats an amount in the smallest currency unit as decimal string in the major unit
+fn format_decimal(minor: u128) -> String {
+    let unit = 10u128.pow(CURRENCY_DECIMALS);
+    format!(
+        "{}.{:0width$}",
+        minor / unit,
+        minor % unit,
+        width = CURRENCY_DECIMALS as usize
+    )
+}
+
+/// Parses a decimal string in the major unit (e.g. `"12.3"`) into the smallest currency unit
+fn parse_decimal(s: &str) -> Result<u64, String> {
+    parse_wide_decimal(s)?
+        .try_into()
+        .map_err(|_| format!("'{}' is not a valid decimal amount", s))
+}
+
+/// Like [`parse_decimal`], for totals that may exceed a `u64`
+fn parse_wide_decimal(s: &str) -> Result<u128, String> {
+    let invalid = || format!("'{}' is not a valid decimal amount", s);
+    let (whole, fraction) = s.split_once('.').unwrap_or((s, ""));
+    if whole.is_empty()
+        || fraction.len() > CURRENCY_DECIMALS as usize
+        || !whole
+            .chars()
+            .chain(fraction.chars())
+            .all(|c| c.is_ascii_digit())
+    {
+        return Err(invalid());
+    }
+    let whole: u128 = whole.parse().map_err(|_| invalid())?;
+    let fraction: u128 = format!("{:0<width$}", fraction, width = CURRENCY_DECIMALS as usize)
+        .parse()
+        .map_err(|_| invalid())?;
+    whole
+        .checked_mul(10u128.pow(CURRENCY_DECIMALS))
+        .and_then(|w| w.checked_add(fraction))
+        .ok_or_else(invalid)
+}
+
+/// Accepts the decimal strings produced by [`format_decimal`]
+struct DecimalVisitor;
+
+impl<'de> de::Visitor<'de> for DecimalVisitor {
+    type Value = u64;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "a decimal string with up to {} decimals",
+            CURRENCY_DECIMALS
+        )
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<u64, E> {
+        parse_decimal(v).map_err(E::custom)
+    }
+}
+
+impl FromStr for Price {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_decimal(s).map(Price)
+    }
+}
+
+impl Serialize for Price {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format_decimal(self.0.into()))
+    }
+}
+
+impl<'de> Deserialize<'de> for Price {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_str(DecimalVisitor).map(Price)
+    }
+}
+
+impl Serialize for Notional {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format_decimal(self.0.into()))
+    }
+}
+
+impl<'de> Deserialize<'de> for Notional {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_str(DecimalVisitor).map(Notional)
+    }
+}
+
+impl fmt::Display for Price {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&format_decimal(self.0.into()))
+    }
+}
+
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl fmt::Display for Notional {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&format_decimal(self.0.into()))
+    }
+}
+
+impl FromStr for Notional {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_decimal(s).map(Notional)
+    }
+}
+
+/// Serializes amounts of money that are kept as plain integers in the smallest currency unit as decimal strings like
+/// [`Notional`], e.g. `#[serde(with = "octopus_common::money::decimal")]` on a balance
+pub mod decimal {
+    use super::*;
+
+    /// An integer amount of money in the smallest currency unit
+    pub trait Minor: Sized {
+        fn format(&self) -> String;
+        fn parse(s: &str) -> Result<Self, String>;
+    }
+
+    impl Minor for u64 {
+        fn format(&self) -> String {
+            format_decimal((*self).into())
+        }
+
+        fn parse(s: &str) -> Result<Self, String> {
+            parse_decimal(s)
+        }
+    }
+
+    /// Totals of many amounts, e.g. in a trial balance
+    impl Minor for u128 {
+        fn format(&self) -> String {
+            format_decimal(*self)
+        }
+
+        fn parse(s: &str) -> Result<Self, String> {
+            parse_wide_decimal(s)
+        }
+    }
+
+    /// Balances that may be negative, like those of the platform's own accounts
+    impl Minor for i128 {
+        fn format(&self) -> String {
+            let sign = if *self < 0 { "-" } else { "" };
+            format!("{}{}", sign, format_decimal(self.unsigned_abs()))
+        }
+
+        fn parse(s: &str) -> Result<Self, String> {
+            let (sign, magnitude) = match s.strip_prefix('-') {
+                Some(magnitude) => (-1, magnitude),
+                None => (1, s),
+            };
+            let magnitude = i128::try_from(parse_wide_decimal(magnitude)?)
+                .map_err(|_| format!("'{}' is not a valid decimal amount", s))?;
+            Ok(sign * magnitude)
+        }
+    }
+
+    pub fn serialize<T: Minor, S: Serializer>(minor: &T, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&minor.format())
+    }
+
+    pub fn deserialize<'de, T: Minor, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<T, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        T::parse(&s).map_err(de::Error::custom)
+    }
+
+    /// Like [`decimal`](self) for the values of a map, e.g. balances per asset
+    pub mod values {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(
+            map: &BTreeMap<String, u64>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            serializer.collect_map(map.iter().map(|(key, minor)| (key, minor.format())))
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<BTreeMap<String, u64>, D::Error> {
+            BTreeMap::<String, String>::deserialize(deserializer)?
+                .into_iter()
+                .map(|(key, s)| Ok((key, parse_decimal(&s).map_err(de::Error::custom)?)))
+                .collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_price_decimal_round_trip() {
+        assert_eq!("12.34".parse(), Ok(Price(1234)));
+        assert_eq!("12.3".parse(), Ok(Price(1230)));
+        assert_eq!("12".parse(), Ok(Price(1200)));
+        assert_eq!("0.01".parse(), Ok(Price(1)));
+        assert_eq!(Price(1234).to_string(), "12.34");
+        assert_eq!(Price(5).to_string(), "0.05");
+        assert_eq!(Price(u64::MAX).to_string().parse(), Ok(Price(u64::MAX)));
+    }
+
+    #[test]
+    fn test_price_rejects_invalid_decimals() {
+        for invalid in [
+            "",
+            ".5",
+            "1.234",
+            "-1",
+            "1e3",
+            "1.2.3",
+            "184467440737095516.16",
+        ] {
+            assert!(invalid.parse::<Price>().is_err(), "accepted '{}'", invalid);
+        }
+    }
+
+    #[test]
+    fn test_decimal_serializes_plain_amounts_of_money() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Amounts {
+            #[serde(with = "decimal")]
+            balance: u64,
+            #[serde(with = "decimal")]
+            total: u128,
+            #[serde(with = "decimal")]
+            net: i128,
+            #[serde(with = "decimal::values")]
+            balances: BTreeMap<String, u64>,
+        }
+        let amounts = Amounts {
+            balance: 1_234,
+            total: u128::from(u64::MAX) * 2,
+            net: -5,
+            balances: [("CASH".to_string(), 100)].into_iter().collect(),
+        };
+        let json = serde_json::to_value(&amounts).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "balance": "12.34",
+                "total": "368934881474191032.30",
+                "net": "-0.05",
+                "balances": { "CASH": "1.00" },
+            })
+        );
+        assert_eq!(serde_json::from_value::<Amounts>(json).unwrap(), amounts);
+    }
+}
@@ -0,0 +1,114 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::money::{Notional, Quantity};
+
+/// Pre-trade limits of a signer. Limits that aren't set aren't enforced.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RiskLimits {
+    /// Largest quantity per order
+    pub max_order_quantity: Option<Quantity>,
+    /// Largest value per order
+    pub max_order_notional: Option<Notional>,
+    /// Most orders in the book at the same time
+    pub max_open_orders: Option<u64>,
+    /// Maximum distance of the order price from the reference price in basis points
+    pub price_collar_bps: Option<u32>,
+    /// Most orders within `rate_window_millis`
+    pub max_orders_per_window: Option<u64>,
+    /// Length of the order rate window in milliseconds
+    #[serde(default = "RiskLimits::default_rate_window")]
+    pub rate_window_millis: u64,
+}
+
+impl RiskLimits {
+    fn default_rate_window() -> u64 {
+        1_000
+    }
+
+    /// Checks whether the limits can be met by any order.
+    ///
+    /// # Errors
+    /// A description of the first limit that rejects every order
+    pub fn check(&self) -> Result<(), String> {
+        if self.max_order_quantity.is_some_and(Quantity::is_zero) {
+            return Err("max_order_quantity must be positive".to_string());
+        }
+        if self.max_order_notional == Some(Notional::ZERO) {
+            return Err("max_order_notional must be positive".to_string());
+        }
+        if self.max_orders_per_window == Some(0) {
+            return Err("max_orders_per_window must be positive".to_string());
+        }
+        if self.rate_window_millis == 0 {
+            return Err("rate_window_millis must be positive".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl Default for RiskLimits {
+    fn default() -> Self {
+        RiskLimits {
+            max_order_quantity: None,
+            max_order_notional: None,
+            max_open_orders: None,
+            price_collar_bps: None,
+            max_orders_per_window: None,
+            rate_window_millis: RiskLimits::default_rate_window(),
+        }
+    }
+}
+
+/// Default [`RiskLimits`] with per-signer overrides
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct RiskConfig {
+    /// Limits for every signer without an override
+    #[serde(default)]
+    pub default: RiskLimits,
+    /// Limits replacing the default for specific signers
+    #[serde(default)]
+    pub signers: BTreeMap<String, RiskLimits>,
+}
+
+impl RiskConfig {
+    /// The limits that apply to `signer`
+    pub fn limits_for(&self, signer: &str) -> &RiskLimits {
+        self.signers.get(signer).unwrap_or(&self.default)
+    }
+
+    /// Checks the default limits and every override.
+    ///
+    /// # Errors
+    /// A description of the first invalid limit
+    pub fn check(&self) -> Result<(), String> {
+        self.default.check()?;
+        for (signer, limits) in &self.signers {
+            limits.check().map_err(|e| format!("{}: {}", signer, e))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_risk_config_check_names_the_signer() {
+        let mut config = RiskConfig::default();
+        assert_eq!(config.check(), Ok(()));
+        config.signers.insert(
+            "ALICE".to_string(),
+            RiskLimits {
+                max_orders_per_window: Some(0),
+                ..RiskLimits::default()
+            },
+        );
+        assert_eq!(
+            config.check(),
+            Err("ALICE: max_orders_per_window must be positive".to_string())
+        );
+    }
+}
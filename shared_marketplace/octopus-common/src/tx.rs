@@ -1,12 +1,116 @@
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
 
 /// A transaction type. Transactions should be able to rebuild a ledger's state
 /// when they are applied in the same sequence to an empty state.
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, ToSchema)]
 pub enum Tx {
     /// Currency was added to the account
-    Deposit { account: String, amount: u64 },
+    Deposit {
+        account: String,
+        #[serde(with = "crate::money::decimal")]
+        #[schema(value_type = String, example = "12.34")]
+        amount: u64,
+    },
 
     /// Currency was withdrawn from the account
-    Withdraw { account: String, amount: u64 },
+    Withdraw {
+        account: String,
+        #[serde(with = "crate::money::decimal")]
+        #[schema(value_type = String, example = "12.34")]
+        amount: u64,
+    },
+
+    /// A trading fee was charged to the account
+    Fee {
+        account: String,
+        #[serde(with = "crate::money::decimal")]
+        #[schema(value_type = String, example = "12.34")]
+        amount: u64,
+    },
+}
+
+impl Tx {
+    /// The account the transaction was applied to
+    pub fn account(&self) -> &str {
+        match self {
+            Tx::Deposit { account, .. }
+            | Tx::Withdraw { account, .. }
+            | Tx::Fee { account, .. } => account,
+        }
+    }
+
+    /// The [`TxKind`] of this transaction
+    pub fn kind(&self) -> TxKind {
+        match self {
+            Tx::Deposit { .. } => TxKind::Deposit,
+            Tx::Withdraw { .. } => TxKind::Withdraw,
+            Tx::Fee { .. } => TxKind::Fee,
+        }
+    }
+}
+
+/// The kind of a [`Tx`] without its data, used for filtering
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum TxKind {
+    Deposit,
+    Withdraw,
+    Fee,
+}
+
+/// A [`Tx`] as it was recorded by the platform
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, ToSchema)]
+pub struct TxRecord {
+    /// Sequence number of the record, unique across all accounts
+    pub id: u64,
+    /// Unix timestamp in milliseconds
+    pub timestamp: u64,
+    /// The recorded transaction
+    pub tx: Tx,
+    /// Balance of the transaction's account after applying it
+    #[serde(with = "crate::money::decimal")]
+    #[schema(value_type = String, example = "12.34")]
+    pub balance: u64,
+    /// Ordinal of the order that caused this transaction (if any)
+    pub ordinal: Option<u64>,
+}
+
+/// Filters and pagination for `GET /v1/account/{signer}/transactions`
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq, Clone, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct StatementQuery {
+    /// Only include records at or after this unix timestamp in milliseconds
+    pub from: Option<u64>,
+    /// Only include records before this unix timestamp in milliseconds
+    pub to: Option<u64>,
+    /// Only include records of this kind
+    pub kind: Option<TxKind>,
+    /// Number of matching records to skip
+    pub offset: Option<usize>,
+    /// Maximum number of records to return
+    pub limit: Option<usize>,
+}
+
+impl StatementQuery {
+    /// The page size used if no `limit` was given
+    pub const DEFAULT_LIMIT: usize = 100;
+
+    /// Checks whether a [`TxRecord`] passes the time range and kind filters
+    pub fn matches(&self, record: &TxRecord) -> bool {
+        self.from.is_none_or(|from| record.timestamp >= from)
+            && self.to.is_none_or(|to| record.timestamp < to)
+            && self.kind.is_none_or(|kind| record.tx.kind() == kind)
+    }
+}
+
+/// A page of an account's transaction records
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, ToSchema)]
+pub struct Statement {
+    /// The account signer
+    pub signer: String,
+    /// Number of records that match the filters, regardless of pagination
+    pub total: usize,
+    /// The requested page of records, oldest first
+    pub entries: Vec<TxRecord>,
 }
@@ -1,12 +1,17 @@
-use std::cmp::Reverse;
+use std::{cmp::Reverse, collections::BTreeMap, fmt};
 
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 use warp::reject::Reject;
 
-use crate::errors::ApplicationError;
+use crate::{
+    errors::{ApplicationError, ErrorCode},
+    fees::FillFee,
+    money::{Price, Quantity},
+};
 
 /// Simplified side of a position as well as order.
-#[derive(Serialize, Deserialize, Clone, PartialOrd, PartialEq, Eq, Debug, Ord)]
+#[derive(Serialize, Deserialize, Clone, PartialOrd, PartialEq, Eq, Debug, Ord, ToSchema)]
 pub enum Side {
     /// Want to buy
     Buy,
@@ -14,31 +19,191 @@ pub enum Side {
     Sell,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Trading phase of a market
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default, ToSchema)]
+pub enum MarketState {
+    /// Continuous matching
+    #[default]
+    Open,
+    /// Trading was stopped by an operator or a circuit breaker, orders are rejected
+    Halted,
+    /// Orders are collected without matching
+    Auction,
+    /// The market is closed, orders are rejected
+    Closed,
+}
+
+/// The price an auction would uncross at if it ended now
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug, ToSchema)]
+pub struct AuctionIndication {
+    /// The single price all auction fills happen at
+    pub price: Price,
+    /// The quantity executed at that price
+    pub volume: Quantity,
+    /// Bid quantity at or above the price that would not be executed
+    pub buy_surplus: Quantity,
+    /// Ask quantity at or below the price that would not be executed
+    pub sell_surplus: Quantity,
+}
+
+/// The fills of an uncrossed auction
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug, ToSchema)]
+pub struct AuctionResult {
+    /// The indication the auction uncrossed at
+    pub indication: AuctionIndication,
+    /// Matched (buy, sell) parts, all at the auction price
+    pub fills: Vec<(PartialOrder, PartialOrder)>,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct UncrossRequest {
+    /// The state the market moves to after the auction: `Open` for an opening auction, `Closed` for a closing auction
+    pub next: MarketState,
+}
+
+/// Settings for automatic trading halts
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct CircuitBreakerConfig {
+    /// Largest allowed price move within the window in basis points
+    pub max_move_bps: u32,
+    /// Length of the window in milliseconds
+    pub window_millis: u64,
+}
+
+impl CircuitBreakerConfig {
+    /// Checks whether the breaker can trip at all.
+    ///
+    /// # Errors
+    /// A description of the first invalid setting
+    pub fn check(&self) -> Result<(), String> {
+        if self.max_move_bps == 0 || self.window_millis == 0 {
+            return Err(format!(
+                "max_move_bps and window_millis must be positive, got {}/{}",
+                self.max_move_bps, self.window_millis
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
 pub struct AccountUpdateRequest {
     pub signer: String,
+    #[serde(with = "crate::money::decimal")]
+    #[schema(value_type = String, example = "12.34")]
     pub amount: u64,
 }
 
-#[derive(Deserialize, Debug)]
-pub struct AccountBalanceRequest {
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct AccountOpenRequest {
+    pub signer: String,
+    pub owner: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct AccountRequest {
     pub signer: String,
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+/// Pulls a resting order from the book
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct CancelRequest {
+    pub signer: String,
+    /// The ordinal from the order's [`Receipt`]
+    pub ordinal: u64,
+}
+
+/// The lifecycle state of an account
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+pub enum AccountStatus {
+    /// The account can receive, withdraw and trade
+    Open,
+    /// The account can only receive funds
+    Frozen,
+    /// The account was closed at a zero balance and can't be used anymore
+    Closed,
+}
+
+/// Descriptive data of an account kept next to its balance
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+pub struct AccountMetadata {
+    /// The person or entity owning the account
+    pub owner: String,
+    /// Unix timestamp in milliseconds of when the account was opened
+    pub created_at: u64,
+    /// Current lifecycle state
+    pub status: AccountStatus,
+}
+
+/// The asset name under which the cash balance of an account is reported
+pub const CASH_ASSET: &str = "CASH";
+
+/// A read-only view of an account as returned by `GET /account/{signer}`
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+pub struct AccountView {
+    /// The account signer
+    pub signer: String,
+    /// Funds that are not committed to resting buy orders
+    #[serde(with = "crate::money::decimal")]
+    #[schema(value_type = String, example = "12.34")]
+    pub available: u64,
+    /// Funds committed to resting buy orders
+    #[serde(with = "crate::money::decimal")]
+    #[schema(value_type = String, example = "12.34")]
+    pub reserved: u64,
+    /// Total balance per asset
+    #[serde(with = "crate::money::decimal::values")]
+    #[schema(value_type = BTreeMap<String, String>)]
+    pub balances: BTreeMap<String, u64>,
+    /// Number of orders of this signer that are still in the book
+    pub open_orders: usize,
+    /// Owner, creation time and status of the account
+    pub metadata: AccountMetadata,
+}
+
+#[derive(Deserialize, Serialize, Debug, ToSchema)]
 pub struct SendRequest {
     pub sender: String,
     pub recipient: String,
+    #[serde(with = "crate::money::decimal")]
+    #[schema(value_type = String, example = "12.34")]
     pub amount: u64,
 }
 
 #[derive(Debug)]
 pub struct OctopusError(pub ApplicationError);
 
-#[derive(Deserialize, Serialize)]
+/// Body of every error response
+#[derive(Deserialize, Serialize, Debug, PartialEq, Eq, ToSchema)]
 pub struct ErrorMessage {
+    /// HTTP status code
     pub code: u16,
+    /// Machine-readable reason
+    pub error: ErrorCode,
+    /// Human-readable description
     pub message: String,
+    /// Problems with individual fields of the request
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub details: Vec<FieldError>,
+}
+
+/// A problem with one field of a request body
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+pub struct FieldError {
+    /// Path of the field, e.g. `amount`. Empty if the problem concerns the whole body.
+    pub field: String,
+    /// What's wrong with the field
+    pub message: String,
+}
+
+impl fmt::Display for ErrorMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({} {})", self.message, self.code, self.error)?;
+        for detail in &self.details {
+            write!(f, "\n  {}: {}", detail.field, detail.message)?;
+        }
+        Ok(())
+    }
 }
 impl OctopusError {
     pub fn new(error: ApplicationError) -> Self {
@@ -51,12 +216,12 @@ impl Reject for OctopusError {
 }
 
 /// An order for a specified symbol to buy or sell an amount at a given price.
-#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug, ToSchema)]
 pub struct Order {
     /// Max/min price (depending on the side)
-    pub price: u64,
+    pub price: Price,
     /// Number of units to trade
-    pub amount: u64,
+    pub amount: Quantity,
     /// The side of the order book (buy or sell)
     pub side: Side,
     /// The account signer
@@ -65,7 +230,7 @@ pub struct Order {
 
 impl Order {
     /// Convert an [`Order`] into a [`PartialOrder`] with the added parameters
-    pub fn into_partial_order(self, ordinal: u64, remaining: u64) -> PartialOrder {
+    pub fn into_partial_order(self, ordinal: u64, remaining: Quantity) -> PartialOrder {
         let Order {
             price,
             amount,
@@ -84,14 +249,14 @@ impl Order {
 }
 
 /// A position represents an unfilled order that is kept in the system for later filling.
-#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, Eq, Ord)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, Eq, ToSchema)]
 pub struct PartialOrder {
     /// Price per unit
-    pub price: u64,
+    pub price: Price,
     /// Initial number of units in the order
-    pub amount: u64,
+    pub amount: Quantity,
     /// Remaining number of units after potential matches
-    pub remaining: u64,
+    pub remaining: Quantity,
     /// Buy or sell side of the book
     pub side: Side,
     /// Signer of the order
@@ -100,27 +265,37 @@ pub struct PartialOrder {
     pub ordinal: u64,
 }
 
+impl Ord for PartialOrder {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        // this reverses the comparison to create a min heap
+        Reverse(self.ordinal).cmp(&Reverse(other.ordinal))
+    }
+}
+
 impl PartialOrd for PartialOrder {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        // this reverses the comparison to create a min heap
-        Reverse(self.ordinal).partial_cmp(&Reverse(other.ordinal))
+        Some(self.cmp(other))
     }
 }
 
 /// A receipt issued to the caller for accepting an [`Order`]
-#[derive(Serialize, Deserialize, Clone, PartialOrd, PartialEq, Eq, Debug)]
+#[derive(Serialize, Deserialize, Clone, PartialOrd, PartialEq, Eq, Debug, ToSchema)]
 pub struct Receipt {
     /// Sequence number
     pub ordinal: u64,
 
     /// Matches that happened immediately
     pub matches: Vec<PartialOrder>,
+
+    /// Fees charged for each of the matches
+    #[serde(default)]
+    pub fees: Vec<FillFee>,
 }
 
 impl PartialOrder {
     /// Splits one [`PartialOrder`] into two by taking a defined `take` amount
-    pub fn take_from(pos: &mut PartialOrder, take: u64, price: u64) -> PartialOrder {
-        pos.remaining -= take;
+    pub fn take_from(pos: &mut PartialOrder, take: Quantity, price: Price) -> PartialOrder {
+        pos.remaining = pos.remaining.saturating_sub(take);
         let mut new = pos.clone();
         new.amount = take;
         new.price = price;
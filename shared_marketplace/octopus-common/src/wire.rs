@@ -0,0 +1,601 @@
+//! A compact binary order entry protocol.
+//!
+//! Every message is a frame: a big-endian `u16` with the length of the payload, followed by the payload. The payload
+//! starts with a one byte message type, followed by the message's fields as big-endian integers. Strings are prefixed by
+//! their length in bytes. Clients send [`Request`]s, the server answers each of them with an [`Response::Ack`] or a
+//! [`Response::Reject`] and reports fills of the connection's orders with [`Response::Fill`]s. A connection has to
+//! start with a [`Request::Logon`], which binds it to a user that may only trade for the accounts it is entitled to.
+
+use std::fmt;
+
+use crate::{
+    errors::ApplicationError,
+    money::{Price, Quantity},
+    types::Side,
+};
+
+/// Bytes of the length prefix of a frame
+pub const LENGTH_PREFIX: usize = 2;
+
+const NEW_ORDER: u8 = 0x01;
+const CANCEL: u8 = 0x02;
+const LOGON: u8 = 0x03;
+const ACK: u8 = 0x81;
+const FILL: u8 = 0x82;
+const REJECT: u8 = 0x83;
+
+/// A message from a client
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Request {
+    /// Places a limit order
+    NewOrder {
+        /// Chosen by the client and returned in the answer
+        request_id: u64,
+        side: Side,
+        price: Price,
+        quantity: Quantity,
+        signer: String,
+    },
+    /// Cancels a resting order
+    Cancel {
+        request_id: u64,
+        ordinal: u64,
+        signer: String,
+    },
+    /// Binds the connection to `user`, acknowledged with ordinal 0
+    Logon {
+        request_id: u64,
+        user: String,
+        password: String,
+    },
+}
+
+/// A message from the server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Response {
+    /// The request succeeded. For a new order, `ordinal` is the order's ordinal.
+    Ack { request_id: u64, ordinal: u64 },
+    /// An order of the connection was (partially) filled
+    Fill {
+        ordinal: u64,
+        price: Price,
+        quantity: Quantity,
+        /// Quantity that is still open
+        leaves: Quantity,
+    },
+    /// The request failed
+    Reject {
+        request_id: u64,
+        code: RejectCode,
+        message: String,
+    },
+}
+
+/// Why a [`Request`] was rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RejectCode {
+    /// The request couldn't be decoded
+    Malformed,
+    /// The account doesn't exist, is frozen or closed
+    Account,
+    /// The account doesn't have enough funds
+    UnderFunded,
+    /// Price or quantity don't follow the market's rules
+    InvalidOrder,
+    /// A pre-trade risk limit was hit
+    RiskLimit,
+    /// The market doesn't accept orders right now
+    MarketClosed,
+    /// There's no such resting order
+    OrderNotFound,
+    /// The connection isn't logged on, the credentials are wrong or the user may not trade for the signer
+    Unauthorized,
+    /// Anything else
+    Other,
+}
+
+impl RejectCode {
+    fn to_u16(self) -> u16 {
+        match self {
+            RejectCode::Malformed => 1,
+            RejectCode::Account => 2,
+            RejectCode::UnderFunded => 3,
+            RejectCode::InvalidOrder => 4,
+            RejectCode::RiskLimit => 5,
+            RejectCode::MarketClosed => 6,
+            RejectCode::OrderNotFound => 7,
+            RejectCode::Unauthorized => 8,
+            RejectCode::Other => 99,
+        }
+    }
+
+    /// Unknown codes are read as [`RejectCode::Other`], so servers can add codes without breaking clients
+    fn from_u16(code: u16) -> Self {
+        match code {
+            1 => RejectCode::Malformed,
+            2 => RejectCode::Account,
+            3 => RejectCode::UnderFunded,
+            4 => RejectCode::InvalidOrder,
+            5 => RejectCode::RiskLimit,
+            6 => RejectCode::MarketClosed,
+            7 => RejectCode::OrderNotFound,
+            8 => RejectCode::Unauthorized,
+            _ => RejectCode::Other,
+        }
+    }
+}
+
+impl From<&ApplicationError> for RejectCode {
+    fn from(error: &ApplicationError) -> Self {
+        match error {
+            ApplicationError::AccountNotFound(_)
+            | ApplicationError::AccountFrozen(_)
+            | ApplicationError::AccountClosed(_) => RejectCode::Account,
+            ApplicationError::AccountUnderFunded(..) => RejectCode::UnderFunded,
+            ApplicationError::NotionalOverflow(..)
+            | ApplicationError::InvalidPrice(..)
+            | ApplicationError::InvalidLotSize(..)
+            | ApplicationError::QuantityOutOfRange(..)
+            | ApplicationError::NotionalTooSmall(..) => RejectCode::InvalidOrder,
+            ApplicationError::OrderSizeLimitExceeded(..)
+            | ApplicationError::NotionalLimitExceeded(..)
+            | ApplicationError::OpenOrderLimitExceeded(..)
+            | ApplicationError::PriceOutsideCollar(..)
+            | ApplicationError::RateLimitExceeded(..) => RejectCode::RiskLimit,
+            ApplicationError::MarketHalted | ApplicationError::MarketClosed => {
+                RejectCode::MarketClosed
+            }
+            ApplicationError::OrderNotFound(_) => RejectCode::OrderNotFound,
+            _ => RejectCode::Other,
+        }
+    }
+}
+
+/// Why bytes couldn't be decoded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WireError {
+    /// The payload has an unknown message type
+    UnknownType(u8),
+    /// The payload is shorter or longer than its message type requires
+    Length(u8),
+    /// A field has an invalid value
+    Invalid(&'static str),
+    /// A string field is longer than its length prefix allows
+    TooLong(&'static str),
+}
+
+impl fmt::Display for WireError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WireError::UnknownType(t) => write!(f, "unknown message type {:#04x}", t),
+            WireError::Length(t) => write!(f, "wrong length for message type {:#04x}", t),
+            WireError::Invalid(field) => write!(f, "invalid {}", field),
+            WireError::TooLong(field) => write!(f, "{} is longer than 255 bytes", field),
+        }
+    }
+}
+
+impl Request {
+    /// Appends the request as a frame to `buffer`
+    ///
+    /// # Errors
+    /// A string is longer than 255 bytes, nothing is appended then
+    pub fn encode(&self, buffer: &mut Vec<u8>) -> Result<(), WireError> {
+        let strings = match self {
+            Request::NewOrder { signer, .. } | Request::Cancel { signer, .. } => {
+                vec![("signer", signer)]
+            }
+            Request::Logon { user, password, .. } => vec![("user", user), ("password", password)],
+        };
+        if let Some((field, _)) = strings.iter().find(|(_, s)| s.len() > u8::MAX as usize) {
+            return Err(WireError::TooLong(field));
+        }
+        frame(buffer, |payload| match self {
+            Request::NewOrder {
+                request_id,
+                side,
+                price,
+                quantity,
+                signer,
+            } => {
+                payload.push(NEW_ORDER);
+                payload.extend_from_slice(&request_id.to_be_bytes());
+                payload.push(match side {
+                    Side::Buy => 1,
+                    Side::Sell => 2,
+                });
+                payload.extend_from_slice(&price.0.to_be_bytes());
+                payload.extend_from_slice(&quantity.0.to_be_bytes());
+                put_short_str(payload, signer);
+            }
+            Request::Cancel {
+                request_id,
+                ordinal,
+                signer,
+            } => {
+                payload.push(CANCEL);
+                payload.extend_from_slice(&request_id.to_be_bytes());
+                payload.extend_from_slice(&ordinal.to_be_bytes());
+                put_short_str(payload, signer);
+            }
+            Request::Logon {
+                request_id,
+                user,
+                password,
+            } => {
+                payload.push(LOGON);
+                payload.extend_from_slice(&request_id.to_be_bytes());
+                put_short_str(payload, user);
+                put_short_str(payload, password);
+            }
+        });
+        Ok(())
+    }
+
+    /// Decodes the first frame in `bytes`. Returns the request and the length of its frame, or `None` if the frame isn't
+    /// complete yet.
+    ///
+    /// # Errors
+    /// The frame is complete but isn't a valid request. It can be skipped using [`frame_len`].
+    pub fn decode(bytes: &[u8]) -> Result<Option<(Request, usize)>, WireError> {
+        let Some(len) = frame_len(bytes) else {
+            return Ok(None);
+        };
+        let mut reader = Reader::new(&bytes[LENGTH_PREFIX..len]);
+        let message_type = reader.u8()?;
+        let request = match message_type {
+            NEW_ORDER => Request::NewOrder {
+                request_id: reader.u64()?,
+                side: match reader.u8()? {
+                    1 => Side::Buy,
+                    2 => Side::Sell,
+                    _ => return Err(WireError::Invalid("side")),
+                },
+                price: Price(reader.u64()?),
+                quantity: Quantity(reader.u64()?),
+                signer: reader.short_str("signer")?,
+            },
+            CANCEL => Request::Cancel {
+                request_id: reader.u64()?,
+                ordinal: reader.u64()?,
+                signer: reader.short_str("signer")?,
+            },
+            LOGON => Request::Logon {
+                request_id: reader.u64()?,
+                user: reader.short_str("user")?,
+                password: reader.short_str("password")?,
+            },
+            other => return Err(WireError::UnknownType(other)),
+        };
+        reader.finish(message_type)?;
+        Ok(Some((request, len)))
+    }
+
+    pub fn request_id(&self) -> u64 {
+        match self {
+            Request::NewOrder { request_id, .. }
+            | Request::Cancel { request_id, .. }
+            | Request::Logon { request_id, .. } => *request_id,
+        }
+    }
+}
+
+impl Response {
+    /// Appends the response as a frame to `buffer`
+    pub fn encode(&self, buffer: &mut Vec<u8>) {
+        frame(buffer, |payload| match self {
+            Response::Ack {
+                request_id,
+                ordinal,
+            } => {
+                payload.push(ACK);
+                payload.extend_from_slice(&request_id.to_be_bytes());
+                payload.extend_from_slice(&ordinal.to_be_bytes());
+            }
+            Response::Fill {
+                ordinal,
+                price,
+                quantity,
+                leaves,
+            } => {
+                payload.push(FILL);
+                payload.extend_from_slice(&ordinal.to_be_bytes());
+                payload.extend_from_slice(&price.0.to_be_bytes());
+                payload.extend_from_slice(&quantity.0.to_be_bytes());
+                payload.extend_from_slice(&leaves.0.to_be_bytes());
+            }
+            Response::Reject {
+                request_id,
+                code,
+                message,
+            } => {
+                payload.push(REJECT);
+                payload.extend_from_slice(&request_id.to_be_bytes());
+                payload.extend_from_slice(&code.to_u16().to_be_bytes());
+                // Leaves room for the other fields within the frame's maximum length
+                let mut end = message.len().min(u16::MAX as usize - 16);
+                while !message.is_char_boundary(end) {
+                    end -= 1;
+                }
+                payload.extend_from_slice(&(end as u16).to_be_bytes());
+                payload.extend_from_slice(&message.as_bytes()[..end]);
+            }
+        })
+    }
+
+    /// Decodes the first frame in `bytes`, like [`Request::decode`]
+    ///
+    /// # Errors
+    /// The frame is complete but isn't a valid response
+    pub fn decode(bytes: &[u8]) -> Result<Option<(Response, usize)>, WireError> {
+        let Some(len) = frame_len(bytes) else {
+            return Ok(None);
+        };
+        let mut reader = Reader::new(&bytes[LENGTH_PREFIX..len]);
+        let message_type = reader.u8()?;
+        let response = match message_type {
+            ACK => Response::Ack {
+                request_id: reader.u64()?,
+                ordinal: reader.u64()?,
+            },
+            FILL => Response::Fill {
+                ordinal: reader.u64()?,
+                price: Price(reader.u64()?),
+                quantity: Quantity(reader.u64()?),
+                leaves: Quantity(reader.u64()?),
+            },
+            REJECT => Response::Reject {
+                request_id: reader.u64()?,
+                code: RejectCode::from_u16(reader.u16()?),
+                message: {
+                    let len = reader.u16()? as usize;
+                    String::from_utf8(reader.take(len)?.to_vec())
+                        .map_err(|_| WireError::Invalid("message"))?
+                },
+            },
+            other => return Err(WireError::UnknownType(other)),
+        };
+        reader.finish(message_type)?;
+        Ok(Some((response, len)))
+    }
+}
+
+/// The length of the first frame in `bytes` including its length prefix, if the frame is complete
+pub fn frame_len(bytes: &[u8]) -> Option<usize> {
+    let prefix: [u8; LENGTH_PREFIX] = bytes.get(..LENGTH_PREFIX)?.try_into().ok()?;
+    let len = LENGTH_PREFIX + u16::from_be_bytes(prefix) as usize;
+    (bytes.len() >= len).then_some(len)
+}
+
+/// Appends a frame with the payload written by `write`
+fn frame(buffer: &mut Vec<u8>, write: impl FnOnce(&mut Vec<u8>)) {
+    let start = buffer.len();
+    buffer.extend_from_slice(&[0; LENGTH_PREFIX]);
+    write(buffer);
+    let len = (buffer.len() - start - LENGTH_PREFIX) as u16;
+    buffer[start..start + LENGTH_PREFIX].copy_from_slice(&len.to_be_bytes());
+}
+
+/// Writes a string with a one byte length. Callers check that it's at most 255 bytes long.
+fn put_short_str(payload: &mut Vec<u8>, s: &str) {
+    let len = u8::try_from(s.len()).expect("short strings are checked before encoding");
+    payload.push(len);
+    payload.extend_from_slice(s.as_bytes());
+}
+
+/// Reads fields from a payload
+struct Reader<'a> {
+    bytes: &'a [u8],
+    message_type: u8,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Reader {
+            bytes,
+            message_type: bytes.first().copied().unwrap_or_default(),
+        }
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], WireError> {
+        if self.bytes.len() < n {
+            return Err(WireError::Length(self.message_type));
+        }
+        let (taken, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, WireError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, WireError> {
+        Ok(u16::from_be_bytes(
+            self.take(2)?.try_into().expect("2 bytes"),
+        ))
+    }
+
+    fn u64(&mut self) -> Result<u64, WireError> {
+        Ok(u64::from_be_bytes(
+            self.take(8)?.try_into().expect("8 bytes"),
+        ))
+    }
+
+    fn short_str(&mut self, field: &'static str) -> Result<String, WireError> {
+        let len = self.u8()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| WireError::Invalid(field))
+    }
+
+    /// Makes sure the whole payload was read
+    fn finish(&self, message_type: u8) -> Result<(), WireError> {
+        match self.bytes.is_empty() {
+            true => Ok(()),
+            false => Err(WireError::Length(message_type)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wire_round_trip() {
+        let requests = [
+            Request::NewOrder {
+                request_id: 7,
+                side: Side::Sell,
+                price: Price(1_050),
+                quantity: Quantity(3),
+                signer: "ALICE".to_string(),
+            },
+            Request::Cancel {
+                request_id: u64::MAX,
+                ordinal: 42,
+                signer: "BOB".to_string(),
+            },
+            Request::Logon {
+                request_id: 0,
+                user: "DESK".to_string(),
+                password: "s3cret".to_string(),
+            },
+        ];
+        let mut buffer = vec![];
+        for request in &requests {
+            request.encode(&mut buffer).unwrap();
+        }
+        // A new order takes 2 + 1 + 8 + 1 + 8 + 8 + 1 + 5 bytes
+        assert_eq!(frame_len(&buffer), Some(34));
+        let (first, len) = Request::decode(&buffer).unwrap().unwrap();
+        assert_eq!(first, requests[0]);
+        let (second, second_len) = Request::decode(&buffer[len..]).unwrap().unwrap();
+        assert_eq!(second, requests[1]);
+        assert_eq!(
+            Request::decode(&buffer[len + second_len..])
+                .unwrap()
+                .unwrap()
+                .0,
+            requests[2]
+        );
+
+        let responses = [
+            Response::Ack {
+                request_id: 7,
+                ordinal: 1,
+            },
+            Response::Fill {
+                ordinal: 1,
+                price: Price(1_050),
+                quantity: Quantity(2),
+                leaves: Quantity(1),
+            },
+            Response::Reject {
+                request_id: 8,
+                code: RejectCode::UnderFunded,
+                message: "AccountUnderFunded(\"BOB\", 100)".to_string(),
+            },
+        ];
+        for response in responses {
+            let mut buffer = vec![];
+            response.encode(&mut buffer);
+            assert_eq!(
+                Response::decode(&buffer),
+                Ok(Some((response, buffer.len())))
+            );
+        }
+    }
+
+    #[test]
+    fn test_wire_decode_partial_and_invalid_frames() {
+        let mut buffer = vec![];
+        Request::Cancel {
+            request_id: 1,
+            ordinal: 2,
+            signer: "ALICE".to_string(),
+        }
+        .encode(&mut buffer)
+        .unwrap();
+        for end in 0..buffer.len() {
+            assert_eq!(Request::decode(&buffer[..end]), Ok(None));
+        }
+
+        // A response isn't a request
+        let mut ack = vec![];
+        Response::Ack {
+            request_id: 1,
+            ordinal: 2,
+        }
+        .encode(&mut ack);
+        assert_eq!(Request::decode(&ack), Err(WireError::UnknownType(ACK)));
+
+        // Frames with missing or extra bytes
+        assert_eq!(
+            Request::decode(&[0, 2, CANCEL, 0]),
+            Err(WireError::Length(CANCEL))
+        );
+        let mut long = ack.clone();
+        long[1] += 1;
+        long.push(0);
+        assert_eq!(Response::decode(&long), Err(WireError::Length(ACK)));
+        let mut order = vec![];
+        Request::NewOrder {
+            request_id: 1,
+            side: Side::Buy,
+            price: Price(1),
+            quantity: Quantity(1),
+            signer: String::new(),
+        }
+        .encode(&mut order)
+        .unwrap();
+        // The side follows the length, the message type and the request id
+        order[LENGTH_PREFIX + 1 + 8] = 3;
+        assert_eq!(Request::decode(&order), Err(WireError::Invalid("side")));
+    }
+
+    #[test]
+    fn test_wire_encode_rejects_signers_that_do_not_fit() {
+        let cancel = |signer: String| Request::Cancel {
+            request_id: 1,
+            ordinal: 2,
+            signer,
+        };
+        let mut buffer = vec![];
+        cancel("A".repeat(255)).encode(&mut buffer).unwrap();
+        assert_eq!(
+            Request::decode(&buffer).unwrap().unwrap().0,
+            cancel("A".repeat(255))
+        );
+
+        let mut buffer = vec![];
+        assert_eq!(
+            cancel("A".repeat(256)).encode(&mut buffer),
+            Err(WireError::TooLong("signer"))
+        );
+        assert!(buffer.is_empty());
+        let logon = Request::Logon {
+            request_id: 1,
+            user: "DESK".to_string(),
+            password: "A".repeat(256),
+        };
+        assert_eq!(
+            logon.encode(&mut buffer),
+            Err(WireError::TooLong("password"))
+        );
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_reject_code_round_trip_and_unknown_codes() {
+        let code = RejectCode::from(&ApplicationError::OrderNotFound(1));
+        assert_eq!(
+            RejectCode::from_u16(code.to_u16()),
+            RejectCode::OrderNotFound
+        );
+        assert_eq!(
+            RejectCode::from_u16(RejectCode::Unauthorized.to_u16()),
+            RejectCode::Unauthorized
+        );
+        assert_eq!(RejectCode::from_u16(1234), RejectCode::Other);
+    }
+}
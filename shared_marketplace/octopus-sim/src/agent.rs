@@ -0,0 +1,298 @@
+use std::collections::{BTreeSet, VecDeque};
+
+use octopus_common::{
+    money::{Price, Quantity},
+    types::{Order, Side},
+};
+use serde::Serialize;
+
+use crate::rng::Rng;
+
+/// What the agents know about the market at the start of a step
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MarketView {
+    pub best_bid: Option<Price>,
+    pub best_ask: Option<Price>,
+    /// Price of the most recent fill
+    pub last_trade: Option<Price>,
+}
+
+impl MarketView {
+    /// Midpoint of the best bid and ask, falling back to the last trade and then to `reference`
+    pub fn mid_or(&self, reference: Price) -> Price {
+        match (self.best_bid, self.best_ask) {
+            (Some(bid), Some(ask)) => Price(bid.0 / 2 + ask.0 / 2 + (bid.0 % 2 + ask.0 % 2) / 2),
+            _ => self.last_trade.unwrap_or(reference),
+        }
+    }
+}
+
+/// Something an agent wants to do in the market
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    Place(Order),
+    Cancel(u64),
+}
+
+/// The kind of an [`Agent`], used in reports
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AgentKind {
+    MarketMaker,
+    RandomTaker,
+    Momentum,
+}
+
+/// A synthetic market participant
+pub trait Agent {
+    /// The signer of the agent's account
+    fn signer(&self) -> &str;
+
+    fn kind(&self) -> AgentKind;
+
+    /// Decides what to do in this step
+    fn act(&mut self, market: &MarketView, rng: &mut Rng) -> Vec<Action>;
+
+    /// One of the agent's orders rests in the book with this ordinal after being placed
+    fn on_rest(&mut self, _ordinal: u64) {}
+
+    /// One of the agent's orders left the book, because it was filled or cancelled
+    fn on_done(&mut self, _ordinal: u64) {}
+
+    /// A trade happened at this price, whoever traded
+    fn on_trade(&mut self, _price: Price) {}
+}
+
+fn order(signer: &str, side: Side, price: Price, amount: u64) -> Order {
+    Order {
+        price,
+        amount: Quantity(amount),
+        side,
+        signer: signer.to_string(),
+    }
+}
+
+/// Quotes a bid and an ask around the mid every step, replacing its previous quotes
+#[derive(Debug, Clone)]
+pub struct MarketMaker {
+    pub signer: String,
+    /// Price to quote around until the market has one
+    pub reference: Price,
+    /// Distance of each quote from the mid in cents
+    pub half_spread: u64,
+    /// Quantity of each quote
+    pub size: u64,
+    resting: BTreeSet<u64>,
+}
+
+impl MarketMaker {
+    pub fn new(signer: &str, reference: Price, half_spread: u64, size: u64) -> Self {
+        MarketMaker {
+            signer: signer.to_string(),
+            reference,
+            half_spread,
+            size,
+            resting: BTreeSet::new(),
+        }
+    }
+}
+
+impl Agent for MarketMaker {
+    fn signer(&self) -> &str {
+        &self.signer
+    }
+
+    fn kind(&self) -> AgentKind {
+        AgentKind::MarketMaker
+    }
+
+    fn act(&mut self, market: &MarketView, rng: &mut Rng) -> Vec<Action> {
+        let mid = market.mid_or(self.reference).0;
+        // A little noise keeps several makers from quoting exactly the same prices
+        let half_spread = self.half_spread + rng.between(0, self.half_spread / 2);
+        let mut actions: Vec<Action> = self.resting.iter().map(|o| Action::Cancel(*o)).collect();
+        actions.push(Action::Place(order(
+            &self.signer,
+            Side::Buy,
+            Price(mid.saturating_sub(half_spread).max(1)),
+            self.size,
+        )));
+        actions.push(Action::Place(order(
+            &self.signer,
+            Side::Sell,
+            Price(mid.saturating_add(half_spread)),
+            self.size,
+        )));
+        actions
+    }
+
+    fn on_rest(&mut self, ordinal: u64) {
+        self.resting.insert(ordinal);
+    }
+
+    fn on_done(&mut self, ordinal: u64) {
+        self.resting.remove(&ordinal);
+    }
+}
+
+/// Sends marketable orders of random side and size now and then
+#[derive(Debug, Clone)]
+pub struct RandomTaker {
+    pub signer: String,
+    /// Probability of trading in a step
+    pub activity: f64,
+    /// Largest order quantity
+    pub max_size: u64,
+}
+
+impl Agent for RandomTaker {
+    fn signer(&self) -> &str {
+        &self.signer
+    }
+
+    fn kind(&self) -> AgentKind {
+        AgentKind::RandomTaker
+    }
+
+    fn act(&mut self, market: &MarketView, rng: &mut Rng) -> Vec<Action> {
+        if !rng.chance(self.activity) {
+            return vec![];
+        }
+        let size = rng.between(1, self.max_size.max(1));
+        let action = match (rng.chance(0.5), market.best_ask, market.best_bid) {
+            (true, Some(ask), _) => order(&self.signer, Side::Buy, ask, size),
+            (false, _, Some(bid)) => order(&self.signer, Side::Sell, bid, size),
+            _ => return vec![],
+        };
+        vec![Action::Place(action)]
+    }
+}
+
+/// Buys when the last trade is above the average of the recent trades and sells when it's below
+#[derive(Debug, Clone)]
+pub struct Momentum {
+    pub signer: String,
+    /// Number of recent trades to average over
+    pub window: usize,
+    /// Quantity of each order
+    pub size: u64,
+    trades: VecDeque<Price>,
+}
+
+impl Momentum {
+    pub fn new(signer: &str, window: usize, size: u64) -> Self {
+        Momentum {
+            signer: signer.to_string(),
+            window: window.max(2),
+            size,
+            trades: VecDeque::new(),
+        }
+    }
+}
+
+impl Agent for Momentum {
+    fn signer(&self) -> &str {
+        &self.signer
+    }
+
+    fn kind(&self) -> AgentKind {
+        AgentKind::Momentum
+    }
+
+    fn act(&mut self, market: &MarketView, _rng: &mut Rng) -> Vec<Action> {
+        let Some(last) = self.trades.back() else {
+            return vec![];
+        };
+        if self.trades.len() < self.window {
+            return vec![];
+        }
+        let average =
+            self.trades.iter().map(|p| p.0 as u128).sum::<u128>() / self.trades.len() as u128;
+        let action = match (last.0 as u128).cmp(&average) {
+            std::cmp::Ordering::Greater => market
+                .best_ask
+                .map(|ask| order(&self.signer, Side::Buy, ask, self.size)),
+            std::cmp::Ordering::Less => market
+                .best_bid
+                .map(|bid| order(&self.signer, Side::Sell, bid, self.size)),
+            std::cmp::Ordering::Equal => None,
+        };
+        action.map(Action::Place).into_iter().collect()
+    }
+
+    fn on_trade(&mut self, price: Price) {
+        self.trades.push_back(price);
+        if self.trades.len() > self.window {
+            self.trades.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // reduce the warnings for naming tests
+    #![allow(non_snake_case)]
+
+    use super::*;
+
+    fn market(bid: u64, ask: u64) -> MarketView {
+        MarketView {
+            best_bid: Some(Price(bid)),
+            best_ask: Some(Price(ask)),
+            last_trade: None,
+        }
+    }
+
+    #[test]
+    fn test_MarketMaker_act_replaces_resting_quotes() {
+        let mut maker = MarketMaker::new("MM", Price(1_000), 10, 5);
+        let mut rng = Rng::new(1);
+        let first = maker.act(&MarketView::default(), &mut rng);
+        assert_eq!(first.len(), 2);
+        let Action::Place(bid) = &first[0] else {
+            panic!("expected a bid");
+        };
+        assert!(bid.side == Side::Buy && bid.price <= Price(990) && bid.price >= Price(985));
+
+        maker.on_rest(1);
+        maker.on_rest(2);
+        maker.on_done(1);
+        let second = maker.act(&market(990, 1_010), &mut rng);
+        assert_eq!(second[0], Action::Cancel(2));
+        assert_eq!(second.len(), 3);
+    }
+
+    #[test]
+    fn test_Momentum_act_follows_the_trend() {
+        let mut momentum = Momentum::new("MO", 3, 1);
+        let mut rng = Rng::new(1);
+        momentum.on_trade(Price(100));
+        momentum.on_trade(Price(101));
+        // Not enough trades yet
+        assert!(momentum.act(&market(99, 103), &mut rng).is_empty());
+
+        momentum.on_trade(Price(105));
+        assert!(matches!(
+            &momentum.act(&market(99, 103), &mut rng)[..],
+            [Action::Place(o)] if o.side == Side::Buy && o.price == Price(103)
+        ));
+
+        momentum.on_trade(Price(90));
+        assert!(matches!(
+            &momentum.act(&market(99, 103), &mut rng)[..],
+            [Action::Place(o)] if o.side == Side::Sell && o.price == Price(99)
+        ));
+    }
+
+    #[test]
+    fn test_MarketView_mid_or_falls_back_to_last_trade() {
+        assert_eq!(market(99, 102).mid_or(Price(1)), Price(100));
+        let one_sided = MarketView {
+            best_bid: Some(Price(99)),
+            best_ask: None,
+            last_trade: Some(Price(97)),
+        };
+        assert_eq!(one_sided.mid_or(Price(1)), Price(97));
+        assert_eq!(MarketView::default().mid_or(Price(1)), Price(1));
+    }
+}
@@ -0,0 +1,7 @@
+//! Synthetic agents trading against an in-process [`TradingPlatform`](octopus_web::trading_platform::TradingPlatform)
+//! or a running `octopus-web` service, for load and behavior testing.
+
+pub mod agent;
+pub mod rng;
+pub mod simulator;
+pub mod venue;
@@ -0,0 +1,72 @@
+//! Runs a market simulation and prints the report as JSON.
+//!
+//! ```text
+//! octopus-sim [--url <service>] [--seed <n>] [--steps <n>] [--makers <n>] [--takers <n>] [--momentum <n>] [--prefix <signer prefix>]
+//! ```
+//!
+//! Without `--url`, the agents trade against an in-process platform.
+
+use std::{env, process::ExitCode};
+
+use octopus_sim::{
+    simulator::{SimConfig, SimReport, Simulator},
+    venue::{Http, InProcess},
+};
+use octopus_web::trading_platform::TradingPlatform;
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let result = match parse(env::args().skip(1).collect()) {
+        Ok((url, config)) => run(url, config).await,
+        Err(e) => Err(e),
+    };
+    match result.and_then(|report| serde_json::to_string_pretty(&report).map_err(|e| e.to_string()))
+    {
+        Ok(report) => {
+            println!("{}", report);
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(url: Option<String>, config: SimConfig) -> Result<SimReport, String> {
+    match url {
+        Some(url) => Simulator::new(Http::new(&url), config).run().await,
+        None => {
+            Simulator::new(InProcess::new(TradingPlatform::new()), config)
+                .run()
+                .await
+        }
+    }
+}
+
+fn parse(args: Vec<String>) -> Result<(Option<String>, SimConfig), String> {
+    let mut url = None;
+    let mut config = SimConfig::default();
+    let mut args = args.into_iter();
+    while let Some(flag) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| format!("Missing value for {}", flag))?;
+        let number = || {
+            value
+                .parse::<u64>()
+                .map_err(|_| format!("Invalid value '{}' for {}", value, flag))
+        };
+        match flag.as_str() {
+            "--url" => url = Some(value.clone()),
+            "--seed" => config.seed = number()?,
+            "--steps" => config.steps = number()?,
+            "--makers" => config.market_makers = number()? as usize,
+            "--takers" => config.takers = number()? as usize,
+            "--momentum" => config.momentum_traders = number()? as usize,
+            "--prefix" => config.prefix = value.clone(),
+            _ => return Err(format!("Unknown option {}", flag)),
+        }
+    }
+    Ok((url, config))
+}
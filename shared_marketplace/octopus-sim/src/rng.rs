@@ -0,0 +1,79 @@
+/// A small, seedable random number generator (SplitMix64). The sequence only depends on the seed, so simulations are
+/// reproducible across platforms and versions.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    /// Creates a generator that always produces the same sequence for the same `seed`
+    pub fn new(seed: u64) -> Self {
+        Rng { state: seed }
+    }
+
+    /// The next pseudo-random number
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `low..=high`
+    pub fn between(&mut self, low: u64, high: u64) -> u64 {
+        debug_assert!(low <= high);
+        match (high - low).checked_add(1) {
+            Some(span) => low + self.next_u64() % span,
+            None => self.next_u64(),
+        }
+    }
+
+    /// `true` with the given probability
+    pub fn chance(&mut self, probability: f64) -> bool {
+        // The 53 high bits make a uniformly distributed f64 in [0, 1)
+        ((self.next_u64() >> 11) as f64 / (1u64 << 53) as f64) < probability
+    }
+
+    /// Shuffles `items` in place (Fisher-Yates)
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = self.between(0, i as u64) as usize;
+            items.swap(i, j);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // reduce the warnings for naming tests
+    #![allow(non_snake_case)]
+
+    use super::*;
+
+    #[test]
+    fn test_Rng_same_seed_same_sequence() {
+        let mut a = Rng::new(42);
+        let mut b = Rng::new(42);
+        let mut c = Rng::new(43);
+        let first: Vec<u64> = (0..10).map(|_| a.next_u64()).collect();
+        assert_eq!(first, (0..10).map(|_| b.next_u64()).collect::<Vec<_>>());
+        assert_ne!(first, (0..10).map(|_| c.next_u64()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_Rng_between_stays_within_bounds() {
+        let mut rng = Rng::new(7);
+        for _ in 0..1_000 {
+            let n = rng.between(3, 5);
+            assert!((3..=5).contains(&n));
+        }
+        assert_eq!(rng.between(9, 9), 9);
+        rng.between(0, u64::MAX);
+
+        let mut items: Vec<u32> = (0..20).collect();
+        rng.shuffle(&mut items);
+        items.sort_unstable();
+        assert_eq!(items, (0..20).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,418 @@
+use std::collections::HashMap;
+
+use octopus_common::{
+    money::{Price, Quantity},
+    types::{Order, Receipt, Side},
+};
+use serde::Serialize;
+
+use crate::{
+    agent::{Action, Agent, AgentKind, MarketMaker, MarketView, Momentum, RandomTaker},
+    rng::Rng,
+    venue::Venue,
+};
+
+/// Parameters of a simulation run
+#[derive(Debug, Clone)]
+pub struct SimConfig {
+    /// Runs with the same seed and configuration against a fresh venue produce the same report
+    pub seed: u64,
+    pub steps: u64,
+    pub market_makers: usize,
+    pub takers: usize,
+    pub momentum_traders: usize,
+    /// Prefix of the agents' signers, change it to run again against the same service
+    pub prefix: String,
+    /// Price the market makers quote around until the market has one
+    pub initial_mid: Price,
+    /// Distance of the makers' quotes from the mid in cents
+    pub half_spread: u64,
+    /// Quantity of each maker quote
+    pub quote_size: u64,
+    /// Probability of a taker trading in a step
+    pub taker_activity: f64,
+    /// Largest taker order
+    pub max_take: u64,
+    /// Number of trades the momentum traders average over
+    pub momentum_window: usize,
+    /// Quantity of each momentum order
+    pub momentum_size: u64,
+    /// Cash every agent starts with, in cents
+    pub deposit: u64,
+    /// Unix timestamp in milliseconds of the first step
+    pub start_millis: u64,
+    /// Simulated time between steps
+    pub step_millis: u64,
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        SimConfig {
+            seed: 1,
+            steps: 1_000,
+            market_makers: 2,
+            takers: 4,
+            momentum_traders: 2,
+            prefix: "SIM".to_string(),
+            initial_mid: Price(10_000),
+            half_spread: 10,
+            quote_size: 10,
+            taker_activity: 0.3,
+            max_take: 5,
+            momentum_window: 5,
+            momentum_size: 2,
+            deposit: 100_000_000,
+            start_millis: 1_700_000_000_000,
+            step_millis: 100,
+        }
+    }
+}
+
+impl SimConfig {
+    /// Creates the agents, market makers first
+    pub fn agents(&self) -> Vec<Box<dyn Agent>> {
+        let signer = |kind: &str, n: usize| format!("{}-{}-{}", self.prefix, kind, n + 1);
+        let mut agents: Vec<Box<dyn Agent>> = vec![];
+        for n in 0..self.market_makers {
+            agents.push(Box::new(MarketMaker::new(
+                &signer("MM", n),
+                self.initial_mid,
+                self.half_spread,
+                self.quote_size,
+            )));
+        }
+        for n in 0..self.takers {
+            agents.push(Box::new(RandomTaker {
+                signer: signer("TAKER", n),
+                activity: self.taker_activity,
+                max_size: self.max_take,
+            }));
+        }
+        for n in 0..self.momentum_traders {
+            agents.push(Box::new(Momentum::new(
+                &signer("MOMENTUM", n),
+                self.momentum_window,
+                self.momentum_size,
+            )));
+        }
+        agents
+    }
+}
+
+/// Results of a single agent
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct AgentReport {
+    pub signer: String,
+    pub kind: AgentKind,
+    /// Orders accepted by the venue
+    pub orders: u64,
+    /// Orders and cancels rejected by the venue
+    pub rejected: u64,
+    /// Number of fills on either side
+    pub fills: u64,
+    /// Traded quantity
+    pub volume: u64,
+    /// Bought minus sold quantity
+    pub position: i64,
+    /// Cash received minus cash paid, including fees, in cents
+    pub cash: i64,
+    /// Fees paid in cents
+    pub fees: u64,
+    /// Cash plus the position valued at the closing price, in cents
+    pub pnl: i64,
+}
+
+/// Summary of a simulation run
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SimReport {
+    pub seed: u64,
+    pub steps: u64,
+    /// Number of fills
+    pub trades: u64,
+    /// Traded quantity
+    pub volume: Quantity,
+    /// Average distance between best bid and best ask in cents, over the steps with both
+    pub average_spread: Option<f64>,
+    /// Steps that started with an empty side of the book
+    pub one_sided_steps: u64,
+    /// Price of the last fill, used to value open positions
+    pub closing_price: Option<Price>,
+    pub agents: Vec<AgentReport>,
+}
+
+/// Bookkeeping of an agent during the run
+#[derive(Debug, Default)]
+struct Tally {
+    orders: u64,
+    rejected: u64,
+    fills: u64,
+    volume: u64,
+    position: i64,
+    cash: i64,
+    fees: u64,
+}
+
+impl Tally {
+    fn fill(&mut self, side: &Side, price: Price, amount: Quantity, fee: u64) {
+        let notional = price.0.saturating_mul(amount.0) as i64;
+        self.fills += 1;
+        self.volume += amount.0;
+        match side {
+            Side::Buy => {
+                self.position += amount.0 as i64;
+                self.cash -= notional;
+            }
+            Side::Sell => {
+                self.position -= amount.0 as i64;
+                self.cash += notional;
+            }
+        }
+        self.cash -= fee as i64;
+        self.fees += fee;
+    }
+}
+
+/// Runs synthetic agents against a [`Venue`]. Each step, all agents act once in a random order.
+pub struct Simulator<V: Venue> {
+    venue: V,
+    config: SimConfig,
+    agents: Vec<Box<dyn Agent>>,
+    tallies: Vec<Tally>,
+    /// Agent index of each resting order
+    owners: HashMap<u64, usize>,
+    rng: Rng,
+    last_trade: Option<Price>,
+    trades: u64,
+    volume: u64,
+}
+
+impl<V: Venue> Simulator<V> {
+    /// Prepares a run with the agents described by the `config`
+    pub fn new(venue: V, config: SimConfig) -> Self {
+        let agents = config.agents();
+        Simulator::with_agents(venue, config, agents)
+    }
+
+    /// Prepares a run with custom agents, the agent counts of the `config` are ignored
+    pub fn with_agents(venue: V, config: SimConfig, agents: Vec<Box<dyn Agent>>) -> Self {
+        Simulator {
+            tallies: agents.iter().map(|_| Tally::default()).collect(),
+            rng: Rng::new(config.seed),
+            venue,
+            config,
+            agents,
+            owners: HashMap::new(),
+            last_trade: None,
+            trades: 0,
+            volume: 0,
+        }
+    }
+
+    /// The venue, e.g. to inspect the platform after a run
+    pub fn venue(&self) -> &V {
+        &self.venue
+    }
+
+    /// Opens and funds the agents' accounts and runs all steps
+    ///
+    /// # Errors
+    /// An account can't be opened or the venue can't be reached
+    pub async fn run(&mut self) -> Result<SimReport, String> {
+        self.venue.set_time(self.config.start_millis);
+        for agent in &self.agents {
+            self.venue
+                .open(agent.signer(), self.config.deposit)
+                .await
+                .map_err(|e| format!("Couldn't open account {}: {}", agent.signer(), e))?;
+        }
+
+        let mut spreads = vec![];
+        let mut one_sided_steps = 0;
+        let mut order: Vec<usize> = (0..self.agents.len()).collect();
+        for step in 0..self.config.steps {
+            let now = self.config.start_millis + step * self.config.step_millis;
+            self.venue.set_time(now);
+            let (best_bid, best_ask) = self.venue.top_of_book().await?;
+            match (best_bid, best_ask) {
+                (Some(bid), Some(ask)) => spreads.push(ask.0.saturating_sub(bid.0)),
+                _ => one_sided_steps += 1,
+            }
+
+            self.rng.shuffle(&mut order);
+            for &index in &order {
+                // Agents see the trades of the agents before them
+                let (best_bid, best_ask) = self.venue.top_of_book().await?;
+                let market = MarketView {
+                    best_bid,
+                    best_ask,
+                    last_trade: self.last_trade,
+                };
+                for action in self.agents[index].act(&market, &mut self.rng) {
+                    self.perform(index, action).await;
+                }
+            }
+        }
+
+        Ok(self.report(spreads, one_sided_steps))
+    }
+
+    async fn perform(&mut self, index: usize, action: Action) {
+        match action {
+            Action::Place(order) => match self.venue.place(order.clone()).await {
+                Ok(receipt) => {
+                    self.tallies[index].orders += 1;
+                    self.settle(index, &order, &receipt);
+                }
+                Err(_) => self.tallies[index].rejected += 1,
+            },
+            Action::Cancel(ordinal) => {
+                let signer = self.agents[index].signer().to_string();
+                if self.venue.cancel(&signer, ordinal).await.is_err() {
+                    self.tallies[index].rejected += 1;
+                }
+                self.owners.remove(&ordinal);
+                self.agents[index].on_done(ordinal);
+            }
+        }
+    }
+
+    /// Books the fills of an accepted order for both sides and tells the agents
+    fn settle(&mut self, taker: usize, order: &Order, receipt: &Receipt) {
+        let mut filled = 0;
+        for (n, m) in receipt.matches.iter().enumerate() {
+            let fee = receipt.fees.get(n);
+            self.tallies[taker].fill(
+                &order.side,
+                m.price,
+                m.amount,
+                fee.map_or(0, |f| f.taker_fee.0),
+            );
+            if let Some(&maker) = self.owners.get(&m.ordinal) {
+                self.tallies[maker].fill(
+                    &m.side,
+                    m.price,
+                    m.amount,
+                    fee.map_or(0, |f| f.maker_fee.0),
+                );
+                if m.remaining == Quantity(0) {
+                    self.owners.remove(&m.ordinal);
+                    self.agents[maker].on_done(m.ordinal);
+                }
+            }
+            filled += m.amount.0;
+            self.trades += 1;
+            self.volume += m.amount.0;
+            self.last_trade = Some(m.price);
+            for agent in &mut self.agents {
+                agent.on_trade(m.price);
+            }
+        }
+        if filled < order.amount.0 {
+            self.owners.insert(receipt.ordinal, taker);
+            self.agents[taker].on_rest(receipt.ordinal);
+        }
+    }
+
+    fn report(&self, spreads: Vec<u64>, one_sided_steps: u64) -> SimReport {
+        let mark = self.last_trade.unwrap_or(self.config.initial_mid).0 as i64;
+        SimReport {
+            seed: self.config.seed,
+            steps: self.config.steps,
+            trades: self.trades,
+            volume: Quantity(self.volume),
+            average_spread: (!spreads.is_empty())
+                .then(|| spreads.iter().sum::<u64>() as f64 / spreads.len() as f64),
+            one_sided_steps,
+            closing_price: self.last_trade,
+            agents: self
+                .agents
+                .iter()
+                .zip(&self.tallies)
+                .map(|(agent, tally)| AgentReport {
+                    signer: agent.signer().to_string(),
+                    kind: agent.kind(),
+                    orders: tally.orders,
+                    rejected: tally.rejected,
+                    fills: tally.fills,
+                    volume: tally.volume,
+                    position: tally.position,
+                    cash: tally.cash,
+                    fees: tally.fees,
+                    pnl: tally.cash + tally.position * mark,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // reduce the warnings for naming tests
+    #![allow(non_snake_case)]
+
+    use super::*;
+    use crate::venue::InProcess;
+    use octopus_common::fees::FeeSchedule;
+    use octopus_web::trading_platform::TradingPlatform;
+
+    fn config(seed: u64) -> SimConfig {
+        SimConfig {
+            seed,
+            steps: 200,
+            ..SimConfig::default()
+        }
+    }
+
+    async fn run(platform: TradingPlatform, config: SimConfig) -> (SimReport, TradingPlatform) {
+        let mut simulator = Simulator::new(InProcess::new(platform), config);
+        let report = simulator.run().await.unwrap();
+        (report, simulator.venue.platform)
+    }
+
+    #[tokio::test]
+    async fn test_Simulator_run_is_reproducible_with_seed() {
+        let (first, _) = run(TradingPlatform::new(), config(7)).await;
+        let (second, _) = run(TradingPlatform::new(), config(7)).await;
+        let (other, _) = run(TradingPlatform::new(), config(8)).await;
+
+        assert_eq!(first, second);
+        assert_ne!(first, other);
+        assert!(first.trades > 0);
+        assert!(first.average_spread.is_some());
+        assert_eq!(first.agents.len(), 8);
+        assert!(first
+            .agents
+            .iter()
+            .filter(|a| a.kind == AgentKind::RandomTaker)
+            .all(|a| a.fills > 0));
+    }
+
+    #[tokio::test]
+    async fn test_Simulator_run_agrees_with_platform_accounts() {
+        let mut platform = TradingPlatform::new();
+        platform.fees = FeeSchedule {
+            maker_bps: 1,
+            taker_bps: 5,
+            tiers: vec![],
+        };
+        let (report, platform) = run(platform, config(3)).await;
+
+        // Every fill has a buyer and a seller among the agents
+        assert_eq!(report.agents.iter().map(|a| a.position).sum::<i64>(), 0);
+        assert_eq!(
+            report.agents.iter().map(|a| a.volume).sum::<u64>(),
+            2 * report.volume.0
+        );
+        // Trading is zero-sum except for the fees
+        let fees: u64 = report.agents.iter().map(|a| a.fees).sum();
+        assert!(fees > 0);
+        assert_eq!(
+            report.agents.iter().map(|a| a.pnl).sum::<i64>(),
+            -(fees as i64)
+        );
+        for agent in &report.agents {
+            let balance = *platform.balance_of(&agent.signer).unwrap() as i64;
+            assert_eq!(balance, SimConfig::default().deposit as i64 + agent.cash);
+        }
+    }
+}
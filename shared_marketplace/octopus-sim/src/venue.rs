@@ -0,0 +1,164 @@
+use std::future::Future;
+
+use octopus_common::{
+    money::Price,
+    types::{
+        AccountOpenRequest, AccountUpdateRequest, CancelRequest, ErrorMessage, Order, PartialOrder,
+        Receipt, Side,
+    },
+};
+use octopus_web::trading_platform::{Clock, TradingPlatform};
+use serde::{de::DeserializeOwned, Serialize};
+
+/// The market the simulated agents trade in
+pub trait Venue {
+    /// Opens an account for `signer` and deposits `amount`
+    fn open(&mut self, signer: &str, amount: u64) -> impl Future<Output = Result<(), String>>;
+
+    /// Places an order
+    fn place(&mut self, order: Order) -> impl Future<Output = Result<Receipt, String>>;
+
+    /// Cancels a resting order of `signer`
+    fn cancel(&mut self, signer: &str, ordinal: u64) -> impl Future<Output = Result<(), String>>;
+
+    /// The best bid and the best ask
+    fn top_of_book(
+        &mut self,
+    ) -> impl Future<Output = Result<(Option<Price>, Option<Price>), String>>;
+
+    /// Moves the venue's clock to the simulated time, if it has one
+    fn set_time(&mut self, _millis: u64) {}
+}
+
+/// A [`TradingPlatform`] in the same process. Its clock follows the simulated time, so runs are reproducible.
+pub struct InProcess {
+    pub platform: TradingPlatform,
+}
+
+impl InProcess {
+    pub fn new(platform: TradingPlatform) -> Self {
+        InProcess { platform }
+    }
+}
+
+impl Venue for InProcess {
+    async fn open(&mut self, signer: &str, amount: u64) -> Result<(), String> {
+        self.platform
+            .open_account(signer, "octopus-sim")
+            .map_err(|e| e.to_string())?;
+        self.platform
+            .deposit(signer, amount)
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    async fn place(&mut self, order: Order) -> Result<Receipt, String> {
+        self.platform.order(order).map_err(|e| e.to_string())
+    }
+
+    async fn cancel(&mut self, signer: &str, ordinal: u64) -> Result<(), String> {
+        self.platform
+            .cancel(signer, ordinal)
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    async fn top_of_book(&mut self) -> Result<(Option<Price>, Option<Price>), String> {
+        let engine = &self.platform.matching_engine;
+        Ok((
+            engine.bids.prices().next_back(),
+            engine.asks.prices().next(),
+        ))
+    }
+
+    fn set_time(&mut self, millis: u64) {
+        self.platform.clock = Clock::Fixed(millis);
+    }
+}
+
+/// A running `octopus-web` service, reached through its HTTP API. Requests are sent one at a time.
+pub struct Http {
+    client: reqwest::Client,
+    base: String,
+}
+
+impl Http {
+    /// Connects to the service at `base`, e.g. `http://localhost:8080`
+    pub fn new(base: &str) -> Self {
+        Http {
+            client: reqwest::Client::new(),
+            base: base.trim_end_matches('/').to_string(),
+        }
+    }
+
+    async fn post<B: Serialize, R: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &B,
+    ) -> Result<R, String> {
+        let response = self
+            .client
+            .post(format!("{}{}", self.base, path))
+            .json(body)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        Http::read(response).await
+    }
+
+    async fn read<R: DeserializeOwned>(response: reqwest::Response) -> Result<R, String> {
+        if response.status().is_success() {
+            response.json().await.map_err(|e| e.to_string())
+        } else {
+            let status = response.status();
+            match response.json::<ErrorMessage>().await {
+                Ok(error) => Err(error.to_string()),
+                Err(_) => Err(status.to_string()),
+            }
+        }
+    }
+}
+
+impl Venue for Http {
+    async fn open(&mut self, signer: &str, amount: u64) -> Result<(), String> {
+        let open = AccountOpenRequest {
+            signer: signer.to_string(),
+            owner: "octopus-sim".to_string(),
+        };
+        self.post::<_, serde_json::Value>("/v1/account/open", &open)
+            .await?;
+        let deposit = AccountUpdateRequest {
+            signer: signer.to_string(),
+            amount,
+        };
+        self.post::<_, serde_json::Value>("/v1/account/deposit", &deposit)
+            .await
+            .map(|_| ())
+    }
+
+    async fn place(&mut self, order: Order) -> Result<Receipt, String> {
+        self.post("/v1/order", &order).await
+    }
+
+    async fn cancel(&mut self, signer: &str, ordinal: u64) -> Result<(), String> {
+        let cancel = CancelRequest {
+            signer: signer.to_string(),
+            ordinal,
+        };
+        self.post::<_, PartialOrder>("/v1/order/cancel", &cancel)
+            .await
+            .map(|_| ())
+    }
+
+    async fn top_of_book(&mut self) -> Result<(Option<Price>, Option<Price>), String> {
+        let response = self
+            .client
+            .get(format!("{}/v1/orderbook", self.base))
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        let book: Vec<PartialOrder> = Http::read(response).await?;
+        let best = |side: Side| book.iter().filter(move |o| o.side == side).map(|o| o.price);
+        Ok((best(Side::Buy).max(), best(Side::Sell).min()))
+    }
+}
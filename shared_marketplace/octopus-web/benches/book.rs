@@ -0,0 +1,307 @@
+//! Compares the FIFO order book of [`MatchingEngine`] with the former `BinaryHeap` per price level on deep books.
+//!
+//! Run with `cargo bench -p octopus-web --bench book`.
+
+use std::{
+    cell::Cell,
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use octopus_common::{
+    money::{Price, Quantity},
+    types::{Order, Side},
+};
+use octopus_web::matching::MatchingEngine;
+
+/// Resting orders per price level
+const ORDERS_PER_LEVEL: u64 = 10;
+/// Number of price levels on each side
+const DEPTHS: [u64; 3] = [10, 100, 1_000];
+
+/// The former order book: a heap ordered by ordinal per price level, cleaned up with `retain` after every order.
+/// Matching follows the same rules as [`MatchingEngine`], so both do the same work.
+mod heap {
+    use std::collections::{BTreeMap, BinaryHeap};
+
+    use octopus_common::{
+        money::{Price, Quantity},
+        types::{Order, PartialOrder, Receipt, Side},
+    };
+
+    #[derive(Default, Clone)]
+    pub struct HeapEngine {
+        ordinal: u64,
+        bids: BTreeMap<Price, BinaryHeap<PartialOrder>>,
+        asks: BTreeMap<Price, BinaryHeap<PartialOrder>>,
+        history: Vec<Receipt>,
+    }
+
+    impl HeapEngine {
+        pub fn process(&mut self, order: Order) -> Receipt {
+            self.ordinal += 1;
+            let original_amount = order.amount;
+            let mut partial = order.into_partial_order(self.ordinal, original_amount);
+            let (opposite, own) = match partial.side {
+                Side::Buy => (&mut self.asks, &mut self.bids),
+                Side::Sell => (&mut self.bids, &mut self.asks),
+            };
+            let matches = match partial.side {
+                Side::Buy => match_order(&partial, opposite.range_mut(Price::MIN..=partial.price)),
+                Side::Sell => match_order(&partial, opposite.range_mut(partial.price..=Price::MAX)),
+            };
+            let matched_amount: Quantity = matches.iter().map(|m| m.amount).sum();
+            if matched_amount < original_amount {
+                partial.amount = original_amount.saturating_sub(matched_amount);
+                partial.remaining = partial.amount;
+                own.entry(partial.price).or_default().push(partial);
+            }
+            self.asks.retain(|_, orders| !orders.is_empty());
+            self.bids.retain(|_, orders| !orders.is_empty());
+            let receipt = Receipt {
+                ordinal: self.ordinal,
+                matches,
+                fees: vec![],
+            };
+            self.history.push(receipt.clone());
+            receipt
+        }
+
+        /// Without an index, the order has to be searched level by level
+        pub fn cancel(&mut self, ordinal: u64) -> Option<PartialOrder> {
+            let mut cancelled = None;
+            for level in self.bids.values_mut().chain(self.asks.values_mut()) {
+                if level.iter().any(|o| o.ordinal == ordinal) {
+                    let (theirs, others): (Vec<_>, Vec<_>) =
+                        level.drain().partition(|o| o.ordinal == ordinal);
+                    cancelled = theirs.into_iter().next();
+                    level.extend(others);
+                    break;
+                }
+            }
+            self.asks.retain(|_, orders| !orders.is_empty());
+            self.bids.retain(|_, orders| !orders.is_empty());
+            cancelled
+        }
+    }
+
+    fn match_order<'a, T>(order: &PartialOrder, levels: T) -> Vec<PartialOrder>
+    where
+        T: Iterator<Item = (&'a Price, &'a mut BinaryHeap<PartialOrder>)>,
+    {
+        let mut remaining_amount = order.amount;
+        let mut matches = vec![];
+        for (price, level) in levels {
+            let mut self_matches = vec![];
+            while let Some(mut pos) = level.pop() {
+                if pos.signer == order.signer {
+                    self_matches.push(pos);
+                    continue;
+                }
+                let take = pos.remaining.min(remaining_amount);
+                matches.push(PartialOrder::take_from(&mut pos, take, *price));
+                remaining_amount = remaining_amount.saturating_sub(take);
+                if !pos.remaining.is_zero() {
+                    level.push(pos);
+                }
+                if remaining_amount.is_zero() {
+                    break;
+                }
+            }
+            level.extend(self_matches);
+            if remaining_amount.is_zero() {
+                break;
+            }
+        }
+        matches
+    }
+}
+
+fn order(side: Side, price: u64, amount: u64, signer: &str) -> Order {
+    Order {
+        price: Price(price),
+        amount: Quantity(amount),
+        side,
+        signer: signer.to_string(),
+    }
+}
+
+/// Resting orders for a book with `depth` levels per side: bids at 1..=depth, asks above them
+fn deep_book(depth: u64) -> Vec<Order> {
+    (0..depth * ORDERS_PER_LEVEL)
+        .flat_map(|n| {
+            let level = n % depth;
+            let signer = format!("MAKER{}", n % 7);
+            [
+                order(Side::Buy, 1 + level, 1, &signer),
+                order(Side::Sell, depth + 1 + level, 1, &signer),
+            ]
+        })
+        .collect()
+}
+
+fn fifo_engine(depth: u64) -> MatchingEngine {
+    let mut engine = MatchingEngine::new();
+    for order in deep_book(depth) {
+        engine.process(order).unwrap();
+    }
+    engine
+}
+
+fn heap_engine(depth: u64) -> heap::HeapEngine {
+    let mut engine = heap::HeapEngine::default();
+    for order in deep_book(depth) {
+        engine.process(order);
+    }
+    engine
+}
+
+/// Both engines behind one interface, so each benchmark runs the same code on them
+trait Engine {
+    /// Processes an order and returns its ordinal
+    fn submit(&mut self, order: Order) -> u64;
+    fn remove(&mut self, ordinal: u64);
+}
+
+impl Engine for MatchingEngine {
+    fn submit(&mut self, order: Order) -> u64 {
+        self.process(order).unwrap().ordinal
+    }
+
+    fn remove(&mut self, ordinal: u64) {
+        self.cancel(ordinal);
+    }
+}
+
+impl Engine for heap::HeapEngine {
+    fn submit(&mut self, order: Order) -> u64 {
+        self.process(order).ordinal
+    }
+
+    fn remove(&mut self, ordinal: u64) {
+        self.cancel(ordinal);
+    }
+}
+
+/// Times `op` on a long-lived engine (cloned engines are allocated tightly and would measure their first
+/// reallocation), restoring the book with `restore` outside of the measurement
+fn steady<E, O, R>(engine: &mut E, iters: u64, mut op: O, mut restore: R) -> Duration
+where
+    E: Engine,
+    O: FnMut(&mut E) -> u64,
+    R: FnMut(&mut E, u64),
+{
+    let mut total = Duration::ZERO;
+    for _ in 0..iters {
+        let start = Instant::now();
+        let ordinal = black_box(op(engine));
+        total += start.elapsed();
+        restore(engine, ordinal);
+    }
+    total
+}
+
+/// Adding a passive order to a deep book
+fn insert(c: &mut Criterion) {
+    let mut group = c.benchmark_group("deep_book/insert");
+    for depth in DEPTHS {
+        let passive = || order(Side::Buy, depth / 2, 1, "TAKER");
+        let mut fifo = fifo_engine(depth);
+        group.bench_with_input(BenchmarkId::new("fifo", depth), &depth, |b, _| {
+            b.iter_custom(|iters| steady(&mut fifo, iters, |e| e.submit(passive()), Engine::remove))
+        });
+        let mut heap = heap_engine(depth);
+        group.bench_with_input(BenchmarkId::new("heap", depth), &depth, |b, _| {
+            b.iter_custom(|iters| steady(&mut heap, iters, |e| e.submit(passive()), Engine::remove))
+        });
+    }
+    group.finish();
+}
+
+/// An aggressive order sweeping a quarter of the ask levels
+fn sweep(c: &mut Criterion) {
+    let mut group = c.benchmark_group("deep_book/sweep");
+    for depth in DEPTHS {
+        let levels = (depth / 4).max(1);
+        let aggressive = || {
+            order(
+                Side::Buy,
+                depth + levels,
+                levels * ORDERS_PER_LEVEL,
+                "TAKER",
+            )
+        };
+        let refill = |engine: &mut dyn Engine| {
+            for n in 0..levels * ORDERS_PER_LEVEL {
+                let price = depth + 1 + n % levels;
+                engine.submit(order(Side::Sell, price, 1, "MAKER"));
+            }
+        };
+        let mut fifo = fifo_engine(depth);
+        group.bench_with_input(BenchmarkId::new("fifo", depth), &depth, |b, _| {
+            b.iter_custom(|iters| {
+                steady(
+                    &mut fifo,
+                    iters,
+                    |e| e.submit(aggressive()),
+                    |e, _| refill(e),
+                )
+            })
+        });
+        let mut heap = heap_engine(depth);
+        group.bench_with_input(BenchmarkId::new("heap", depth), &depth, |b, _| {
+            b.iter_custom(|iters| {
+                steady(
+                    &mut heap,
+                    iters,
+                    |e| e.submit(aggressive()),
+                    |e, _| refill(e),
+                )
+            })
+        });
+    }
+    group.finish();
+}
+
+/// Cancelling an order in the middle of the book
+fn cancel(c: &mut Criterion) {
+    let mut group = c.benchmark_group("deep_book/cancel");
+    for depth in DEPTHS {
+        let resting = || order(Side::Buy, depth / 2, 1, "MAKER");
+        let mut fifo = fifo_engine(depth);
+        let ordinal = Cell::new(fifo.submit(resting()));
+        group.bench_with_input(BenchmarkId::new("fifo", depth), &depth, |b, _| {
+            b.iter_custom(|iters| {
+                steady(
+                    &mut fifo,
+                    iters,
+                    |e| {
+                        e.remove(ordinal.get());
+                        ordinal.get()
+                    },
+                    |e, _| ordinal.set(e.submit(resting())),
+                )
+            })
+        });
+        let mut heap = heap_engine(depth);
+        let ordinal = Cell::new(heap.submit(resting()));
+        group.bench_with_input(BenchmarkId::new("heap", depth), &depth, |b, _| {
+            b.iter_custom(|iters| {
+                steady(
+                    &mut heap,
+                    iters,
+                    |e| {
+                        e.remove(ordinal.get());
+                        ordinal.get()
+                    },
+                    |e, _| ordinal.set(e.submit(resting())),
+                )
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, insert, sweep, cancel);
+criterion_main!(benches);
@@ -0,0 +1,216 @@
+//! Throughput of order entry while other clients read the order book and the history, comparing the former
+//! `Arc<Mutex<TradingPlatform>>` setup with the [`Pipeline`].
+//!
+//! Run with `cargo bench -p octopus-web --bench pipeline`.
+
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use octopus_common::{
+    money::{Price, Quantity},
+    types::{Order, Side},
+};
+use octopus_web::{
+    pipeline::{Command, Pipeline, DEFAULT_QUEUE_CAPACITY},
+    trading_platform::TradingPlatform,
+};
+use tokio::sync::{watch, Mutex};
+
+const WRITERS: u64 = 8;
+const ORDERS_PER_WRITER: u64 = 250;
+/// Resting orders far away from the traded prices, so reading the book isn't free
+const RESTING_ORDERS: u64 = 2_000;
+
+fn signer(i: u64) -> String {
+    format!("SIGNER{}", i)
+}
+
+/// Orders of writer `i`: even writers buy, odd writers sell around a price of 100
+fn order(i: u64, n: u64) -> Order {
+    Order {
+        price: Price(98 + n % 5),
+        amount: Quantity(1),
+        side: if i.is_multiple_of(2) {
+            Side::Buy
+        } else {
+            Side::Sell
+        },
+        signer: signer(i),
+    }
+}
+
+fn platform() -> TradingPlatform {
+    let mut platform = TradingPlatform::new();
+    for i in 0..=WRITERS {
+        platform.open_account(&signer(i), "bench").unwrap();
+        platform.deposit(&signer(i), u64::MAX / 4).unwrap();
+    }
+    for n in 0..RESTING_ORDERS {
+        let (side, price) = if n.is_multiple_of(2) {
+            (Side::Buy, 1 + n % 50)
+        } else {
+            (Side::Sell, 1_000 + n % 50)
+        };
+        platform
+            .order(Order {
+                price: Price(price),
+                amount: Quantity(1),
+                side,
+                signer: signer(WRITERS),
+            })
+            .unwrap();
+    }
+    platform
+}
+
+/// A single read of a client
+type BoxedRead = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Spawns `readers` tasks that keep serializing the book and the history until `done` is set
+fn spawn_readers<F>(
+    readers: usize,
+    done: &watch::Receiver<bool>,
+    read: F,
+) -> Vec<tokio::task::JoinHandle<()>>
+where
+    F: Fn() -> BoxedRead + Send + Sync + 'static,
+{
+    let read = Arc::new(read);
+    (0..readers)
+        .map(|_| {
+            let done = done.clone();
+            let read = Arc::clone(&read);
+            tokio::spawn(async move {
+                while !*done.borrow() {
+                    read().await;
+                }
+            })
+        })
+        .collect()
+}
+
+async fn mutex_load(readers: usize) -> Duration {
+    let db = Arc::new(Mutex::new(platform()));
+    let (finished, done) = watch::channel(false);
+    let reader_db = Arc::clone(&db);
+    let reader_tasks = spawn_readers(readers, &done, move || {
+        let db = Arc::clone(&reader_db);
+        Box::pin(async move {
+            // Like the former handlers, serialize while holding the lock
+            let platform = db.lock().await;
+            let _ = warp::reply::json(&platform.orderbook());
+            let _ = warp::reply::json(&platform.matching_engine.history);
+        })
+    });
+
+    let start = Instant::now();
+    let writers: Vec<_> = (0..WRITERS)
+        .map(|i| {
+            let db = Arc::clone(&db);
+            tokio::spawn(async move {
+                for n in 0..ORDERS_PER_WRITER {
+                    let receipt = db.lock().await.order(order(i, n));
+                    let _ = warp::reply::json(&receipt.unwrap());
+                }
+            })
+        })
+        .collect();
+    for writer in writers {
+        writer.await.unwrap();
+    }
+    let elapsed = start.elapsed();
+
+    finished.send_replace(true);
+    for reader in reader_tasks {
+        reader.await.unwrap();
+    }
+    elapsed
+}
+
+async fn pipeline_load(readers: usize) -> Duration {
+    let pipeline = Pipeline::spawn(platform(), DEFAULT_QUEUE_CAPACITY);
+    let (finished, done) = watch::channel(false);
+    let reader_pipeline = pipeline.clone();
+    let reader_tasks = spawn_readers(readers, &done, move || {
+        let snapshot = reader_pipeline.snapshot();
+        Box::pin(async move {
+            let _ = warp::reply::json(&snapshot.orderbook);
+            let _ = warp::reply::json(&snapshot.history);
+            tokio::task::yield_now().await;
+        })
+    });
+
+    let start = Instant::now();
+    let writers: Vec<_> = (0..WRITERS)
+        .map(|i| {
+            let pipeline = pipeline.clone();
+            tokio::spawn(async move {
+                for n in 0..ORDERS_PER_WRITER {
+                    let outcome = pipeline.execute(Command::Order(order(i, n))).await;
+                    let _ = warp::reply::json(&outcome.unwrap());
+                }
+            })
+        })
+        .collect();
+    for writer in writers {
+        writer.await.unwrap();
+    }
+    let elapsed = start.elapsed();
+
+    finished.send_replace(true);
+    for reader in reader_tasks {
+        reader.await.unwrap();
+    }
+    elapsed
+}
+
+fn concurrent_load(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(4)
+        .enable_all()
+        .build()
+        .unwrap();
+    let mut group = c.benchmark_group("concurrent_load");
+    group.throughput(Throughput::Elements(WRITERS * ORDERS_PER_WRITER));
+    group.sample_size(10);
+    for readers in [0, 4, 16] {
+        group.bench_with_input(
+            BenchmarkId::new("mutex", readers),
+            &readers,
+            |b, &readers| {
+                b.to_async(&runtime)
+                    .iter_custom(|iters| timed(iters, readers, mutex_load))
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("pipeline", readers),
+            &readers,
+            |b, &readers| {
+                b.to_async(&runtime)
+                    .iter_custom(|iters| timed(iters, readers, pipeline_load))
+            },
+        );
+    }
+    group.finish();
+}
+
+/// Sums up the durations of `iters` runs, leaving out the setup of each run
+async fn timed<F, Fut>(iters: u64, readers: usize, load: F) -> Duration
+where
+    F: Fn(usize) -> Fut,
+    Fut: Future<Output = Duration>,
+{
+    let mut total = Duration::ZERO;
+    for _ in 0..iters {
+        total += load(readers).await;
+    }
+    total
+}
+
+criterion_group!(benches, concurrent_load);
+criterion_main!(benches);
@@ -0,0 +1,10 @@
+use std::{env, error::Error};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    // Use the bundled compiler unless one is configured, so builds don't depend on a protoc installation
+    if env::var_os("PROTOC").is_none() {
+        env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+    }
+    tonic_build::compile_protos("proto/octopus.proto")?;
+    Ok(())
+}
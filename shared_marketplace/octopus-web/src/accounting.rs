@@ -1,10 +1,19 @@
-use octopus_common::{errors::ApplicationError, tx::Tx};
-use std::collections::HashMap;
+use octopus_common::{
+    errors::ApplicationError,
+    ledger::{LedgerAccount, Posting, SystemAccount, TrialBalance, TrialBalanceLine},
+    tx::Tx,
+    types::{AccountMetadata, AccountStatus},
+};
+use std::collections::{BTreeMap, HashMap};
 
-/// A type for managing accounts and their current currency balance
+/// A type for managing accounts and their current currency balance. Every change is booked as a double-entry [`Posting`]
+/// that debits one account and credits another, so the sum of all balances (including the [`SystemAccount`]s) is always zero.
 #[derive(Debug, Default)]
 pub struct Accounts {
     accounts: HashMap<String, u64>,
+    metadata: HashMap<String, AccountMetadata>,
+    system: HashMap<SystemAccount, i128>,
+    journal: Vec<Posting>,
 }
 
 impl Accounts {
@@ -12,6 +21,9 @@ impl Accounts {
     pub fn new() -> Self {
         Accounts {
             accounts: HashMap::new(),
+            metadata: HashMap::new(),
+            system: HashMap::new(),
+            journal: Vec::new(),
         }
     }
 
@@ -22,61 +34,169 @@ impl Accounts {
             .ok_or(ApplicationError::AccountNotFound(signer.to_string()))
     }
 
-    /// Either deposits the `amount` provided into the `signer` account or adds the amount to the existing account.
+    /// Retrieves owner, creation time and status of an account
+    pub fn metadata_of(&self, signer: &str) -> Result<&AccountMetadata, ApplicationError> {
+        self.metadata
+            .get(signer)
+            .ok_or(ApplicationError::AccountNotFound(signer.to_string()))
+    }
+
+    /// The signers of all accounts, including frozen and closed ones, in no particular order
+    pub fn signers(&self) -> impl Iterator<Item = &str> {
+        self.accounts.keys().map(|signer| signer.as_str())
+    }
+
+    /// Opens a new account with a zero balance.
+    ///
     /// # Errors
-    /// Attempted overflow
-    pub fn deposit(&mut self, signer: &str, amount: u64) -> Result<Tx, ApplicationError> {
-        if let Some(account) = self.accounts.get_mut(signer) {
-            (*account)
-                .checked_add(amount)
-                .and_then(|r| {
-                    *account = r;
-                    Some(r)
-                })
-                .ok_or(ApplicationError::AccountOverFunded(
-                    signer.to_string(),
-                    amount,
-                ))
-                // Using map() here is an easy way to only manipulate the non-error result
-                .map(|_| Tx::Deposit {
-                    account: signer.to_string(),
-                    amount,
-                })
-        } else {
-            self.accounts.insert(signer.to_string(), amount);
-            Ok(Tx::Deposit {
-                account: signer.to_string(),
-                amount,
-            })
+    /// An account with the same signer was opened before (even if it's closed now)
+    pub fn open(
+        &mut self,
+        signer: &str,
+        owner: &str,
+        created_at: u64,
+    ) -> Result<&AccountMetadata, ApplicationError> {
+        if self.accounts.contains_key(signer) {
+            return Err(ApplicationError::AccountAlreadyExists(signer.to_string()));
         }
+        self.accounts.insert(signer.to_string(), 0);
+        Ok(self
+            .metadata
+            .entry(signer.to_string())
+            .or_insert(AccountMetadata {
+                owner: owner.to_string(),
+                created_at,
+                status: AccountStatus::Open,
+            }))
     }
 
-    /// Withdraws the `amount` from the `signer` account.
+    /// Freezes an open account so it can still receive funds but not withdraw, send or trade.
+    ///
     /// # Errors
-    /// Attempted overflow
-    pub fn withdraw(&mut self, signer: &str, amount: u64) -> Result<Tx, ApplicationError> {
-        if let Some(account) = self.accounts.get_mut(signer) {
-            (*account)
-                .checked_sub(amount)
-                .and_then(|r| {
-                    *account = r;
-                    Some(r)
-                })
-                .ok_or(ApplicationError::AccountUnderFunded(
-                    signer.to_string(),
-                    amount,
-                ))
-                // Using map() here is an easy way to only manipulate the non-error result
-                .map(|_| Tx::Withdraw {
-                    account: signer.to_string(),
-                    amount,
-                })
-        } else {
-            Err(ApplicationError::AccountNotFound(signer.to_string()))
+    /// The account doesn't exist or is closed
+    pub fn freeze(&mut self, signer: &str) -> Result<&AccountMetadata, ApplicationError> {
+        self.transition(signer, AccountStatus::Frozen)
+    }
+
+    /// Lifts a freeze from an account.
+    ///
+    /// # Errors
+    /// The account doesn't exist or is closed
+    pub fn unfreeze(&mut self, signer: &str) -> Result<&AccountMetadata, ApplicationError> {
+        self.transition(signer, AccountStatus::Open)
+    }
+
+    /// Closes an account for good. The account and its history are kept but it can't be used anymore.
+    ///
+    /// # Errors
+    /// - The account doesn't exist or is closed already
+    /// - The balance isn't zero
+    pub fn close(&mut self, signer: &str) -> Result<&AccountMetadata, ApplicationError> {
+        match self.balance_of(signer)? {
+            0 => self.transition(signer, AccountStatus::Closed),
+            balance => Err(ApplicationError::AccountNotEmpty(
+                signer.to_string(),
+                *balance,
+            )),
+        }
+    }
+
+    /// Checks whether the account is allowed to give away funds, i.e. it's open
+    ///
+    /// # Errors
+    /// The account doesn't exist, is frozen or closed
+    pub fn ensure_active(&self, signer: &str) -> Result<(), ApplicationError> {
+        match self.metadata_of(signer)?.status {
+            AccountStatus::Open => Ok(()),
+            AccountStatus::Frozen => Err(ApplicationError::AccountFrozen(signer.to_string())),
+            AccountStatus::Closed => Err(ApplicationError::AccountClosed(signer.to_string())),
         }
     }
 
-    /// Withdraws the amount from the sender account and deposits it in the recipient account.
+    /// Checks whether the account is allowed to receive funds, i.e. it isn't closed
+    ///
+    /// # Errors
+    /// The account doesn't exist or is closed
+    pub fn ensure_not_closed(&self, signer: &str) -> Result<(), ApplicationError> {
+        match self.metadata_of(signer)?.status {
+            AccountStatus::Closed => Err(ApplicationError::AccountClosed(signer.to_string())),
+            _ => Ok(()),
+        }
+    }
+
+    fn transition(
+        &mut self,
+        signer: &str,
+        status: AccountStatus,
+    ) -> Result<&AccountMetadata, ApplicationError> {
+        self.ensure_not_closed(signer)?;
+        let metadata = self
+            .metadata
+            .get_mut(signer)
+            .ok_or(ApplicationError::AccountNotFound(signer.to_string()))?;
+        metadata.status = status;
+        Ok(metadata)
+    }
+
+    /// Retrieves the balance of a system account. Deposits from outside the platform make [`SystemAccount::External`] negative.
+    pub fn system_balance(&self, account: SystemAccount) -> i128 {
+        self.system.get(&account).copied().unwrap_or(0)
+    }
+
+    /// All postings in the order they were booked
+    pub fn journal(&self) -> &[Posting] {
+        &self.journal
+    }
+
+    /// Deposits the `amount` provided into the existing `signer` account. The funds are taken from [`SystemAccount::External`].
+    /// # Errors
+    /// - The account doesn't exist or is closed
+    /// - Attempted overflow
+    pub fn deposit(&mut self, signer: &str, amount: u64) -> Result<Tx, ApplicationError> {
+        self.post(
+            LedgerAccount::System(SystemAccount::External),
+            LedgerAccount::Customer(signer.to_string()),
+            amount,
+        )
+        .map(|_| Tx::Deposit {
+            account: signer.to_string(),
+            amount,
+        })
+    }
+
+    /// Withdraws the `amount` from the `signer` account into [`SystemAccount::External`].
+    /// # Errors
+    /// - The account doesn't exist, is frozen or closed
+    /// - Attempted underflow
+    pub fn withdraw(&mut self, signer: &str, amount: u64) -> Result<Tx, ApplicationError> {
+        self.post(
+            LedgerAccount::Customer(signer.to_string()),
+            LedgerAccount::System(SystemAccount::External),
+            amount,
+        )
+        .map(|_| Tx::Withdraw {
+            account: signer.to_string(),
+            amount,
+        })
+    }
+
+    /// Charges a trading fee to the `signer` account and credits it to [`SystemAccount::Fees`].
+    /// # Errors
+    /// - The account doesn't exist, is frozen or closed
+    /// - Attempted underflow
+    pub fn charge_fee(&mut self, signer: &str, amount: u64) -> Result<Tx, ApplicationError> {
+        self.post(
+            LedgerAccount::Customer(signer.to_string()),
+            LedgerAccount::System(SystemAccount::Fees),
+            amount,
+        )
+        .map(|_| Tx::Fee {
+            account: signer.to_string(),
+            amount,
+        })
+    }
+
+    /// Moves the amount from the sender account to the recipient account in a single posting.
     ///
     /// # Errors
     /// The account doesn't exist
@@ -86,30 +206,167 @@ impl Accounts {
         recipient: &str,
         amount: u64,
     ) -> Result<(Tx, Tx), ApplicationError> {
-        if self.accounts.contains_key(sender)  // sender exists
-            && self.accounts.contains_key(recipient) // recipient exists
-            && self
-                .accounts
-                .get(sender)
-                .map(|amt| *amt >= amount) // sender has sufficient funds
-                .unwrap_or(false)
-        {
-            // The ? operator is a built-in shorthand for
-            // if let Err(e) = my_func_call() { return Err(e); }
-            let tx_withdraw = self.withdraw(sender, amount)?;
-            self.deposit(recipient, amount)
-                .map_err(|e| {
-                    // return the funds to the sender on error
-                    self.deposit(sender, amount).unwrap();
-                    e
-                })
-                .map(|tx_deposit| (tx_withdraw, tx_deposit))
-        } else {
-            if !self.accounts.contains_key(sender) {
-                Err(ApplicationError::AccountNotFound(sender.to_string()))
-            } else {
-                Err(ApplicationError::AccountNotFound(recipient.to_string()))
+        self.post(
+            LedgerAccount::Customer(sender.to_string()),
+            LedgerAccount::Customer(recipient.to_string()),
+            amount,
+        )
+        .map(|_| {
+            (
+                Tx::Withdraw {
+                    account: sender.to_string(),
+                    amount,
+                },
+                Tx::Deposit {
+                    account: recipient.to_string(),
+                    amount,
+                },
+            )
+        })
+    }
+
+    /// Books a [`Posting`] that moves `amount` from `debit` to `credit`. Either both sides are applied or neither.
+    ///
+    /// # Errors
+    /// - A customer account doesn't exist
+    /// - The debited customer account is frozen or closed, or the credited one is closed
+    /// - The debited customer account has insufficient funds
+    /// - The credited customer account would overflow
+    pub fn post(
+        &mut self,
+        debit: LedgerAccount,
+        credit: LedgerAccount,
+        amount: u64,
+    ) -> Result<&Posting, ApplicationError> {
+        // Validate both sides before touching any balance
+        let (debited, credited) = self.validate(&debit, &credit, amount, &HashMap::new())?;
+
+        match (&debit, debited) {
+            (LedgerAccount::Customer(signer), Some(balance)) => {
+                self.accounts.insert(signer.clone(), balance);
+            }
+            (LedgerAccount::System(account), _) => {
+                *self.system.entry(*account).or_insert(0) -= amount as i128;
+            }
+            _ => unreachable!("customer balances are validated above"),
+        }
+        match (&credit, credited) {
+            (LedgerAccount::Customer(signer), Some(balance)) => {
+                self.accounts.insert(signer.clone(), balance);
+            }
+            (LedgerAccount::System(account), _) => {
+                *self.system.entry(*account).or_insert(0) += amount as i128;
             }
+            _ => unreachable!("customer balances are validated above"),
+        }
+
+        self.journal.push(Posting {
+            id: self.journal.len() as u64 + 1,
+            debit,
+            credit,
+            amount,
+        });
+        debug_assert_eq!(self.verify(), Ok(()));
+        Ok(self.journal.last().expect("a posting was just added"))
+    }
+
+    /// Checks that the postings could be booked one after another, without booking any of them.
+    ///
+    /// # Errors
+    /// The first posting that [`Accounts::post`] would reject
+    pub fn check_postings(
+        &self,
+        postings: &[(LedgerAccount, LedgerAccount, u64)],
+    ) -> Result<(), ApplicationError> {
+        let mut pending = HashMap::new();
+        for (debit, credit, amount) in postings {
+            let (debited, credited) = self.validate(debit, credit, *amount, &pending)?;
+            for (account, balance) in [(debit, debited), (credit, credited)] {
+                if let (LedgerAccount::Customer(signer), Some(balance)) = (account, balance) {
+                    pending.insert(signer.as_str(), balance);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Computes the customer balances after moving `amount` from `debit` to `credit`, starting from the `pending`
+    /// balances where there are any
+    fn validate(
+        &self,
+        debit: &LedgerAccount,
+        credit: &LedgerAccount,
+        amount: u64,
+        pending: &HashMap<&str, u64>,
+    ) -> Result<(Option<u64>, Option<u64>), ApplicationError> {
+        let balance_of = |signer: &str| match pending.get(signer) {
+            Some(balance) => Ok(*balance),
+            None => self.balance_of(signer).copied(),
+        };
+        let debited = match debit {
+            LedgerAccount::Customer(signer) => Some({
+                self.ensure_active(signer)?;
+                balance_of(signer)?
+                    .checked_sub(amount)
+                    .ok_or(ApplicationError::AccountUnderFunded(signer.clone(), amount))?
+            }),
+            LedgerAccount::System(_) => None,
+        };
+        let credited = match credit {
+            LedgerAccount::Customer(signer) => Some({
+                self.ensure_not_closed(signer)?;
+                // A posting from an account to itself credits the balance left after the debit
+                let balance = match (debit, debited) {
+                    (LedgerAccount::Customer(sender), Some(balance)) if sender == signer => balance,
+                    _ => balance_of(signer)?,
+                };
+                balance
+                    .checked_add(amount)
+                    .ok_or(ApplicationError::AccountOverFunded(signer.clone(), amount))?
+            }),
+            LedgerAccount::System(_) => None,
+        };
+        Ok((debited, credited))
+    }
+
+    /// Checks the double-entry invariant: the sum of all balances must be zero.
+    ///
+    /// # Errors
+    /// The sum of all balances isn't zero
+    pub fn verify(&self) -> Result<(), ApplicationError> {
+        let sum = self.accounts.values().map(|b| *b as i128).sum::<i128>()
+            + self.system.values().sum::<i128>();
+        if sum == 0 {
+            Ok(())
+        } else {
+            Err(ApplicationError::LedgerImbalance(sum))
+        }
+    }
+
+    /// Summarizes the journal into debit and credit totals per account
+    pub fn trial_balance(&self) -> TrialBalance {
+        let mut totals: BTreeMap<LedgerAccount, (u128, u128)> = BTreeMap::new();
+        for posting in &self.journal {
+            totals.entry(posting.debit.clone()).or_default().0 += posting.amount as u128;
+            totals.entry(posting.credit.clone()).or_default().1 += posting.amount as u128;
+        }
+        let lines: Vec<TrialBalanceLine> = totals
+            .into_iter()
+            .map(|(account, (debits, credits))| TrialBalanceLine {
+                account,
+                debits,
+                credits,
+                balance: credits as i128 - debits as i128,
+            })
+            .collect();
+        let total_debits = lines.iter().map(|l| l.debits).sum();
+        let total_credits = lines.iter().map(|l| l.credits).sum();
+
+        TrialBalance {
+            lines,
+            total_debits,
+            total_credits,
+            balanced: total_debits == total_credits && self.verify().is_ok(),
         }
     }
 }
@@ -121,7 +378,7 @@ mod tests {
     #[test]
     fn test_accounts_withdraw_underfunded() {
         let mut accounts = Accounts::new();
-        accounts.deposit("a-key", 0).unwrap();
+        accounts.open("a-key", "owner", 0).expect("Couldn't open");
         let actual = accounts.withdraw("a-key", 100);
         assert_eq!(
             actual,
@@ -135,6 +392,7 @@ mod tests {
     #[test]
     fn test_accounts_deposit_overfunded() {
         let mut accounts = Accounts::new();
+        accounts.open("a-key", "owner", 0).expect("Couldn't open");
         accounts
             .deposit("a-key", 1)
             .expect("Initial deposit failed");
@@ -151,6 +409,7 @@ mod tests {
     #[test]
     fn test_accounts_deposit_works() {
         let mut accounts = Accounts::new();
+        accounts.open("a-key", "owner", 0).expect("Couldn't open");
         let amt = 100;
         let actual = accounts.deposit("a-key", amt);
         assert_eq!(
@@ -165,6 +424,7 @@ mod tests {
     #[test]
     fn test_accounts_withdraw_works() {
         let mut accounts = Accounts::new();
+        accounts.open("a-key", "owner", 0).expect("Couldn't open");
         let amt = 100;
         accounts.deposit("a-key", amt).expect("Couldn't deposit");
         let actual = accounts.withdraw("a-key", amt);
@@ -180,12 +440,11 @@ mod tests {
     #[test]
     fn test_accounts_send_works() {
         let mut accounts = Accounts::new();
+        accounts.open("a-key", "owner", 0).expect("Couldn't open");
+        accounts.open("b-key", "owner", 0).expect("Couldn't open");
         let amt = 100;
         accounts.deposit("a-key", amt).expect("Couldn't deposit");
 
-        // creating the receiver is also required
-        accounts.deposit("b-key", 0).expect("Couldn't deposit");
-
         let (tx1, tx2) = accounts.send("a-key", "b-key", amt).expect("Send failed");
         assert_eq!(
             tx1,
@@ -215,12 +474,11 @@ mod tests {
     #[test]
     fn test_accounts_send_underfunded_fails_and_rolls_back() {
         let mut accounts = Accounts::new();
+        accounts.open("a-key", "owner", 0).expect("Couldn't open");
+        accounts.open("b-key", "owner", 0).expect("Couldn't open");
         let amt = 100;
         accounts.deposit("a-key", amt).expect("Couldn't deposit");
 
-        // creating the receiver is also required
-        accounts.deposit("b-key", 0).expect("Couldn't deposit");
-
         let actual = accounts.send("a-key", "b-key", amt + 1);
         assert!(actual.is_err());
         let expected: HashMap<String, u64> =
@@ -230,13 +488,29 @@ mod tests {
         assert_eq!(accounts.accounts, expected);
     }
 
+    #[test]
+    fn test_accounts_send_unknown_recipient_fails() {
+        let mut accounts = Accounts::new();
+        accounts.open("a-key", "owner", 0).expect("Couldn't open");
+        accounts.deposit("a-key", 100).expect("Couldn't deposit");
+
+        let actual = accounts.send("a-key", "b-key", 1);
+        assert_eq!(
+            actual,
+            Err(ApplicationError::AccountNotFound("b-key".to_string()))
+        );
+        assert_eq!(accounts.balance_of("a-key"), Ok(&100));
+    }
+
     #[test]
     fn test_accounts_send_overfunded_fails_and_rolls_back() {
         let mut accounts = Accounts::new();
+        accounts.open("a-key", "owner", 0).expect("Couldn't open");
+        accounts.open("b-key", "owner", 0).expect("Couldn't open");
         let amt = 100;
         accounts.deposit("a-key", amt).expect("Couldn't deposit");
 
-        // creating the receiver is also required
+        // The receiver can't take another cent
         accounts
             .deposit("b-key", u64::MAX)
             .expect("Couldn't deposit");
@@ -249,4 +523,196 @@ mod tests {
                 .collect();
         assert_eq!(accounts.accounts, expected);
     }
+
+    #[test]
+    fn test_accounts_send_to_self_keeps_balance() {
+        let mut accounts = Accounts::new();
+        accounts.open("a-key", "owner", 0).expect("Couldn't open");
+        accounts.deposit("a-key", 100).expect("Couldn't deposit");
+
+        accounts.send("a-key", "a-key", 50).expect("Send failed");
+        assert_eq!(accounts.balance_of("a-key"), Ok(&100));
+        assert_eq!(accounts.verify(), Ok(()));
+
+        // The funds still have to be there
+        assert_eq!(
+            accounts.send("a-key", "a-key", 101),
+            Err(ApplicationError::AccountUnderFunded(
+                "a-key".to_string(),
+                101
+            ))
+        );
+        assert_eq!(accounts.balance_of("a-key"), Ok(&100));
+    }
+
+    #[test]
+    fn test_accounts_postings_keep_ledger_balanced() {
+        let mut accounts = Accounts::new();
+        accounts.open("a-key", "owner", 0).expect("Couldn't open");
+        accounts.open("b-key", "owner", 0).expect("Couldn't open");
+        accounts.deposit("a-key", 100).expect("Couldn't deposit");
+        accounts.deposit("b-key", 50).expect("Couldn't deposit");
+        accounts.send("a-key", "b-key", 30).expect("Send failed");
+        accounts.withdraw("b-key", 20).expect("Couldn't withdraw");
+        accounts
+            .post(
+                LedgerAccount::Customer("a-key".to_string()),
+                LedgerAccount::System(SystemAccount::Fees),
+                5,
+            )
+            .expect("Couldn't post fee");
+
+        assert_eq!(accounts.journal().len(), 5);
+        assert_eq!(accounts.verify(), Ok(()));
+        assert_eq!(accounts.system_balance(SystemAccount::External), -130);
+        assert_eq!(accounts.system_balance(SystemAccount::Fees), 5);
+
+        let trial_balance = accounts.trial_balance();
+        assert!(trial_balance.balanced);
+        assert_eq!(trial_balance.total_debits, 205);
+        assert_eq!(trial_balance.total_credits, 205);
+        assert_eq!(
+            trial_balance.lines,
+            vec![
+                TrialBalanceLine {
+                    account: LedgerAccount::Customer("a-key".to_string()),
+                    debits: 35,
+                    credits: 100,
+                    balance: 65,
+                },
+                TrialBalanceLine {
+                    account: LedgerAccount::Customer("b-key".to_string()),
+                    debits: 20,
+                    credits: 80,
+                    balance: 60,
+                },
+                TrialBalanceLine {
+                    account: LedgerAccount::System(SystemAccount::External),
+                    debits: 150,
+                    credits: 20,
+                    balance: -130,
+                },
+                TrialBalanceLine {
+                    account: LedgerAccount::System(SystemAccount::Fees),
+                    debits: 0,
+                    credits: 5,
+                    balance: 5,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_accounts_failed_posting_is_not_journaled() {
+        let mut accounts = Accounts::new();
+        accounts.open("a-key", "owner", 0).expect("Couldn't open");
+        accounts.deposit("a-key", 10).expect("Couldn't deposit");
+
+        assert!(accounts.withdraw("a-key", 11).is_err());
+        assert!(accounts.withdraw("c-key", 1).is_err());
+        assert_eq!(accounts.journal().len(), 1);
+        assert_eq!(accounts.system_balance(SystemAccount::External), -10);
+        assert_eq!(accounts.verify(), Ok(()));
+    }
+
+    #[test]
+    fn test_accounts_check_postings_applies_them_in_order_without_booking() {
+        let mut accounts = Accounts::new();
+        accounts.open("a-key", "owner", 0).expect("Couldn't open");
+        accounts.open("b-key", "owner", 0).expect("Couldn't open");
+        accounts.deposit("a-key", 10).expect("Couldn't deposit");
+
+        let customer = |signer: &str| LedgerAccount::Customer(signer.to_string());
+        let fees = LedgerAccount::System(SystemAccount::Fees);
+        // b-key can only pay the fee from what a-key sent it before
+        let postings = [
+            (customer("a-key"), customer("b-key"), 8),
+            (customer("b-key"), fees.clone(), 3),
+        ];
+        assert_eq!(accounts.check_postings(&postings), Ok(()));
+        assert_eq!(
+            accounts.check_postings(&[postings[0].clone(), (customer("a-key"), fees, 3)]),
+            Err(ApplicationError::AccountUnderFunded("a-key".to_string(), 3))
+        );
+        assert_eq!(
+            accounts.check_postings(&[(customer("b-key"), customer("a-key"), 1)]),
+            Err(ApplicationError::AccountUnderFunded("b-key".to_string(), 1))
+        );
+
+        assert_eq!(accounts.journal().len(), 1);
+        assert_eq!(accounts.balance_of("a-key"), Ok(&10));
+        assert_eq!(accounts.balance_of("b-key"), Ok(&0));
+    }
+
+    #[test]
+    fn test_accounts_deposit_requires_open_account() {
+        let mut accounts = Accounts::new();
+        assert_eq!(
+            accounts.deposit("a-key", 1),
+            Err(ApplicationError::AccountNotFound("a-key".to_string()))
+        );
+
+        accounts.open("a-key", "owner", 42).expect("Couldn't open");
+        assert_eq!(
+            accounts.open("a-key", "someone else", 43),
+            Err(ApplicationError::AccountAlreadyExists("a-key".to_string()))
+        );
+        assert_eq!(
+            accounts.metadata_of("a-key"),
+            Ok(&AccountMetadata {
+                owner: "owner".to_string(),
+                created_at: 42,
+                status: AccountStatus::Open,
+            })
+        );
+        assert!(accounts.deposit("a-key", 1).is_ok());
+    }
+
+    #[test]
+    fn test_accounts_frozen_account_can_only_receive() {
+        let mut accounts = Accounts::new();
+        accounts.open("a-key", "owner", 0).expect("Couldn't open");
+        accounts.open("b-key", "owner", 0).expect("Couldn't open");
+        accounts.deposit("a-key", 100).expect("Couldn't deposit");
+        accounts.deposit("b-key", 100).expect("Couldn't deposit");
+
+        accounts.freeze("a-key").expect("Couldn't freeze");
+        let frozen = || Err(ApplicationError::AccountFrozen("a-key".to_string()));
+        assert_eq!(accounts.withdraw("a-key", 1).map(|_| ()), frozen());
+        assert_eq!(accounts.send("a-key", "b-key", 1).map(|_| ()), frozen());
+        assert_eq!(accounts.ensure_active("a-key"), frozen());
+
+        assert!(accounts.deposit("a-key", 1).is_ok());
+        assert!(accounts.send("b-key", "a-key", 1).is_ok());
+        assert_eq!(accounts.balance_of("a-key"), Ok(&102));
+
+        accounts.unfreeze("a-key").expect("Couldn't unfreeze");
+        assert!(accounts.withdraw("a-key", 2).is_ok());
+    }
+
+    #[test]
+    fn test_accounts_close_requires_zero_balance() {
+        let mut accounts = Accounts::new();
+        accounts.open("a-key", "owner", 0).expect("Couldn't open");
+        accounts.deposit("a-key", 10).expect("Couldn't deposit");
+
+        assert_eq!(
+            accounts.close("a-key"),
+            Err(ApplicationError::AccountNotEmpty("a-key".to_string(), 10))
+        );
+        accounts.withdraw("a-key", 10).expect("Couldn't withdraw");
+        assert_eq!(
+            accounts.close("a-key").map(|m| m.status),
+            Ok(AccountStatus::Closed)
+        );
+
+        let closed = || Err(ApplicationError::AccountClosed("a-key".to_string()));
+        assert_eq!(accounts.deposit("a-key", 1).map(|_| ()), closed());
+        assert_eq!(accounts.unfreeze("a-key").map(|_| ()), closed());
+        assert_eq!(accounts.close("a-key").map(|_| ()), closed());
+        assert_eq!(
+            accounts.open("a-key", "owner", 0).map(|_| ()),
+            Err(ApplicationError::AccountAlreadyExists("a-key".to_string()))
+        );
+    }
 }
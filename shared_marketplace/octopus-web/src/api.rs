@@ -0,0 +1,1133 @@
+//! The HTTP interface of the trading platform. Every route is versioned under `/v1` and described by the OpenAPI
+//! document served at `/openapi.json`. Prometheus metrics are served at `/metrics`. Operators freeze and close accounts
+//! and change the trading phase on a separate admin interface, see [`admin_routes`].
+
+use std::{
+    collections::BTreeMap, convert::Infallible, future::Future, net::SocketAddr, pin::Pin,
+    sync::Arc,
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use octopus_common::{
+    errors::{ApplicationError, ErrorCode},
+    fees::FeeSchedule,
+    instrument::InstrumentSpec,
+    ledger::TrialBalance,
+    tx::{Statement, StatementQuery, Tx, TxKind},
+    types::{
+        AccountMetadata, AccountOpenRequest, AccountRequest, AccountUpdateRequest, AccountView,
+        AuctionIndication, AuctionResult, CancelRequest, ErrorMessage, FieldError, MarketState,
+        OctopusError, Order, PartialOrder, Receipt, SendRequest, Side, UncrossRequest,
+    },
+};
+use percent_encoding::percent_decode_str;
+use serde::{de::DeserializeOwned, Deserialize};
+use utoipa::OpenApi;
+use warp::{
+    body,
+    hyper::{body::Bytes, StatusCode},
+    reject::Reject,
+    Filter, Rejection, Reply,
+};
+
+use crate::{
+    config::TlsConfig,
+    gateway::same_secret,
+    metrics,
+    pipeline::{Caller, Command, Pipeline},
+};
+
+/// The OpenAPI document of all routes
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Octopus",
+        description = "Accounts, order entry and market data of the trading platform. Prices and other amounts of money are decimal strings in the major currency unit."
+    ),
+    paths(
+        account,
+        statement,
+        open,
+        deposit,
+        withdraw,
+        send,
+        order,
+        cancel,
+        history,
+        orderbook,
+        market,
+        fees,
+        market_state,
+        auction_indication,
+        trial_balance
+    ),
+    // Query parameters only reference their schemas
+    components(schemas(TxKind)),
+    tags(
+        (name = "accounts", description = "Opening, funding and closing accounts"),
+        (name = "orders", description = "Placing and cancelling orders"),
+        (name = "market", description = "Order book, trading rules and trading phases"),
+        (name = "ledger", description = "Double-entry bookkeeping")
+    )
+)]
+pub struct ApiDoc;
+
+/// The OpenAPI document of the operator routes, see [`admin_routes`]
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Octopus operations",
+        description = "Freezing and closing accounts and changing the trading phase. Every request carries the name and password of an operator with HTTP basic authentication."
+    ),
+    paths(freeze, unfreeze, close, halt, resume, start_auction, uncross),
+    // Schemas the auction results only reference
+    components(schemas(Side)),
+    tags(
+        (name = "accounts", description = "Freezing and closing accounts"),
+        (name = "market", description = "Trading phases")
+    )
+)]
+pub struct AdminDoc;
+
+/// An operator that may use the [`admin_routes`]
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Operator {
+    /// The password its requests have to carry
+    pub password: String,
+}
+
+impl Operator {
+    /// Whether `password` is the operator's password, compared with [`same_secret`]
+    pub fn accepts(&self, password: &str) -> bool {
+        same_secret(&self.password, password)
+    }
+}
+
+/// All routes under `/v1` plus `/openapi.json` and `/metrics`, with errors rendered as [`ErrorMessage`]s. Every request is
+/// traced and its latency recorded per route.
+pub fn routes(
+    pipeline: Pipeline,
+) -> impl Filter<Extract = (impl Reply,), Error = Infallible> + Clone {
+    let account_path = warp::path("account");
+
+    let balance_route = account_path
+        .and(signer())
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(with_pipeline(pipeline.clone()))
+        .and_then(account);
+
+    let statement_route = account_path
+        .and(signer())
+        .and(warp::path("transactions"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(with_pipeline(pipeline.clone()))
+        .and(warp::query::<StatementQuery>())
+        .and_then(statement);
+
+    let open_route = account_path
+        .and(warp::path("open"))
+        .and(warp::post())
+        .and(with_pipeline(pipeline.clone()))
+        .and(json_body::<AccountOpenRequest>())
+        .and_then(open);
+
+    let withdraw_route = account_path
+        .and(warp::path("withdraw"))
+        .and(warp::post())
+        .and(with_pipeline(pipeline.clone()))
+        .and(json_body::<AccountUpdateRequest>())
+        .and_then(withdraw);
+
+    let deposit_route = account_path
+        .and(warp::path("deposit"))
+        .and(warp::post())
+        .and(with_pipeline(pipeline.clone()))
+        .and(json_body::<AccountUpdateRequest>())
+        .and_then(deposit);
+
+    let send_route = account_path
+        .and(warp::path("send"))
+        .and(warp::post())
+        .and(with_pipeline(pipeline.clone()))
+        .and(json_body::<SendRequest>())
+        .and_then(send);
+
+    let order_path = warp::path("order");
+    let order_route = order_path
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(with_pipeline(pipeline.clone()))
+        .and(json_body::<Order>())
+        .and_then(order);
+
+    let cancel_route = order_path
+        .and(warp::path("cancel"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(with_pipeline(pipeline.clone()))
+        .and(json_body::<CancelRequest>())
+        .and_then(cancel);
+
+    let history_route = order_path
+        .and(warp::path("history"))
+        .and(warp::get())
+        .and(with_pipeline(pipeline.clone()))
+        .and_then(history);
+
+    let orderbook_path = warp::path("orderbook");
+    let orderbook_route = orderbook_path
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(with_pipeline(pipeline.clone()))
+        .and_then(orderbook);
+
+    let market_route = warp::path("market")
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(with_pipeline(pipeline.clone()))
+        .and_then(market);
+
+    let fees_route = warp::path("market")
+        .and(warp::path("fees"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(with_pipeline(pipeline.clone()))
+        .and_then(fees);
+
+    let market_state_route = warp::path("market")
+        .and(warp::path("state"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(with_pipeline(pipeline.clone()))
+        .and_then(market_state);
+
+    let auction_route = warp::path("market")
+        .and(warp::path("auction"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(with_pipeline(pipeline.clone()))
+        .and_then(auction_indication);
+
+    let trial_balance_route = warp::path("ledger")
+        .and(warp::path("trial-balance"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(with_pipeline(pipeline.clone()))
+        .and_then(trial_balance);
+
+    let account_route = balance_route
+        .or(statement_route)
+        .or(open_route)
+        .or(withdraw_route)
+        .or(deposit_route)
+        .or(send_route)
+        .or(order_route)
+        .or(cancel_route)
+        .or(history_route)
+        .or(orderbook_route)
+        .or(trial_balance_route)
+        .or(market_route)
+        .or(fees_route)
+        .or(market_state_route)
+        .or(auction_route);
+
+    let openapi_route = warp::path("openapi.json")
+        .and(warp::path::end())
+        .and(warp::get())
+        .map(|| warp::reply::json(&ApiDoc::openapi()));
+
+    let metrics_route = warp::path("metrics")
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(with_pipeline(pipeline.clone()))
+        .map(|pipeline: Pipeline| {
+            warp::reply::with_header(
+                pipeline.render_metrics(),
+                "content-type",
+                metrics::CONTENT_TYPE,
+            )
+        });
+
+    let observe = observe(pipeline, ApiDoc::openapi(), &["/openapi.json", "/metrics"]);
+    warp::path("v1")
+        .and(account_route)
+        .or(openapi_route)
+        .or(metrics_route)
+        .recover(error_handler)
+        .with(observe)
+        .with(warp::trace::request())
+}
+
+/// The operator routes under `/v1` plus their own `/openapi.json`. Every request has to carry the name and password of
+/// one of `operators` with HTTP basic authentication, the commands are audited with the operator's name.
+pub fn admin_routes(
+    pipeline: Pipeline,
+    operators: BTreeMap<String, Operator>,
+) -> impl Filter<Extract = (impl Reply,), Error = Infallible> + Clone {
+    let operators = Arc::new(operators);
+    let account_path = warp::path("account");
+
+    let freeze_route = account_path
+        .and(warp::path("freeze"))
+        .and(warp::post())
+        .and(with_operator(pipeline.clone(), operators.clone()))
+        .and(json_body::<AccountRequest>())
+        .and_then(freeze);
+
+    let unfreeze_route = account_path
+        .and(warp::path("unfreeze"))
+        .and(warp::post())
+        .and(with_operator(pipeline.clone(), operators.clone()))
+        .and(json_body::<AccountRequest>())
+        .and_then(unfreeze);
+
+    let close_route = account_path
+        .and(warp::path("close"))
+        .and(warp::post())
+        .and(with_operator(pipeline.clone(), operators.clone()))
+        .and(json_body::<AccountRequest>())
+        .and_then(close);
+
+    let halt_route = warp::path("market")
+        .and(warp::path("halt"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(with_operator(pipeline.clone(), operators.clone()))
+        .and_then(halt);
+
+    let resume_route = warp::path("market")
+        .and(warp::path("resume"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(with_operator(pipeline.clone(), operators.clone()))
+        .and_then(resume);
+
+    let auction_start_route = warp::path("market")
+        .and(warp::path("auction"))
+        .and(warp::path("start"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(with_operator(pipeline.clone(), operators.clone()))
+        .and_then(start_auction);
+
+    let uncross_route = warp::path("market")
+        .and(warp::path("auction"))
+        .and(warp::path("uncross"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(with_operator(pipeline.clone(), operators.clone()))
+        .and(json_body::<UncrossRequest>())
+        .and_then(uncross);
+
+    let operator_route = freeze_route
+        .or(unfreeze_route)
+        .or(close_route)
+        .or(halt_route)
+        .or(resume_route)
+        .or(auction_start_route)
+        .or(uncross_route);
+
+    let openapi_route = warp::path("openapi.json")
+        .and(warp::path::end())
+        .and(warp::get())
+        .map(|| warp::reply::json(&AdminDoc::openapi()));
+
+    let observe = observe(pipeline, AdminDoc::openapi(), &["/openapi.json"]);
+    warp::path("v1")
+        .and(operator_route)
+        .or(openapi_route)
+        .recover(error_handler)
+        .with(observe)
+        .with(warp::trace::request())
+}
+
+/// Records the latency of every request per route, labelled with the paths of `doc` and `extra`
+fn observe(
+    pipeline: Pipeline,
+    doc: utoipa::openapi::OpenApi,
+    extra: &[&str],
+) -> warp::log::Log<impl Fn(warp::log::Info<'_>) + Clone> {
+    let mut templates: Vec<String> = doc
+        .paths
+        .paths
+        .into_keys()
+        .chain(extra.iter().map(|path| path.to_string()))
+        .collect();
+    templates.sort();
+    let templates: Arc<[String]> = templates.into();
+    warp::log::custom(move |info| {
+        pipeline.metrics().observe_request(
+            route_template(&templates, info.path()),
+            info.method().as_str(),
+            info.status().as_u16(),
+            info.elapsed(),
+        )
+    })
+}
+
+/// A bound HTTP server, running until it's shut down
+pub type Server = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Binds [`routes`] to `address`, over HTTPS if `tls` has a certificate. Clients have to present a certificate issued by
+/// `tls.client_ca` if it is set. The returned server stops accepting connections once `shutdown` completes.
+pub fn serve(
+    pipeline: Pipeline,
+    address: SocketAddr,
+    tls: &TlsConfig,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> Result<(SocketAddr, Server), String> {
+    let server = warp::serve(routes(pipeline));
+    let (Some(cert), Some(key)) = (&tls.cert, &tls.key) else {
+        let (address, server) = server
+            .try_bind_with_graceful_shutdown(address, shutdown)
+            .map_err(|e| format!("Couldn't bind the HTTP address {}: {}", address, e))?;
+        return Ok((address, Box::pin(server)));
+    };
+    let server = server.tls().cert_path(cert).key_path(key);
+    let server = match &tls.client_ca {
+        Some(client_ca) => server.client_auth_required_path(client_ca),
+        None => server,
+    };
+    let (address, server) = server
+        .try_bind_with_graceful_shutdown(address, shutdown)
+        .map_err(|e| format!("Couldn't serve HTTPS on {}: {}", address, e))?;
+    Ok((address, Box::pin(server)))
+}
+
+/// Binds [`admin_routes`] to `address`. The admin interface is only served over mutual TLS: operators have to present a
+/// certificate issued by `tls.client_ca` besides their password.
+pub fn serve_admin(
+    pipeline: Pipeline,
+    address: SocketAddr,
+    tls: &TlsConfig,
+    operators: BTreeMap<String, Operator>,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> Result<(SocketAddr, Server), String> {
+    let (Some(cert), Some(key), Some(client_ca)) = (&tls.cert, &tls.key, &tls.client_ca) else {
+        return Err("The admin interface requires tls.cert, tls.key and tls.client_ca".to_string());
+    };
+    let (address, server) = warp::serve(admin_routes(pipeline, operators))
+        .tls()
+        .cert_path(cert)
+        .key_path(key)
+        .client_auth_required_path(client_ca)
+        .try_bind_with_graceful_shutdown(address, shutdown)
+        .map_err(|e| format!("Couldn't serve the admin interface on {}: {}", address, e))?;
+    Ok((address, Box::pin(server)))
+}
+
+/// The template in `templates` that `path` belongs to, so metrics aren't labelled with signers. Templates are sorted, so
+/// literal segments like `open` are tried before parameters like `{signer}`.
+fn route_template<'a>(templates: &'a [String], path: &str) -> &'a str {
+    let segments: Vec<&str> = path.split('/').collect();
+    templates
+        .iter()
+        .find(|template| {
+            let parts: Vec<&str> = template.split('/').collect();
+            parts.len() == segments.len()
+                && parts
+                    .iter()
+                    .zip(&segments)
+                    .all(|(part, segment)| part.starts_with('{') || part == segment)
+        })
+        .map_or("unmatched", String::as_str)
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/account/{signer}",
+    tag = "accounts",
+    params(("signer" = String, Path, description = "Signer of the account")),
+    responses(
+        (status = 200, description = "Balances and metadata of the account", body = AccountView),
+        (status = 404, description = "The account doesn't exist", body = ErrorMessage),
+        (status = 503, description = "The matching engine is unavailable", body = ErrorMessage)
+    )
+)]
+async fn account(signer: String, pipeline: Pipeline) -> Result<impl Reply, Rejection> {
+    execute(&pipeline, Command::Account { signer }).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/account/{signer}/transactions",
+    tag = "accounts",
+    params(("signer" = String, Path, description = "Signer of the account"), StatementQuery),
+    responses(
+        (status = 200, description = "The account's transactions, oldest first", body = Statement),
+        (status = 400, description = "The query is invalid", body = ErrorMessage),
+        (status = 404, description = "The account doesn't exist", body = ErrorMessage),
+        (status = 503, description = "The matching engine is unavailable", body = ErrorMessage)
+    )
+)]
+async fn statement(
+    signer: String,
+    pipeline: Pipeline,
+    query: StatementQuery,
+) -> Result<impl Reply, Rejection> {
+    execute(&pipeline, Command::Statement { signer, query }).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/account/open",
+    tag = "accounts",
+    request_body = AccountOpenRequest,
+    responses(
+        (status = 200, description = "The account was opened", body = AccountMetadata),
+        (status = 400, description = "The body is invalid", body = ErrorMessage),
+        (status = 409, description = "The account exists already", body = ErrorMessage),
+        (status = 503, description = "The matching engine is unavailable", body = ErrorMessage)
+    )
+)]
+async fn open(pipeline: Pipeline, req: AccountOpenRequest) -> Result<impl Reply, Rejection> {
+    let command = Command::OpenAccount {
+        signer: req.signer,
+        owner: req.owner,
+    };
+    execute(&pipeline, command).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/account/freeze",
+    tag = "accounts",
+    request_body = AccountRequest,
+    responses(
+        (status = 200, description = "The account was frozen", body = AccountMetadata),
+        (status = 400, description = "The body is invalid", body = ErrorMessage),
+        (status = 401, description = "The request doesn't carry the credentials of an operator", body = ErrorMessage),
+        (status = 404, description = "The account doesn't exist", body = ErrorMessage),
+        (status = 410, description = "The account is closed", body = ErrorMessage),
+        (status = 503, description = "The matching engine is unavailable", body = ErrorMessage)
+    )
+)]
+async fn freeze(pipeline: Pipeline, req: AccountRequest) -> Result<impl Reply, Rejection> {
+    execute(&pipeline, Command::FreezeAccount { signer: req.signer }).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/account/unfreeze",
+    tag = "accounts",
+    request_body = AccountRequest,
+    responses(
+        (status = 200, description = "The account was unfrozen", body = AccountMetadata),
+        (status = 400, description = "The body is invalid", body = ErrorMessage),
+        (status = 401, description = "The request doesn't carry the credentials of an operator", body = ErrorMessage),
+        (status = 404, description = "The account doesn't exist", body = ErrorMessage),
+        (status = 410, description = "The account is closed", body = ErrorMessage),
+        (status = 503, description = "The matching engine is unavailable", body = ErrorMessage)
+    )
+)]
+async fn unfreeze(pipeline: Pipeline, req: AccountRequest) -> Result<impl Reply, Rejection> {
+    execute(&pipeline, Command::UnfreezeAccount { signer: req.signer }).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/account/close",
+    tag = "accounts",
+    request_body = AccountRequest,
+    responses(
+        (status = 200, description = "The account was closed", body = AccountMetadata),
+        (status = 400, description = "The body is invalid", body = ErrorMessage),
+        (status = 401, description = "The request doesn't carry the credentials of an operator", body = ErrorMessage),
+        (status = 404, description = "The account doesn't exist", body = ErrorMessage),
+        (status = 409, description = "The account has a balance", body = ErrorMessage),
+        (status = 410, description = "The account is closed", body = ErrorMessage),
+        (status = 503, description = "The matching engine is unavailable", body = ErrorMessage)
+    )
+)]
+async fn close(pipeline: Pipeline, req: AccountRequest) -> Result<impl Reply, Rejection> {
+    execute(&pipeline, Command::CloseAccount { signer: req.signer }).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/account/deposit",
+    tag = "accounts",
+    request_body = AccountUpdateRequest,
+    responses(
+        (status = 200, description = "The recorded deposit", body = Tx),
+        (status = 400, description = "The body is invalid", body = ErrorMessage),
+        (status = 404, description = "The account doesn't exist", body = ErrorMessage),
+        (status = 410, description = "The account is closed", body = ErrorMessage),
+        (status = 422, description = "The balance would overflow", body = ErrorMessage),
+        (status = 503, description = "The matching engine is unavailable", body = ErrorMessage)
+    )
+)]
+async fn deposit(pipeline: Pipeline, req: AccountUpdateRequest) -> Result<impl Reply, Rejection> {
+    let command = Command::Deposit {
+        signer: req.signer,
+        amount: req.amount,
+    };
+    execute(&pipeline, command).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/account/withdraw",
+    tag = "accounts",
+    request_body = AccountUpdateRequest,
+    responses(
+        (status = 200, description = "The recorded withdrawal", body = Tx),
+        (status = 400, description = "The body is invalid", body = ErrorMessage),
+        (status = 403, description = "The account is frozen", body = ErrorMessage),
+        (status = 404, description = "The account doesn't exist", body = ErrorMessage),
+        (status = 410, description = "The account is closed", body = ErrorMessage),
+        (status = 422, description = "The available balance is too low", body = ErrorMessage),
+        (status = 503, description = "The matching engine is unavailable", body = ErrorMessage)
+    )
+)]
+async fn withdraw(pipeline: Pipeline, req: AccountUpdateRequest) -> Result<impl Reply, Rejection> {
+    let command = Command::Withdraw {
+        signer: req.signer,
+        amount: req.amount,
+    };
+    execute(&pipeline, command).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/account/send",
+    tag = "accounts",
+    request_body = SendRequest,
+    responses(
+        (status = 200, description = "The withdrawal from the sender and the deposit to the recipient", body = [Tx]),
+        (status = 400, description = "The body is invalid", body = ErrorMessage),
+        (status = 403, description = "The account is frozen", body = ErrorMessage),
+        (status = 404, description = "The account doesn't exist", body = ErrorMessage),
+        (status = 410, description = "The account is closed", body = ErrorMessage),
+        (status = 422, description = "The available balance is too low or the recipient's would overflow", body = ErrorMessage),
+        (status = 503, description = "The matching engine is unavailable", body = ErrorMessage)
+    )
+)]
+async fn send(pipeline: Pipeline, req: SendRequest) -> Result<impl Reply, Rejection> {
+    let command = Command::Send {
+        sender: req.sender,
+        recipient: req.recipient,
+        amount: req.amount,
+    };
+    execute(&pipeline, command).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/order",
+    tag = "orders",
+    request_body = Order,
+    responses(
+        (status = 200, description = "The order was accepted", body = Receipt),
+        (status = 400, description = "The body is invalid or the order breaks the market's trading rules", body = ErrorMessage),
+        (status = 403, description = "The account is frozen", body = ErrorMessage),
+        (status = 404, description = "The account doesn't exist", body = ErrorMessage),
+        (status = 409, description = "The market doesn't accept orders", body = ErrorMessage),
+        (status = 410, description = "The account is closed", body = ErrorMessage),
+        (status = 422, description = "The available balance is too low or the order exceeds a risk limit", body = ErrorMessage),
+        (status = 429, description = "The signer sent too many orders", body = ErrorMessage),
+        (status = 503, description = "The matching engine is unavailable", body = ErrorMessage)
+    )
+)]
+async fn order(pipeline: Pipeline, order: Order) -> Result<impl Reply, Rejection> {
+    execute(&pipeline, Command::Order(order)).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/order/cancel",
+    tag = "orders",
+    request_body = CancelRequest,
+    responses(
+        (status = 200, description = "The cancelled order with its remaining quantity", body = PartialOrder),
+        (status = 400, description = "The body is invalid", body = ErrorMessage),
+        (status = 404, description = "The signer has no such resting order", body = ErrorMessage),
+        (status = 503, description = "The matching engine is unavailable", body = ErrorMessage)
+    )
+)]
+async fn cancel(pipeline: Pipeline, req: CancelRequest) -> Result<impl Reply, Rejection> {
+    let command = Command::Cancel {
+        signer: req.signer,
+        ordinal: req.ordinal,
+    };
+    execute(&pipeline, command).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/order/history",
+    tag = "orders",
+    responses(
+        (status = 200, description = "Receipts of all accepted orders, oldest first", body = [Receipt])
+    )
+)]
+async fn history(pipeline: Pipeline) -> Result<impl Reply, Infallible> {
+    Ok(warp::reply::json(&(pipeline.snapshot().history)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/orderbook",
+    tag = "market",
+    responses(
+        (status = 200, description = "All resting orders, asks first", body = [PartialOrder])
+    )
+)]
+async fn orderbook(pipeline: Pipeline) -> Result<impl Reply, Infallible> {
+    Ok(warp::reply::json(&(pipeline.snapshot().orderbook)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/market",
+    tag = "market",
+    responses(
+        (status = 200, description = "Trading rules of the market", body = InstrumentSpec)
+    )
+)]
+async fn market(pipeline: Pipeline) -> Result<impl Reply, Infallible> {
+    Ok(warp::reply::json(&(pipeline.snapshot().instrument)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/market/fees",
+    tag = "market",
+    responses(
+        (status = 200, description = "The market's fee schedule", body = FeeSchedule)
+    )
+)]
+async fn fees(pipeline: Pipeline) -> Result<impl Reply, Infallible> {
+    Ok(warp::reply::json(&(pipeline.snapshot().fees)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/market/state",
+    tag = "market",
+    responses(
+        (status = 200, description = "The market's trading phase", body = MarketState)
+    )
+)]
+async fn market_state(pipeline: Pipeline) -> Result<impl Reply, Infallible> {
+    Ok(warp::reply::json(&(pipeline.snapshot().market_state)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/market/halt",
+    tag = "market",
+    responses(
+        (status = 200, description = "Trading is halted", body = MarketState),
+        (status = 401, description = "The request doesn't carry the credentials of an operator", body = ErrorMessage),
+        (status = 409, description = "The market isn't open", body = ErrorMessage),
+        (status = 503, description = "The matching engine is unavailable", body = ErrorMessage)
+    )
+)]
+async fn halt(pipeline: Pipeline) -> Result<impl Reply, Rejection> {
+    execute(&pipeline, Command::Halt).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/market/resume",
+    tag = "market",
+    responses(
+        (status = 200, description = "Trading continues", body = MarketState),
+        (status = 401, description = "The request doesn't carry the credentials of an operator", body = ErrorMessage),
+        (status = 409, description = "The market isn't halted", body = ErrorMessage),
+        (status = 503, description = "The matching engine is unavailable", body = ErrorMessage)
+    )
+)]
+async fn resume(pipeline: Pipeline) -> Result<impl Reply, Rejection> {
+    execute(&pipeline, Command::Resume).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/market/auction",
+    tag = "market",
+    responses(
+        (status = 200, description = "The price the running auction would uncross at, if any", body = Option<AuctionIndication>)
+    )
+)]
+async fn auction_indication(pipeline: Pipeline) -> Result<impl Reply, Infallible> {
+    Ok(warp::reply::json(&(pipeline.snapshot().indication)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/market/auction/start",
+    tag = "market",
+    responses(
+        (status = 200, description = "Orders are collected without matching", body = MarketState),
+        (status = 401, description = "The request doesn't carry the credentials of an operator", body = ErrorMessage),
+        (status = 409, description = "The market is halted or already in an auction", body = ErrorMessage),
+        (status = 503, description = "The matching engine is unavailable", body = ErrorMessage)
+    )
+)]
+async fn start_auction(pipeline: Pipeline) -> Result<impl Reply, Rejection> {
+    execute(&pipeline, Command::StartAuction).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/market/auction/uncross",
+    tag = "market",
+    request_body = UncrossRequest,
+    responses(
+        (status = 200, description = "The fills of the auction, if it uncrossed", body = Option<AuctionResult>),
+        (status = 400, description = "The body is invalid", body = ErrorMessage),
+        (status = 401, description = "The request doesn't carry the credentials of an operator", body = ErrorMessage),
+        (status = 409, description = "There is no auction running, or `next` is an auction", body = ErrorMessage),
+        (status = 503, description = "The matching engine is unavailable", body = ErrorMessage)
+    )
+)]
+async fn uncross(pipeline: Pipeline, req: UncrossRequest) -> Result<impl Reply, Rejection> {
+    execute(&pipeline, Command::Uncross { next: req.next }).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/ledger/trial-balance",
+    tag = "ledger",
+    responses(
+        (status = 200, description = "Debits and credits of all ledger accounts", body = TrialBalance),
+        (status = 500, description = "The ledger is out of balance", body = ErrorMessage),
+        (status = 503, description = "The matching engine is unavailable", body = ErrorMessage)
+    )
+)]
+async fn trial_balance(pipeline: Pipeline) -> Result<impl Reply, Rejection> {
+    execute(&pipeline, Command::TrialBalance).await
+}
+
+/// Runs a [`Command`] through the matching task and replies with its outcome. Serialization happens here, outside of the matching task.
+async fn execute(pipeline: &Pipeline, command: Command) -> Result<warp::reply::Json, Rejection> {
+    match pipeline.execute(command).await {
+        Ok(outcome) => Ok(warp::reply::json(&outcome)),
+        Err(msg) => Err(warp::reject::custom(OctopusError::new(msg))),
+    }
+}
+
+/// A request of an admin route without the credentials of an operator
+#[derive(Debug)]
+struct Unauthorized;
+
+impl Reject for Unauthorized {}
+
+/// A request body that couldn't be deserialized
+#[derive(Debug)]
+struct InvalidBody(FieldError);
+
+impl Reject for InvalidBody {}
+
+/// Like [`body::json`], but rejects with the path of the field that couldn't be deserialized
+fn json_body<T: DeserializeOwned + Send>() -> impl Filter<Extract = (T,), Error = Rejection> + Clone
+{
+    body::bytes().and_then(|bytes: Bytes| async move {
+        let deserializer = &mut serde_json::Deserializer::from_slice(&bytes);
+        serde_path_to_error::deserialize(deserializer)
+            .map_err(|e| warp::reject::custom(InvalidBody(field_error(e))))
+    })
+}
+
+fn field_error(error: serde_path_to_error::Error<serde_json::Error>) -> FieldError {
+    let mut field = match error.path().to_string() {
+        root if root == "." => String::new(),
+        path => path,
+    };
+    // serde_json appends the position, which means nothing to most clients
+    let message = error.inner().to_string();
+    let message = match message.rsplit_once(" at line ") {
+        Some((message, _)) => message.to_owned(),
+        None => message,
+    };
+    // Missing fields are reported for the object that lacks them
+    if let Some(missing) = message
+        .strip_prefix("missing field `")
+        .and_then(|rest| rest.strip_suffix('`'))
+    {
+        if !field.is_empty() {
+            field.push('.');
+        }
+        field.push_str(missing);
+    }
+    FieldError { field, message }
+}
+
+/// The field of an order an [`ApplicationError`] is about, if any
+fn order_field(error: &ApplicationError) -> Option<&'static str> {
+    match error {
+        ApplicationError::InvalidPrice(..) | ApplicationError::PriceOutsideCollar(..) => {
+            Some("price")
+        }
+        ApplicationError::InvalidLotSize(..)
+        | ApplicationError::QuantityOutOfRange(..)
+        | ApplicationError::OrderSizeLimitExceeded(..) => Some("amount"),
+        _ => None,
+    }
+}
+
+/// The HTTP status of an [`ErrorCode`]
+fn status(error: ErrorCode) -> StatusCode {
+    match error {
+        ErrorCode::InvalidBody
+        | ErrorCode::InvalidQuery
+        | ErrorCode::NotionalOverflow
+        | ErrorCode::InvalidPrice
+        | ErrorCode::InvalidLotSize
+        | ErrorCode::QuantityOutOfRange
+        | ErrorCode::NotionalTooSmall => StatusCode::BAD_REQUEST,
+        ErrorCode::AccountFrozen => StatusCode::FORBIDDEN,
+        ErrorCode::NotFound | ErrorCode::AccountNotFound | ErrorCode::OrderNotFound => {
+            StatusCode::NOT_FOUND
+        }
+        ErrorCode::Unauthorized => StatusCode::UNAUTHORIZED,
+        ErrorCode::MethodNotAllowed => StatusCode::METHOD_NOT_ALLOWED,
+        ErrorCode::AccountAlreadyExists
+        | ErrorCode::AccountNotEmpty
+        | ErrorCode::MarketHalted
+        | ErrorCode::MarketClosed
+        | ErrorCode::NoAuction
+        | ErrorCode::InvalidMarketTransition => StatusCode::CONFLICT,
+        ErrorCode::AccountClosed => StatusCode::GONE,
+        ErrorCode::AccountUnderFunded
+        | ErrorCode::AccountOverFunded
+        | ErrorCode::OrderSizeLimitExceeded
+        | ErrorCode::NotionalLimitExceeded
+        | ErrorCode::OpenOrderLimitExceeded
+        | ErrorCode::PriceOutsideCollar => StatusCode::UNPROCESSABLE_ENTITY,
+        ErrorCode::RateLimitExceeded => StatusCode::TOO_MANY_REQUESTS,
+        ErrorCode::LedgerImbalance | ErrorCode::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        ErrorCode::EngineUnavailable => StatusCode::SERVICE_UNAVAILABLE,
+    }
+}
+
+async fn error_handler(err: Rejection) -> Result<impl Reply, Infallible> {
+    let error;
+    let message: String;
+    let mut details = vec![];
+
+    if err.is_not_found() {
+        error = ErrorCode::NotFound;
+        message = "There is no route for this path".to_owned();
+    } else if let Some(InvalidBody(detail)) = err.find() {
+        error = ErrorCode::InvalidBody;
+        message = "The request body is invalid".to_owned();
+        details.push(detail.clone());
+    } else if let Some(e) = err.find::<warp::reject::InvalidQuery>() {
+        error = ErrorCode::InvalidQuery;
+        message = e.to_string();
+    } else if let Some(OctopusError(e)) = err.find() {
+        error = e.code();
+        message = e.to_string();
+        if let Some(field) = order_field(e) {
+            details.push(FieldError {
+                field: field.to_owned(),
+                message: message.clone(),
+            });
+        }
+    } else if err.find::<Unauthorized>().is_some() {
+        error = ErrorCode::Unauthorized;
+        message = "The request doesn't carry the credentials of an operator".to_owned();
+    } else if err.find::<warp::reject::MethodNotAllowed>().is_some() {
+        // Checked after the application errors, because a route with the same path but another method rejects every
+        // request that another route failed to handle, e.g. `GET /v1/account/{signer}` for `POST /v1/account/open`
+        error = ErrorCode::MethodNotAllowed;
+        message = "The route doesn't support this method".to_owned();
+    } else {
+        // We should have expected this... Just log and say its a 500
+        tracing::error!("unhandled rejection: {:?}", err);
+        error = ErrorCode::Internal;
+        message = "UNHANDLED_REJECTION".to_owned();
+    }
+
+    let code = status(error);
+    let json = warp::reply::json(&ErrorMessage {
+        code: code.as_u16(),
+        error,
+        message,
+        details,
+    });
+
+    Ok(warp::reply::with_status(json, code))
+}
+
+/// The signer in a path segment, percent-decoded so signers with e.g. `/` or spaces can be addressed
+fn signer() -> impl Filter<Extract = (String,), Error = Rejection> + Copy {
+    warp::path::param::<String>().and_then(|segment: String| async move {
+        percent_decode_str(&segment)
+            .decode_utf8()
+            .map(|signer| signer.into_owned())
+            .map_err(|_| warp::reject::not_found())
+    })
+}
+
+/// The pipeline, recording commands as sent by the remote address of the request
+fn with_pipeline(
+    pipeline: Pipeline,
+) -> impl Filter<Extract = (Pipeline,), Error = std::convert::Infallible> + Clone {
+    warp::addr::remote().map(move |address: Option<SocketAddr>| {
+        pipeline.with_caller(Caller::new(
+            "http",
+            address.map(|address| address.to_string()),
+            None,
+        ))
+    })
+}
+
+/// The pipeline, recording commands as sent by the operator whose name and password the request carries with HTTP basic
+/// authentication. Requests without the credentials of one of `operators` are rejected.
+fn with_operator(
+    pipeline: Pipeline,
+    operators: Arc<BTreeMap<String, Operator>>,
+) -> impl Filter<Extract = (Pipeline,), Error = Rejection> + Clone {
+    warp::addr::remote()
+        .and(warp::header::optional::<String>("authorization"))
+        .and_then(
+            move |address: Option<SocketAddr>, authorization: Option<String>| {
+                let (pipeline, operators) = (pipeline.clone(), operators.clone());
+                async move {
+                    let address = address.map(|address| address.to_string());
+                    let operator = authorization.as_deref().and_then(basic_credentials).filter(
+                        |(name, password)| {
+                            operators
+                                .get(name)
+                                .is_some_and(|operator| operator.accepts(password))
+                        },
+                    );
+                    let Some((name, _)) = operator else {
+                        tracing::warn!(?address, "refused a request without operator credentials");
+                        return Err(warp::reject::custom(Unauthorized));
+                    };
+                    Ok(pipeline.with_caller(Caller::new("admin", address, Some(name))))
+                }
+            },
+        )
+}
+
+/// The name and password of an `Authorization: Basic` header
+fn basic_credentials(authorization: &str) -> Option<(String, String)> {
+    let (scheme, encoded) = authorization.split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("basic") {
+        return None;
+    }
+    let decoded = String::from_utf8(STANDARD.decode(encoded.trim()).ok()?).ok()?;
+    let (name, password) = decoded.split_once(':')?;
+    Some((name.to_string(), password.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    // reduce the warnings for naming tests
+    #![allow(non_snake_case)]
+
+    use super::*;
+    use crate::{pipeline::DEFAULT_QUEUE_CAPACITY, trading_platform::TradingPlatform};
+
+    #[tokio::test]
+    async fn test_routes_percent_decode_signers_in_paths() {
+        let pipeline = Pipeline::spawn(TradingPlatform::new(), DEFAULT_QUEUE_CAPACITY);
+        pipeline
+            .execute(Command::OpenAccount {
+                signer: "ALICE/B C?".to_string(),
+                owner: "alice".to_string(),
+            })
+            .await
+            .unwrap();
+        let routes = routes(pipeline);
+
+        let response = warp::test::request()
+            .path("/v1/account/ALICE%2FB%20C%3F")
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let view: AccountView = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(view.signer, "ALICE/B C?");
+
+        let response = warp::test::request()
+            .path("/v1/account/ALICE%2FB%20C%3F/transactions")
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_admin_routes_need_an_operator_and_audit_its_name() {
+        let path = std::env::temp_dir().join(format!("octopus-api-{}.audit", std::process::id()));
+        let audit = crate::audit::AuditLog::open(&path).unwrap();
+        let pipeline = Pipeline::spawn_with_logs(
+            TradingPlatform::new(),
+            DEFAULT_QUEUE_CAPACITY,
+            None,
+            Some(audit),
+        );
+        let operators = BTreeMap::from([(
+            "ops".to_string(),
+            Operator {
+                password: "0p3rate".to_string(),
+            },
+        )]);
+        let admin = admin_routes(pipeline.clone(), operators);
+        let halt = |authorization: Option<&str>| {
+            let request = warp::test::request()
+                .method("POST")
+                .path("/v1/market/halt")
+                .remote_addr(SocketAddr::from(([10, 0, 0, 1], 5000)));
+            match authorization {
+                Some(authorization) => request.header("authorization", authorization),
+                None => request,
+            }
+        };
+
+        // Trading phases can't be changed on the public routes
+        let response = halt(None).reply(&routes(pipeline.clone())).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let basic = |credentials: &str| format!("Basic {}", STANDARD.encode(credentials));
+        for authorization in [
+            None,
+            Some(basic("ops:wrong")),
+            Some(basic("nobody:0p3rate")),
+            Some("Bearer 0p3rate".to_string()),
+        ] {
+            let response = halt(authorization.as_deref()).reply(&admin).await;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+            let error: ErrorMessage = serde_json::from_slice(response.body()).unwrap();
+            assert_eq!(error.error, ErrorCode::Unauthorized);
+        }
+        assert_eq!(pipeline.snapshot().market_state, MarketState::Open);
+
+        let response = halt(Some(&basic("ops:0p3rate"))).reply(&admin).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(pipeline.snapshot().market_state, MarketState::Halted);
+        // Routes of the public interface aren't served to operators
+        let response = warp::test::request()
+            .path("/v1/orderbook")
+            .header("authorization", basic("ops:0p3rate"))
+            .reply(&admin)
+            .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let records: Vec<crate::audit::AuditRecord> = text
+            .lines()
+            .map(|line| {
+                let line: serde_json::Value = serde_json::from_str(line).unwrap();
+                serde_json::from_value(line["record"].clone()).unwrap()
+            })
+            .collect();
+        assert_eq!(records.len(), 1);
+        assert_eq!(
+            records[0].caller,
+            Caller::new(
+                "admin",
+                Some("10.0.0.1:5000".to_string()),
+                Some("ops".to_string())
+            )
+        );
+    }
+}
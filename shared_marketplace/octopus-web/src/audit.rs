@@ -0,0 +1,473 @@
+//! An append-only, hash-chained record of every state changing command, who sent it and how it ended.
+//!
+//! Each line of an audit file is `{"record":<AuditRecord>,"hash":"<hex>"}`, where the hash is the SHA-256 of the
+//! previous line's hash followed by the exact bytes of the record. The first record chains to [`GENESIS`]. Changing,
+//! removing, inserting or reordering records breaks the chain, which [`verify`] detects. Removing records from the end
+//! can only be detected by comparing the head hash with one kept elsewhere.
+
+use std::{
+    fmt,
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use octopus_common::errors::{ApplicationError, ErrorCode};
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    journal::truncate_torn_line,
+    pipeline::{Caller, Command, Outcome},
+};
+
+/// The hash the first record of a file chains to
+pub const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// A state changing [`Command`] as it was applied by the matching task
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AuditRecord {
+    /// Position of the record in the audit file, starting at 0
+    pub index: u64,
+    /// Sequence number of the command in the pipeline
+    pub sequence: u64,
+    /// Unix timestamp in milliseconds the command was applied at
+    pub timestamp: u64,
+    pub caller: Caller,
+    pub command: Command,
+    pub outcome: AuditOutcome,
+    /// Ordinal of the order the command placed or cancelled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ordinal: Option<u64>,
+}
+
+/// How a command ended
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOutcome {
+    /// The command was applied, with its serialized [`Outcome`]
+    Applied(serde_json::Value),
+    /// The command failed without changing anything
+    Rejected { code: ErrorCode, message: String },
+}
+
+impl AuditOutcome {
+    pub fn new(outcome: &Result<Outcome, ApplicationError>) -> Self {
+        match outcome {
+            Ok(outcome) => AuditOutcome::Applied(
+                serde_json::to_value(outcome).expect("Outcomes should be serializable"),
+            ),
+            Err(e) => AuditOutcome::Rejected {
+                code: e.code(),
+                message: e.to_string(),
+            },
+        }
+    }
+}
+
+/// A line of an audit file as written
+#[derive(Serialize)]
+struct Line<'a> {
+    record: &'a AuditRecord,
+    hash: &'a str,
+}
+
+/// A line of an audit file as read, keeping the record's bytes for hashing
+#[derive(Deserialize)]
+struct RawLine<'a> {
+    #[serde(borrow)]
+    record: &'a RawValue,
+    hash: String,
+}
+
+/// Verifying an audit file failed
+#[derive(Debug, PartialEq, Eq)]
+pub enum AuditError {
+    /// The file couldn't be read
+    Io(String),
+    /// A line couldn't be parsed (line number starting at 1, reason)
+    Parse(usize, String),
+    /// A line's hash doesn't match its record and the previous line (line number)
+    Tampered(usize),
+    /// A line has an unexpected index (line number, expected index, found index)
+    Gap(usize, u64, u64),
+    /// No record has the expected hash, so records were removed from the end (the hash)
+    Truncated(String),
+}
+
+impl fmt::Display for AuditError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuditError::Io(reason) => write!(f, "Couldn't read the audit file: {}", reason),
+            AuditError::Parse(line, reason) => write!(f, "Line {}: {}", line, reason),
+            AuditError::Tampered(line) => {
+                write!(f, "Line {}: the hash doesn't match the record", line)
+            }
+            AuditError::Gap(line, expected, found) => write!(
+                f,
+                "Line {}: expected record {} but found record {}",
+                line, expected, found
+            ),
+            AuditError::Truncated(hash) => write!(f, "No record has the hash {}", hash),
+        }
+    }
+}
+
+/// The state of a verified audit file
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct AuditSummary {
+    /// Number of records
+    pub records: u64,
+    /// Hash of the last record, [`GENESIS`] for an empty file
+    pub head: String,
+}
+
+/// Hash of a record with the exact bytes `record` that follows the record with hash `previous`
+fn chain(previous: &str, record: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(previous.as_bytes());
+    hasher.update(record.as_bytes());
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Checks that every line of an audit file chains to the previous one and that no record is missing. Empty lines are
+/// skipped.
+///
+/// # Errors
+/// The input can't be read, contains a line that isn't a record, or was tampered with
+pub fn verify(input: impl BufRead) -> Result<AuditSummary, AuditError> {
+    verify_from(input, GENESIS)
+}
+
+/// Like [`verify`], additionally checking that the record with the hash `anchor` is still in the file, e.g. a head
+/// that was kept elsewhere earlier. This detects records removed from the end.
+///
+/// # Errors
+/// Like [`verify`], or no record has the hash `anchor`
+pub fn verify_from(input: impl BufRead, anchor: &str) -> Result<AuditSummary, AuditError> {
+    let mut anchored = anchor == GENESIS;
+    let mut summary = AuditSummary {
+        records: 0,
+        head: GENESIS.to_string(),
+    };
+    for (n, line) in input.lines().enumerate() {
+        let line = line.map_err(|e| AuditError::Io(e.to_string()))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let raw: RawLine =
+            serde_json::from_str(&line).map_err(|e| AuditError::Parse(n + 1, e.to_string()))?;
+        let record: AuditRecord = serde_json::from_str(raw.record.get())
+            .map_err(|e| AuditError::Parse(n + 1, e.to_string()))?;
+        if chain(&summary.head, raw.record.get()) != raw.hash {
+            return Err(AuditError::Tampered(n + 1));
+        }
+        if record.index != summary.records {
+            return Err(AuditError::Gap(n + 1, summary.records, record.index));
+        }
+        summary.records += 1;
+        summary.head = raw.hash;
+        anchored |= summary.head == anchor;
+    }
+    match anchored {
+        true => Ok(summary),
+        false => Err(AuditError::Truncated(anchor.to_string())),
+    }
+}
+
+/// Appends [`AuditRecord`]s to a file or any other sink, continuing a hash chain
+pub struct AuditLog {
+    out: BufWriter<Box<dyn Write + Send>>,
+    /// The audit file, synced to disk on every flush
+    file: Option<File>,
+    /// Index of the next record
+    index: u64,
+    /// Hash of the last record
+    head: String,
+}
+
+impl AuditLog {
+    /// Starts a new chain in `out`
+    pub fn new(out: impl Write + Send + 'static) -> Self {
+        AuditLog {
+            out: BufWriter::new(Box::new(out)),
+            file: None,
+            index: 0,
+            head: GENESIS.to_string(),
+        }
+    }
+
+    /// Opens the audit file at `path`, creating it if necessary. An existing file is verified and new records continue
+    /// its chain. A last record that was only partly written is cut off first, it was never acknowledged.
+    ///
+    /// # Errors
+    /// The file can't be opened for writing or fails verification
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, AuditError> {
+        let path = path.as_ref();
+        let io = |e: io::Error| AuditError::Io(e.to_string());
+        let mut file: File = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .read(true)
+            .open(path)
+            .map_err(io)?;
+        let torn = truncate_torn_line(&mut file).map_err(io)?;
+        if torn > 0 {
+            tracing::warn!(
+                "dropped {} bytes of an unfinished record at the end of the audit log {}",
+                torn,
+                path.display()
+            );
+        }
+        let summary = verify(BufReader::new(file.try_clone().map_err(io)?))?;
+        Ok(AuditLog {
+            out: BufWriter::new(Box::new(file.try_clone().map_err(io)?)),
+            file: Some(file),
+            index: summary.records,
+            head: summary.head,
+        })
+    }
+
+    /// Buffers a record of a command, call [`AuditLog::flush`] to write it out
+    ///
+    /// # Errors
+    /// The record can't be written
+    pub fn append(
+        &mut self,
+        sequence: u64,
+        timestamp: u64,
+        caller: &Caller,
+        command: Command,
+        outcome: &Result<Outcome, ApplicationError>,
+    ) -> io::Result<()> {
+        let ordinal = match outcome {
+            Ok(Outcome::Receipt(receipt)) => Some(receipt.ordinal),
+            Ok(Outcome::Cancelled(order)) => Some(order.ordinal),
+            _ => None,
+        };
+        let record = AuditRecord {
+            index: self.index,
+            sequence,
+            timestamp,
+            caller: caller.clone(),
+            command,
+            outcome: AuditOutcome::new(outcome),
+            ordinal,
+        };
+        // The line is serialized in one go, so the hashed bytes are exactly the record's bytes in it
+        let hash = chain(&self.head, &serde_json::to_string(&record)?);
+        // The whole line is written at once, a failed serialization leaves nothing behind
+        let mut line = serde_json::to_vec(&Line {
+            record: &record,
+            hash: &hash,
+        })?;
+        line.push(b'\n');
+        self.out.write_all(&line)?;
+        self.index += 1;
+        self.head = hash;
+        Ok(())
+    }
+
+    /// Writes all buffered records to the sink and, for an audit file, waits until they are on disk
+    ///
+    /// # Errors
+    /// The sink failed
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()?;
+        self.file.as_ref().map_or(Ok(()), File::sync_data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use octopus_common::types::{AccountMetadata, AccountStatus};
+
+    use super::*;
+    use crate::testing::Shared;
+
+    fn written(records: usize) -> String {
+        let sink = Shared::default();
+        let mut log = AuditLog::new(sink.clone());
+        let caller = Caller::new("http", Some("127.0.0.1:4000".to_string()), None);
+        for n in 0..records {
+            let signer = format!("SIGNER{}", n);
+            let command = Command::OpenAccount {
+                signer,
+                owner: "audit".to_string(),
+            };
+            let outcome = Ok(Outcome::Account(AccountMetadata {
+                owner: "audit".to_string(),
+                created_at: 1_700_000_000_000,
+                status: AccountStatus::Open,
+            }));
+            log.append(n as u64 + 1, 1_700_000_000_000, &caller, command, &outcome)
+                .unwrap();
+        }
+        log.append(
+            records as u64 + 1,
+            1_700_000_000_000,
+            &caller,
+            Command::Halt,
+            &Err(ApplicationError::MarketClosed),
+        )
+        .unwrap();
+        log.flush().unwrap();
+        let bytes = sink.bytes();
+        String::from_utf8(bytes).unwrap()
+    }
+
+    #[test]
+    fn test_audit_chain_verifies() {
+        let audit = written(3);
+        let summary = verify(audit.as_bytes()).unwrap();
+        assert_eq!(summary.records, 4);
+        assert_ne!(summary.head, GENESIS);
+
+        let rejected: serde_json::Value =
+            serde_json::from_str(audit.lines().last().unwrap()).unwrap();
+        assert_eq!(
+            rejected["record"]["outcome"]["rejected"]["code"],
+            "MARKET_CLOSED"
+        );
+        assert_eq!(rejected["record"]["caller"]["interface"], "http");
+    }
+
+    #[test]
+    fn test_audit_detects_changed_records() {
+        let audit = written(3).replacen("SIGNER1", "SIGNER7", 1);
+        assert_eq!(verify(audit.as_bytes()), Err(AuditError::Tampered(2)));
+    }
+
+    #[test]
+    fn test_audit_detects_removed_and_reordered_records() {
+        let audit = written(3);
+        let mut lines: Vec<&str> = audit.lines().collect();
+        lines.remove(1);
+        assert_eq!(
+            verify(lines.join("\n").as_bytes()),
+            Err(AuditError::Tampered(2))
+        );
+        let mut lines: Vec<&str> = audit.lines().collect();
+        lines.swap(1, 2);
+        assert_eq!(
+            verify(lines.join("\n").as_bytes()),
+            Err(AuditError::Tampered(2))
+        );
+    }
+
+    #[test]
+    fn test_audit_detects_rehashed_gaps() {
+        // Someone who recomputes the hashes after removing a record still leaves a gap in the indices
+        let audit = written(3);
+        let mut head = GENESIS.to_string();
+        let mut forged = vec![];
+        for (_, line) in audit.lines().enumerate().filter(|(n, _)| *n != 1) {
+            let raw: RawLine = serde_json::from_str(line).unwrap();
+            head = chain(&head, raw.record.get());
+            forged.push(format!(
+                "{{\"record\":{},\"hash\":\"{}\"}}",
+                raw.record.get(),
+                head
+            ));
+        }
+        assert_eq!(
+            verify(forged.join("\n").as_bytes()),
+            Err(AuditError::Gap(2, 1, 2))
+        );
+    }
+
+    #[test]
+    fn test_audit_detects_truncation_after_an_anchor() {
+        let audit = written(3);
+        let anchor = verify(audit.as_bytes()).unwrap().head;
+        assert_eq!(verify_from(audit.as_bytes(), &anchor).unwrap().records, 4);
+
+        // Records appended after the anchor was taken are fine
+        let sink = Shared::default();
+        let mut log = AuditLog {
+            out: BufWriter::new(Box::new(sink.clone())),
+            file: None,
+            index: 4,
+            head: anchor.clone(),
+        };
+        log.append(
+            5,
+            0,
+            &Caller::default(),
+            Command::Resume,
+            &Err(ApplicationError::MarketClosed),
+        )
+        .unwrap();
+        log.flush().unwrap();
+        let appended = format!("{}{}", audit, String::from_utf8(sink.bytes()).unwrap());
+        assert_eq!(
+            verify_from(appended.as_bytes(), &anchor).unwrap().records,
+            5
+        );
+
+        let truncated: String = audit.lines().take(2).map(|l| format!("{}\n", l)).collect();
+        assert_eq!(
+            verify_from(truncated.as_bytes(), &anchor),
+            Err(AuditError::Truncated(anchor))
+        );
+    }
+
+    #[test]
+    fn test_audit_open_continues_the_chain() {
+        let path = std::env::temp_dir().join(format!("octopus-audit-{}.jsonl", std::process::id()));
+        std::fs::write(&path, written(2)).unwrap();
+        let mut log = AuditLog::open(&path).unwrap();
+        let caller = Caller::default();
+        log.append(
+            9,
+            0,
+            &caller,
+            Command::Resume,
+            &Err(ApplicationError::MarketClosed),
+        )
+        .unwrap();
+        log.flush().unwrap();
+        let summary = verify(BufReader::new(File::open(&path).unwrap())).unwrap();
+        assert_eq!(summary.records, 4);
+
+        let tampered = std::fs::read_to_string(&path)
+            .unwrap()
+            .replacen("audit", "other", 1);
+        std::fs::write(&path, tampered).unwrap();
+        assert!(matches!(
+            AuditLog::open(&path),
+            Err(AuditError::Tampered(1))
+        ));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_audit_open_cuts_off_a_torn_last_record() {
+        let path =
+            std::env::temp_dir().join(format!("octopus-audit-torn-{}.jsonl", std::process::id()));
+        let complete = written(1);
+        let next = written(2);
+        let torn = &next.lines().nth(2).unwrap()[..20];
+        std::fs::write(&path, complete.clone() + torn).unwrap();
+
+        let mut log = AuditLog::open(&path).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), complete);
+        log.append(
+            9,
+            0,
+            &Caller::default(),
+            Command::Resume,
+            &Err(ApplicationError::MarketClosed),
+        )
+        .unwrap();
+        log.flush().unwrap();
+        let summary = verify(BufReader::new(File::open(&path).unwrap())).unwrap();
+        assert_eq!(summary.records, 3);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,40 @@
+//! Verifies an audit file written by the service (see `OCTOPUS_AUDIT`) and prints the number of records and the hash of
+//! the last one as JSON.
+//!
+//! ```text
+//! octopus-audit <audit.jsonl> [--head <hash>]
+//! ```
+//!
+//! Fails if a record was changed, removed, inserted or reordered. Records removed from the end of the file are only
+//! detected when a head printed by an earlier run is given with `--head`, records appended since then are fine.
+
+use std::{env, fs::File, io::BufReader, process::ExitCode};
+
+use octopus_web::audit::{verify_from, GENESIS};
+
+fn main() -> ExitCode {
+    match run(env::args().skip(1).collect()) {
+        Ok(report) => {
+            println!("{}", report);
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: Vec<String>) -> Result<String, String> {
+    let usage = || "Usage: octopus-audit <audit.jsonl> [--head <hash>]".to_string();
+    let (path, head) = match &args[..] {
+        [path] => (path, None),
+        [path, flag, head] if flag == "--head" => (path, Some(head)),
+        _ => return Err(usage()),
+    };
+
+    let file = File::open(path).map_err(|e| format!("Couldn't open {}: {}", path, e))?;
+    let summary = verify_from(BufReader::new(file), head.map_or(GENESIS, String::as_str))
+        .map_err(|e| format!("{}: {}", path, e))?;
+    serde_json::to_string_pretty(&summary).map_err(|e| e.to_string())
+}
@@ -0,0 +1,81 @@
+//! Replays a recorded order stream through an empty [`TradingPlatform`] and prints the resulting fills, balances and
+//! book as JSON.
+//!
+//! ```text
+//! octopus-replay <journal.jsonl | orders.csv> [--deposit <cents>] [--config <file.json>]
+//! ```
+//!
+//! Files ending in `.csv` are read with [`read_csv`], funding every signer with the deposit (default 1,000,000 cents).
+//! Everything else is read as a journal written by the service (see `OCTOPUS_JOURNAL`).
+//!
+//! The market is set up like the one in the service's config file if one is given, so the instrument, fees, risk
+//! limits and circuit breaker match the recording. Otherwise the defaults of [`TradingPlatform::new`] apply.
+
+use std::{env, fs::File, io::BufReader, path::Path, process::ExitCode};
+
+use octopus_web::{
+    config::Config,
+    journal::{read_csv, read_journal},
+    replay::{Passive, Replay},
+    trading_platform::TradingPlatform,
+};
+
+const DEFAULT_DEPOSIT: u64 = 1_000_000;
+
+fn main() -> ExitCode {
+    match run(env::args().skip(1).collect()) {
+        Ok(report) => {
+            println!("{}", report);
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: Vec<String>) -> Result<String, String> {
+    let usage = || {
+        "Usage: octopus-replay <journal.jsonl | orders.csv> [--deposit <cents>] [--config <file.json>]"
+            .to_string()
+    };
+    let mut args = args.iter();
+    let path = args.next().ok_or_else(usage)?;
+    let mut deposit = DEFAULT_DEPOSIT;
+    let mut platform = TradingPlatform::new();
+    while let Some(flag) = args.next() {
+        let value = args.next().ok_or_else(usage)?;
+        match flag.as_str() {
+            "--deposit" => {
+                deposit = value
+                    .parse()
+                    .map_err(|_| format!("Invalid deposit '{}'", value))?
+            }
+            "--config" => {
+                let config = Config::read(Path::new(value))?;
+                config
+                    .market
+                    .check()
+                    .map_err(|e| format!("{}: market: {}", value, e))?;
+                platform = config.platform();
+            }
+            _ => return Err(usage()),
+        }
+    }
+
+    let file = File::open(path).map_err(|e| format!("Couldn't open {}: {}", path, e))?;
+    let input = BufReader::new(file);
+    let is_csv = Path::new(path)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"));
+    let entries = if is_csv {
+        read_csv(input, deposit)
+    } else {
+        read_journal(input)
+    }
+    .map_err(|e| format!("{}: {}", path, e))?;
+
+    let report = Replay::new(platform, Passive).run(entries)?;
+    serde_json::to_string_pretty(&report).map_err(|e| e.to_string())
+}
@@ -0,0 +1,518 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    ops::Bound,
+};
+
+use octopus_common::{
+    money::{Price, Quantity},
+    types::PartialOrder,
+};
+
+/// Position of an order in [`BookSide`]'s slots
+type SlotId = usize;
+
+/// A resting order and its neighbours within its price level
+#[derive(Debug, Clone)]
+struct Slot {
+    order: PartialOrder,
+    prev: Option<SlotId>,
+    next: Option<SlotId>,
+}
+
+/// The orders resting at one price, linked through their slots from oldest (`head`) to newest (`tail`)
+#[derive(Debug, Clone, Copy)]
+struct Level {
+    head: SlotId,
+    tail: SlotId,
+    len: usize,
+}
+
+/// The orders one signer has resting on a [`BookSide`]
+#[derive(Debug, Default, Clone, Copy)]
+struct Resting {
+    orders: usize,
+    /// The value of their remaining quantity at their limit prices
+    notional: u128,
+}
+
+/// One side of the order book. Each price level is a FIFO queue linked through a shared slot arena, and an
+/// ordinal → slot index allows removing any order in O(1) (plus O(log n) if its level becomes empty).
+#[derive(Debug, Default, Clone)]
+pub struct BookSide {
+    levels: BTreeMap<Price, Level>,
+    slots: Vec<Option<Slot>>,
+    /// Slots of removed orders, reused before the arena grows
+    free: Vec<SlotId>,
+    index: HashMap<u64, SlotId>,
+    /// Totals of each signer's resting orders
+    signers: HashMap<String, Resting>,
+}
+
+impl BookSide {
+    /// Creates an empty side
+    pub fn new() -> Self {
+        BookSide::default()
+    }
+
+    /// Number of price levels
+    pub fn len(&self) -> usize {
+        self.levels.len()
+    }
+
+    /// The value of the remaining quantity `signer` has resting on this side, at the orders' limit prices
+    pub fn notional_of(&self, signer: &str) -> u128 {
+        self.signers.get(signer).map_or(0, |r| r.notional)
+    }
+
+    /// Number of orders `signer` has resting on this side
+    pub fn order_count_of(&self, signer: &str) -> usize {
+        self.signers.get(signer).map_or(0, |r| r.orders)
+    }
+
+    /// Whether there are no resting orders
+    pub fn is_empty(&self) -> bool {
+        self.levels.is_empty()
+    }
+
+    /// Number of resting orders
+    pub fn order_count(&self) -> usize {
+        self.index.len()
+    }
+
+    /// All prices with resting orders, lowest first
+    pub fn prices(&self) -> impl DoubleEndedIterator<Item = Price> + '_ {
+        self.levels.keys().copied()
+    }
+
+    /// The orders resting at `price`, oldest first
+    pub fn level(&self, price: Price) -> LevelIter<'_> {
+        LevelIter {
+            side: self,
+            next: self.levels.get(&price).map(|l| l.head),
+        }
+    }
+
+    /// All levels with their orders, lowest price first
+    pub fn levels(&self) -> impl DoubleEndedIterator<Item = (Price, LevelIter<'_>)> {
+        self.levels.iter().map(|(price, level)| {
+            (
+                *price,
+                LevelIter {
+                    side: self,
+                    next: Some(level.head),
+                },
+            )
+        })
+    }
+
+    /// All resting orders, lowest price first and oldest first within a level
+    pub fn orders(&self) -> impl Iterator<Item = &PartialOrder> {
+        self.levels().flat_map(|(_, orders)| orders)
+    }
+
+    /// The resting order with this ordinal
+    pub fn get(&self, ordinal: u64) -> Option<&PartialOrder> {
+        self.index.get(&ordinal).map(|slot| &self.slot(*slot).order)
+    }
+
+    /// The lowest price with resting orders within `range`
+    pub fn first_price_in(&self, range: (Bound<Price>, Bound<Price>)) -> Option<Price> {
+        self.levels.range(range).next().map(|(price, _)| *price)
+    }
+
+    /// The highest price with resting orders within `range`
+    pub fn last_price_in(&self, range: (Bound<Price>, Bound<Price>)) -> Option<Price> {
+        self.levels
+            .range(range)
+            .next_back()
+            .map(|(price, _)| *price)
+    }
+
+    /// Appends an order to the end of its price level
+    pub fn push_back(&mut self, order: PartialOrder) {
+        let resting = self.signers.entry(order.signer.clone()).or_default();
+        resting.orders += 1;
+        resting.notional += order.price.0 as u128 * order.remaining.0 as u128;
+        let price = order.price;
+        let ordinal = order.ordinal;
+        let slot = Slot {
+            order,
+            prev: None,
+            next: None,
+        };
+        let id = match self.free.pop() {
+            Some(id) => {
+                self.slots[id] = Some(slot);
+                id
+            }
+            None => {
+                self.slots.push(Some(slot));
+                self.slots.len() - 1
+            }
+        };
+        self.index.insert(ordinal, id);
+
+        match self.levels.get(&price).map(|l| l.tail) {
+            Some(tail) => {
+                self.slot_mut(tail).next = Some(id);
+                self.slot_mut(id).prev = Some(tail);
+                let level = self.levels.get_mut(&price).expect("the level exists");
+                level.tail = id;
+                level.len += 1;
+            }
+            None => {
+                self.levels.insert(
+                    price,
+                    Level {
+                        head: id,
+                        tail: id,
+                        len: 1,
+                    },
+                );
+            }
+        }
+    }
+
+    /// Removes the order with this ordinal, dropping its level if it was the last one there
+    pub fn remove(&mut self, ordinal: u64) -> Option<PartialOrder> {
+        let id = self.index.remove(&ordinal)?;
+        let order = self.unlink(id);
+        self.release(&order.signer, order.price, order.remaining, true);
+        Some(order)
+    }
+
+    /// Fills up to `quantity` of the order with this ordinal at `price`, which leaves the book once it is fully filled.
+    ///
+    /// Returns the filled part.
+    pub fn take(&mut self, ordinal: u64, quantity: Quantity, price: Price) -> Option<PartialOrder> {
+        let id = *self.index.get(&ordinal)?;
+        let slot = self.slot_mut(id);
+        let take = slot.order.remaining.min(quantity);
+        let filled = PartialOrder::take_from(&mut slot.order, take, price);
+        let (signer, limit) = (slot.order.signer.clone(), slot.order.price);
+        let done = slot.order.remaining.is_zero();
+        if done {
+            self.index.remove(&ordinal);
+            self.unlink(id);
+        }
+        self.release(&signer, limit, take, done);
+        Some(filled)
+    }
+
+    /// Removes all orders, lowest price first and oldest first within a level
+    pub fn drain(&mut self) -> Vec<PartialOrder> {
+        let mut orders = Vec::with_capacity(self.order_count());
+        let levels = std::mem::take(&mut self.levels);
+        for level in levels.values() {
+            let mut next = Some(level.head);
+            while let Some(id) = next {
+                let slot = self.slots[id].take().expect("linked slots are occupied");
+                next = slot.next;
+                orders.push(slot.order);
+            }
+        }
+        self.slots.clear();
+        self.free.clear();
+        self.index.clear();
+        self.signers.clear();
+        orders
+    }
+
+    /// Plans filling up to `quantity` from the level at `price` in time priority, skipping orders of `signer`
+    /// (self-matches), without changing the book. The fills are appended to `matches`, [`BookSide::take`] applies them.
+    ///
+    /// Returns the filled quantity.
+    pub fn plan_fill(
+        &self,
+        price: Price,
+        signer: &str,
+        quantity: Quantity,
+        matches: &mut Vec<PartialOrder>,
+    ) -> Quantity {
+        let mut left = quantity;
+        for resting in self.level(price) {
+            if left.is_zero() {
+                break;
+            }
+            if resting.signer == signer {
+                continue;
+            }
+            let take = resting.remaining.min(left);
+            matches.push(PartialOrder::take_from(&mut resting.clone(), take, price));
+            left = left.saturating_sub(take);
+        }
+        quantity.saturating_sub(left)
+    }
+
+    /// Takes `quantity` at `price` off the notional of `signer`, and the order off their count if it left the book
+    fn release(&mut self, signer: &str, price: Price, quantity: Quantity, left: bool) {
+        if let Some(resting) = self.signers.get_mut(signer) {
+            resting.notional -= price.0 as u128 * quantity.0 as u128;
+            if left {
+                resting.orders -= 1;
+                if resting.orders == 0 {
+                    self.signers.remove(signer);
+                }
+            }
+        }
+    }
+
+    /// Takes a slot out of its level and frees it
+    fn unlink(&mut self, id: SlotId) -> PartialOrder {
+        let slot = self.slots[id].take().expect("indexed slots are occupied");
+        self.free.push(id);
+        if let Some(prev) = slot.prev {
+            self.slot_mut(prev).next = slot.next;
+        }
+        if let Some(next) = slot.next {
+            self.slot_mut(next).prev = slot.prev;
+        }
+
+        let price = slot.order.price;
+        let level = self
+            .levels
+            .get_mut(&price)
+            .expect("linked orders have a level");
+        level.len -= 1;
+        if level.len == 0 {
+            self.levels.remove(&price);
+        } else {
+            if level.head == id {
+                level.head = slot.next.expect("non-empty levels have a head");
+            }
+            if level.tail == id {
+                level.tail = slot.prev.expect("non-empty levels have a tail");
+            }
+        }
+        slot.order
+    }
+
+    fn slot(&self, id: SlotId) -> &Slot {
+        self.slots[id].as_ref().expect("linked slots are occupied")
+    }
+
+    fn slot_mut(&mut self, id: SlotId) -> &mut Slot {
+        self.slots[id].as_mut().expect("linked slots are occupied")
+    }
+}
+
+/// Iterates over the orders of one price level, oldest first
+#[derive(Clone)]
+pub struct LevelIter<'a> {
+    side: &'a BookSide,
+    next: Option<SlotId>,
+}
+
+impl<'a> Iterator for LevelIter<'a> {
+    type Item = &'a PartialOrder;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let slot = self.side.slot(self.next?);
+        self.next = slot.next;
+        Some(&slot.order)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // reduce the warnings for naming tests
+    #![allow(non_snake_case)]
+
+    use super::*;
+    use octopus_common::types::Side;
+
+    fn order(ordinal: u64, price: u64, amount: u64, signer: &str) -> PartialOrder {
+        PartialOrder {
+            price: Price(price),
+            amount: Quantity(amount),
+            remaining: Quantity(amount),
+            side: Side::Sell,
+            signer: signer.to_string(),
+            ordinal,
+        }
+    }
+
+    fn ordinals(side: &BookSide) -> Vec<u64> {
+        side.orders().map(|o| o.ordinal).collect()
+    }
+
+    #[test]
+    fn test_BookSide_push_back_keeps_price_and_time_priority() {
+        let mut side = BookSide::new();
+        side.push_back(order(1, 11, 1, "A"));
+        side.push_back(order(2, 10, 1, "A"));
+        side.push_back(order(3, 11, 1, "B"));
+        side.push_back(order(4, 10, 1, "B"));
+
+        assert_eq!(ordinals(&side), vec![2, 4, 1, 3]);
+        assert_eq!(side.len(), 2);
+        assert_eq!(side.order_count(), 4);
+        assert_eq!(
+            side.prices().collect::<Vec<_>>(),
+            vec![Price(10), Price(11)]
+        );
+        assert_eq!(side.level(Price(11)).count(), 2);
+        assert_eq!(side.level(Price(12)).count(), 0);
+    }
+
+    #[test]
+    fn test_BookSide_remove_unlinks_from_any_position() {
+        let mut side = BookSide::new();
+        for ordinal in 1..=4 {
+            side.push_back(order(ordinal, 10, 1, "A"));
+        }
+
+        // middle, head and tail
+        assert_eq!(side.remove(2).map(|o| o.ordinal), Some(2));
+        assert_eq!(side.remove(1).map(|o| o.ordinal), Some(1));
+        assert_eq!(side.remove(4).map(|o| o.ordinal), Some(4));
+        assert_eq!(side.remove(4), None);
+        assert_eq!(ordinals(&side), vec![3]);
+
+        // Freed slots are reused and the level keeps its order
+        side.push_back(order(5, 10, 1, "A"));
+        side.push_back(order(6, 10, 1, "A"));
+        assert_eq!(ordinals(&side), vec![3, 5, 6]);
+        assert_eq!(side.slots.len(), 4);
+
+        for ordinal in [3, 5, 6] {
+            assert!(side.remove(ordinal).is_some());
+        }
+        assert!(side.is_empty());
+        assert_eq!(side.get(3), None);
+    }
+
+    #[test]
+    fn test_BookSide_plan_fill_takes_oldest_first_and_skips_self_matches() {
+        let mut side = BookSide::new();
+        side.push_back(order(1, 10, 3, "A"));
+        side.push_back(order(2, 10, 3, "B"));
+        side.push_back(order(3, 10, 3, "C"));
+        let fill = |side: &mut BookSide, quantity| {
+            let before: Vec<PartialOrder> = side.orders().cloned().collect();
+            let mut matches = vec![];
+            let filled = side.plan_fill(Price(10), "A", Quantity(quantity), &mut matches);
+            // Planning leaves the book as it is
+            assert_eq!(side.orders().cloned().collect::<Vec<_>>(), before);
+            for m in &matches {
+                side.take(m.ordinal, m.amount, m.price);
+            }
+            (filled, matches)
+        };
+
+        let (filled, matches) = fill(&mut side, 4);
+        assert_eq!(filled, Quantity(4));
+        assert_eq!(
+            matches
+                .iter()
+                .map(|m| (m.ordinal, m.amount, m.remaining))
+                .collect::<Vec<_>>(),
+            vec![(2, Quantity(3), Quantity(0)), (3, Quantity(1), Quantity(2))]
+        );
+        assert_eq!(ordinals(&side), vec![1, 3]);
+        assert_eq!(side.get(3).map(|o| o.remaining), Some(Quantity(2)));
+
+        // Only the signer's own order is left
+        assert_eq!(fill(&mut side, 10).0, Quantity(2));
+        assert_eq!(ordinals(&side), vec![1]);
+        assert_eq!(fill(&mut side, 10), (Quantity(0), vec![]));
+    }
+
+    #[test]
+    fn test_BookSide_take_fills_one_order_in_place() {
+        let mut side = BookSide::new();
+        side.push_back(order(1, 10, 3, "A"));
+        side.push_back(order(2, 10, 3, "B"));
+
+        let filled = side.take(2, Quantity(2), Price(9)).unwrap();
+        assert_eq!(
+            (filled.amount, filled.remaining, filled.price),
+            (Quantity(2), Quantity(1), Price(9))
+        );
+        assert_eq!(ordinals(&side), vec![1, 2]);
+
+        // A fully filled order leaves the book, more than is left is never taken
+        assert_eq!(
+            side.take(2, Quantity(5), Price(10)).map(|o| o.amount),
+            Some(Quantity(1))
+        );
+        assert_eq!(ordinals(&side), vec![1]);
+        assert_eq!(side.take(2, Quantity(1), Price(10)), None);
+    }
+
+    #[test]
+    fn test_BookSide_signer_totals_follow_every_change() {
+        let mut side = BookSide::new();
+        side.push_back(order(1, 10, 3, "A"));
+        side.push_back(order(2, 12, 2, "A"));
+        side.push_back(order(3, 11, 1, "B"));
+        assert_eq!(side.notional_of("A"), 54);
+        assert_eq!(side.notional_of("B"), 11);
+        assert_eq!(side.notional_of("C"), 0);
+        assert_eq!(side.order_count_of("A"), 2);
+
+        // Fills release the limit price, whatever they trade at
+        side.take(1, Quantity(1), Price(9));
+        assert_eq!(side.notional_of("A"), 44);
+        assert_eq!(side.order_count_of("A"), 2);
+        side.take(1, Quantity(2), Price(10));
+        assert_eq!(side.notional_of("A"), 24);
+        assert_eq!(side.order_count_of("A"), 1);
+        side.remove(2);
+        assert_eq!(side.notional_of("A"), 0);
+        assert_eq!(side.order_count_of("A"), 0);
+        assert!(!side.signers.contains_key("A"));
+
+        side.drain();
+        assert_eq!(side.notional_of("B"), 0);
+        assert_eq!(side.order_count_of("B"), 0);
+    }
+
+    #[test]
+    fn test_BookSide_first_price_in_walks_levels_within_range() {
+        let mut side = BookSide::new();
+        for (ordinal, price) in [(1, 10), (2, 12), (3, 14)] {
+            side.push_back(order(ordinal, price, 1, "A"));
+        }
+
+        let up_to_13 = |from| side.first_price_in((from, Bound::Included(Price(13))));
+        assert_eq!(up_to_13(Bound::Unbounded), Some(Price(10)));
+        assert_eq!(up_to_13(Bound::Excluded(Price(10))), Some(Price(12)));
+        assert_eq!(up_to_13(Bound::Excluded(Price(12))), None);
+        assert_eq!(up_to_13(Bound::Excluded(Price(13))), None);
+        assert_eq!(
+            side.first_price_in((Bound::Included(Price(11)), Bound::Unbounded)),
+            Some(Price(12))
+        );
+    }
+
+    #[test]
+    fn test_BookSide_last_price_in_walks_levels_down_within_range() {
+        let mut side = BookSide::new();
+        for (ordinal, price) in [(1, 10), (2, 12), (3, 14)] {
+            side.push_back(order(ordinal, price, 1, "A"));
+        }
+
+        let from_11 = |to| side.last_price_in((Bound::Included(Price(11)), to));
+        assert_eq!(from_11(Bound::Unbounded), Some(Price(14)));
+        assert_eq!(from_11(Bound::Excluded(Price(14))), Some(Price(12)));
+        assert_eq!(from_11(Bound::Excluded(Price(12))), None);
+        assert_eq!(
+            side.last_price_in((Bound::Unbounded, Bound::Included(Price(13)))),
+            Some(Price(12))
+        );
+    }
+
+    #[test]
+    fn test_BookSide_drain_empties_in_priority_order() {
+        let mut side = BookSide::new();
+        side.push_back(order(1, 11, 1, "A"));
+        side.push_back(order(2, 10, 1, "A"));
+        side.push_back(order(3, 10, 1, "A"));
+
+        let drained: Vec<u64> = side.drain().iter().map(|o| o.ordinal).collect();
+        assert_eq!(drained, vec![2, 3, 1]);
+        assert!(side.is_empty());
+        assert_eq!(side.order_count(), 0);
+    }
+}
@@ -0,0 +1,78 @@
+use octopus_common::{fees::BPS_PER_UNIT, money::Price, types::CircuitBreakerConfig};
+use std::collections::VecDeque;
+
+/// Watches trade prices and trips when the price moves too far within a time window
+#[derive(Debug, Default)]
+pub struct CircuitBreaker {
+    /// The breaker is disabled without a configuration
+    pub config: Option<CircuitBreakerConfig>,
+    trades: VecDeque<(u64, Price)>,
+}
+
+impl CircuitBreaker {
+    /// Creates a [`CircuitBreaker`] without any recorded trades
+    pub fn new(config: Option<CircuitBreakerConfig>) -> Self {
+        CircuitBreaker {
+            config,
+            trades: VecDeque::new(),
+        }
+    }
+
+    /// Records a trade and returns `true` if its price moved more than allowed from the oldest trade within the window
+    pub fn record(&mut self, now: u64, price: Price) -> bool {
+        let Some(config) = &self.config else {
+            return false;
+        };
+        while self
+            .trades
+            .front()
+            .is_some_and(|(ts, _)| now.saturating_sub(*ts) > config.window_millis)
+        {
+            self.trades.pop_front();
+        }
+        let reference = self.trades.front().map_or(price, |(_, p)| *p);
+        self.trades.push_back((now, price));
+
+        let moved = reference.0.abs_diff(price.0) as u128 * BPS_PER_UNIT as u128;
+        moved > reference.0 as u128 * config.max_move_bps as u128
+    }
+
+    /// Forgets all trades, e.g. when trading resumes after a halt
+    pub fn reset(&mut self) {
+        self.trades.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // reduce the warnings for naming tests
+    #![allow(non_snake_case)]
+
+    use super::*;
+
+    #[test]
+    fn test_CircuitBreaker_record_trips_on_large_moves_within_window() {
+        let mut breaker = CircuitBreaker::new(Some(CircuitBreakerConfig {
+            max_move_bps: 1_000,
+            window_millis: 100,
+        }));
+
+        assert!(!breaker.record(0, Price(100)));
+        assert!(!breaker.record(10, Price(110)));
+        assert!(!breaker.record(20, Price(90)));
+        assert!(breaker.record(30, Price(111)));
+
+        // outside of the window the reference price moves along
+        breaker.reset();
+        assert!(!breaker.record(0, Price(100)));
+        assert!(!breaker.record(200, Price(150)));
+        assert!(!breaker.record(250, Price(160)));
+    }
+
+    #[test]
+    fn test_CircuitBreaker_record_without_config_never_trips() {
+        let mut breaker = CircuitBreaker::new(None);
+        assert!(!breaker.record(0, Price(1)));
+        assert!(!breaker.record(1, Price(1_000)));
+    }
+}
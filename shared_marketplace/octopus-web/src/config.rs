@@ -0,0 +1,609 @@
+//! Settings of the `octopus-web` server. They are read from a JSON file, overridden by `OCTOPUS_*` environment variables
+//! and then by command line flags, and validated before anything starts.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
+
+use octopus_common::{
+    fees::FeeSchedule,
+    instrument::InstrumentSpec,
+    money::{Notional, Price, Quantity},
+    risk::RiskConfig,
+    types::CircuitBreakerConfig,
+};
+use serde::Deserialize;
+
+use crate::{
+    api::Operator, circuit_breaker::CircuitBreaker, gateway::Counterparty, risk::RiskEngine,
+    trading_platform::TradingPlatform,
+};
+
+pub const USAGE: &str = "\
+Usage: octopus-web [--config <file.json>] [--<setting> <value>]...
+
+Settings, each also read from the environment variable OCTOPUS_<SETTING>, e.g. OCTOPUS_HTTP_ADDR:
+  --http-addr <ip:port>      HTTP interface (default 127.0.0.1:8080)
+  --admin-addr <ip:port>     Operator interface over mutual TLS, off by default
+  --grpc-addr <ip:port>      gRPC interface, off by default
+  --wire-addr <ip:port>      Binary order entry, off by default
+  --fix-addr <ip:port>       FIX 4.4 gateway, off by default
+  --fix-comp-id <id>         The gateway's CompID (default OCTOPUS)
+  --data-dir <dir>           Keeps the journal, audit log and FIX sequence numbers
+  --journal <file>           Journal of all state changes, replayed at startup, relative to the data directory
+  --audit <file>             Hash-chained audit log, relative to the data directory
+  --fix-store <dir>          FIX sequence numbers, relative to the data directory
+  --tls-cert <file>          PEM certificate chain, serves HTTPS together with --tls-key
+  --tls-key <file>           PEM private key of the certificate
+  --tls-client-ca <file>     PEM CA bundle clients have to present a certificate of
+
+The market with its instrument spec, fees and risk limits, the counterparties of the binary order entry and the FIX
+gateway with their passwords and the other accounts they may trade for (wire_counterparties, fix_counterparties), and
+the operators of the admin interface with their passwords (operators) can only be set in the file. OCTOPUS_CONFIG names the file if --config isn't given.";
+
+/// Settings that can be given as a flag `--<name> <value>` or an environment variable `OCTOPUS_<NAME>`
+const SETTINGS: [&str; 13] = [
+    "http-addr",
+    "admin-addr",
+    "grpc-addr",
+    "wire-addr",
+    "fix-addr",
+    "fix-comp-id",
+    "data-dir",
+    "journal",
+    "audit",
+    "fix-store",
+    "tls-cert",
+    "tls-key",
+    "tls-client-ca",
+];
+
+/// All settings of the server
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Address of the HTTP interface
+    pub http_addr: SocketAddr,
+    pub tls: TlsConfig,
+    /// Address of the operator interface, disabled without. It requires `tls.client_ca`.
+    pub admin_addr: Option<SocketAddr>,
+    /// The operators that may use the admin interface, by name
+    pub operators: BTreeMap<String, Operator>,
+    /// Address of the gRPC interface, disabled without
+    pub grpc_addr: Option<SocketAddr>,
+    /// Address of the binary order entry protocol, disabled without
+    pub wire_addr: Option<SocketAddr>,
+    /// The users that may log on to the binary order entry
+    pub wire_counterparties: BTreeMap<String, Counterparty>,
+    /// Address of the FIX gateway, disabled without
+    pub fix_addr: Option<SocketAddr>,
+    /// The CompID counterparties have to send as TargetCompID
+    pub fix_comp_id: String,
+    /// The FIX counterparties that may log on, by SenderCompID
+    pub fix_counterparties: BTreeMap<String, Counterparty>,
+    /// Directory of all files the server writes. The journal, audit log and FIX store are kept there by default and
+    /// relative paths of them are resolved against it.
+    pub data_dir: Option<PathBuf>,
+    pub journal: Option<PathBuf>,
+    pub audit: Option<PathBuf>,
+    pub fix_store: Option<PathBuf>,
+    /// The market traded on the server
+    pub market: MarketConfig,
+}
+
+/// Certificates for serving HTTPS
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsConfig {
+    /// PEM certificate chain
+    pub cert: Option<PathBuf>,
+    /// PEM private key of the certificate
+    pub key: Option<PathBuf>,
+    /// PEM bundle of the CAs client certificates have to be issued by, client certificates aren't required without
+    pub client_ca: Option<PathBuf>,
+}
+
+/// A market: the [`InstrumentSpec`] with every field optional, plus its fees and limits
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct MarketConfig {
+    pub symbol: String,
+    pub tick_size: Option<Price>,
+    pub lot_size: Option<Quantity>,
+    pub min_quantity: Option<Quantity>,
+    pub max_quantity: Option<Quantity>,
+    pub min_notional: Option<Notional>,
+    #[serde(default)]
+    pub fees: FeeSchedule,
+    #[serde(default)]
+    pub risk: RiskConfig,
+    /// Halts the market on large price moves, disabled without
+    pub circuit_breaker: Option<CircuitBreakerConfig>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            http_addr: SocketAddr::from(([127, 0, 0, 1], 8080)),
+            tls: TlsConfig::default(),
+            admin_addr: None,
+            operators: BTreeMap::new(),
+            grpc_addr: None,
+            wire_addr: None,
+            wire_counterparties: BTreeMap::new(),
+            fix_addr: None,
+            fix_comp_id: "OCTOPUS".to_string(),
+            fix_counterparties: BTreeMap::new(),
+            data_dir: None,
+            journal: None,
+            audit: None,
+            fix_store: None,
+            market: MarketConfig::default(),
+        }
+    }
+}
+
+impl Default for MarketConfig {
+    fn default() -> Self {
+        MarketConfig {
+            symbol: InstrumentSpec::default().symbol,
+            tick_size: None,
+            lot_size: None,
+            min_quantity: None,
+            max_quantity: None,
+            min_notional: None,
+            fees: FeeSchedule::default(),
+            risk: RiskConfig::default(),
+            circuit_breaker: None,
+        }
+    }
+}
+
+impl MarketConfig {
+    /// The market's trading rules, with the defaults of [`InstrumentSpec`] for fields that aren't set
+    pub fn instrument(&self) -> InstrumentSpec {
+        let default = InstrumentSpec::default();
+        InstrumentSpec {
+            symbol: self.symbol.clone(),
+            tick_size: self.tick_size.unwrap_or(default.tick_size),
+            lot_size: self.lot_size.unwrap_or(default.lot_size),
+            min_quantity: self.min_quantity.unwrap_or(default.min_quantity),
+            max_quantity: self.max_quantity.unwrap_or(default.max_quantity),
+            min_notional: self.min_notional.unwrap_or(default.min_notional),
+        }
+    }
+
+    /// Checks the instrument, fees, limits and circuit breaker.
+    ///
+    /// # Errors
+    /// A description of the first invalid setting
+    pub fn check(&self) -> Result<(), String> {
+        self.instrument().check()?;
+        self.fees.check().map_err(|e| format!("fees: {}", e))?;
+        self.risk.check().map_err(|e| format!("risk: {}", e))?;
+        if let Some(circuit_breaker) = &self.circuit_breaker {
+            circuit_breaker
+                .check()
+                .map_err(|e| format!("circuit_breaker: {}", e))?;
+        }
+        Ok(())
+    }
+}
+
+impl Config {
+    /// Reads the settings from the file given with `--config` or `OCTOPUS_CONFIG`, the environment and `args`, which
+    /// don't include the program name, and validates them. `env` looks up environment variables.
+    ///
+    /// # Errors
+    /// A description of the first setting that can't be read or is invalid
+    pub fn load(args: &[String], env: impl Fn(&str) -> Option<String>) -> Result<Config, String> {
+        let mut flags = vec![];
+        let mut file = env("OCTOPUS_CONFIG");
+        let mut args = args.iter();
+        while let Some(flag) = args.next() {
+            let value = args
+                .next()
+                .ok_or_else(|| format!("Missing value for {}", flag))?;
+            match flag.strip_prefix("--") {
+                Some("config") => file = Some(value.clone()),
+                Some(name) if SETTINGS.contains(&name) => flags.push((flag, name, value)),
+                _ => return Err(format!("Unknown option {}", flag)),
+            }
+        }
+
+        let mut config = match file {
+            Some(path) => Config::read(Path::new(&path))?,
+            None => Config::default(),
+        };
+        for name in SETTINGS {
+            let variable = format!("OCTOPUS_{}", name.to_uppercase().replace('-', "_"));
+            if let Some(value) = env(&variable) {
+                config
+                    .set(name, &value)
+                    .map_err(|e| format!("Invalid value '{}' for {}: {}", value, variable, e))?;
+            }
+        }
+        for (flag, name, value) in flags {
+            config
+                .set(name, value)
+                .map_err(|e| format!("Invalid value '{}' for {}: {}", value, flag, e))?;
+        }
+        config.check()?;
+        Ok(config)
+    }
+
+    /// Reads a config file, naming the file and the field in errors
+    ///
+    /// # Errors
+    /// The file can't be read or isn't a valid config
+    pub fn read(path: &Path) -> Result<Config, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Couldn't read {}: {}", path.display(), e))?;
+        let deserializer = &mut serde_json::Deserializer::from_str(&text);
+        serde_path_to_error::deserialize(deserializer)
+            .map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Sets the setting `name` from [`SETTINGS`] to `value`
+    fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let address = || value.parse::<SocketAddr>().map_err(|e| e.to_string());
+        let path = || Some(PathBuf::from(value));
+        match name {
+            "http-addr" => self.http_addr = address()?,
+            "admin-addr" => self.admin_addr = Some(address()?),
+            "grpc-addr" => self.grpc_addr = Some(address()?),
+            "wire-addr" => self.wire_addr = Some(address()?),
+            "fix-addr" => self.fix_addr = Some(address()?),
+            "fix-comp-id" => self.fix_comp_id = value.to_string(),
+            "data-dir" => self.data_dir = path(),
+            "journal" => self.journal = path(),
+            "audit" => self.audit = path(),
+            "fix-store" => self.fix_store = path(),
+            "tls-cert" => self.tls.cert = path(),
+            "tls-key" => self.tls.key = path(),
+            "tls-client-ca" => self.tls.client_ca = path(),
+            _ => unreachable!("{} isn't a setting", name),
+        }
+        Ok(())
+    }
+
+    /// Checks that the settings fit together and that the files they name exist.
+    ///
+    /// # Errors
+    /// A description of the first invalid setting
+    pub fn check(&self) -> Result<(), String> {
+        self.market.check().map_err(|e| format!("market: {}", e))?;
+
+        let addresses = [
+            ("http_addr", Some(self.http_addr)),
+            ("admin_addr", self.admin_addr),
+            ("grpc_addr", self.grpc_addr),
+            ("wire_addr", self.wire_addr),
+            ("fix_addr", self.fix_addr),
+        ];
+        for (i, (name, address)) in addresses.iter().enumerate() {
+            let Some(address) = address else {
+                continue;
+            };
+            if let Some((other, _)) = addresses[i + 1..]
+                .iter()
+                .find(|(_, other)| other.is_some_and(|other| overlap(*address, other)))
+            {
+                return Err(format!(
+                    "{} and {} both use port {}",
+                    name,
+                    other,
+                    address.port()
+                ));
+            }
+        }
+        if self.fix_addr.is_some() && self.fix_comp_id.is_empty() {
+            return Err("fix_comp_id must not be empty".to_string());
+        }
+        let counterparties = [
+            ("wire_counterparties", &self.wire_counterparties),
+            ("fix_counterparties", &self.fix_counterparties),
+        ];
+        for (name, counterparties) in counterparties {
+            if let Some((id, _)) = counterparties
+                .iter()
+                .find(|(_, counterparty)| counterparty.password.is_empty())
+            {
+                return Err(format!("{}: {}: password must not be empty", name, id));
+            }
+        }
+        if let Some((name, _)) = self
+            .operators
+            .iter()
+            .find(|(_, operator)| operator.password.is_empty())
+        {
+            return Err(format!("operators: {}: password must not be empty", name));
+        }
+        if self.admin_addr.is_some() {
+            if self.tls.client_ca.is_none() {
+                return Err("admin_addr requires tls.client_ca".to_string());
+            }
+            if self.operators.is_empty() {
+                return Err("admin_addr requires operators".to_string());
+            }
+        }
+
+        match (&self.tls.cert, &self.tls.key) {
+            (Some(_), None) => return Err("tls.cert is set without tls.key".to_string()),
+            (None, Some(_)) => return Err("tls.key is set without tls.cert".to_string()),
+            (None, None) if self.tls.client_ca.is_some() => {
+                return Err("tls.client_ca requires tls.cert and tls.key".to_string())
+            }
+            _ => {}
+        }
+        let files = [
+            ("tls.cert", &self.tls.cert),
+            ("tls.key", &self.tls.key),
+            ("tls.client_ca", &self.tls.client_ca),
+        ];
+        for (name, path) in files {
+            if let Some(path) = path {
+                if !path.is_file() {
+                    return Err(format!("{}: {} isn't a file", name, path.display()));
+                }
+            }
+        }
+        if let Some(dir) = &self.data_dir {
+            if dir.exists() && !dir.is_dir() {
+                return Err(format!("data_dir: {} isn't a directory", dir.display()));
+            }
+        }
+        Ok(())
+    }
+
+    /// `path` resolved against the data directory, or `default` in the data directory if `path` isn't set
+    fn data_path(&self, path: &Option<PathBuf>, default: &str) -> Option<PathBuf> {
+        match (&self.data_dir, path) {
+            (Some(dir), Some(path)) => Some(dir.join(path)),
+            (Some(dir), None) => Some(dir.join(default)),
+            (None, path) => path.clone(),
+        }
+    }
+
+    /// Where the journal is written, if anywhere
+    pub fn journal_path(&self) -> Option<PathBuf> {
+        self.data_path(&self.journal, "journal.jsonl")
+    }
+
+    /// Where the audit log is written, if anywhere
+    pub fn audit_path(&self) -> Option<PathBuf> {
+        self.data_path(&self.audit, "audit.jsonl")
+    }
+
+    /// Where the FIX sequence numbers are kept, in memory if `None`
+    pub fn fix_store_path(&self) -> Option<PathBuf> {
+        self.data_path(&self.fix_store, "fix")
+    }
+
+    /// An empty platform for the configured market
+    pub fn platform(&self) -> TradingPlatform {
+        let market = &self.market;
+        TradingPlatform {
+            instrument: market.instrument(),
+            fees: market.fees.clone(),
+            risk: RiskEngine::new(market.risk.clone()),
+            circuit_breaker: CircuitBreaker::new(market.circuit_breaker.clone()),
+            ..TradingPlatform::new()
+        }
+    }
+}
+
+/// Whether two listeners can't be bound at the same time
+fn overlap(a: SocketAddr, b: SocketAddr) -> bool {
+    a.port() == b.port() && (a.ip() == b.ip() || a.ip().is_unspecified() || b.ip().is_unspecified())
+}
+
+#[cfg(test)]
+mod tests {
+    // reduce the warnings for naming tests
+    #![allow(non_snake_case)]
+
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    /// Writes `content` to a config file unique to the test
+    fn file(name: &str, content: &str) -> String {
+        let path = std::env::temp_dir().join(format!(
+            "octopus-config-{}-{}.json",
+            std::process::id(),
+            name
+        ));
+        fs::write(&path, content).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_Config_load_defaults_to_the_previous_behaviour() {
+        let config = Config::load(&[], |_| None).unwrap();
+        assert_eq!(config, Config::default());
+        assert_eq!(config.http_addr.to_string(), "127.0.0.1:8080");
+        assert_eq!(config.journal_path(), None);
+        assert_eq!(config.platform().instrument, InstrumentSpec::default());
+    }
+
+    #[test]
+    fn test_Config_load_prefers_flags_over_environment_over_file() {
+        let path = file(
+            "precedence",
+            r#"{
+                "http_addr": "0.0.0.0:80",
+                "grpc_addr": "0.0.0.0:81",
+                "wire_addr": "0.0.0.0:82",
+                "fix_counterparties": { "DESK": { "password": "s3cret", "accounts": ["ALICE", "BOB"] } },
+                "operators": { "ops": { "password": "0p3rate" } },
+                "market": {
+                    "symbol": "SQUID",
+                    "tick_size": "0.05",
+                    "lot_size": 10,
+                    "fees": { "maker_bps": 1, "taker_bps": 5 },
+                    "risk": { "default": { "max_order_quantity": 1000 } },
+                    "circuit_breaker": { "max_move_bps": 500, "window_millis": 60000 }
+                }
+            }"#,
+        );
+        let env = BTreeMap::from([
+            ("OCTOPUS_CONFIG", path.as_str()),
+            ("OCTOPUS_GRPC_ADDR", "0.0.0.0:91"),
+            ("OCTOPUS_WIRE_ADDR", "0.0.0.0:92"),
+            ("OCTOPUS_DATA_DIR", "/var/lib/octopus"),
+        ]);
+        let config = Config::load(
+            &args(&["--wire-addr", "0.0.0.0:102", "--audit", "/audit.jsonl"]),
+            |name| env.get(name).map(|value| value.to_string()),
+        )
+        .unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(config.http_addr.port(), 80);
+        assert_eq!(config.grpc_addr.unwrap().port(), 91);
+        assert_eq!(config.wire_addr.unwrap().port(), 102);
+        assert_eq!(
+            config.journal_path(),
+            Some(PathBuf::from("/var/lib/octopus/journal.jsonl"))
+        );
+        // Absolute paths stay where they are
+        assert_eq!(config.audit_path(), Some(PathBuf::from("/audit.jsonl")));
+        assert_eq!(
+            config.fix_counterparties,
+            BTreeMap::from([(
+                "DESK".to_string(),
+                Counterparty {
+                    password: "s3cret".to_string(),
+                    accounts: vec!["ALICE".to_string(), "BOB".to_string()]
+                }
+            )])
+        );
+        assert_eq!(
+            config.operators,
+            BTreeMap::from([(
+                "ops".to_string(),
+                Operator {
+                    password: "0p3rate".to_string()
+                }
+            )])
+        );
+
+        let platform = config.platform();
+        assert_eq!(platform.instrument.symbol, "SQUID");
+        assert_eq!(platform.instrument.tick_size, Price(5));
+        assert_eq!(platform.instrument.lot_size, Quantity(10));
+        assert_eq!(platform.fees.taker_bps, 5);
+        assert_eq!(
+            platform.risk.config.default.max_order_quantity,
+            Some(Quantity(1000))
+        );
+        assert!(platform.circuit_breaker.config.is_some());
+    }
+
+    #[test]
+    fn test_Config_load_names_the_invalid_setting() {
+        let load = |content: &str, flags: &[&str]| {
+            let path = file("invalid", content);
+            let mut all = args(&["--config", &path]);
+            all.extend(args(flags));
+            let result = Config::load(&all, |_| None).unwrap_err();
+            fs::remove_file(&path).unwrap();
+            result.replace(&path, "FILE")
+        };
+
+        assert_eq!(
+            load(r#"{ "http_port": 80 }"#, &[]),
+            "FILE: http_port: unknown field `http_port`, expected one of `http_addr`, `tls`, `admin_addr`, `operators`, `grpc_addr`, `wire_addr`, `wire_counterparties`, `fix_addr`, `fix_comp_id`, `fix_counterparties`, `data_dir`, `journal`, `audit`, `fix_store`, `market` at line 1 column 13"
+        );
+        assert_eq!(
+            load(r#"{ "market": { "symbol": "X", "tick_size": 5 } }"#, &[]),
+            "FILE: market.tick_size: invalid type: integer `5`, expected a decimal string with up to 2 decimals at line 1 column 43"
+        );
+        assert_eq!(
+            load(
+                r#"{ "market": { "symbol": "X", "tick_size": "0.00" } }"#,
+                &[]
+            ),
+            "market: X: tick_size must be positive"
+        );
+        assert_eq!(
+            load(
+                r#"{ "market": { "symbol": "X", "fees": { "maker_bps": 20000, "taker_bps": 0 } } }"#,
+                &[]
+            ),
+            "market: fees: fee rates must be at most 10000 bps, got 20000/0"
+        );
+        assert_eq!(
+            load("{}", &["--grpc-addr", "127.0.0.1:8080"]),
+            "http_addr and grpc_addr both use port 8080"
+        );
+        assert_eq!(
+            load(
+                r#"{ "fix_counterparties": { "DESK": { "password": "" } } }"#,
+                &[]
+            ),
+            "fix_counterparties: DESK: password must not be empty"
+        );
+        assert_eq!(
+            load(
+                r#"{ "wire_counterparties": { "BOT": { "password": "" } } }"#,
+                &[]
+            ),
+            "wire_counterparties: BOT: password must not be empty"
+        );
+        assert_eq!(
+            load(r#"{ "operators": { "ops": { "password": "" } } }"#, &[]),
+            "operators: ops: password must not be empty"
+        );
+        // Operators have to present a client certificate
+        assert_eq!(
+            load(
+                r#"{ "operators": { "ops": { "password": "0p3rate" } } }"#,
+                &["--admin-addr", "127.0.0.1:8443"]
+            ),
+            "admin_addr requires tls.client_ca"
+        );
+        assert_eq!(
+            load(
+                "{}",
+                &[
+                    "--admin-addr",
+                    "127.0.0.1:8443",
+                    "--tls-client-ca",
+                    "/ca.pem"
+                ]
+            ),
+            "admin_addr requires operators"
+        );
+        assert_eq!(
+            load("{}", &["--admin-addr", "127.0.0.1:8080"]),
+            "http_addr and admin_addr both use port 8080"
+        );
+        // Different interfaces may share a port
+        assert!(Config::load(&args(&["--grpc-addr", "127.0.0.2:8080"]), |_| None).is_ok());
+        assert_eq!(
+            load("{}", &["--http-addr", "localhost"]),
+            "Invalid value 'localhost' for --http-addr: invalid socket address syntax"
+        );
+        assert_eq!(
+            load("{}", &["--tls-key", "/key.pem"]),
+            "tls.key is set without tls.cert"
+        );
+        assert_eq!(
+            load(
+                "{}",
+                &["--tls-cert", "/missing.pem", "--tls-key", "/key.pem"]
+            ),
+            "tls.cert: /missing.pem isn't a file"
+        );
+        assert_eq!(load("{}", &["--port", "1"]), "Unknown option --port");
+    }
+}
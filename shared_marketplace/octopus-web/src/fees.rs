@@ -0,0 +1,85 @@
+use octopus_common::{fees::VOLUME_WINDOW_MILLIS, money::Notional};
+use std::collections::{HashMap, VecDeque};
+
+/// The fills of a signer within the fee volume window and their running total, which can't overflow
+#[derive(Debug, Default)]
+struct Volume {
+    fills: VecDeque<(u64, Notional)>,
+    total: u128,
+}
+
+impl Volume {
+    /// Forgets the fills before `start` and takes them off the total
+    fn expire(&mut self, start: u64) {
+        while self.fills.front().is_some_and(|(ts, _)| *ts < start) {
+            if let Some((_, notional)) = self.fills.pop_front() {
+                self.total -= notional.0 as u128;
+            }
+        }
+    }
+}
+
+/// Keeps track of the notional each signer traded within the fee volume window
+#[derive(Debug, Default)]
+pub struct VolumeTracker {
+    volumes: HashMap<String, Volume>,
+}
+
+impl VolumeTracker {
+    /// Creates an empty [`VolumeTracker`]
+    pub fn new() -> Self {
+        VolumeTracker {
+            volumes: HashMap::new(),
+        }
+    }
+
+    /// Adds a fill of `notional` at `timestamp` to the `signer`'s volume and forgets fills that dropped out of the window
+    pub fn record(&mut self, signer: &str, timestamp: u64, notional: Notional) {
+        let volume = self.volumes.entry(signer.to_string()).or_default();
+        volume.fills.push_back((timestamp, notional));
+        volume.total += notional.0 as u128;
+        volume.expire(timestamp.saturating_sub(VOLUME_WINDOW_MILLIS));
+    }
+
+    /// The notional the `signer` traded within the window before `now`, forgetting fills that dropped out of it
+    pub fn trailing(&mut self, signer: &str, now: u64) -> Notional {
+        self.volumes
+            .get_mut(signer)
+            .map_or(Notional::ZERO, |volume| {
+                volume.expire(now.saturating_sub(VOLUME_WINDOW_MILLIS));
+                Notional(u64::try_from(volume.total).unwrap_or(u64::MAX))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // reduce the warnings for naming tests
+    #![allow(non_snake_case)]
+
+    use super::*;
+
+    #[test]
+    fn test_VolumeTracker_trailing_only_counts_window() {
+        let mut volumes = VolumeTracker::new();
+        volumes.record("ALICE", 0, Notional(100));
+        volumes.record("ALICE", 10, Notional(50));
+        volumes.record("BOB", 10, Notional(7));
+
+        assert_eq!(volumes.trailing("ALICE", 10), Notional(150));
+        assert_eq!(
+            volumes.trailing("ALICE", VOLUME_WINDOW_MILLIS + 5),
+            Notional(50)
+        );
+        assert_eq!(volumes.trailing("CHARLIE", 10), Notional::ZERO);
+
+        // asking for a later window drops the fills outside of it
+        assert_eq!(volumes.volumes["ALICE"].fills.len(), 1);
+        volumes.record("ALICE", 2 * VOLUME_WINDOW_MILLIS + 10, Notional(1));
+        assert_eq!(
+            volumes.trailing("ALICE", 2 * VOLUME_WINDOW_MILLIS + 10),
+            Notional(1)
+        );
+        assert_eq!(volumes.volumes["ALICE"].fills.len(), 1);
+    }
+}
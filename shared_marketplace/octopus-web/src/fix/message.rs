@@ -0,0 +1,356 @@
+use std::fmt;
+
+/// Separates the fields of a message
+pub const SOH: u8 = 0x01;
+
+/// The only protocol version the gateway speaks
+pub const BEGIN_STRING: &str = "FIX.4.4";
+
+/// Longest body accepted, far more than any supported message needs
+const MAX_BODY_LENGTH: usize = 1 << 16;
+
+/// Tags of the fields the gateway reads or writes
+pub mod tag {
+    pub const ACCOUNT: u32 = 1;
+    pub const AVG_PX: u32 = 6;
+    pub const BEGIN_SEQ_NO: u32 = 7;
+    pub const BEGIN_STRING: u32 = 8;
+    pub const BODY_LENGTH: u32 = 9;
+    pub const CHECK_SUM: u32 = 10;
+    pub const CL_ORD_ID: u32 = 11;
+    pub const CUM_QTY: u32 = 14;
+    pub const END_SEQ_NO: u32 = 16;
+    pub const EXEC_ID: u32 = 17;
+    pub const LAST_PX: u32 = 31;
+    pub const LAST_QTY: u32 = 32;
+    pub const MSG_SEQ_NUM: u32 = 34;
+    pub const MSG_TYPE: u32 = 35;
+    pub const NEW_SEQ_NO: u32 = 36;
+    pub const ORDER_ID: u32 = 37;
+    pub const ORDER_QTY: u32 = 38;
+    pub const ORD_STATUS: u32 = 39;
+    pub const ORD_TYPE: u32 = 40;
+    pub const ORIG_CL_ORD_ID: u32 = 41;
+    pub const POSS_DUP_FLAG: u32 = 43;
+    pub const PRICE: u32 = 44;
+    pub const REF_SEQ_NUM: u32 = 45;
+    pub const SENDER_COMP_ID: u32 = 49;
+    pub const SENDING_TIME: u32 = 52;
+    pub const SIDE: u32 = 54;
+    pub const SYMBOL: u32 = 55;
+    pub const TARGET_COMP_ID: u32 = 56;
+    pub const TEXT: u32 = 58;
+    pub const TRANSACT_TIME: u32 = 60;
+    pub const ENCRYPT_METHOD: u32 = 98;
+    pub const CXL_REJ_REASON: u32 = 102;
+    pub const ORD_REJ_REASON: u32 = 103;
+    pub const HEART_BT_INT: u32 = 108;
+    pub const TEST_REQ_ID: u32 = 112;
+    pub const ORIG_SENDING_TIME: u32 = 122;
+    pub const GAP_FILL_FLAG: u32 = 123;
+    pub const RESET_SEQ_NUM_FLAG: u32 = 141;
+    pub const EXEC_TYPE: u32 = 150;
+    pub const LEAVES_QTY: u32 = 151;
+    pub const REF_TAG_ID: u32 = 371;
+    pub const REF_MSG_TYPE: u32 = 372;
+    pub const SESSION_REJECT_REASON: u32 = 373;
+    pub const CXL_REJ_RESPONSE_TO: u32 = 434;
+    pub const USERNAME: u32 = 553;
+    pub const PASSWORD: u32 = 554;
+}
+
+/// Values of the MsgType field
+pub mod msg_type {
+    pub const HEARTBEAT: &str = "0";
+    pub const TEST_REQUEST: &str = "1";
+    pub const RESEND_REQUEST: &str = "2";
+    pub const REJECT: &str = "3";
+    pub const SEQUENCE_RESET: &str = "4";
+    pub const LOGOUT: &str = "5";
+    pub const EXECUTION_REPORT: &str = "8";
+    pub const ORDER_CANCEL_REJECT: &str = "9";
+    pub const LOGON: &str = "A";
+    pub const NEW_ORDER_SINGLE: &str = "D";
+    pub const ORDER_CANCEL_REQUEST: &str = "F";
+}
+
+/// A FIX message without the BeginString, BodyLength and CheckSum fields, which are added when it's encoded.
+/// The MsgType is always the first field. Repeating groups aren't supported.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    fields: Vec<(u32, String)>,
+}
+
+/// Why bytes couldn't be decoded into a [`Message`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The bytes don't look like a FIX 4.4 message at all, so the stream can't be read any further
+    Garbled(String),
+    /// A complete message of `len` bytes arrived with a wrong checksum and should be skipped
+    CheckSum {
+        expected: u8,
+        actual: u8,
+        len: usize,
+    },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Garbled(reason) => write!(f, "garbled message: {}", reason),
+            DecodeError::CheckSum {
+                expected, actual, ..
+            } => write!(f, "checksum {} doesn't match {}", actual, expected),
+        }
+    }
+}
+
+impl Message {
+    /// Creates a message of type `msg_type` without any other fields
+    pub fn new(msg_type: &str) -> Self {
+        Message {
+            fields: vec![(tag::MSG_TYPE, msg_type.to_string())],
+        }
+    }
+
+    /// Appends a field
+    pub fn with(mut self, tag: u32, value: impl ToString) -> Self {
+        self.fields.push((tag, value.to_string()));
+        self
+    }
+
+    /// Replaces the first field with this `tag`, or appends one
+    pub fn set(&mut self, tag: u32, value: impl ToString) {
+        match self.fields.iter_mut().find(|(t, _)| *t == tag) {
+            Some((_, v)) => *v = value.to_string(),
+            None => self.fields.push((tag, value.to_string())),
+        }
+    }
+
+    /// The value of the first field with this `tag`
+    pub fn get(&self, tag: u32) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(t, _)| *t == tag)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn msg_type(&self) -> &str {
+        &self.fields[0].1
+    }
+
+    /// The MsgSeqNum, if it's present and a number
+    pub fn seq_num(&self) -> Option<u64> {
+        self.get(tag::MSG_SEQ_NUM).and_then(|s| s.parse().ok())
+    }
+
+    /// Whether a Y/N field is present and set to Y
+    pub fn flag(&self, tag: u32) -> bool {
+        self.get(tag) == Some("Y")
+    }
+
+    /// All fields in order, starting with the MsgType
+    pub fn fields(&self) -> &[(u32, String)] {
+        &self.fields
+    }
+
+    /// Encodes the message including the BeginString, BodyLength and CheckSum fields
+    pub fn encode(&self) -> Vec<u8> {
+        let mut body = Vec::with_capacity(self.fields.len() * 8);
+        for (tag, value) in &self.fields {
+            body.extend_from_slice(format!("{}={}", tag, value).as_bytes());
+            body.push(SOH);
+        }
+        let mut bytes = format!(
+            "{}={}\u{1}{}={}\u{1}",
+            tag::BEGIN_STRING,
+            BEGIN_STRING,
+            tag::BODY_LENGTH,
+            body.len()
+        )
+        .into_bytes();
+        bytes.extend_from_slice(&body);
+        let checksum = checksum(&bytes);
+        bytes.extend_from_slice(format!("{}={:03}\u{1}", tag::CHECK_SUM, checksum).as_bytes());
+        bytes
+    }
+
+    /// Decodes the first message in `bytes`. Returns the message and the number of bytes it took up, or `None` if the
+    /// message isn't complete yet.
+    ///
+    /// # Errors
+    /// The bytes aren't a FIX 4.4 message or its checksum is wrong
+    pub fn decode(bytes: &[u8]) -> Result<Option<(Message, usize)>, DecodeError> {
+        let begin = format!(
+            "{}={}\u{1}{}=",
+            tag::BEGIN_STRING,
+            BEGIN_STRING,
+            tag::BODY_LENGTH
+        );
+        let prefix = &bytes[..bytes.len().min(begin.len())];
+        if !begin.as_bytes().starts_with(prefix) {
+            return Err(DecodeError::Garbled(
+                "expected BeginString FIX.4.4 and BodyLength".to_string(),
+            ));
+        }
+        if bytes.len() == prefix.len() {
+            return Ok(None);
+        }
+        let length_end = match bytes[begin.len()..].iter().position(|b| *b == SOH) {
+            Some(position) => begin.len() + position,
+            // BodyLength is small, so a long run of digits means it isn't a message
+            None if bytes.len() - begin.len() > 10 => {
+                return Err(DecodeError::Garbled("BodyLength is too long".to_string()))
+            }
+            None => return Ok(None),
+        };
+        let body_length: usize = std::str::from_utf8(&bytes[begin.len()..length_end])
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .filter(|length| *length <= MAX_BODY_LENGTH)
+            .ok_or_else(|| DecodeError::Garbled("BodyLength is invalid".to_string()))?;
+        let body_start = length_end + 1;
+        let body_end = body_start + body_length;
+        // The CheckSum field always has three digits: "10=123<SOH>"
+        let len = body_end + 7;
+        if bytes.len() < len {
+            return Ok(None);
+        }
+        let trailer = &bytes[body_end..len];
+        let actual = match (trailer.strip_prefix(b"10="), trailer.last()) {
+            (Some(digits), Some(&SOH)) => std::str::from_utf8(&digits[..3])
+                .ok()
+                .and_then(|s| s.parse::<u8>().ok()),
+            _ => None,
+        }
+        .ok_or_else(|| DecodeError::Garbled("expected CheckSum after the body".to_string()))?;
+        let expected = checksum(&bytes[..body_end]);
+        if actual != expected {
+            return Err(DecodeError::CheckSum {
+                expected,
+                actual,
+                len,
+            });
+        }
+
+        let body = std::str::from_utf8(&bytes[body_start..body_end])
+            .map_err(|_| DecodeError::Garbled("the body isn't UTF-8".to_string()))?;
+        let mut fields = vec![];
+        for field in body.strip_suffix('\u{1}').unwrap_or(body).split('\u{1}') {
+            let (tag, value) = field
+                .split_once('=')
+                .and_then(|(tag, value)| Some((tag.parse::<u32>().ok()?, value)))
+                .ok_or_else(|| DecodeError::Garbled(format!("invalid field '{}'", field)))?;
+            fields.push((tag, value.to_string()));
+        }
+        if fields.first().map(|(tag, _)| *tag) != Some(tag::MSG_TYPE) {
+            return Err(DecodeError::Garbled(
+                "MsgType has to be the first field of the body".to_string(),
+            ));
+        }
+        Ok(Some((Message { fields }, len)))
+    }
+}
+
+/// The sum of all bytes modulo 256
+fn checksum(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0u8, |sum, b| sum.wrapping_add(*b))
+}
+
+/// Formats a unix timestamp in milliseconds as FIX UTCTimestamp, e.g. `20240131-09:30:00.000`
+pub fn utc_timestamp(millis: u64) -> String {
+    let seconds = millis / 1000;
+    let (year, month, day) = civil_from_days((seconds / 86_400) as i64);
+    let time = seconds % 86_400;
+    format!(
+        "{:04}{:02}{:02}-{:02}:{:02}:{:02}.{:03}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60,
+        millis % 1000
+    )
+}
+
+/// Converts days since 1970-01-01 into a (year, month, day) date of the proleptic Gregorian calendar
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    // See http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    // reduce the warnings for naming tests
+    #![allow(non_snake_case)]
+
+    use super::*;
+
+    #[test]
+    fn test_Message_encode_adds_length_and_checksum() {
+        let heartbeat = Message::new(msg_type::HEARTBEAT)
+            .with(tag::SENDER_COMP_ID, "A")
+            .with(tag::TARGET_COMP_ID, "B")
+            .with(tag::MSG_SEQ_NUM, 1);
+        let encoded = String::from_utf8(heartbeat.encode()).unwrap();
+        assert_eq!(
+            encoded.replace('\u{1}', "|"),
+            "8=FIX.4.4|9=20|35=0|49=A|56=B|34=1|10=125|"
+        );
+    }
+
+    #[test]
+    fn test_Message_decode_round_trip_and_partial_input() {
+        let logon = Message::new(msg_type::LOGON)
+            .with(tag::MSG_SEQ_NUM, 1)
+            .with(tag::HEART_BT_INT, 30);
+        let mut bytes = logon.encode();
+        let len = bytes.len();
+        for end in 0..len {
+            assert_eq!(Message::decode(&bytes[..end]), Ok(None));
+        }
+        // Whatever follows the message is left alone
+        bytes.extend_from_slice(b"8=FIX");
+        assert_eq!(Message::decode(&bytes), Ok(Some((logon.clone(), len))));
+        assert_eq!(logon.seq_num(), Some(1));
+        assert_eq!(logon.get(tag::HEART_BT_INT), Some("30"));
+        assert_eq!(logon.msg_type(), msg_type::LOGON);
+    }
+
+    #[test]
+    fn test_Message_decode_rejects_bad_input() {
+        let mut bytes = Message::new(msg_type::HEARTBEAT).encode();
+        let len = bytes.len();
+        bytes[len - 2] = b'0';
+        assert!(matches!(
+            Message::decode(&bytes),
+            Err(DecodeError::CheckSum { len: l, .. }) if l == len
+        ));
+        assert!(matches!(
+            Message::decode(b"8=FIX.4.2\x019=5\x01"),
+            Err(DecodeError::Garbled(_))
+        ));
+        assert!(matches!(
+            Message::decode(b"8=FIX.4.4\x019=x\x01"),
+            Err(DecodeError::Garbled(_))
+        ));
+    }
+
+    #[test]
+    fn test_utc_timestamp_formats_dates() {
+        assert_eq!(utc_timestamp(0), "19700101-00:00:00.000");
+        assert_eq!(utc_timestamp(951_782_400_000), "20000229-00:00:00.000");
+        assert_eq!(utc_timestamp(1_706_693_400_123), "20240131-09:30:00.123");
+    }
+}
@@ -0,0 +1,15 @@
+//! A FIX 4.4 order entry gateway.
+//!
+//! Configured counterparties connect over TCP and log on with a Logon message carrying their Password, anyone else gets a
+//! Logout. NewOrderSingle and OrderCancelRequest messages are translated into
+//! [`Order`](octopus_common::types::Order)s and cancels and executed through the same
+//! [`Pipeline`](crate::pipeline::Pipeline) as the HTTP API. Their results, and fills of resting orders, are reported with
+//! ExecutionReports and OrderCancelRejects. Orders trade for the account of the counterparty's SenderCompID, or for the
+//! account named in the Account field if the SenderCompID is entitled to it. Only limit orders for the platform's instrument are supported.
+
+pub mod message;
+pub mod session;
+pub mod store;
+
+pub use session::Acceptor;
+pub use store::SeqStore;
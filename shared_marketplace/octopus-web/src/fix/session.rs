@@ -0,0 +1,871 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use octopus_common::{
+    errors::ApplicationError,
+    money::{Price, Quantity},
+    types::{Order, Side},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    time,
+};
+
+use super::{
+    message::{msg_type, tag, utc_timestamp, DecodeError, Message},
+    store::{SeqNums, SeqStore},
+};
+use crate::{
+    gateway::{self, Counterparty, FillTracker},
+    pipeline::{Caller, Command, Outcome, Pipeline, Snapshot},
+    trading_platform::Clock,
+};
+
+/// How long a new connection may take to send its Logon
+const LOGON_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often heartbeats and timeouts are checked
+const TICK: Duration = Duration::from_secs(1);
+
+/// Accepts FIX sessions over TCP and executes their orders through a [`Pipeline`].
+///
+/// Only configured [`Counterparty`]s can log on, with their password. Sessions are identified by the counterparty's
+/// SenderCompID and may only be connected once at a time. Their sequence
+/// numbers are kept in a [`SeqStore`], while their orders are remembered for as long as the process runs, so fills that
+/// happen while a session is disconnected are reported when it logs on again.
+#[derive(Clone)]
+pub struct Acceptor {
+    pipeline: Pipeline,
+    comp_id: String,
+    store: SeqStore,
+    /// The state of every session seen so far, `None` while it's connected
+    sessions: Arc<Mutex<HashMap<String, Option<SessionState>>>>,
+    /// The counterparties that may log on, by SenderCompID
+    counterparties: Arc<BTreeMap<String, Counterparty>>,
+}
+
+impl Acceptor {
+    /// Creates an acceptor that expects counterparties to send `comp_id` as TargetCompID. Nobody can log on until
+    /// [`Acceptor::with_counterparties`] names them.
+    pub fn new(pipeline: Pipeline, comp_id: &str, store: SeqStore) -> Self {
+        Acceptor {
+            pipeline,
+            comp_id: comp_id.to_string(),
+            store,
+            sessions: Arc::new(Mutex::new(HashMap::new())),
+            counterparties: Arc::new(BTreeMap::new()),
+        }
+    }
+
+    /// Lets the `counterparties`, by SenderCompID, log on
+    pub fn with_counterparties(mut self, counterparties: BTreeMap<String, Counterparty>) -> Self {
+        self.counterparties = Arc::new(counterparties);
+        self
+    }
+
+    /// Whether a Logon carries the credentials of a configured counterparty. The Username is optional but has to be
+    /// the SenderCompID if it is given.
+    fn is_authenticated(&self, their_id: &str, logon: &Message) -> bool {
+        let Some(counterparty) = self.counterparties.get(their_id) else {
+            return false;
+        };
+        logon.get(tag::USERNAME).is_none_or(|user| user == their_id)
+            && logon
+                .get(tag::PASSWORD)
+                .is_some_and(|password| counterparty.accepts(password))
+    }
+
+    /// Whether the session of `their_id` may trade for `account`
+    fn is_entitled(&self, their_id: &str, account: &str) -> bool {
+        gateway::is_entitled(&self.counterparties, their_id, account)
+    }
+
+    /// Accepts connections on `listener` and serves each of them in its own task
+    pub async fn serve(self, listener: TcpListener) {
+        gateway::accept(listener, "FIX", |stream, _| {
+            let acceptor = self.clone();
+            async move { acceptor.handle(stream).await }
+        })
+        .await
+    }
+
+    /// Serves a single connection, which has to start with a Logon
+    async fn handle(&self, stream: TcpStream) -> io::Result<()> {
+        let mut connection = Connection {
+            stream,
+            buffer: vec![],
+        };
+        let logon = match time::timeout(LOGON_TIMEOUT, connection.next()).await {
+            Ok(Ok(Some(logon))) => logon,
+            Ok(Ok(None)) | Err(_) => return Ok(()),
+            Ok(Err(e)) => return Err(e),
+        };
+        // Anything but a valid Logon is answered by closing the connection
+        let their_id = match (
+            logon.msg_type(),
+            logon.get(tag::SENDER_COMP_ID),
+            logon.get(tag::TARGET_COMP_ID),
+        ) {
+            (msg_type::LOGON, Some(sender), Some(target))
+                if target == self.comp_id && is_valid_comp_id(sender) =>
+            {
+                sender.to_string()
+            }
+            _ => return Ok(()),
+        };
+        if !self.is_authenticated(&their_id, &logon) {
+            tracing::warn!("refused a FIX Logon of {}", their_id);
+            return connection
+                .refuse(
+                    &self.comp_id,
+                    &their_id,
+                    "Unknown SenderCompID or wrong password",
+                )
+                .await;
+        }
+        let Some(state) = self.check_out(&their_id)? else {
+            return Ok(());
+        };
+
+        let address = connection.stream.peer_addr().ok().map(|a| a.to_string());
+        let caller = Caller::new("fix", address, Some(their_id.clone()));
+        let now = Instant::now();
+        let mut session = Session {
+            acceptor: self,
+            pipeline: self.pipeline.with_caller(caller),
+            connection,
+            their_id,
+            state,
+            heartbeat: None,
+            last_sent: now,
+            last_received: now,
+            test_request: None,
+            awaiting_resend: false,
+        };
+        let result = session.run(logon).await;
+        self.check_in(&session.their_id, session.state);
+        result
+    }
+
+    /// Takes the state of a session for a new connection, or `None` if the session is connected already
+    fn check_out(&self, their_id: &str) -> io::Result<Option<SessionState>> {
+        let mut sessions = self.sessions.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(state) = sessions.get_mut(their_id) {
+            return Ok(state.take());
+        }
+        let seq_nums = self.store.load(their_id)?;
+        sessions.insert(their_id.to_string(), None);
+        Ok(Some(SessionState {
+            seq_nums,
+            orders: HashMap::new(),
+            cl_ord_ids: HashMap::new(),
+            fills: FillTracker::new(&self.pipeline.snapshot()),
+        }))
+    }
+
+    /// Returns the state of a session after its connection closed
+    fn check_in(&self, their_id: &str, state: SessionState) {
+        let mut sessions = self.sessions.lock().unwrap_or_else(PoisonError::into_inner);
+        sessions.insert(their_id.to_string(), Some(state));
+    }
+}
+
+/// CompIDs name the session's file in the [`SeqStore`], so they are restricted to a safe set of characters
+fn is_valid_comp_id(comp_id: &str) -> bool {
+    !comp_id.is_empty()
+        && comp_id.len() <= 64
+        && comp_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// A TCP connection that reads whole messages
+struct Connection {
+    stream: TcpStream,
+    /// Bytes received but not decoded yet
+    buffer: Vec<u8>,
+}
+
+impl Connection {
+    /// Reads the next message, skipping messages with a wrong checksum. Returns `None` when the connection was closed.
+    /// Cancelling the future doesn't lose any data.
+    async fn next(&mut self) -> io::Result<Option<Message>> {
+        loop {
+            match Message::decode(&self.buffer) {
+                Ok(Some((message, len))) => {
+                    self.buffer.drain(..len);
+                    return Ok(Some(message));
+                }
+                Ok(None) => {}
+                Err(DecodeError::CheckSum { len, .. }) => {
+                    self.buffer.drain(..len);
+                    continue;
+                }
+                Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e.to_string())),
+            }
+            let mut chunk = [0u8; 4096];
+            let read = self.stream.read(&mut chunk).await?;
+            if read == 0 {
+                return Ok(None);
+            }
+            self.buffer.extend_from_slice(&chunk[..read]);
+        }
+    }
+
+    /// Answers a Logon that opened no session with a Logout. Nothing is stored for the connection, so the Logout always
+    /// has MsgSeqNum 1.
+    async fn refuse(&mut self, comp_id: &str, their_id: &str, text: &str) -> io::Result<()> {
+        let logout = Message::new(msg_type::LOGOUT)
+            .with(tag::SENDER_COMP_ID, comp_id)
+            .with(tag::TARGET_COMP_ID, their_id)
+            .with(tag::MSG_SEQ_NUM, 1)
+            .with(tag::SENDING_TIME, utc_timestamp(Clock::System.now()))
+            .with(tag::TEXT, text);
+        self.stream.write_all(&logout.encode()).await
+    }
+}
+
+/// What survives a session's connection
+struct SessionState {
+    seq_nums: SeqNums,
+    /// Orders accepted in this session by ordinal
+    orders: HashMap<u64, Tracked>,
+    /// Ordinals by ClOrdID
+    cl_ord_ids: HashMap<String, u64>,
+    /// Fills of the session's open orders
+    fills: FillTracker,
+}
+
+/// An order accepted in a session and what was reported about it
+struct Tracked {
+    cl_ord_id: String,
+    signer: String,
+    symbol: String,
+    side: Side,
+    price: Price,
+    quantity: u64,
+    cum_qty: u64,
+    /// Sum of price times quantity of all fills, for the average price
+    filled_value: u128,
+    cancelled: bool,
+    /// Number of execution reports sent, to number the ExecIDs
+    reports: u64,
+}
+
+impl Tracked {
+    fn leaves_qty(&self) -> u64 {
+        if self.cancelled {
+            0
+        } else {
+            self.quantity.saturating_sub(self.cum_qty)
+        }
+    }
+
+    fn is_open(&self) -> bool {
+        self.leaves_qty() > 0
+    }
+
+    fn ord_status(&self) -> &'static str {
+        match (self.cancelled, self.cum_qty) {
+            (true, _) => "4",
+            (false, 0) => "0",
+            (false, cum_qty) if cum_qty < self.quantity => "1",
+            _ => "2",
+        }
+    }
+
+    /// An ExecutionReport with the order's current state
+    fn report(&mut self, ordinal: u64, exec_type: &str) -> Message {
+        self.reports += 1;
+        let avg_px = self
+            .filled_value
+            .checked_div(self.cum_qty as u128)
+            .unwrap_or(0);
+        Message::new(msg_type::EXECUTION_REPORT)
+            .with(tag::ORDER_ID, ordinal)
+            .with(tag::CL_ORD_ID, &self.cl_ord_id)
+            .with(tag::EXEC_ID, format!("{}-{}", ordinal, self.reports))
+            .with(tag::EXEC_TYPE, exec_type)
+            .with(tag::ORD_STATUS, self.ord_status())
+            .with(tag::ACCOUNT, &self.signer)
+            .with(tag::SYMBOL, &self.symbol)
+            .with(tag::SIDE, side_code(&self.side))
+            .with(tag::ORDER_QTY, self.quantity)
+            .with(tag::PRICE, self.price)
+            .with(tag::LEAVES_QTY, self.leaves_qty())
+            .with(tag::CUM_QTY, self.cum_qty)
+            .with(tag::AVG_PX, Price(avg_px as u64))
+            .with(tag::TRANSACT_TIME, utc_timestamp(Clock::System.now()))
+    }
+}
+
+fn side_code(side: &Side) -> &'static str {
+    match side {
+        Side::Buy => "1",
+        Side::Sell => "2",
+    }
+}
+
+/// OrdRejReason for an order the platform rejected
+fn ord_rej_reason(error: &ApplicationError) -> u32 {
+    match error {
+        // Exchange closed
+        ApplicationError::MarketHalted | ApplicationError::MarketClosed => 2,
+        // Order exceeds limit
+        ApplicationError::AccountUnderFunded(..)
+        | ApplicationError::OrderSizeLimitExceeded(..)
+        | ApplicationError::NotionalLimitExceeded(..)
+        | ApplicationError::OpenOrderLimitExceeded(..)
+        | ApplicationError::RateLimitExceeded(..) => 3,
+        // Other
+        _ => 99,
+    }
+}
+
+/// Whether a connection should stay open after handling something
+#[derive(Debug, PartialEq, Eq)]
+enum Flow {
+    Continue,
+    Disconnect,
+}
+
+/// A logged on connection
+struct Session<'a> {
+    acceptor: &'a Acceptor,
+    /// Records the session's commands as sent by the counterparty
+    pipeline: Pipeline,
+    connection: Connection,
+    their_id: String,
+    state: SessionState,
+    /// The agreed HeartBtInt, `None` if the counterparty asked for 0
+    heartbeat: Option<Duration>,
+    last_sent: Instant,
+    last_received: Instant,
+    /// When a TestRequest was sent that wasn't answered yet
+    test_request: Option<Instant>,
+    /// Whether a ResendRequest for a gap in the incoming sequence is outstanding
+    awaiting_resend: bool,
+}
+
+impl Session<'_> {
+    async fn run(&mut self, logon: Message) -> io::Result<()> {
+        if self.on_logon(&logon).await? == Flow::Disconnect {
+            return Ok(());
+        }
+        let mut updates = self.acceptor.pipeline.updates();
+        // Fills that happened while the session was disconnected
+        let snapshot = updates.borrow_and_update().clone();
+        self.report_fills(&snapshot).await?;
+
+        let mut ticks = time::interval(TICK);
+        loop {
+            let flow = tokio::select! {
+                message = self.connection.next() => match message? {
+                    Some(message) => {
+                        self.last_received = Instant::now();
+                        self.test_request = None;
+                        self.on_message(message).await?
+                    }
+                    None => Flow::Disconnect,
+                },
+                _ = ticks.tick() => self.on_tick().await?,
+                changed = updates.changed() => match changed {
+                    Ok(()) => {
+                        let snapshot = updates.borrow_and_update().clone();
+                        self.report_fills(&snapshot).await?;
+                        Flow::Continue
+                    }
+                    // The matching task stopped
+                    Err(_) => self.logout("The market is unavailable").await?,
+                },
+            };
+            if flow == Flow::Disconnect {
+                return Ok(());
+            }
+        }
+    }
+
+    async fn on_logon(&mut self, logon: &Message) -> io::Result<Flow> {
+        if logon.flag(tag::RESET_SEQ_NUM_FLAG) {
+            self.state.seq_nums = SeqNums::default();
+        }
+        let heartbeat = logon
+            .get(tag::HEART_BT_INT)
+            .and_then(|s| s.parse::<u64>().ok());
+        let (Some(seq_num), Some(heartbeat)) = (logon.seq_num(), heartbeat) else {
+            return self.logout("Logon requires MsgSeqNum and HeartBtInt").await;
+        };
+        if logon.get(tag::ENCRYPT_METHOD).is_some_and(|m| m != "0") {
+            return self.logout("Encryption isn't supported").await;
+        }
+        let expected = self.state.seq_nums.next_incoming;
+        if seq_num < expected {
+            return self.too_low(expected, seq_num).await;
+        }
+
+        let mut reply = Message::new(msg_type::LOGON)
+            .with(tag::ENCRYPT_METHOD, 0)
+            .with(tag::HEART_BT_INT, heartbeat);
+        if logon.flag(tag::RESET_SEQ_NUM_FLAG) {
+            reply = reply.with(tag::RESET_SEQ_NUM_FLAG, "Y");
+        }
+        self.send(reply).await?;
+        if seq_num == expected {
+            self.state.seq_nums.next_incoming += 1;
+            self.persist();
+        } else {
+            self.request_resend(expected).await?;
+        }
+        self.heartbeat = (heartbeat > 0).then(|| Duration::from_secs(heartbeat));
+        Ok(Flow::Continue)
+    }
+
+    /// Checks the header and sequence number of a message, then handles it
+    async fn on_message(&mut self, message: Message) -> io::Result<Flow> {
+        if message.get(tag::SENDER_COMP_ID) != Some(self.their_id.as_str())
+            || message.get(tag::TARGET_COMP_ID) != Some(self.acceptor.comp_id.as_str())
+        {
+            return self.logout("Invalid SenderCompID or TargetCompID").await;
+        }
+        let Some(seq_num) = message.seq_num() else {
+            return self.logout("MsgSeqNum is missing").await;
+        };
+        let expected = self.state.seq_nums.next_incoming;
+
+        // A SequenceReset without GapFillFlag moves the expected number whatever its own number is
+        if message.msg_type() == msg_type::SEQUENCE_RESET && !message.flag(tag::GAP_FILL_FLAG) {
+            return match message.get(tag::NEW_SEQ_NO).and_then(|n| n.parse().ok()) {
+                Some(new_seq_no) if new_seq_no >= expected => {
+                    self.state.seq_nums.next_incoming = new_seq_no;
+                    self.awaiting_resend = false;
+                    self.persist();
+                    Ok(Flow::Continue)
+                }
+                _ => {
+                    self.reject(&message, Some(tag::NEW_SEQ_NO), 5, "NewSeqNo is too low")
+                        .await
+                }
+            };
+        }
+        if seq_num < expected {
+            // Messages that are sent again were handled already
+            if message.flag(tag::POSS_DUP_FLAG) {
+                return Ok(Flow::Continue);
+            }
+            return self.too_low(expected, seq_num).await;
+        }
+        if seq_num > expected {
+            if message.msg_type() == msg_type::LOGOUT {
+                return self.logout("Logout confirmed").await;
+            }
+            // Messages after a gap are dropped, the counterparty sends them again after the missing ones
+            if !self.awaiting_resend {
+                self.request_resend(expected).await?;
+            }
+            return Ok(Flow::Continue);
+        }
+        self.awaiting_resend = false;
+        self.state.seq_nums.next_incoming += 1;
+        self.persist();
+
+        match message.msg_type() {
+            msg_type::HEARTBEAT | msg_type::REJECT => Ok(Flow::Continue),
+            msg_type::TEST_REQUEST => {
+                let mut heartbeat = Message::new(msg_type::HEARTBEAT);
+                if let Some(id) = message.get(tag::TEST_REQ_ID) {
+                    heartbeat = heartbeat.with(tag::TEST_REQ_ID, id);
+                }
+                self.send(heartbeat).await?;
+                Ok(Flow::Continue)
+            }
+            msg_type::RESEND_REQUEST => self.on_resend_request(&message).await,
+            msg_type::SEQUENCE_RESET => {
+                match message.get(tag::NEW_SEQ_NO).and_then(|n| n.parse().ok()) {
+                    Some(new_seq_no) if new_seq_no > seq_num => {
+                        self.state.seq_nums.next_incoming = new_seq_no;
+                        self.persist();
+                        Ok(Flow::Continue)
+                    }
+                    _ => {
+                        self.reject(&message, Some(tag::NEW_SEQ_NO), 5, "NewSeqNo is too low")
+                            .await
+                    }
+                }
+            }
+            msg_type::LOGOUT => self.logout("Logout confirmed").await,
+            msg_type::NEW_ORDER_SINGLE => self.on_new_order(&message).await,
+            msg_type::ORDER_CANCEL_REQUEST => self.on_cancel(&message).await,
+            msg_type::LOGON => self.reject(&message, None, 99, "Already logged on").await,
+            _ => self.reject(&message, None, 11, "Unsupported MsgType").await,
+        }
+    }
+
+    /// Application messages aren't stored, so a ResendRequest is answered by skipping everything that was sent since
+    async fn on_resend_request(&mut self, message: &Message) -> io::Result<Flow> {
+        let Some(begin) = message
+            .get(tag::BEGIN_SEQ_NO)
+            .and_then(|n| n.parse::<u64>().ok())
+        else {
+            return self
+                .reject(message, Some(tag::BEGIN_SEQ_NO), 1, "BeginSeqNo is missing")
+                .await;
+        };
+        let next_outgoing = self.state.seq_nums.next_outgoing;
+        if begin < next_outgoing {
+            let gap_fill = Message::new(msg_type::SEQUENCE_RESET)
+                .with(tag::GAP_FILL_FLAG, "Y")
+                .with(tag::NEW_SEQ_NO, next_outgoing);
+            self.write(gap_fill, begin.max(1), true).await?;
+        }
+        Ok(Flow::Continue)
+    }
+
+    async fn on_new_order(&mut self, message: &Message) -> io::Result<Flow> {
+        let required = [
+            tag::CL_ORD_ID,
+            tag::SYMBOL,
+            tag::SIDE,
+            tag::ORDER_QTY,
+            tag::ORD_TYPE,
+        ];
+        if let Some(missing) = required.into_iter().find(|t| message.get(*t).is_none()) {
+            return self
+                .reject(message, Some(missing), 1, "Required tag missing")
+                .await;
+        }
+        let field = |t: u32| message.get(t).unwrap_or_default();
+        let side = match field(tag::SIDE) {
+            "1" => Side::Buy,
+            "2" => Side::Sell,
+            _ => {
+                return self
+                    .reject(
+                        message,
+                        Some(tag::SIDE),
+                        5,
+                        "Only buy and sell are supported",
+                    )
+                    .await
+            }
+        };
+        let Ok(quantity) = field(tag::ORDER_QTY).parse::<u64>() else {
+            return self
+                .reject(
+                    message,
+                    Some(tag::ORDER_QTY),
+                    6,
+                    "OrderQty has to be a whole number",
+                )
+                .await;
+        };
+        let price = match message.get(tag::PRICE).map(str::parse::<Price>) {
+            Some(Ok(price)) => price,
+            Some(Err(e)) => return self.reject(message, Some(tag::PRICE), 6, &e).await,
+            None if field(tag::ORD_TYPE) == "2" => {
+                return self
+                    .reject(message, Some(tag::PRICE), 1, "Limit orders require a Price")
+                    .await
+            }
+            None => Price(0),
+        };
+        let mut order = Tracked {
+            cl_ord_id: field(tag::CL_ORD_ID).to_string(),
+            signer: message
+                .get(tag::ACCOUNT)
+                .unwrap_or(&self.their_id)
+                .to_string(),
+            symbol: field(tag::SYMBOL).to_string(),
+            side,
+            price,
+            quantity,
+            cum_qty: 0,
+            filled_value: 0,
+            cancelled: false,
+            reports: 0,
+        };
+
+        let instrument = &self.acceptor.pipeline.snapshot().instrument;
+        let rejection = if field(tag::ORD_TYPE) != "2" {
+            Some((11, "Only limit orders are supported".to_string()))
+        } else if !self.acceptor.is_entitled(&self.their_id, &order.signer) {
+            Some((15, format!("Not entitled to account {}", order.signer)))
+        } else if self.state.cl_ord_ids.contains_key(&order.cl_ord_id) {
+            Some((6, "Duplicate ClOrdID".to_string()))
+        } else if order.symbol != instrument.symbol {
+            Some((
+                1,
+                format!("Unknown symbol, only {} is traded", instrument.symbol),
+            ))
+        } else {
+            None
+        };
+        let result = match rejection {
+            Some(rejection) => Err(rejection),
+            None => {
+                let command = Command::Order(Order {
+                    price,
+                    amount: Quantity(quantity),
+                    side: order.side.clone(),
+                    signer: order.signer.clone(),
+                });
+                match self.pipeline.execute(command).await {
+                    Ok(Outcome::Receipt(receipt)) => Ok(receipt.ordinal),
+                    Ok(outcome) => unreachable!("orders result in receipts, not {:?}", outcome),
+                    Err(e) => Err((ord_rej_reason(&e), e.to_string())),
+                }
+            }
+        };
+        match result {
+            Ok(ordinal) => {
+                let new = order.report(ordinal, "0");
+                self.state
+                    .cl_ord_ids
+                    .insert(order.cl_ord_id.clone(), ordinal);
+                self.state.fills.track(ordinal, Quantity(quantity));
+                self.state.orders.insert(ordinal, order);
+                self.send(new).await?;
+                // The order's own fills
+                let snapshot = self.acceptor.pipeline.snapshot();
+                self.report_fills(&snapshot).await?;
+            }
+            Err((reason, text)) => {
+                order.cancelled = true;
+                let mut rejected = order
+                    .report(0, "8")
+                    .with(tag::ORD_REJ_REASON, reason)
+                    .with(tag::TEXT, text);
+                rejected.set(tag::ORDER_ID, "NONE");
+                rejected.set(tag::ORD_STATUS, "8");
+                rejected.set(tag::EXEC_ID, format!("R-{}", field(tag::MSG_SEQ_NUM)));
+                self.send(rejected).await?;
+            }
+        }
+        Ok(Flow::Continue)
+    }
+
+    async fn on_cancel(&mut self, message: &Message) -> io::Result<Flow> {
+        let (Some(orig_cl_ord_id), Some(cl_ord_id)) = (
+            message.get(tag::ORIG_CL_ORD_ID),
+            message.get(tag::CL_ORD_ID),
+        ) else {
+            return self
+                .reject(message, None, 1, "OrigClOrdID and ClOrdID are required")
+                .await;
+        };
+        let ordinal = self.state.cl_ord_ids.get(orig_cl_ord_id).copied();
+        let Some((ordinal, order)) = ordinal.and_then(|o| Some((o, self.state.orders.get(&o)?)))
+        else {
+            let reject = cancel_reject(None, "8", cl_ord_id, orig_cl_ord_id, 1, "Unknown order");
+            self.send(reject).await?;
+            return Ok(Flow::Continue);
+        };
+        let command = Command::Cancel {
+            signer: order.signer.clone(),
+            ordinal,
+        };
+        let result = match order.is_open() {
+            true => self.pipeline.execute(command).await.map(|_| ()),
+            false => Err(ApplicationError::OrderNotFound(ordinal)),
+        };
+        // Fills that happened before the cancel come first
+        let snapshot = self.acceptor.pipeline.snapshot();
+        self.report_fills(&snapshot).await?;
+
+        let order = self
+            .state
+            .orders
+            .get_mut(&ordinal)
+            .expect("orders are never forgotten");
+        let reply = match result {
+            Ok(()) => {
+                order.cancelled = true;
+                self.state.fills.forget(ordinal);
+                let mut cancelled = order
+                    .report(ordinal, "4")
+                    .with(tag::ORIG_CL_ORD_ID, orig_cl_ord_id);
+                cancelled.set(tag::CL_ORD_ID, cl_ord_id);
+                cancelled
+            }
+            Err(ApplicationError::OrderNotFound(_)) => cancel_reject(
+                Some(ordinal),
+                order.ord_status(),
+                cl_ord_id,
+                orig_cl_ord_id,
+                0,
+                "Too late to cancel",
+            ),
+            Err(e) => cancel_reject(
+                Some(ordinal),
+                order.ord_status(),
+                cl_ord_id,
+                orig_cl_ord_id,
+                99,
+                &e.to_string(),
+            ),
+        };
+        self.send(reply).await?;
+        Ok(Flow::Continue)
+    }
+
+    /// Sends an ExecutionReport for every fill of an open order of the session in the history entries that weren't
+    /// checked yet
+    async fn report_fills(&mut self, snapshot: &Snapshot) -> io::Result<()> {
+        let mut reports = vec![];
+        for fill in self.state.fills.fills(snapshot) {
+            let order = self
+                .state
+                .orders
+                .get_mut(&fill.ordinal)
+                .expect("orders are never forgotten");
+            order.cum_qty += fill.quantity.0;
+            order.filled_value += fill.price.0 as u128 * fill.quantity.0 as u128;
+            let report = order
+                .report(fill.ordinal, "F")
+                .with(tag::LAST_PX, fill.price)
+                .with(tag::LAST_QTY, fill.quantity);
+            reports.push(report);
+        }
+        for report in reports {
+            self.send(report).await?;
+        }
+        Ok(())
+    }
+
+    async fn on_tick(&mut self) -> io::Result<Flow> {
+        let Some(interval) = self.heartbeat else {
+            return Ok(Flow::Continue);
+        };
+        // Some allowance for the time messages take to arrive
+        let timeout = interval + interval / 5;
+        match self.test_request {
+            Some(sent) if sent.elapsed() >= timeout => {
+                return self.logout("TestRequest wasn't answered").await;
+            }
+            None if self.last_received.elapsed() >= timeout => {
+                let id = utc_timestamp(Clock::System.now());
+                self.send(Message::new(msg_type::TEST_REQUEST).with(tag::TEST_REQ_ID, id))
+                    .await?;
+                self.test_request = Some(Instant::now());
+            }
+            _ => {}
+        }
+        if self.last_sent.elapsed() >= interval {
+            self.send(Message::new(msg_type::HEARTBEAT)).await?;
+        }
+        Ok(Flow::Continue)
+    }
+
+    async fn request_resend(&mut self, from: u64) -> io::Result<()> {
+        self.awaiting_resend = true;
+        let resend = Message::new(msg_type::RESEND_REQUEST)
+            .with(tag::BEGIN_SEQ_NO, from)
+            .with(tag::END_SEQ_NO, 0);
+        self.send(resend).await
+    }
+
+    async fn too_low(&mut self, expected: u64, seq_num: u64) -> io::Result<Flow> {
+        let text = format!(
+            "MsgSeqNum too low, expecting {} but received {}",
+            expected, seq_num
+        );
+        self.logout(&text).await
+    }
+
+    /// Sends a Logout and ends the session
+    async fn logout(&mut self, text: &str) -> io::Result<Flow> {
+        self.send(Message::new(msg_type::LOGOUT).with(tag::TEXT, text))
+            .await?;
+        Ok(Flow::Disconnect)
+    }
+
+    /// Rejects a message that can't be handled
+    async fn reject(
+        &mut self,
+        message: &Message,
+        ref_tag: Option<u32>,
+        reason: u32,
+        text: &str,
+    ) -> io::Result<Flow> {
+        let mut reject = Message::new(msg_type::REJECT)
+            .with(tag::REF_SEQ_NUM, message.seq_num().unwrap_or_default())
+            .with(tag::REF_MSG_TYPE, message.msg_type());
+        if let Some(ref_tag) = ref_tag {
+            reject = reject.with(tag::REF_TAG_ID, ref_tag);
+        }
+        let reject = reject
+            .with(tag::SESSION_REJECT_REASON, reason)
+            .with(tag::TEXT, text);
+        self.send(reject).await?;
+        Ok(Flow::Continue)
+    }
+
+    /// Sends a message with the next outgoing sequence number
+    async fn send(&mut self, message: Message) -> io::Result<()> {
+        let seq_num = self.state.seq_nums.next_outgoing;
+        self.state.seq_nums.next_outgoing += 1;
+        self.persist();
+        self.write(message, seq_num, false).await
+    }
+
+    /// Adds the standard header to a message and writes it
+    async fn write(&mut self, message: Message, seq_num: u64, poss_dup: bool) -> io::Result<()> {
+        let now = utc_timestamp(Clock::System.now());
+        let mut stamped = Message::new(message.msg_type())
+            .with(tag::SENDER_COMP_ID, &self.acceptor.comp_id)
+            .with(tag::TARGET_COMP_ID, &self.their_id)
+            .with(tag::MSG_SEQ_NUM, seq_num)
+            .with(tag::SENDING_TIME, &now);
+        if poss_dup {
+            stamped = stamped
+                .with(tag::POSS_DUP_FLAG, "Y")
+                .with(tag::ORIG_SENDING_TIME, &now);
+        }
+        for (tag, value) in &message.fields()[1..] {
+            stamped = stamped.with(*tag, value);
+        }
+        self.connection.stream.write_all(&stamped.encode()).await?;
+        self.last_sent = Instant::now();
+        Ok(())
+    }
+
+    fn persist(&self) {
+        if let Err(e) = self
+            .acceptor
+            .store
+            .save(&self.their_id, self.state.seq_nums)
+        {
+            tracing::error!(
+                "couldn't store the sequence numbers of {}: {}",
+                self.their_id,
+                e
+            );
+        }
+    }
+}
+
+/// An OrderCancelReject for a cancel request
+fn cancel_reject(
+    ordinal: Option<u64>,
+    ord_status: &str,
+    cl_ord_id: &str,
+    orig_cl_ord_id: &str,
+    reason: u32,
+    text: &str,
+) -> Message {
+    Message::new(msg_type::ORDER_CANCEL_REJECT)
+        .with(
+            tag::ORDER_ID,
+            ordinal.map_or("NONE".to_string(), |o| o.to_string()),
+        )
+        .with(tag::CL_ORD_ID, cl_ord_id)
+        .with(tag::ORIG_CL_ORD_ID, orig_cl_ord_id)
+        .with(tag::ORD_STATUS, ord_status)
+        .with(tag::CXL_REJ_RESPONSE_TO, 1)
+        .with(tag::CXL_REJ_REASON, reason)
+        .with(tag::TEXT, text)
+}
@@ -0,0 +1,125 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// The sequence numbers of a FIX session
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SeqNums {
+    /// MsgSeqNum expected from the counterparty
+    pub next_incoming: u64,
+    /// MsgSeqNum of the next message sent to the counterparty
+    pub next_outgoing: u64,
+}
+
+impl Default for SeqNums {
+    fn default() -> Self {
+        SeqNums {
+            next_incoming: 1,
+            next_outgoing: 1,
+        }
+    }
+}
+
+/// Keeps the [`SeqNums`] of each session, so sessions continue where they left off after a restart
+#[derive(Debug, Clone, Default)]
+pub struct SeqStore {
+    /// Without a directory, sequence numbers only last as long as the process
+    dir: Option<PathBuf>,
+}
+
+impl SeqStore {
+    /// A store that doesn't persist anything
+    pub fn memory() -> Self {
+        SeqStore { dir: None }
+    }
+
+    /// A store that keeps one file per session in `dir`, which is created if necessary
+    pub fn directory(dir: impl AsRef<Path>) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+        Ok(SeqStore {
+            dir: Some(dir.as_ref().to_path_buf()),
+        })
+    }
+
+    fn path(&self, session: &str) -> Option<PathBuf> {
+        self.dir
+            .as_ref()
+            .map(|dir| dir.join(format!("{}.seqnums", session)))
+    }
+
+    /// The stored sequence numbers of `session`, or fresh ones if it wasn't stored before
+    pub fn load(&self, session: &str) -> io::Result<SeqNums> {
+        let Some(path) = self.path(session) else {
+            return Ok(SeqNums::default());
+        };
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(SeqNums::default()),
+            Err(e) => return Err(e),
+        };
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} doesn't contain two sequence numbers", path.display()),
+            )
+        };
+        let mut numbers = content.split_whitespace().map(|n| n.parse::<u64>());
+        match (numbers.next(), numbers.next()) {
+            (Some(Ok(next_incoming)), Some(Ok(next_outgoing))) => Ok(SeqNums {
+                next_incoming,
+                next_outgoing,
+            }),
+            _ => Err(invalid()),
+        }
+    }
+
+    /// Stores the sequence numbers of `session`, replacing the previous ones at once
+    pub fn save(&self, session: &str, seq_nums: SeqNums) -> io::Result<()> {
+        let Some(path) = self.path(session) else {
+            return Ok(());
+        };
+        let temporary = path.with_extension("seqnums.tmp");
+        fs::write(
+            &temporary,
+            format!("{} {}\n", seq_nums.next_incoming, seq_nums.next_outgoing),
+        )?;
+        fs::rename(temporary, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // reduce the warnings for naming tests
+    #![allow(non_snake_case)]
+
+    use super::*;
+
+    #[test]
+    fn test_SeqStore_directory_persists_sequence_numbers() {
+        let dir = std::env::temp_dir().join(format!("octopus-seqstore-{}", std::process::id()));
+        let store = SeqStore::directory(&dir).unwrap();
+        assert_eq!(store.load("CLIENT").unwrap(), SeqNums::default());
+
+        let seq_nums = SeqNums {
+            next_incoming: 7,
+            next_outgoing: 12,
+        };
+        store.save("CLIENT", seq_nums).unwrap();
+        assert_eq!(store.load("CLIENT").unwrap(), seq_nums);
+        // Another store on the same directory, e.g. after a restart
+        assert_eq!(
+            SeqStore::directory(&dir).unwrap().load("CLIENT").unwrap(),
+            seq_nums
+        );
+        assert_eq!(store.load("OTHER").unwrap(), SeqNums::default());
+
+        fs::write(dir.join("BROKEN.seqnums"), "7").unwrap();
+        assert!(store.load("BROKEN").is_err());
+        assert_eq!(
+            SeqStore::memory().load("CLIENT").unwrap(),
+            SeqNums::default()
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
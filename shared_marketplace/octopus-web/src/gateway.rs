@@ -0,0 +1,144 @@
+//! What the TCP order entry gateways ([`crate::fix`] and [`crate::wire`]) have in common: accepting connections,
+//! authenticating counterparties and following the fills of each connection's orders.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    future::Future,
+    io,
+    net::SocketAddr,
+};
+
+use octopus_common::money::{Price, Quantity};
+use serde::Deserialize;
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::pipeline::Snapshot;
+
+/// Accepts connections on `listener` and serves each of them in its own task with the future `handle` returns for it.
+/// `protocol` names the connections in errors.
+pub async fn accept<F, Fut>(listener: TcpListener, protocol: &'static str, handle: F)
+where
+    F: Fn(TcpStream, SocketAddr) -> Fut,
+    Fut: Future<Output = io::Result<()>> + Send + 'static,
+{
+    loop {
+        match listener.accept().await {
+            Ok((stream, address)) => {
+                let connection = handle(stream, address);
+                tokio::spawn(async move {
+                    if let Err(e) = connection.await {
+                        tracing::warn!(%address, "{} connection failed: {}", protocol, e);
+                    }
+                });
+            }
+            Err(e) => tracing::error!("couldn't accept a {} connection: {}", protocol, e),
+        }
+    }
+}
+
+/// A counterparty that may log on to a gateway, e.g. with its FIX SenderCompID
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Counterparty {
+    /// The password its logon has to carry
+    pub password: String,
+    /// The accounts it may trade for besides its own
+    #[serde(default)]
+    pub accounts: Vec<String>,
+}
+
+impl Counterparty {
+    /// Whether `password` is the counterparty's password, compared with [`same_secret`]
+    pub fn accepts(&self, password: &str) -> bool {
+        same_secret(&self.password, password)
+    }
+}
+
+/// Whether `given` equals `secret`. The comparison doesn't give away through its timing how much of it matched.
+pub fn same_secret(secret: &str, given: &str) -> bool {
+    secret.len() == given.len()
+        && secret
+            .bytes()
+            .zip(given.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Whether the counterparty `id` may trade for `account`: its own or one it is entitled to in `counterparties`
+pub fn is_entitled(
+    counterparties: &BTreeMap<String, Counterparty>,
+    id: &str,
+    account: &str,
+) -> bool {
+    account == id
+        || counterparties
+            .get(id)
+            .is_some_and(|c| c.accounts.iter().any(|a| a == account))
+}
+
+/// A fill of an order followed by a [`FillTracker`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fill {
+    pub ordinal: u64,
+    pub price: Price,
+    pub quantity: Quantity,
+    /// Quantity of the order that is still open
+    pub leaves: Quantity,
+}
+
+/// Follows the matching history for fills of a connection's orders
+#[derive(Debug)]
+pub struct FillTracker {
+    /// Open quantity of the followed orders by ordinal
+    open: HashMap<u64, Quantity>,
+    /// Number of history entries that were checked for fills
+    history_seen: usize,
+}
+
+impl FillTracker {
+    /// Follows the history from the entries after those in `snapshot` on
+    pub fn new(snapshot: &Snapshot) -> Self {
+        FillTracker {
+            open: HashMap::new(),
+            history_seen: snapshot.history.len(),
+        }
+    }
+
+    /// Reports fills of the order with this ordinal, of which `quantity` is open, from now on
+    pub fn track(&mut self, ordinal: u64, quantity: Quantity) {
+        self.open.insert(ordinal, quantity);
+    }
+
+    /// Stops reporting fills of the order with this ordinal, e.g. because it was cancelled
+    pub fn forget(&mut self, ordinal: u64) {
+        self.open.remove(&ordinal);
+    }
+
+    /// The fills of followed orders in the history entries of `snapshot` that weren't checked yet. Orders are forgotten
+    /// once they are filled.
+    pub fn fills(&mut self, snapshot: &Snapshot) -> Vec<Fill> {
+        let mut fills = vec![];
+        for receipt in snapshot.history.iter_from(self.history_seen) {
+            for fill in &receipt.matches {
+                // Both the order the receipt is for and the one it matched may be followed
+                for ordinal in [receipt.ordinal, fill.ordinal] {
+                    let Some(leaves) = self.open.get_mut(&ordinal) else {
+                        continue;
+                    };
+                    *leaves = leaves.saturating_sub(fill.amount);
+                    fills.push(Fill {
+                        ordinal,
+                        price: fill.price,
+                        quantity: fill.amount,
+                        leaves: *leaves,
+                    });
+                    if leaves.is_zero() {
+                        self.open.remove(&ordinal);
+                    }
+                }
+            }
+        }
+        self.history_seen = snapshot.history.len();
+        fills
+    }
+}
@@ -0,0 +1,541 @@
+//! Serves the gRPC interface of `proto/octopus.proto`, which mirrors the HTTP routes.
+//!
+//! Commands are executed through the [`Pipeline`] like those of every other interface, reads are served from its latest
+//! [`Snapshot`]. Market data is streamed from the published snapshots: subscribers that fall behind skip intermediate
+//! books, but never trades.
+
+use std::{collections::BTreeMap, future::Future, pin::Pin, sync::Arc};
+
+use octopus_common::{
+    errors::ApplicationError,
+    money::{Price, Quantity},
+    tx::{Statement, StatementQuery, Tx, TxKind, TxRecord},
+    types::{
+        AccountMetadata, AccountStatus, AccountView, MarketState, Order, PartialOrder, Receipt,
+        Side,
+    },
+};
+use tokio::{net::TcpListener, sync::mpsc};
+use tokio_stream::{
+    wrappers::{ReceiverStream, TcpListenerStream},
+    Stream,
+};
+use tonic::{transport::Server, Request, Response, Status};
+
+use crate::pipeline::{Caller, Command, Outcome, Pipeline, Snapshot};
+
+/// Types and client generated from `proto/octopus.proto`
+pub mod proto {
+    tonic::include_proto!("octopus.v1");
+}
+
+use proto::octopus_server::{Octopus, OctopusServer};
+
+/// Number of market data messages buffered for a subscriber before it is considered slow
+const MARKET_DATA_BUFFER: usize = 64;
+
+/// Serves the gRPC interface on `listener` until the server fails
+pub async fn serve(
+    pipeline: Pipeline,
+    listener: TcpListener,
+) -> Result<(), tonic::transport::Error> {
+    serve_with_shutdown(pipeline, listener, std::future::pending()).await
+}
+
+/// Like [`serve`], but stops accepting connections once `shutdown` completes and returns when the open ones are done
+pub async fn serve_with_shutdown(
+    pipeline: Pipeline,
+    listener: TcpListener,
+    shutdown: impl Future<Output = ()>,
+) -> Result<(), tonic::transport::Error> {
+    Server::builder()
+        .trace_fn(|request| tracing::info_span!("grpc", path = %request.uri().path()))
+        .add_service(OctopusServer::new(Service::new(pipeline)))
+        .serve_with_incoming_shutdown(TcpListenerStream::new(listener), shutdown)
+        .await
+}
+
+/// Implementation of the generated [`Octopus`] service
+#[derive(Clone)]
+pub struct Service {
+    pipeline: Pipeline,
+}
+
+impl Service {
+    pub fn new(pipeline: Pipeline) -> Self {
+        Service { pipeline }
+    }
+
+    async fn execute(&self, caller: Caller, command: Command) -> Result<Outcome, Status> {
+        self.pipeline
+            .with_caller(caller)
+            .execute(command)
+            .await
+            .map_err(status)
+    }
+}
+
+#[tonic::async_trait]
+impl Octopus for Service {
+    async fn open_account(
+        &self,
+        request: Request<proto::OpenAccountRequest>,
+    ) -> Result<Response<proto::AccountMetadata>, Status> {
+        let caller = caller(&request);
+        let request = request.into_inner();
+        let command = Command::OpenAccount {
+            signer: request.signer,
+            owner: request.owner,
+        };
+        match self.execute(caller, command).await? {
+            Outcome::Account(metadata) => Ok(Response::new(metadata.into())),
+            outcome => unreachable!("accounts are opened with metadata, not {:?}", outcome),
+        }
+    }
+
+    async fn freeze_account(
+        &self,
+        request: Request<proto::AccountRequest>,
+    ) -> Result<Response<proto::AccountMetadata>, Status> {
+        let caller = caller(&request);
+        let signer = request.into_inner().signer;
+        match self
+            .execute(caller, Command::FreezeAccount { signer })
+            .await?
+        {
+            Outcome::Account(metadata) => Ok(Response::new(metadata.into())),
+            outcome => unreachable!("accounts are frozen with metadata, not {:?}", outcome),
+        }
+    }
+
+    async fn unfreeze_account(
+        &self,
+        request: Request<proto::AccountRequest>,
+    ) -> Result<Response<proto::AccountMetadata>, Status> {
+        let caller = caller(&request);
+        let signer = request.into_inner().signer;
+        match self
+            .execute(caller, Command::UnfreezeAccount { signer })
+            .await?
+        {
+            Outcome::Account(metadata) => Ok(Response::new(metadata.into())),
+            outcome => unreachable!("accounts are unfrozen with metadata, not {:?}", outcome),
+        }
+    }
+
+    async fn close_account(
+        &self,
+        request: Request<proto::AccountRequest>,
+    ) -> Result<Response<proto::AccountMetadata>, Status> {
+        let caller = caller(&request);
+        let signer = request.into_inner().signer;
+        match self
+            .execute(caller, Command::CloseAccount { signer })
+            .await?
+        {
+            Outcome::Account(metadata) => Ok(Response::new(metadata.into())),
+            outcome => unreachable!("accounts are closed with metadata, not {:?}", outcome),
+        }
+    }
+
+    async fn get_account(
+        &self,
+        request: Request<proto::AccountRequest>,
+    ) -> Result<Response<proto::AccountView>, Status> {
+        let caller = caller(&request);
+        let signer = request.into_inner().signer;
+        match self.execute(caller, Command::Account { signer }).await? {
+            Outcome::AccountView(view) => Ok(Response::new(view.into())),
+            outcome => unreachable!("accounts are viewed, not {:?}", outcome),
+        }
+    }
+
+    async fn get_statement(
+        &self,
+        request: Request<proto::StatementRequest>,
+    ) -> Result<Response<proto::Statement>, Status> {
+        let caller = caller(&request);
+        let request = request.into_inner();
+        let kind = match proto::TransactionKind::try_from(request.kind) {
+            Ok(proto::TransactionKind::Unspecified) => None,
+            Ok(proto::TransactionKind::Deposit) => Some(TxKind::Deposit),
+            Ok(proto::TransactionKind::Withdraw) => Some(TxKind::Withdraw),
+            Ok(proto::TransactionKind::Fee) => Some(TxKind::Fee),
+            Err(_) => return Err(Status::invalid_argument("unknown transaction kind")),
+        };
+        let query = StatementQuery {
+            from: request.from,
+            to: request.to,
+            kind,
+            offset: request.offset.map(|offset| offset as usize),
+            limit: request.limit.map(|limit| limit as usize),
+        };
+        let command = Command::Statement {
+            signer: request.signer,
+            query,
+        };
+        match self.execute(caller, command).await? {
+            Outcome::Statement(statement) => Ok(Response::new(statement.into())),
+            outcome => unreachable!("statements are queried, not {:?}", outcome),
+        }
+    }
+
+    async fn deposit(
+        &self,
+        request: Request<proto::AmountRequest>,
+    ) -> Result<Response<proto::Transaction>, Status> {
+        let caller = caller(&request);
+        let request = request.into_inner();
+        let command = Command::Deposit {
+            signer: request.signer,
+            amount: request.amount,
+        };
+        match self.execute(caller, command).await? {
+            Outcome::Tx(tx) => Ok(Response::new(tx.into())),
+            outcome => unreachable!("deposits result in a transaction, not {:?}", outcome),
+        }
+    }
+
+    async fn withdraw(
+        &self,
+        request: Request<proto::AmountRequest>,
+    ) -> Result<Response<proto::Transaction>, Status> {
+        let caller = caller(&request);
+        let request = request.into_inner();
+        let command = Command::Withdraw {
+            signer: request.signer,
+            amount: request.amount,
+        };
+        match self.execute(caller, command).await? {
+            Outcome::Tx(tx) => Ok(Response::new(tx.into())),
+            outcome => unreachable!("withdrawals result in a transaction, not {:?}", outcome),
+        }
+    }
+
+    async fn send(
+        &self,
+        request: Request<proto::SendRequest>,
+    ) -> Result<Response<proto::Transfer>, Status> {
+        let caller = caller(&request);
+        let request = request.into_inner();
+        let command = Command::Send {
+            sender: request.sender,
+            recipient: request.recipient,
+            amount: request.amount,
+        };
+        match self.execute(caller, command).await? {
+            Outcome::Transfer((withdraw, deposit)) => Ok(Response::new(proto::Transfer {
+                withdraw: Some(withdraw.into()),
+                deposit: Some(deposit.into()),
+            })),
+            outcome => unreachable!("sends result in a transfer, not {:?}", outcome),
+        }
+    }
+
+    async fn place_order(
+        &self,
+        request: Request<proto::OrderRequest>,
+    ) -> Result<Response<proto::Receipt>, Status> {
+        let caller = caller(&request);
+        let request = request.into_inner();
+        let order = Order {
+            price: Price(request.price),
+            amount: Quantity(request.amount),
+            side: side(request.side)
+                .ok_or_else(|| Status::invalid_argument("the side must be buy or sell"))?,
+            signer: request.signer,
+        };
+        match self.execute(caller, Command::Order(order)).await? {
+            Outcome::Receipt(receipt) => Ok(Response::new(receipt.into())),
+            outcome => unreachable!("orders result in receipts, not {:?}", outcome),
+        }
+    }
+
+    async fn cancel_order(
+        &self,
+        request: Request<proto::CancelRequest>,
+    ) -> Result<Response<proto::Order>, Status> {
+        let caller = caller(&request);
+        let request = request.into_inner();
+        let command = Command::Cancel {
+            signer: request.signer,
+            ordinal: request.ordinal,
+        };
+        match self.execute(caller, command).await? {
+            Outcome::Cancelled(order) => Ok(Response::new(order.into())),
+            outcome => unreachable!("cancels return the order, not {:?}", outcome),
+        }
+    }
+
+    async fn get_history(
+        &self,
+        request: Request<proto::HistoryRequest>,
+    ) -> Result<Response<proto::History>, Status> {
+        let request = request.into_inner();
+        let history = &self.pipeline.snapshot().history;
+        let limit = request.limit.map_or(usize::MAX, |limit| limit as usize);
+        let receipts = history
+            .iter_from((request.offset as usize).min(history.len()))
+            .take(limit)
+            .cloned()
+            .map(proto::Receipt::from)
+            .collect();
+        Ok(Response::new(proto::History {
+            total: history.len() as u64,
+            receipts,
+        }))
+    }
+
+    async fn get_order_book(
+        &self,
+        _request: Request<proto::OrderBookRequest>,
+    ) -> Result<Response<proto::OrderBook>, Status> {
+        let book = &self.pipeline.snapshot().orderbook;
+        let orders = |side: &BTreeMap<Price, Arc<[PartialOrder]>>| {
+            side.values()
+                .flat_map(|level| level.iter())
+                .cloned()
+                .map(proto::Order::from)
+                .collect()
+        };
+        Ok(Response::new(proto::OrderBook {
+            asks: orders(&book.asks),
+            bids: orders(&book.bids),
+        }))
+    }
+
+    type StreamMarketDataStream =
+        Pin<Box<dyn Stream<Item = Result<proto::MarketData, Status>> + Send + 'static>>;
+
+    async fn stream_market_data(
+        &self,
+        request: Request<proto::MarketDataRequest>,
+    ) -> Result<Response<Self::StreamMarketDataStream>, Status> {
+        let depth = match request.into_inner().depth {
+            0 => usize::MAX,
+            depth => depth as usize,
+        };
+        let mut updates = self.pipeline.updates();
+        let (sender, receiver) = mpsc::channel(MARKET_DATA_BUFFER);
+        tokio::spawn(async move {
+            let mut history_seen = updates.borrow().history.len();
+            loop {
+                let snapshot = updates.borrow_and_update().clone();
+                let message = market_data(&snapshot, depth, history_seen);
+                history_seen = snapshot.history.len();
+                // The subscriber went away
+                if sender.send(Ok(message)).await.is_err() {
+                    return;
+                }
+                // The matching task stopped, e.g. because the server shuts down
+                if updates.changed().await.is_err() {
+                    let _ = sender
+                        .send(Err(Status::unavailable("The market data stream ended")))
+                        .await;
+                    return;
+                }
+            }
+        });
+        Ok(Response::new(Box::pin(ReceiverStream::new(receiver))))
+    }
+}
+
+/// Builds a market data message with `depth` levels per side and the trades of the history entries from `history_seen` on
+fn market_data(snapshot: &Snapshot, depth: usize, history_seen: usize) -> proto::MarketData {
+    let trades = snapshot
+        .history
+        .iter_from(history_seen)
+        .flat_map(|receipt| {
+            receipt.matches.iter().map(|fill| proto::Trade {
+                ordinal: receipt.ordinal,
+                maker_ordinal: fill.ordinal,
+                price: fill.price.0,
+                quantity: fill.amount.0,
+            })
+        })
+        .collect();
+    proto::MarketData {
+        sequence: snapshot.sequence,
+        market_state: proto::MarketState::from(snapshot.market_state) as i32,
+        bids: levels(snapshot.orderbook.bids.iter().rev(), depth),
+        asks: levels(snapshot.orderbook.asks.iter(), depth),
+        trades,
+    }
+}
+
+/// Aggregates the first `depth` price levels of one side of a [`crate::pipeline::BookView`]
+fn levels<'a>(
+    side: impl Iterator<Item = (&'a Price, &'a Arc<[PartialOrder]>)>,
+    depth: usize,
+) -> Vec<proto::PriceLevel> {
+    side.take(depth)
+        .map(|(price, orders)| proto::PriceLevel {
+            price: price.0,
+            quantity: orders.iter().map(|order| order.remaining.0).sum(),
+            orders: orders.len() as u64,
+        })
+        .collect()
+}
+
+/// The [`Side`] of a request, if it's buy or sell
+fn side(side: i32) -> Option<Side> {
+    match proto::Side::try_from(side) {
+        Ok(proto::Side::Buy) => Some(Side::Buy),
+        Ok(proto::Side::Sell) => Some(Side::Sell),
+        _ => None,
+    }
+}
+
+/// Who sent `request`, as recorded in the audit log
+fn caller<T>(request: &Request<T>) -> Caller {
+    let address = request.remote_addr().map(|address| address.to_string());
+    Caller::new("grpc", address, None)
+}
+
+/// Maps an [`ApplicationError`] to the gRPC status closest to its HTTP status
+fn status(error: ApplicationError) -> Status {
+    let message = error.to_string();
+    match error {
+        ApplicationError::AccountNotFound(_) | ApplicationError::OrderNotFound(_) => {
+            Status::not_found(message)
+        }
+        ApplicationError::AccountAlreadyExists(_) => Status::already_exists(message),
+        ApplicationError::AccountFrozen(_) => Status::permission_denied(message),
+        ApplicationError::AccountUnderFunded(..)
+        | ApplicationError::AccountClosed(_)
+        | ApplicationError::AccountNotEmpty(..)
+        | ApplicationError::MarketHalted
+        | ApplicationError::MarketClosed
+        | ApplicationError::NoAuction
+        | ApplicationError::InvalidMarketTransition(..) => Status::failed_precondition(message),
+        ApplicationError::AccountOverFunded(..)
+        | ApplicationError::NotionalOverflow(..)
+        | ApplicationError::InvalidPrice(..)
+        | ApplicationError::InvalidLotSize(..)
+        | ApplicationError::QuantityOutOfRange(..)
+        | ApplicationError::NotionalTooSmall(..) => Status::invalid_argument(message),
+        ApplicationError::OrderSizeLimitExceeded(..)
+        | ApplicationError::NotionalLimitExceeded(..)
+        | ApplicationError::OpenOrderLimitExceeded(..)
+        | ApplicationError::PriceOutsideCollar(..)
+        | ApplicationError::RateLimitExceeded(..) => Status::resource_exhausted(message),
+        ApplicationError::EngineUnavailable => Status::unavailable(message),
+        ApplicationError::LedgerImbalance(_) => Status::internal(message),
+    }
+}
+
+impl From<Side> for proto::Side {
+    fn from(side: Side) -> Self {
+        match side {
+            Side::Buy => proto::Side::Buy,
+            Side::Sell => proto::Side::Sell,
+        }
+    }
+}
+
+impl From<MarketState> for proto::MarketState {
+    fn from(state: MarketState) -> Self {
+        match state {
+            MarketState::Open => proto::MarketState::Open,
+            MarketState::Halted => proto::MarketState::Halted,
+            MarketState::Auction => proto::MarketState::Auction,
+            MarketState::Closed => proto::MarketState::Closed,
+        }
+    }
+}
+
+impl From<AccountMetadata> for proto::AccountMetadata {
+    fn from(metadata: AccountMetadata) -> Self {
+        let status = match metadata.status {
+            AccountStatus::Open => proto::AccountStatus::Open,
+            AccountStatus::Frozen => proto::AccountStatus::Frozen,
+            AccountStatus::Closed => proto::AccountStatus::Closed,
+        };
+        proto::AccountMetadata {
+            owner: metadata.owner,
+            created_at: metadata.created_at,
+            status: status as i32,
+        }
+    }
+}
+
+impl From<AccountView> for proto::AccountView {
+    fn from(view: AccountView) -> Self {
+        proto::AccountView {
+            signer: view.signer,
+            available: view.available,
+            reserved: view.reserved,
+            balances: view.balances.into_iter().collect(),
+            open_orders: view.open_orders as u64,
+            metadata: Some(view.metadata.into()),
+        }
+    }
+}
+
+impl From<Tx> for proto::Transaction {
+    fn from(tx: Tx) -> Self {
+        let (kind, account, amount) = match tx {
+            Tx::Deposit { account, amount } => (proto::TransactionKind::Deposit, account, amount),
+            Tx::Withdraw { account, amount } => (proto::TransactionKind::Withdraw, account, amount),
+            Tx::Fee { account, amount } => (proto::TransactionKind::Fee, account, amount),
+        };
+        proto::Transaction {
+            kind: kind as i32,
+            account,
+            amount,
+        }
+    }
+}
+
+impl From<TxRecord> for proto::TransactionRecord {
+    fn from(record: TxRecord) -> Self {
+        proto::TransactionRecord {
+            id: record.id,
+            timestamp: record.timestamp,
+            transaction: Some(record.tx.into()),
+            balance: record.balance,
+            ordinal: record.ordinal,
+        }
+    }
+}
+
+impl From<Statement> for proto::Statement {
+    fn from(statement: Statement) -> Self {
+        proto::Statement {
+            signer: statement.signer,
+            total: statement.total as u64,
+            entries: statement.entries.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<PartialOrder> for proto::Order {
+    fn from(order: PartialOrder) -> Self {
+        proto::Order {
+            price: order.price.0,
+            amount: order.amount.0,
+            remaining: order.remaining.0,
+            side: proto::Side::from(order.side) as i32,
+            signer: order.signer,
+            ordinal: order.ordinal,
+        }
+    }
+}
+
+impl From<Receipt> for proto::Receipt {
+    fn from(receipt: Receipt) -> Self {
+        proto::Receipt {
+            ordinal: receipt.ordinal,
+            matches: receipt.matches.into_iter().map(Into::into).collect(),
+            fees: receipt
+                .fees
+                .into_iter()
+                .map(|fee| proto::FillFee {
+                    ordinal: fee.ordinal,
+                    maker_fee: fee.maker_fee.0,
+                    taker_fee: fee.taker_fee.0,
+                })
+                .collect(),
+        }
+    }
+}
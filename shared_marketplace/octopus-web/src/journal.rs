@@ -0,0 +1,405 @@
+use std::{
+    collections::BTreeSet,
+    fmt,
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use octopus_common::{
+    money::{Price, Quantity},
+    types::{Order, Side},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::pipeline::Command;
+
+/// A state changing [`Command`] as it was applied by the matching task. Applying all entries of a journal to an empty
+/// [`TradingPlatform`](crate::trading_platform::TradingPlatform) in sequence rebuilds its state.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct JournalEntry {
+    /// Sequence number of the command in the pipeline, including queries and failed commands
+    pub sequence: u64,
+    /// Unix timestamp in milliseconds the command was applied at
+    pub timestamp: u64,
+    pub command: Command,
+}
+
+/// Appends [`JournalEntry`]s to a file or any other sink, one JSON document per line
+pub struct Journal {
+    out: BufWriter<Box<dyn Write + Send>>,
+    /// The journal file, synced to disk on every flush
+    file: Option<File>,
+    /// Sequence number of the last entry, 0 if there is none
+    last_sequence: u64,
+}
+
+impl Journal {
+    /// Writes the journal to `out`
+    pub fn new(out: impl Write + Send + 'static) -> Self {
+        Journal {
+            out: BufWriter::new(Box::new(out)),
+            file: None,
+            last_sequence: 0,
+        }
+    }
+
+    /// Opens the journal file at `path`, creating it if necessary. New entries are appended after the ones already in
+    /// the file, which are returned so the state they lead to can be restored (see [`crate::replay::restore`]). A last
+    /// line that was only partly written is cut off, see [`truncate_torn_line`].
+    ///
+    /// # Errors
+    /// - The file can't be opened for reading and writing
+    /// - An existing entry can't be read
+    pub fn open(path: impl AsRef<Path>) -> io::Result<(Self, Vec<JournalEntry>)> {
+        let path = path.as_ref();
+        let mut file: File = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .read(true)
+            .open(path)?;
+        let torn = truncate_torn_line(&mut file)?;
+        if torn > 0 {
+            tracing::warn!(
+                "dropped {} bytes of an unfinished entry at the end of the journal {}",
+                torn,
+                path.display()
+            );
+        }
+        let entries = read_journal(BufReader::new(file.try_clone()?))
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        let mut journal = Journal::new(file.try_clone()?);
+        journal.file = Some(file);
+        journal.last_sequence = entries.last().map_or(0, |entry| entry.sequence);
+        Ok((journal, entries))
+    }
+
+    /// Sequence number of the last entry appended or read at [`Journal::open`], 0 if there is none. The matching task
+    /// continues numbering commands after it.
+    pub fn last_sequence(&self) -> u64 {
+        self.last_sequence
+    }
+
+    /// Buffers an entry, call [`Journal::flush`] to write it out
+    ///
+    /// # Errors
+    /// The entry can't be written
+    pub fn append(&mut self, entry: &JournalEntry) -> io::Result<()> {
+        // The whole line is written at once, a failed serialization leaves nothing behind
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        self.out.write_all(&line)?;
+        self.last_sequence = entry.sequence;
+        Ok(())
+    }
+
+    /// Writes all buffered entries to the sink and, for a journal file, waits until they are on disk
+    ///
+    /// # Errors
+    /// The sink failed
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()?;
+        self.file.as_ref().map_or(Ok(()), File::sync_data)
+    }
+}
+
+/// Cuts off the last line of a log `file` if it doesn't end with a newline, which only happens if the process died
+/// while writing it. Such a line was never acknowledged, so it is safe to drop. The file is left positioned at its start.
+///
+/// Returns the number of bytes cut off.
+///
+/// # Errors
+/// The file can't be read or truncated
+pub(crate) fn truncate_torn_line(file: &mut File) -> io::Result<u64> {
+    let len = file.metadata()?.len();
+    let mut end = len;
+    let mut chunk = [0u8; 4096];
+    while end > 0 {
+        let start = end.saturating_sub(chunk.len() as u64);
+        let chunk = &mut chunk[..(end - start) as usize];
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(chunk)?;
+        if let Some(newline) = chunk.iter().rposition(|b| *b == b'\n') {
+            end = start + newline as u64 + 1;
+            break;
+        }
+        end = start;
+    }
+    if end < len {
+        file.set_len(end)?;
+    }
+    file.seek(SeekFrom::Start(0))?;
+    Ok(len - end)
+}
+
+/// Reading a recorded stream of commands failed
+#[derive(Debug, PartialEq, Eq)]
+pub enum ReadError {
+    /// The input couldn't be read
+    Io(String),
+    /// A line couldn't be parsed (line number starting at 1, reason)
+    Parse(usize, String),
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReadError::Io(reason) => write!(f, "Couldn't read the input: {}", reason),
+            ReadError::Parse(line, reason) => write!(f, "Line {}: {}", line, reason),
+        }
+    }
+}
+
+/// Reads a journal written by [`Journal`]. Empty lines are skipped.
+///
+/// # Errors
+/// The input can't be read or contains a line that isn't a [`JournalEntry`]
+pub fn read_journal(input: impl BufRead) -> Result<Vec<JournalEntry>, ReadError> {
+    let mut entries = vec![];
+    for (n, line) in input.lines().enumerate() {
+        let line = line.map_err(|e| ReadError::Io(e.to_string()))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry =
+            serde_json::from_str(&line).map_err(|e| ReadError::Parse(n + 1, e.to_string()))?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// Header of the order files accepted by [`read_csv`]
+pub const CSV_HEADER: &str = "timestamp,signer,side,price,amount";
+
+/// Reads orders from a CSV file with the columns of [`CSV_HEADER`], e.g. `1700000000000,ALICE,buy,10.50,3`.
+/// An account is opened and funded with `deposit` right before the first order of each signer, so a plain order
+/// stream can be replayed on an empty platform. The header line is optional.
+///
+/// # Errors
+/// The input can't be read or contains an invalid line
+pub fn read_csv(input: impl BufRead, deposit: u64) -> Result<Vec<JournalEntry>, ReadError> {
+    let mut entries: Vec<JournalEntry> = vec![];
+    let mut signers = BTreeSet::new();
+    for (n, line) in input.lines().enumerate() {
+        let line = line.map_err(|e| ReadError::Io(e.to_string()))?;
+        let line = line.trim();
+        if line.is_empty() || (n == 0 && line.eq_ignore_ascii_case(CSV_HEADER)) {
+            continue;
+        }
+        let (timestamp, order) = parse_csv_order(line).map_err(|e| ReadError::Parse(n + 1, e))?;
+        let mut push = |command| {
+            entries.push(JournalEntry {
+                sequence: entries.len() as u64 + 1,
+                timestamp,
+                command,
+            })
+        };
+        if signers.insert(order.signer.clone()) {
+            push(Command::OpenAccount {
+                signer: order.signer.clone(),
+                owner: order.signer.clone(),
+            });
+            push(Command::Deposit {
+                signer: order.signer.clone(),
+                amount: deposit,
+            });
+        }
+        push(Command::Order(order));
+    }
+    Ok(entries)
+}
+
+fn parse_csv_order(line: &str) -> Result<(u64, Order), String> {
+    let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+    let [timestamp, signer, side, price, amount] = fields[..] else {
+        return Err(format!("Expected the columns {}", CSV_HEADER));
+    };
+    let timestamp = timestamp
+        .parse()
+        .map_err(|_| format!("Invalid timestamp '{}'", timestamp))?;
+    let side = match side.to_lowercase().as_str() {
+        "buy" => Side::Buy,
+        "sell" => Side::Sell,
+        _ => return Err(format!("Invalid side '{}'", side)),
+    };
+    let price: Price = price.parse()?;
+    let amount = amount
+        .parse()
+        .map(Quantity)
+        .map_err(|_| format!("Invalid amount '{}'", amount))?;
+    Ok((
+        timestamp,
+        Order {
+            price,
+            amount,
+            side,
+            signer: signer.to_string(),
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    // reduce the warnings for naming tests
+    #![allow(non_snake_case)]
+
+    use super::*;
+    use crate::testing::Shared;
+
+    #[test]
+    fn test_Journal_append_round_trips_through_read_journal() {
+        let sink = Shared::default();
+        let mut journal = Journal::new(sink.clone());
+        let entries = vec![
+            JournalEntry {
+                sequence: 1,
+                timestamp: 1_000,
+                command: Command::OpenAccount {
+                    signer: "ALICE".to_string(),
+                    owner: "alice".to_string(),
+                },
+            },
+            JournalEntry {
+                sequence: 3,
+                timestamp: 1_001,
+                command: Command::Order(Order {
+                    price: Price(1_050),
+                    amount: Quantity(2),
+                    side: Side::Sell,
+                    signer: "ALICE".to_string(),
+                }),
+            },
+        ];
+        for entry in &entries {
+            journal.append(entry).unwrap();
+        }
+        journal.flush().unwrap();
+        assert_eq!(journal.last_sequence(), 3);
+
+        let written = sink.bytes();
+        assert_eq!(written.iter().filter(|b| **b == b'\n').count(), 2);
+        assert_eq!(read_journal(&written[..]), Ok(entries));
+        assert!(matches!(
+            read_journal(&b"{}\n"[..]),
+            Err(ReadError::Parse(1, _))
+        ));
+    }
+
+    #[test]
+    fn test_Journal_open_continues_after_existing_entries() {
+        let path =
+            std::env::temp_dir().join(format!("octopus-journal-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let entry = |sequence| JournalEntry {
+            sequence,
+            timestamp: 1_000 + sequence,
+            command: Command::Halt,
+        };
+
+        let (mut journal, existing) = Journal::open(&path).unwrap();
+        assert_eq!(existing, vec![]);
+        assert_eq!(journal.last_sequence(), 0);
+        journal.append(&entry(1)).unwrap();
+        journal.append(&entry(4)).unwrap();
+        journal.flush().unwrap();
+        drop(journal);
+
+        let (mut journal, existing) = Journal::open(&path).unwrap();
+        assert_eq!(existing, vec![entry(1), entry(4)]);
+        assert_eq!(journal.last_sequence(), 4);
+        journal.append(&entry(5)).unwrap();
+        journal.flush().unwrap();
+        drop(journal);
+        let (_, existing) = Journal::open(&path).unwrap();
+        assert_eq!(existing, vec![entry(1), entry(4), entry(5)]);
+
+        // A journal that can't be read isn't appended to
+        std::fs::write(
+            &path,
+            format!(
+                "{{\"sequence\":\n{}\n",
+                serde_json::to_string(&entry(1)).unwrap()
+            ),
+        )
+        .unwrap();
+        assert_eq!(
+            Journal::open(&path).err().map(|e| e.kind()),
+            Some(io::ErrorKind::InvalidData)
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_Journal_open_cuts_off_a_torn_last_line() {
+        let path =
+            std::env::temp_dir().join(format!("octopus-journal-torn-{}.jsonl", std::process::id()));
+        let entry = |sequence| JournalEntry {
+            sequence,
+            timestamp: 1_000 + sequence,
+            command: Command::Halt,
+        };
+        let line = |sequence| serde_json::to_string(&entry(sequence)).unwrap() + "\n";
+        let complete = line(1) + &line(2);
+        std::fs::write(&path, complete.clone() + &line(3)[..10]).unwrap();
+
+        let (mut journal, existing) = Journal::open(&path).unwrap();
+        assert_eq!(existing, vec![entry(1), entry(2)]);
+        assert_eq!(journal.last_sequence(), 2);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), complete);
+
+        // New entries start on a line of their own
+        journal.append(&entry(3)).unwrap();
+        journal.flush().unwrap();
+        drop(journal);
+        let (_, existing) = Journal::open(&path).unwrap();
+        assert_eq!(existing, vec![entry(1), entry(2), entry(3)]);
+
+        // A single torn line leaves an empty journal
+        std::fs::write(&path, &line(1)[..5]).unwrap();
+        let (_, existing) = Journal::open(&path).unwrap();
+        assert_eq!(existing, vec![]);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 0);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_read_csv_opens_and_funds_accounts_before_first_order() {
+        let csv = "timestamp,signer,side,price,amount\n\
+                   100,ALICE,sell,10.50,3\n\
+                   \n\
+                   101,BOB,Buy,10.5,1\n\
+                   102,ALICE,sell,11,1\n";
+        let entries = read_csv(csv.as_bytes(), 5_000).unwrap();
+
+        let commands: Vec<&Command> = entries.iter().map(|e| &e.command).collect();
+        assert_eq!(commands.len(), 7);
+        assert!(matches!(commands[0], Command::OpenAccount { signer, .. } if signer == "ALICE"));
+        assert!(matches!(
+            commands[1],
+            Command::Deposit { amount: 5_000, .. }
+        ));
+        assert!(
+            matches!(commands[2], Command::Order(o) if o.price == Price(1_050) && o.amount == Quantity(3))
+        );
+        assert!(matches!(commands[3], Command::OpenAccount { signer, .. } if signer == "BOB"));
+        assert!(matches!(commands[5], Command::Order(o) if o.side == Side::Buy));
+        assert!(matches!(commands[6], Command::Order(o) if o.signer == "ALICE"));
+        assert_eq!(
+            entries.iter().map(|e| e.sequence).collect::<Vec<_>>(),
+            (1..=7).collect::<Vec<_>>()
+        );
+        assert_eq!(entries[6].timestamp, 102);
+
+        assert!(matches!(
+            read_csv("100,ALICE,hold,1,1".as_bytes(), 0),
+            Err(ReadError::Parse(1, _))
+        ));
+        assert!(matches!(
+            read_csv(
+                "timestamp,signer,side,price,amount\n100,ALICE,buy".as_bytes(),
+                0
+            ),
+            Err(ReadError::Parse(2, _))
+        ));
+    }
+}
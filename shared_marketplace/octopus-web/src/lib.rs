@@ -1,3 +1,20 @@
 pub mod accounting;
+pub mod api;
+pub mod audit;
+pub mod book;
+pub mod circuit_breaker;
+pub mod config;
+pub mod fees;
+pub mod fix;
+pub mod gateway;
+pub mod grpc;
+pub mod journal;
 pub mod matching;
+pub mod metrics;
+pub mod pipeline;
+pub mod replay;
+pub mod risk;
+#[cfg(test)]
+mod testing;
 pub mod trading_platform;
+pub mod wire;
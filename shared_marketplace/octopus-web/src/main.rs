@@ -1,192 +1,195 @@
-use std::{convert::Infallible, error::Error, sync::Arc};
-
-use octopus_common::{
-    errors::ApplicationError,
-    types::{
-        AccountBalanceRequest, AccountUpdateRequest, ErrorMessage, OctopusError, Order, SendRequest,
-    },
+use std::{env, fs, io, net::SocketAddr, process::ExitCode, time::Duration};
+
+use octopus_web::{
+    api,
+    audit::AuditLog,
+    config::{Config, USAGE},
+    fix::{Acceptor, SeqStore},
+    grpc,
+    journal::Journal,
+    pipeline::{Pipeline, DEFAULT_QUEUE_CAPACITY},
+    replay, wire,
 };
-use octopus_web::trading_platform::TradingPlatform;
-use tokio::sync::Mutex;
-use warp::{body, hyper::StatusCode, Filter, Rejection, Reply};
-
-#[tokio::main]
-async fn main() {
-    pretty_env_logger::init();
-    let platform = TradingPlatform::new();
-    let db = Arc::new(Mutex::new(platform));
-
-    let account_path = warp::path("account");
-
-    let balance_route = account_path
-        .and(warp::path::end())
-        .and(warp::get())
-        .and(with_db(Arc::clone(&db)))
-        .and(body::json::<AccountBalanceRequest>())
-        .and_then(account);
-
-    let withdraw_route = account_path
-        .and(warp::path("withdraw"))
-        .and(warp::post())
-        .and(with_db(Arc::clone(&db)))
-        .and(body::json::<AccountUpdateRequest>())
-        .and_then(withdraw);
-
-    let deposit_route = account_path
-        .and(warp::path("deposit"))
-        .and(warp::post())
-        .and(with_db(Arc::clone(&db)))
-        .and(body::json::<AccountUpdateRequest>())
-        .and_then(deposit);
-
-    let send_route = account_path
-        .and(warp::path("send"))
-        .and(warp::post())
-        .and(with_db(Arc::clone(&db)))
-        .and(body::json::<SendRequest>())
-        .and_then(send);
-
-    let order_path = warp::path("order");
-    let order_route = order_path
-        .and(warp::path::end())
-        .and(warp::post())
-        .and(with_db(Arc::clone(&db)))
-        .and(body::json::<Order>())
-        .and_then(order);
-
-    let history_route = order_path
-        .and(warp::path("history"))
-        .and(warp::get())
-        .and(with_db(Arc::clone(&db)))
-        .and_then(history);
-
-    let orderbook_path = warp::path("orderbook");
-    let orderbook_route = orderbook_path
-        .and(warp::path::end())
-        .and(warp::get())
-        .and(with_db(Arc::clone(&db)))
-        .and_then(orderbook);
+use tokio::{
+    net::TcpListener,
+    runtime::Runtime,
+    signal::unix::{signal, SignalKind},
+    sync::watch,
+    time,
+};
+use tracing_subscriber::{filter::LevelFilter, EnvFilter};
 
-    let account_route = balance_route
-        .or(withdraw_route)
-        .or(deposit_route)
-        .or(send_route)
-        .or(order_route)
-        .or(history_route)
-        .or(orderbook_route)
-        .recover(error_handler);
+/// How long open connections get to finish their requests once the server shuts down
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(5);
 
-    warp::serve(account_route).run(([127, 0, 0, 1], 8080)).await;
-}
-async fn account(db: Db, req: AccountBalanceRequest) -> Result<impl Reply, Rejection> {
-    match db.lock().await.balance_of(&req.signer) {
-        Ok(amount) => Ok(warp::reply::json(amount)),
-        Err(msg) => Err(warp::reject::custom(OctopusError::new(msg))),
-    }
-}
-async fn deposit(db: Db, req: AccountUpdateRequest) -> Result<impl Reply, Rejection> {
-    match db.lock().await.deposit(&req.signer, req.amount) {
-        Ok(tx) => Ok(warp::reply::json(&tx)),
-        Err(msg) => Err(warp::reject::custom(OctopusError::new(msg))),
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
     }
-}
-async fn withdraw(db: Db, req: AccountUpdateRequest) -> Result<impl Reply, Rejection> {
-    match db.lock().await.withdraw(&req.signer, req.amount) {
-        Ok(tx) => Ok(warp::reply::json(&tx)),
-        Err(msg) => Err(warp::reject::custom(OctopusError::new(msg))),
+    // Log records and spans are filtered by RUST_LOG, e.g. `RUST_LOG=octopus_web=debug` for every command. Warnings
+    // and errors go to stderr unless RUST_LOG says otherwise.
+    let filter = EnvFilter::builder()
+        .with_default_directive(LevelFilter::WARN.into())
+        .from_env_lossy();
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(io::stderr)
+        .init();
+    let runtime = match Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            eprintln!("Couldn't start the runtime: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let result = match Config::load(&args, |name| env::var(name).ok()) {
+        Ok(config) => runtime.block_on(run(config)),
+        Err(e) => Err(e),
+    };
+    // Everything was drained and flushed by `run`, tasks that are left, like those of idle connections, aren't waited for
+    runtime.shutdown_background();
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
     }
 }
-async fn send(db: Db, req: SendRequest) -> Result<impl Reply, Rejection> {
-    match db
-        .lock()
-        .await
-        .send(&req.sender, &req.recipient, req.amount)
-    {
-        Ok(tx) => Ok(warp::reply::json(&tx)),
-        Err(msg) => Err(warp::reject::custom(OctopusError::new(msg))),
+
+/// Starts every configured interface and serves until SIGTERM or Ctrl-C, then shuts down gracefully: listeners are
+/// closed, queued commands are applied and logged, stream clients are told the market closed and requests in flight
+/// are answered
+async fn run(config: Config) -> Result<(), String> {
+    // Listening from the start, so a signal during startup isn't lost
+    let mut terminate =
+        signal(SignalKind::terminate()).map_err(|e| format!("Couldn't handle SIGTERM: {}", e))?;
+    let (stop, _) = watch::channel(());
+    let stopped = |stop: &watch::Sender<()>| {
+        let mut stopped = stop.subscribe();
+        async move {
+            let _ = stopped.changed().await;
+        }
+    };
+    if let Some(dir) = &config.data_dir {
+        fs::create_dir_all(dir).map_err(|e| {
+            format!(
+                "Couldn't create the data directory {}: {}",
+                dir.display(),
+                e
+            )
+        })?;
     }
-}
-async fn order(db: Db, order: Order) -> Result<impl Reply, Rejection> {
-    match db.lock().await.order(order) {
-        Ok(receipt) => Ok(warp::reply::json(&receipt)),
-        Err(msg) => Err(warp::reject::custom(OctopusError::new(msg))),
+    // State changing commands are recorded for `octopus-replay` if a journal file is configured. The commands already
+    // in it are replayed first, so the server continues where it stopped.
+    let (platform, journal) = match config.journal_path() {
+        Some(path) => {
+            let (journal, entries) = Journal::open(&path)
+                .map_err(|e| format!("Couldn't open the journal {}: {}", path.display(), e))?;
+            (replay::restore(config.platform(), entries), Some(journal))
+        }
+        None => (config.platform(), None),
+    };
+    // State changing commands are also recorded with their caller and outcome for compliance, see `octopus-audit`
+    let audit = match config.audit_path() {
+        Some(path) => Some(
+            AuditLog::open(&path)
+                .map_err(|e| format!("The audit log {} is unusable: {}", path.display(), e))?,
+        ),
+        None => None,
+    };
+    let pipeline = Pipeline::spawn_with_logs(platform, DEFAULT_QUEUE_CAPACITY, journal, audit);
+
+    // Accept loops are aborted on shutdown, connections that are open already keep their own tasks
+    let mut acceptors = vec![];
+    // Institutional clients can trade over FIX 4.4 if the gateway has an address
+    if let Some(address) = config.fix_addr {
+        let store = match config.fix_store_path() {
+            Some(dir) => SeqStore::directory(&dir)
+                .map_err(|e| format!("Couldn't open the FIX store {}: {}", dir.display(), e))?,
+            None => SeqStore::memory(),
+        };
+        let listener = bind("FIX", address).await?;
+        let acceptor = Acceptor::new(pipeline.clone(), &config.fix_comp_id, store)
+            .with_counterparties(config.fix_counterparties.clone());
+        acceptors.push(tokio::spawn(acceptor.serve(listener)));
     }
-}
 
-async fn history(db: Db) -> Result<impl Reply, Infallible> {
-    Ok(warp::reply::json(
-        &(db.lock().await.matching_engine.history),
-    ))
-}
-
-async fn orderbook(db: Db) -> Result<impl Reply, Infallible> {
-    Ok(warp::reply::json(&(db.lock().await.orderbook())))
-}
-
-async fn error_handler(err: Rejection) -> Result<impl Reply, Infallible> {
-    let code;
-    let message: String;
-
-    if err.is_not_found() {
-        code = StatusCode::NOT_FOUND;
-        message = "NOT_FOUND".to_owned();
-    } else if let Some(e) = err.find::<warp::filters::body::BodyDeserializeError>() {
-        // This error happens if the body could not be deserialized correctly
-        // We can use the cause to analyze the error and customize the error message
-        message = match e.source() {
-            Some(cause) => {
-                if cause.to_string().contains("denom") {
-                    "FIELD_ERROR: denom".to_owned()
-                } else {
-                    "BAD_REQUEST".to_owned()
-                }
-            }
-            None => "BAD_REQUEST".to_owned(),
-        };
-        code = StatusCode::BAD_REQUEST;
-    } else if let Some(_) = err.find::<warp::reject::MethodNotAllowed>() {
-        // We can handle a specific error, here METHOD_NOT_ALLOWED,
-        // and render it however we want
-        code = StatusCode::METHOD_NOT_ALLOWED;
-        message = "METHOD_NOT_ALLOWED".to_owned();
-    } else if let Some(octopus_error) = err.find::<OctopusError>() {
-        match octopus_error {
-            OctopusError(ApplicationError::AccountNotFound(signer)) => {
-                code = StatusCode::NOT_FOUND;
-                message = format!("Cannot find account {}", signer);
-            }
+    // Market makers can use the binary order entry protocol if it has an address
+    if let Some(address) = config.wire_addr {
+        let listener = bind("order entry", address).await?;
+        acceptors.push(tokio::spawn(wire::serve(
+            pipeline.clone(),
+            listener,
+            config.wire_counterparties.clone(),
+        )));
+    }
 
-            OctopusError(ApplicationError::AccountOverFunded(signer, amount)) => {
-                code = StatusCode::INTERNAL_SERVER_ERROR;
-                message = format!(
-                    "Cannot exceed maximum with deposit of {} to  account {}",
-                    amount, signer
-                );
-            }
-            OctopusError(ApplicationError::AccountUnderFunded(signer, amount)) => {
-                code = StatusCode::INTERNAL_SERVER_ERROR;
-                message = format!(
-                    "Cannot withdraw from {}  from underfunded  account {}",
-                    amount, signer
-                );
+    // Internal services can use the gRPC interface if it has an address
+    let mut servers = vec![];
+    if let Some(address) = config.grpc_addr {
+        let listener = bind("gRPC", address).await?;
+        let server = grpc::serve_with_shutdown(pipeline.clone(), listener, stopped(&stop));
+        servers.push(tokio::spawn(async move {
+            if let Err(e) = server.await {
+                tracing::error!("gRPC server failed: {}", e);
             }
-        }
-    } else {
-        // We should have expected this... Just log and say its a 500
-        eprintln!("unhandled rejection: {:?}", err);
-        code = StatusCode::INTERNAL_SERVER_ERROR;
-        message = "UNHANDLED_REJECTION".to_owned();
+        }));
     }
 
-    let json = warp::reply::json(&ErrorMessage {
-        code: code.as_u16(),
-        message,
-    });
+    // Balances and credentials only travel over HTTPS if a certificate is configured
+    let (_, server) = api::serve(
+        pipeline.clone(),
+        config.http_addr,
+        &config.tls,
+        stopped(&stop),
+    )?;
+    servers.push(tokio::spawn(server));
+
+    // Operators freeze and close accounts and change the trading phase on their own interface, over mutual TLS only
+    if let Some(address) = config.admin_addr {
+        let (_, server) = api::serve_admin(
+            pipeline.clone(),
+            address,
+            &config.tls,
+            config.operators.clone(),
+            stopped(&stop),
+        )?;
+        servers.push(tokio::spawn(server));
+    }
 
-    Ok(warp::reply::with_status(json, code))
+    tokio::select! {
+        _ = terminate.recv() => {}
+        result = tokio::signal::ctrl_c() => {
+            result.map_err(|e| format!("Couldn't handle Ctrl-C: {}", e))?;
+        }
+    }
+    tracing::warn!("Shutting down");
+    stop.send_replace(());
+    for acceptor in acceptors {
+        acceptor.abort();
+    }
+    // Commands that arrive from now on are refused with ENGINE_UNAVAILABLE
+    pipeline.shutdown().await;
+    // Clients can hold idle keep-alive connections open, those are dropped once the grace period is over
+    let drained = async {
+        for server in servers {
+            let _ = server.await;
+        }
+    };
+    if time::timeout(SHUTDOWN_GRACE, drained).await.is_err() {
+        tracing::warn!(
+            "Closing the connections still open after {:?}",
+            SHUTDOWN_GRACE
+        );
+    }
+    Ok(())
 }
 
-type Db = Arc<Mutex<TradingPlatform>>;
-fn with_db(db: Db) -> impl Filter<Extract = (Db,), Error = std::convert::Infallible> + Clone {
-    warp::any().map(move || db.clone())
+async fn bind(interface: &str, address: SocketAddr) -> Result<TcpListener, String> {
+    TcpListener::bind(address)
+        .await
+        .map_err(|e| format!("Couldn't bind the {} address {}: {}", interface, address, e))
 }
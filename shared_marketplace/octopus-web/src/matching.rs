@@ -1,21 +1,26 @@
-use std::collections::{BTreeMap, BinaryHeap};
+use std::{collections::HashMap, ops::Bound};
 
 use octopus_common::{
     errors::ApplicationError,
-    types::{Order, PartialOrder, Receipt, Side},
+    money::{Price, Quantity},
+    types::{AuctionIndication, AuctionResult, MarketState, Order, PartialOrder, Receipt, Side},
 };
 
-#[derive(Default, Debug)]
+use crate::book::BookSide;
+
+#[derive(Default, Debug, Clone)]
 pub struct MatchingEngine {
     /// The last sequence number
     pub ordinal: u64,
 
-    /// The "Bid" or "Buy" side of the order book. Ordered by ordinal number.
-    pub bids: BTreeMap<u64, BinaryHeap<PartialOrder>>,
-    /// The "Ask" or "Sell" side of the order book. Ordered by ordinal number.
-    pub asks: BTreeMap<u64, BinaryHeap<PartialOrder>>,
+    /// The "Bid" or "Buy" side of the order book. Ordered by price, then ordinal number.
+    pub bids: BookSide,
+    /// The "Ask" or "Sell" side of the order book. Ordered by price, then ordinal number.
+    pub asks: BookSide,
     /// Previous matches for record keeping
     pub history: Vec<Receipt>,
+    /// The current trading phase
+    pub state: MarketState,
 }
 
 impl MatchingEngine {
@@ -23,129 +28,358 @@ impl MatchingEngine {
     pub fn new() -> Self {
         MatchingEngine {
             ordinal: 0,
-            bids: BTreeMap::new(),
-            asks: BTreeMap::new(),
+            bids: BookSide::new(),
+            asks: BookSide::new(),
             history: Vec::new(),
+            state: MarketState::Open,
+        }
+    }
+
+    /// Checks whether the market's state allows new orders.
+    ///
+    /// # Errors
+    /// The market is halted or closed
+    pub fn ensure_accepting(&self) -> Result<(), ApplicationError> {
+        match self.state {
+            MarketState::Open | MarketState::Auction => Ok(()),
+            MarketState::Halted => Err(ApplicationError::MarketHalted),
+            MarketState::Closed => Err(ApplicationError::MarketClosed),
         }
     }
 
     /// Processes an [`Order`] and returns a [`Receipt`]
     /// This includes matching the order to whatever is in the current books and adding the remainder (if any) to the book for future matching.
+    /// During an auction the order is only added to the book.
+    ///
+    /// Self-match prevention is a deliberate matching rule: an order never trades with resting orders of its own signer.
+    /// It skips them and rests if nothing else fills it, so a signer's own bid and ask may cross. Orders of different
+    /// signers never stay crossed.
+    ///
+    /// # Errors
+    /// The market is halted or closed
     pub fn process(&mut self, order: Order) -> Result<Receipt, ApplicationError> {
+        self.ensure_accepting()?;
+
         // Increment the ordinal number for this order
         self.ordinal += 1;
         let ordinal = self.ordinal;
 
         let original_amount = order.amount;
+        if self.state == MarketState::Auction {
+            let partial = order.into_partial_order(ordinal, original_amount);
+            match partial.side {
+                Side::Buy => self.bids.push_back(partial),
+                Side::Sell => self.asks.push_back(partial),
+            };
+            let receipt = Receipt {
+                ordinal,
+                matches: vec![],
+                fees: vec![],
+            };
+            self.history.push(receipt.clone());
+            return Ok(receipt);
+        }
+
+        let matches = self.matches(&order);
         let mut partial = order.into_partial_order(ordinal, original_amount);
+        let (opposite, own) = match &partial.side {
+            Side::Buy => (&mut self.asks, &mut self.bids),
+            Side::Sell => (&mut self.bids, &mut self.asks),
+        };
+        for m in &matches {
+            opposite.take(m.ordinal, m.amount, m.price);
+        }
+        let matched_amount: Quantity = matches.iter().map(|m| m.amount).sum();
+
+        // The order wasn't fully matched
+        if matched_amount < original_amount {
+            partial.amount = original_amount.saturating_sub(matched_amount);
+            partial.remaining = partial.amount;
+            own.push_back(partial);
+        }
+        let receipt = Receipt {
+            ordinal,
+            matches,
+            fees: vec![],
+        };
 
-        // Orders are matched to the opposite side
-        let receipt = match &partial.side {
-            Side::Buy => {
-                // Fetch all orders in the expected price range from this side of the orderbook
-                let orderbook_entry = self.asks.range_mut(u64::MIN..=partial.price);
-
-                let receipt = MatchingEngine::match_order(&partial, orderbook_entry, ordinal)?;
-                let matched_amount: u64 = receipt.matches.iter().map(|m| m.amount).sum();
-
-                // The order wasn't fully matched
-                if matched_amount < original_amount {
-                    partial.amount = original_amount - matched_amount;
-                    let price = partial.price;
-                    let bids = self.bids.entry(price).or_insert(vec![].into());
-                    bids.push(partial);
+        // Keep a log of matches
+        self.history.push(receipt.clone());
+        Ok(receipt)
+    }
+
+    /// Removes a resting order from the book in O(1)
+    pub fn cancel(&mut self, ordinal: u64) -> Option<PartialOrder> {
+        self.bids
+            .remove(ordinal)
+            .or_else(|| self.asks.remove(ordinal))
+    }
+
+    /// The resting order with this ordinal
+    pub fn get(&self, ordinal: u64) -> Option<&PartialOrder> {
+        self.bids.get(ordinal).or_else(|| self.asks.get(ordinal))
+    }
+
+    /// Computes the price an auction would uncross at: the price with the highest executable volume. Ties are broken by
+    /// 1. the lowest surplus,
+    /// 2. market pressure: the highest price if there is only buy surplus, the lowest if there is only sell surplus,
+    /// 3. the price closest to the `reference` price (e.g. the last trade), and finally the lowest price.
+    ///
+    /// Self-matches don't count towards the volume, so it is exactly what [`MatchingEngine::auction`] executes.
+    ///
+    /// Returns `None` if the book isn't crossed.
+    pub fn indicative(&self, reference: Option<Price>) -> Option<AuctionIndication> {
+        let candidates = self.bids.prices().chain(self.asks.prices());
+
+        let indications: Vec<AuctionIndication> = candidates
+            .map(|price| {
+                let crossing = Crossing::at(&self.bids, &self.asks, price);
+                let volume = crossing.volume();
+                AuctionIndication {
+                    price,
+                    volume: Quantity(volume),
+                    buy_surplus: Quantity(crossing.demand - volume),
+                    sell_surplus: Quantity(crossing.supply - volume),
                 }
-                receipt
+            })
+            .filter(|i| !i.volume.is_zero())
+            .collect();
+
+        let max_volume = indications.iter().map(|i| i.volume).max()?;
+        let surplus = |i: &AuctionIndication| i.buy_surplus.0.max(i.sell_surplus.0);
+        let min_surplus = indications
+            .iter()
+            .filter(|i| i.volume == max_volume)
+            .map(surplus)
+            .min()?;
+        let mut best: Vec<AuctionIndication> = indications
+            .into_iter()
+            .filter(|i| i.volume == max_volume && surplus(i) == min_surplus)
+            .collect();
+        best.sort_by_key(|i| i.price);
+        best.dedup_by_key(|i| i.price);
+
+        if best.iter().all(|i| !i.buy_surplus.is_zero()) {
+            best.pop()
+        } else if best.iter().all(|i| !i.sell_surplus.is_zero()) {
+            best.into_iter().next()
+        } else {
+            match reference {
+                Some(reference) => best
+                    .into_iter()
+                    .min_by_key(|i| i.price.0.abs_diff(reference.0)),
+                None => best.into_iter().next(),
             }
-            Side::Sell => {
-                // Fetch all orders in the expected price range from this side of the orderbook
-                let orderbook_entry = self.bids.range_mut(partial.price..=u64::MAX);
-
-                let receipt = MatchingEngine::match_order(&partial, orderbook_entry, ordinal)?;
-                let matched_amount: u64 = receipt.matches.iter().map(|m| m.amount).sum();
-
-                // The order wasn't fully matched
-                if matched_amount < original_amount {
-                    partial.amount = original_amount - matched_amount;
-                    let price = partial.price;
-                    let bids = self.asks.entry(price).or_insert(vec![].into());
-                    bids.push(partial);
+        }
+    }
+
+    /// Plans the end of an auction: all crossing orders execute at the [`MatchingEngine::indicative`] price and volume,
+    /// without self-matches. Orders are paired in price-time priority, except that a pair only takes as much as still
+    /// leaves the rest of the volume executable between the other orders.
+    ///
+    /// The book isn't changed, [`MatchingEngine::close_auction`] executes the result.
+    pub fn auction(&self, reference: Option<Price>) -> Option<AuctionResult> {
+        let indication = self.indicative(reference)?;
+        let price = indication.price;
+        let mut crossing = Crossing::at(&self.bids, &self.asks, price);
+        let mut bids: Vec<PartialOrder> = self
+            .bids
+            .levels()
+            .rev()
+            .flat_map(|(_, orders)| orders)
+            .filter(|o| o.price >= price)
+            .cloned()
+            .collect();
+        let mut asks: Vec<PartialOrder> = self
+            .asks
+            .orders()
+            .filter(|o| o.price <= price)
+            .cloned()
+            .collect();
+
+        let mut fills = vec![];
+        let mut left = indication.volume.0;
+        // A pair that has to hold back is revisited once the orders ahead of it filled
+        let mut progress = true;
+        while left > 0 && progress {
+            progress = false;
+            for bid in bids.iter_mut() {
+                for ask in asks.iter_mut() {
+                    if left == 0 || bid.remaining.is_zero() {
+                        break;
+                    }
+                    if ask.remaining.is_zero() || ask.signer == bid.signer {
+                        continue;
+                    }
+                    let take = bid
+                        .remaining
+                        .0
+                        .min(ask.remaining.0)
+                        .min(left)
+                        .min(crossing.limit(&bid.signer, &ask.signer, left));
+                    if take == 0 {
+                        continue;
+                    }
+                    crossing.fill(&bid.signer, &ask.signer, take);
+                    left -= take;
+                    progress = true;
+                    fills.push((
+                        PartialOrder::take_from(bid, Quantity(take), price),
+                        PartialOrder::take_from(ask, Quantity(take), price),
+                    ));
                 }
-                receipt
             }
-        };
+        }
+        debug_assert_eq!(left, 0, "the indicated volume is executable");
+        Some(AuctionResult { indication, fills })
+    }
 
-        // Cleanup: Remove price entries without orders from the orderbook
-        self.asks.retain(|_, orders| !orders.is_empty());
-        self.bids.retain(|_, orders| !orders.is_empty());
+    /// Executes the fills of an [`MatchingEngine::auction`] on the book and moves the market to the `next` state
+    pub fn close_auction(&mut self, result: Option<&AuctionResult>, next: MarketState) {
+        for (buy, sell) in result.map_or(&[][..], |r| &r.fills[..]) {
+            self.bids.take(buy.ordinal, buy.amount, buy.price);
+            self.asks.take(sell.ordinal, sell.amount, sell.price);
+            // Keep a log of matches from the buyers' point of view
+            self.history.push(Receipt {
+                ordinal: buy.ordinal,
+                matches: vec![sell.clone()],
+                fees: vec![],
+            });
+        }
+        self.state = next;
+    }
 
-        // Keep a log of matches
-        self.history.push(receipt.clone());
-        Ok(receipt)
+    /// Ends an auction by executing its [`MatchingEngine::auction`] right away. Afterwards the market moves to the `next` state.
+    pub fn uncross(
+        &mut self,
+        reference: Option<Price>,
+        next: MarketState,
+    ) -> Option<AuctionResult> {
+        let result = self.auction(reference);
+        self.close_auction(result.as_ref(), next);
+        result
+    }
+
+    /// Removes all resting orders of `signer` from both sides of the book and returns them
+    pub fn remove_orders_of(&mut self, signer: &str) -> Vec<PartialOrder> {
+        let mut removed = vec![];
+        for side in [&mut self.bids, &mut self.asks] {
+            let theirs: Vec<u64> = side
+                .orders()
+                .filter(|o| o.signer == signer)
+                .map(|o| o.ordinal)
+                .collect();
+            removed.extend(
+                theirs
+                    .into_iter()
+                    .filter_map(|ordinal| side.remove(ordinal)),
+            );
+        }
+        removed.sort_by_key(|o| o.ordinal);
+        removed
     }
 
-    /// Matches an order to the provided order book side.
-    /// # Parameters
-    /// - `orderbook_entry`: a pre-filtered iterator for order book_entry in the correct price range
-    /// - `ordinal` the next ordinal number to use if a position is opened
-    fn match_order<'a, T>(
-        order: &PartialOrder,
-        mut orderbook_entry: T,
-        ordinal: u64,
-    ) -> Result<Receipt, ApplicationError>
-    where
-        T: Iterator<Item = (&'a u64, &'a mut BinaryHeap<PartialOrder>)>,
-    {
+    /// The matches an incoming order gets if it is processed now, without changing the book. Each level in the
+    /// order's price range, walked from the best price for the order (the lowest ask for a buy, the highest bid for a
+    /// sell), fills as much as it can (skipping self-matches) until the order is filled. Orders only match while the
+    /// market is open.
+    pub fn matches(&self, order: &Order) -> Vec<PartialOrder> {
+        if self.state != MarketState::Open {
+            return vec![];
+        }
+        let (opposite, (mut from, mut to)) = match order.side {
+            Side::Buy => (&self.asks, (Bound::Unbounded, Bound::Included(order.price))),
+            Side::Sell => (&self.bids, (Bound::Included(order.price), Bound::Unbounded)),
+        };
         let mut remaining_amount = order.amount;
         let mut matches = vec![];
-
-        // Each matching position's amount is subtraced
-        'outer: while remaining_amount > 0 {
-            // The iterator contains all orderbook_entry of a price point
-            match orderbook_entry.next() {
-                Some((price, orderbook_entry)) => {
-                    // Self-matches are illegal
-                    let mut self_matches = vec![];
-                    // pop a position off the heap
-                    'ask_loop: while let Some(mut pos) = orderbook_entry.pop() {
-                        // A self-match is illegal so we keep the order and skip the matching for it
-                        if pos.signer == order.signer {
-                            self_matches.push(pos);
-                            continue 'ask_loop;
-                        }
-
-                        // Using checked_sub guards against overlow errors
-                        match pos.remaining.checked_sub(remaining_amount) {
-                            Some(_) => {
-                                matches.push(PartialOrder::take_from(
-                                    &mut pos,
-                                    remaining_amount,
-                                    *price,
-                                ));
-                                if pos.remaining > 0 {
-                                    orderbook_entry.push(pos);
-                                }
-                                break 'ask_loop;
-                            }
-                            None => {
-                                remaining_amount -= pos.remaining;
-                                pos.remaining = 0;
-                                matches.push(pos);
-                            }
-                        }
+        while !remaining_amount.is_zero() {
+            let best = match order.side {
+                Side::Buy => opposite.first_price_in((from, to)),
+                Side::Sell => opposite.last_price_in((from, to)),
+            };
+            match best {
+                Some(price) => {
+                    let filled =
+                        opposite.plan_fill(price, &order.signer, remaining_amount, &mut matches);
+                    remaining_amount = remaining_amount.saturating_sub(filled);
+                    match order.side {
+                        Side::Buy => from = Bound::Excluded(price),
+                        Side::Sell => to = Bound::Excluded(price),
                     }
-
-                    // Return the self-matched orders at their price in the book. To avoid an infinite loop, we put the orders back after finishing the matching for this price point
-                    self_matches
-                        .into_iter()
-                        .for_each(|m| orderbook_entry.push(m));
                 }
                 // Nothing left to match with
-                None => break 'outer,
+                None => break,
             }
         }
+        matches
+    }
+}
+
+/// The quantities that can trade at one auction price, in total and per signer
+#[derive(Debug, Default)]
+struct Crossing<'a> {
+    demand: u64,
+    supply: u64,
+    /// Bid and ask quantity of each signer
+    signers: HashMap<&'a str, (u64, u64)>,
+}
+
+impl<'a> Crossing<'a> {
+    /// Sums up the bids at or above and the asks at or below `price`
+    fn at(bids: &'a BookSide, asks: &'a BookSide, price: Price) -> Self {
+        let mut crossing = Crossing::default();
+        for bid in bids.orders().filter(|o| o.price >= price) {
+            crossing.demand = crossing.demand.saturating_add(bid.remaining.0);
+            let (demand, _) = crossing.signers.entry(&bid.signer).or_default();
+            *demand = demand.saturating_add(bid.remaining.0);
+        }
+        for ask in asks.orders().filter(|o| o.price <= price) {
+            crossing.supply = crossing.supply.saturating_add(ask.remaining.0);
+            let (_, supply) = crossing.signers.entry(&ask.signer).or_default();
+            *supply = supply.saturating_add(ask.remaining.0);
+        }
+        crossing
+    }
 
-        Ok(Receipt { ordinal, matches })
+    /// The quantity of all orders that aren't `signer`'s
+    fn without(&self, signer: &str) -> u64 {
+        let (demand, supply) = self.signers.get(signer).copied().unwrap_or_default();
+        self.demand
+            .saturating_sub(demand)
+            .saturating_add(self.supply.saturating_sub(supply))
+    }
+
+    /// The highest volume that can execute without self-matches. Every unit a signer trades is matched with another
+    /// signer's order, so the volume is at most the quantity of all orders that aren't that signer's.
+    fn volume(&self) -> u64 {
+        self.signers
+            .keys()
+            .map(|signer| self.without(signer))
+            .fold(self.demand.min(self.supply), u64::min)
+    }
+
+    /// The most `buyer` and `seller` can trade with each other while `left` stays executable: a fill between them takes
+    /// twice its quantity from the orders of any other signer, but only once from the volume left.
+    fn limit(&self, buyer: &str, seller: &str, left: u64) -> u64 {
+        self.signers
+            .keys()
+            .filter(|signer| **signer != buyer && **signer != seller)
+            .map(|signer| self.without(signer).saturating_sub(left))
+            .min()
+            .unwrap_or(u64::MAX)
+    }
+
+    /// Takes a fill of `quantity` between `buyer` and `seller` out of the crossing quantities
+    fn fill(&mut self, buyer: &str, seller: &str, quantity: u64) {
+        self.demand = self.demand.saturating_sub(quantity);
+        self.supply = self.supply.saturating_sub(quantity);
+        if let Some((demand, _)) = self.signers.get_mut(buyer) {
+            *demand = demand.saturating_sub(quantity);
+        }
+        if let Some((_, supply)) = self.signers.get_mut(seller) {
+            *supply = supply.saturating_sub(quantity);
+        }
     }
 }
 
@@ -162,8 +396,8 @@ mod tests {
 
         let alice_receipt = matching_engine
             .process(Order {
-                price: 10,
-                amount: 1,
+                price: Price(10),
+                amount: Quantity(1),
                 side: Side::Sell,
                 signer: "ALICE".to_string(),
             })
@@ -173,8 +407,8 @@ mod tests {
 
         let bob_receipt = matching_engine
             .process(Order {
-                price: 10,
-                amount: 2,
+                price: Price(10),
+                amount: Quantity(2),
                 side: Side::Buy,
                 signer: "BOB".to_string(),
             })
@@ -183,9 +417,9 @@ mod tests {
         assert_eq!(
             bob_receipt.matches,
             vec![PartialOrder {
-                price: 10,
-                amount: 1,
-                remaining: 0,
+                price: Price(10),
+                amount: Quantity(1),
+                remaining: Quantity(0),
                 side: Side::Sell,
                 signer: "ALICE".to_string(),
                 ordinal: 1
@@ -201,8 +435,8 @@ mod tests {
 
         let alice_receipt = matching_engine
             .process(Order {
-                price: 10,
-                amount: 2,
+                price: Price(10),
+                amount: Quantity(2),
                 side: Side::Sell,
                 signer: "ALICE".to_string(),
             })
@@ -212,8 +446,8 @@ mod tests {
 
         let bob_receipt = matching_engine
             .process(Order {
-                price: 10,
-                amount: 2,
+                price: Price(10),
+                amount: Quantity(2),
                 side: Side::Buy,
                 signer: "BOB".to_string(),
             })
@@ -222,9 +456,9 @@ mod tests {
         assert_eq!(
             bob_receipt.matches,
             vec![PartialOrder {
-                price: 10,
-                amount: 2,
-                remaining: 0,
+                price: Price(10),
+                amount: Quantity(2),
+                remaining: Quantity(0),
                 side: Side::Sell,
                 signer: "ALICE".to_string(),
                 ordinal: 1
@@ -242,8 +476,8 @@ mod tests {
 
         let alice_receipt = matching_engine
             .process(Order {
-                price: 10,
-                amount: 1,
+                price: Price(10),
+                amount: Quantity(1),
                 side: Side::Sell,
                 signer: "ALICE".to_string(),
             })
@@ -253,8 +487,8 @@ mod tests {
 
         let charlie_receipt = matching_engine
             .process(Order {
-                price: 10,
-                amount: 1,
+                price: Price(10),
+                amount: Quantity(1),
                 side: Side::Sell,
                 signer: "CHARLIE".to_string(),
             })
@@ -264,8 +498,8 @@ mod tests {
 
         let bob_receipt = matching_engine
             .process(Order {
-                price: 10,
-                amount: 2,
+                price: Price(10),
+                amount: Quantity(2),
                 side: Side::Buy,
                 signer: "BOB".to_string(),
             })
@@ -275,17 +509,17 @@ mod tests {
             bob_receipt.matches,
             vec![
                 PartialOrder {
-                    price: 10,
-                    amount: 1,
-                    remaining: 0,
+                    price: Price(10),
+                    amount: Quantity(1),
+                    remaining: Quantity(0),
                     side: Side::Sell,
                     signer: "ALICE".to_string(),
                     ordinal: 1
                 },
                 PartialOrder {
-                    price: 10,
-                    amount: 1,
-                    remaining: 0,
+                    price: Price(10),
+                    amount: Quantity(1),
+                    remaining: Quantity(0),
                     side: Side::Sell,
                     signer: "CHARLIE".to_string(),
                     ordinal: 2
@@ -303,8 +537,8 @@ mod tests {
 
         let alice_receipt = matching_engine
             .process(Order {
-                price: 11,
-                amount: 1,
+                price: Price(11),
+                amount: Quantity(1),
                 side: Side::Sell,
                 signer: "ALICE".to_string(),
             })
@@ -314,8 +548,8 @@ mod tests {
 
         let charlie_receipt = matching_engine
             .process(Order {
-                price: 9,
-                amount: 1,
+                price: Price(9),
+                amount: Quantity(1),
                 side: Side::Sell,
                 signer: "CHARLIE".to_string(),
             })
@@ -325,8 +559,8 @@ mod tests {
 
         let bob_receipt = matching_engine
             .process(Order {
-                price: 15,
-                amount: 2,
+                price: Price(15),
+                amount: Quantity(2),
                 side: Side::Buy,
                 signer: "BOB".to_string(),
             })
@@ -336,17 +570,17 @@ mod tests {
             bob_receipt.matches,
             vec![
                 PartialOrder {
-                    price: 9,
-                    amount: 1,
-                    remaining: 0,
+                    price: Price(9),
+                    amount: Quantity(1),
+                    remaining: Quantity(0),
                     side: Side::Sell,
                     signer: "CHARLIE".to_string(),
                     ordinal: 2
                 },
                 PartialOrder {
-                    price: 11,
-                    amount: 1,
-                    remaining: 0,
+                    price: Price(11),
+                    amount: Quantity(1),
+                    remaining: Quantity(0),
                     side: Side::Sell,
                     signer: "ALICE".to_string(),
                     ordinal: 1
@@ -359,13 +593,13 @@ mod tests {
     }
 
     #[test]
-    fn test_MatchingEngine_process_fully_match_order_multi_match_price_ranges_buy() {
+    fn test_MatchingEngine_process_fully_match_order_multi_match_highest_bid_first() {
         let mut matching_engine = MatchingEngine::new();
 
         let alice_receipt = matching_engine
             .process(Order {
-                price: 10,
-                amount: 1,
+                price: Price(5),
+                amount: Quantity(1),
                 side: Side::Buy,
                 signer: "ALICE".to_string(),
             })
@@ -375,8 +609,8 @@ mod tests {
 
         let charlie_receipt = matching_engine
             .process(Order {
-                price: 5,
-                amount: 1,
+                price: Price(10),
+                amount: Quantity(1),
                 side: Side::Buy,
                 signer: "CHARLIE".to_string(),
             })
@@ -386,28 +620,29 @@ mod tests {
 
         let bob_receipt = matching_engine
             .process(Order {
-                price: 4,
-                amount: 2,
+                price: Price(4),
+                amount: Quantity(2),
                 side: Side::Sell,
                 signer: "BOB".to_string(),
             })
             .unwrap();
 
+        // The highest bid fills first although it rested later
         assert_eq!(
             bob_receipt.matches,
             vec![
                 PartialOrder {
-                    price: 5,
-                    amount: 1,
-                    remaining: 0,
+                    price: Price(10),
+                    amount: Quantity(1),
+                    remaining: Quantity(0),
                     side: Side::Buy,
                     signer: "CHARLIE".to_string(),
                     ordinal: 2
                 },
                 PartialOrder {
-                    price: 10,
-                    amount: 1,
-                    remaining: 0,
+                    price: Price(5),
+                    amount: Quantity(1),
+                    remaining: Quantity(0),
                     side: Side::Buy,
                     signer: "ALICE".to_string(),
                     ordinal: 1
@@ -425,8 +660,8 @@ mod tests {
 
         let alice_receipt = matching_engine
             .process(Order {
-                price: 10,
-                amount: 1,
+                price: Price(10),
+                amount: Quantity(1),
                 side: Side::Sell,
                 signer: "ALICE".to_string(),
             })
@@ -436,8 +671,8 @@ mod tests {
 
         let charlie_receipt = matching_engine
             .process(Order {
-                price: 10,
-                amount: 1,
+                price: Price(10),
+                amount: Quantity(1),
                 side: Side::Sell,
                 signer: "CHARLIE".to_string(),
             })
@@ -447,8 +682,8 @@ mod tests {
 
         let alice_receipt = matching_engine
             .process(Order {
-                price: 10,
-                amount: 2,
+                price: Price(10),
+                amount: Quantity(2),
                 side: Side::Buy,
                 signer: "ALICE".to_string(),
             })
@@ -457,9 +692,9 @@ mod tests {
         assert_eq!(
             alice_receipt.matches,
             vec![PartialOrder {
-                price: 10,
-                amount: 1,
-                remaining: 0,
+                price: Price(10),
+                amount: Quantity(1),
+                remaining: Quantity(0),
                 side: Side::Sell,
                 signer: "CHARLIE".to_string(),
                 ordinal: 2
@@ -476,8 +711,8 @@ mod tests {
 
         let alice_receipt = matching_engine
             .process(Order {
-                price: 10,
-                amount: 2,
+                price: Price(10),
+                amount: Quantity(2),
                 side: Side::Sell,
                 signer: "ALICE".to_string(),
             })
@@ -487,8 +722,8 @@ mod tests {
 
         let bob_receipt = matching_engine
             .process(Order {
-                price: 11,
-                amount: 2,
+                price: Price(11),
+                amount: Quantity(2),
                 side: Side::Sell,
                 signer: "BOB".to_string(),
             })
@@ -504,8 +739,8 @@ mod tests {
         assert_eq!(matching_engine.ordinal, 0);
         let receipt = matching_engine
             .process(Order {
-                price: 10,
-                amount: 1,
+                price: Price(10),
+                amount: Quantity(1),
                 side: Side::Buy,
                 signer: "ALICE".to_string(),
             })
@@ -514,8 +749,8 @@ mod tests {
 
         let receipt = matching_engine
             .process(Order {
-                price: 10,
-                amount: 1,
+                price: Price(10),
+                amount: Quantity(1),
                 side: Side::Buy,
                 signer: "BOB".to_string(),
             })
@@ -524,8 +759,8 @@ mod tests {
 
         let receipt = matching_engine
             .process(Order {
-                price: 10,
-                amount: 1,
+                price: Price(10),
+                amount: Quantity(1),
                 side: Side::Buy,
                 signer: "CHARLIE".to_string(),
             })
@@ -533,4 +768,413 @@ mod tests {
         assert_eq!(receipt.ordinal, matching_engine.ordinal);
         assert_eq!(matching_engine.ordinal, 3);
     }
+
+    #[test]
+    fn test_MatchingEngine_process_rejects_orders_while_halted_or_closed() {
+        let mut matching_engine = MatchingEngine::new();
+        let order = Order {
+            price: Price(10),
+            amount: Quantity(1),
+            side: Side::Buy,
+            signer: "ALICE".to_string(),
+        };
+
+        matching_engine.state = MarketState::Halted;
+        assert_eq!(
+            matching_engine.process(order.clone()),
+            Err(ApplicationError::MarketHalted)
+        );
+        matching_engine.state = MarketState::Closed;
+        assert_eq!(
+            matching_engine.process(order.clone()),
+            Err(ApplicationError::MarketClosed)
+        );
+        assert_eq!(matching_engine.ordinal, 0);
+        assert!(matching_engine.bids.is_empty());
+
+        matching_engine.state = MarketState::Open;
+        assert!(matching_engine.process(order).is_ok());
+        assert_eq!(matching_engine.bids.len(), 1);
+    }
+
+    #[test]
+    fn test_MatchingEngine_process_auction_queues_without_matching() {
+        let mut matching_engine = MatchingEngine::new();
+        matching_engine.state = MarketState::Auction;
+
+        matching_engine
+            .process(Order {
+                price: Price(10),
+                amount: Quantity(1),
+                side: Side::Sell,
+                signer: "ALICE".to_string(),
+            })
+            .unwrap();
+        let receipt = matching_engine
+            .process(Order {
+                price: Price(12),
+                amount: Quantity(1),
+                side: Side::Buy,
+                signer: "BOB".to_string(),
+            })
+            .unwrap();
+
+        assert_eq!(receipt.matches, vec![]);
+        assert_eq!(matching_engine.asks.len(), 1);
+        assert_eq!(matching_engine.bids.len(), 1);
+    }
+
+    fn auction_book(orders: &[(Side, u64, u64, &str)]) -> MatchingEngine {
+        let mut matching_engine = MatchingEngine::new();
+        matching_engine.state = MarketState::Auction;
+        for (side, price, amount, signer) in orders {
+            matching_engine
+                .process(Order {
+                    price: Price(*price),
+                    amount: Quantity(*amount),
+                    side: side.clone(),
+                    signer: signer.to_string(),
+                })
+                .unwrap();
+        }
+        matching_engine
+    }
+
+    #[test]
+    fn test_MatchingEngine_indicative_maximizes_volume_then_minimizes_surplus() {
+        let matching_engine = auction_book(&[
+            (Side::Buy, 102, 10, "B1"),
+            (Side::Buy, 101, 20, "B2"),
+            (Side::Buy, 100, 15, "B3"),
+            (Side::Sell, 99, 10, "S1"),
+            (Side::Sell, 100, 15, "S2"),
+            (Side::Sell, 102, 20, "S3"),
+        ]);
+
+        // 25 units are executable at 100 and 101, but 101 leaves the lower surplus
+        assert_eq!(
+            matching_engine.indicative(None),
+            Some(AuctionIndication {
+                price: Price(101),
+                volume: Quantity(25),
+                buy_surplus: Quantity(5),
+                sell_surplus: Quantity(0),
+            })
+        );
+    }
+
+    #[test]
+    fn test_MatchingEngine_indicative_tie_breaks_by_pressure_and_reference() {
+        // Only buy surplus: the highest price wins
+        let matching_engine =
+            auction_book(&[(Side::Buy, 101, 20, "B1"), (Side::Sell, 100, 10, "S1")]);
+        assert_eq!(
+            matching_engine.indicative(None).map(|i| i.price),
+            Some(Price(101))
+        );
+
+        // Only sell surplus: the lowest price wins
+        let matching_engine =
+            auction_book(&[(Side::Buy, 101, 10, "B1"), (Side::Sell, 100, 20, "S1")]);
+        assert_eq!(
+            matching_engine.indicative(None).map(|i| i.price),
+            Some(Price(100))
+        );
+
+        // No surplus: the price closest to the reference wins
+        let matching_engine =
+            auction_book(&[(Side::Buy, 101, 10, "B1"), (Side::Sell, 100, 10, "S1")]);
+        assert_eq!(
+            matching_engine
+                .indicative(Some(Price(105)))
+                .map(|i| i.price),
+            Some(Price(101))
+        );
+        assert_eq!(
+            matching_engine.indicative(Some(Price(90))).map(|i| i.price),
+            Some(Price(100))
+        );
+        assert_eq!(
+            matching_engine.indicative(None).map(|i| i.price),
+            Some(Price(100))
+        );
+
+        // A book that isn't crossed has no indication
+        let matching_engine =
+            auction_book(&[(Side::Buy, 99, 10, "B1"), (Side::Sell, 100, 10, "S1")]);
+        assert_eq!(matching_engine.indicative(None), None);
+    }
+
+    #[test]
+    fn test_MatchingEngine_uncross_fills_at_single_price_and_opens_market() {
+        let mut matching_engine = auction_book(&[
+            (Side::Buy, 102, 10, "B1"),
+            (Side::Buy, 101, 20, "B2"),
+            (Side::Buy, 100, 15, "B3"),
+            (Side::Sell, 99, 10, "S1"),
+            (Side::Sell, 100, 15, "S2"),
+            (Side::Sell, 102, 20, "S3"),
+        ]);
+
+        let result = matching_engine
+            .uncross(None, MarketState::Open)
+            .expect("The book is crossed");
+        let fills: Vec<_> = result
+            .fills
+            .iter()
+            .map(|(b, s)| {
+                (
+                    b.signer.as_str(),
+                    s.signer.as_str(),
+                    b.amount,
+                    b.price,
+                    s.price,
+                )
+            })
+            .collect();
+        assert_eq!(
+            fills,
+            vec![
+                ("B1", "S1", Quantity(10), Price(101), Price(101)),
+                ("B2", "S2", Quantity(15), Price(101), Price(101)),
+            ]
+        );
+        assert_eq!(matching_engine.state, MarketState::Open);
+
+        // The remaining orders aren't crossed anymore
+        assert_eq!(matching_engine.indicative(None), None);
+        let remaining: Vec<_> = matching_engine
+            .bids
+            .orders()
+            .chain(matching_engine.asks.orders())
+            .map(|o| (o.signer.as_str(), o.remaining))
+            .collect();
+        assert_eq!(
+            remaining,
+            vec![
+                ("B3", Quantity(15)),
+                ("B2", Quantity(5)),
+                ("S3", Quantity(20))
+            ]
+        );
+        assert_eq!(matching_engine.history.len(), 6 + 2);
+    }
+
+    #[test]
+    fn test_MatchingEngine_uncross_executes_indicated_volume_without_self_matches() {
+        let mut matching_engine = auction_book(&[
+            (Side::Buy, 10, 5, "C"),
+            (Side::Buy, 10, 5, "A"),
+            (Side::Sell, 10, 5, "B"),
+            (Side::Sell, 10, 5, "A"),
+        ]);
+        // A's own orders can't match each other, but C buying from A and A buying from B fills everything
+        let indication = matching_engine
+            .indicative(None)
+            .expect("The book is crossed");
+        assert_eq!(indication.volume, Quantity(10));
+        assert_eq!(indication.buy_surplus, Quantity(0));
+        assert_eq!(indication.sell_surplus, Quantity(0));
+
+        // Matching C with B first, in time priority, would leave only A's orders
+        let result = matching_engine
+            .uncross(None, MarketState::Open)
+            .expect("The book is crossed");
+        let fills: Vec<_> = result
+            .fills
+            .iter()
+            .map(|(b, s)| (b.signer.as_str(), s.signer.as_str(), b.amount))
+            .collect();
+        assert_eq!(
+            fills,
+            vec![("C", "A", Quantity(5)), ("A", "B", Quantity(5))]
+        );
+        assert!(matching_engine.bids.is_empty());
+        assert!(matching_engine.asks.is_empty());
+
+        // Self-matches don't count towards the indication
+        let matching_engine = auction_book(&[
+            (Side::Buy, 10, 5, "A"),
+            (Side::Buy, 10, 5, "B"),
+            (Side::Sell, 10, 8, "A"),
+        ]);
+        assert_eq!(
+            matching_engine.indicative(None),
+            Some(AuctionIndication {
+                price: Price(10),
+                volume: Quantity(5),
+                buy_surplus: Quantity(5),
+                sell_surplus: Quantity(3),
+            })
+        );
+    }
+
+    #[test]
+    fn test_MatchingEngine_uncross_without_cross_only_changes_state() {
+        let mut matching_engine =
+            auction_book(&[(Side::Buy, 99, 10, "B1"), (Side::Sell, 100, 10, "S1")]);
+        assert_eq!(matching_engine.uncross(None, MarketState::Closed), None);
+        assert_eq!(matching_engine.state, MarketState::Closed);
+        assert_eq!(matching_engine.bids.len(), 1);
+        assert_eq!(matching_engine.asks.len(), 1);
+    }
+
+    #[test]
+    fn test_MatchingEngine_process_stops_once_order_is_filled() {
+        let mut matching_engine = MatchingEngine::new();
+        for price in [1, 2] {
+            matching_engine
+                .process(Order {
+                    price: Price(price),
+                    amount: Quantity(10),
+                    side: Side::Sell,
+                    signer: "ALICE".to_string(),
+                })
+                .unwrap();
+        }
+
+        let bob_receipt = matching_engine
+            .process(Order {
+                price: Price(2),
+                amount: Quantity(5),
+                side: Side::Buy,
+                signer: "BOB".to_string(),
+            })
+            .unwrap();
+        assert_eq!(
+            bob_receipt.matches,
+            vec![PartialOrder {
+                price: Price(1),
+                amount: Quantity(5),
+                remaining: Quantity(5),
+                side: Side::Sell,
+                signer: "ALICE".to_string(),
+                ordinal: 1
+            }]
+        );
+
+        // The second level is untouched
+        let charlie_receipt = matching_engine
+            .process(Order {
+                price: Price(2),
+                amount: Quantity(15),
+                side: Side::Buy,
+                signer: "CHARLIE".to_string(),
+            })
+            .unwrap();
+        let matched: Vec<_> = charlie_receipt
+            .matches
+            .iter()
+            .map(|m| (m.ordinal, m.amount))
+            .collect();
+        assert_eq!(matched, vec![(1, Quantity(5)), (2, Quantity(10))]);
+        assert!(matching_engine.asks.is_empty());
+    }
+
+    #[test]
+    fn test_MatchingEngine_process_reports_filled_part_of_partially_filled_order() {
+        let mut matching_engine = MatchingEngine::new();
+        matching_engine
+            .process(Order {
+                price: Price(1),
+                amount: Quantity(10),
+                side: Side::Sell,
+                signer: "ALICE".to_string(),
+            })
+            .unwrap();
+        matching_engine
+            .process(Order {
+                price: Price(1),
+                amount: Quantity(4),
+                side: Side::Buy,
+                signer: "BOB".to_string(),
+            })
+            .unwrap();
+
+        // Filling the rest of the order only reports what was left, the rest of the buy order rests
+        let charlie_receipt = matching_engine
+            .process(Order {
+                price: Price(1),
+                amount: Quantity(10),
+                side: Side::Buy,
+                signer: "CHARLIE".to_string(),
+            })
+            .unwrap();
+        assert_eq!(charlie_receipt.matches.len(), 1);
+        assert_eq!(charlie_receipt.matches[0].amount, Quantity(6));
+        assert_eq!(charlie_receipt.matches[0].remaining, Quantity(0));
+        assert!(matching_engine.asks.is_empty());
+        assert_eq!(matching_engine.bids.len(), 1);
+    }
+
+    #[test]
+    fn test_MatchingEngine_process_rests_unfilled_remainder() {
+        let mut matching_engine = MatchingEngine::new();
+        matching_engine
+            .process(Order {
+                price: Price(10),
+                amount: Quantity(3),
+                side: Side::Sell,
+                signer: "ALICE".to_string(),
+            })
+            .unwrap();
+        matching_engine
+            .process(Order {
+                price: Price(10),
+                amount: Quantity(10),
+                side: Side::Buy,
+                signer: "BOB".to_string(),
+            })
+            .unwrap();
+
+        // Only the remainder can be matched
+        let charlie_receipt = matching_engine
+            .process(Order {
+                price: Price(10),
+                amount: Quantity(10),
+                side: Side::Sell,
+                signer: "CHARLIE".to_string(),
+            })
+            .unwrap();
+        assert_eq!(charlie_receipt.matches.len(), 1);
+        assert_eq!(charlie_receipt.matches[0].amount, Quantity(7));
+        assert_eq!(charlie_receipt.matches[0].remaining, Quantity(0));
+        assert!(matching_engine.bids.is_empty());
+        assert_eq!(matching_engine.asks.len(), 1);
+    }
+
+    #[test]
+    fn test_MatchingEngine_cancel_removes_only_that_order() {
+        let mut matching_engine = MatchingEngine::new();
+        for signer in ["ALICE", "BOB", "CHARLIE"] {
+            matching_engine
+                .process(Order {
+                    price: Price(10),
+                    amount: Quantity(1),
+                    side: Side::Sell,
+                    signer: signer.to_string(),
+                })
+                .unwrap();
+        }
+
+        assert_eq!(
+            matching_engine.cancel(2).map(|o| o.signer),
+            Some("BOB".to_string())
+        );
+        assert_eq!(matching_engine.cancel(2), None);
+        assert_eq!(matching_engine.get(2), None);
+
+        // Time priority of the others is kept
+        let receipt = matching_engine
+            .process(Order {
+                price: Price(10),
+                amount: Quantity(2),
+                side: Side::Buy,
+                signer: "DAVE".to_string(),
+            })
+            .unwrap();
+        let signers: Vec<_> = receipt.matches.iter().map(|m| m.signer.as_str()).collect();
+        assert_eq!(signers, vec!["ALICE", "CHARLIE"]);
+        assert!(matching_engine.asks.is_empty());
+    }
 }
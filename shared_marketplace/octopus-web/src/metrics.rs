@@ -0,0 +1,271 @@
+//! Prometheus metrics of a [`Pipeline`](crate::pipeline::Pipeline), rendered in the text exposition format at
+//! `/metrics`.
+
+use std::time::Duration;
+
+use octopus_common::{errors::ApplicationError, types::Receipt};
+use prometheus::{
+    exponential_buckets, Encoder, Histogram, HistogramOpts, HistogramVec, IntCounterVec, IntGauge,
+    IntGaugeVec, Opts, Registry, TextEncoder,
+};
+
+use crate::pipeline::{BookView, Outcome};
+
+/// Content type of [`Metrics::render`]
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// Counters and histograms of one pipeline. Each pipeline has its own registry, so several can run in one process.
+pub struct Metrics {
+    registry: Registry,
+    orders_accepted: IntCounterVec,
+    orders_rejected: IntCounterVec,
+    matches: IntCounterVec,
+    traded_quantity: IntCounterVec,
+    traded_notional: IntCounterVec,
+    book_quantity: IntGaugeVec,
+    book_levels: IntGaugeVec,
+    queue_depth: IntGauge,
+    queue_wait: Histogram,
+    apply_duration: HistogramVec,
+    request_duration: HistogramVec,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new_custom(Some("octopus".to_string()), None)
+            .expect("The prefix should be valid");
+        // Commands wait and apply in microseconds, so the default buckets starting at 5ms are too coarse
+        let fast = exponential_buckets(1e-6, 4.0, 12).expect("The buckets should be valid");
+        let metrics = Metrics {
+            orders_accepted: IntCounterVec::new(
+                Opts::new("orders_accepted_total", "Orders accepted by the platform"),
+                &["market"],
+            )
+            .unwrap(),
+            orders_rejected: IntCounterVec::new(
+                Opts::new(
+                    "orders_rejected_total",
+                    "Orders rejected by the platform, by error code",
+                ),
+                &["market", "reason"],
+            )
+            .unwrap(),
+            matches: IntCounterVec::new(
+                Opts::new(
+                    "matches_total",
+                    "Fills between an incoming and a resting order",
+                ),
+                &["market"],
+            )
+            .unwrap(),
+            traded_quantity: IntCounterVec::new(
+                Opts::new("traded_quantity_total", "Units traded"),
+                &["market"],
+            )
+            .unwrap(),
+            traded_notional: IntCounterVec::new(
+                Opts::new(
+                    "traded_notional_total",
+                    "Value traded in the smallest currency unit",
+                ),
+                &["market"],
+            )
+            .unwrap(),
+            book_quantity: IntGaugeVec::new(
+                Opts::new("book_quantity", "Units resting in the book"),
+                &["market", "side"],
+            )
+            .unwrap(),
+            book_levels: IntGaugeVec::new(
+                Opts::new("book_levels", "Price levels in the book"),
+                &["market", "side"],
+            )
+            .unwrap(),
+            queue_depth: IntGauge::new(
+                "command_queue_depth",
+                "Commands waiting for the matching task",
+            )
+            .unwrap(),
+            queue_wait: Histogram::with_opts(
+                HistogramOpts::new(
+                    "command_queue_wait_seconds",
+                    "Time between sending a command and the matching task picking it up",
+                )
+                .buckets(fast.clone()),
+            )
+            .unwrap(),
+            apply_duration: HistogramVec::new(
+                HistogramOpts::new(
+                    "command_apply_seconds",
+                    "Time the matching task spent applying a command",
+                )
+                .buckets(fast),
+                &["command"],
+            )
+            .unwrap(),
+            request_duration: HistogramVec::new(
+                HistogramOpts::new(
+                    "http_request_duration_seconds",
+                    "Time to answer an HTTP request",
+                ),
+                &["route", "method", "status"],
+            )
+            .unwrap(),
+            registry,
+        };
+        for collector in [
+            Box::new(metrics.orders_accepted.clone()) as Box<dyn prometheus::core::Collector>,
+            Box::new(metrics.orders_rejected.clone()),
+            Box::new(metrics.matches.clone()),
+            Box::new(metrics.traded_quantity.clone()),
+            Box::new(metrics.traded_notional.clone()),
+            Box::new(metrics.book_quantity.clone()),
+            Box::new(metrics.book_levels.clone()),
+            Box::new(metrics.queue_depth.clone()),
+            Box::new(metrics.queue_wait.clone()),
+            Box::new(metrics.apply_duration.clone()),
+            Box::new(metrics.request_duration.clone()),
+        ] {
+            metrics
+                .registry
+                .register(collector)
+                .expect("Metric names should be unique");
+        }
+        metrics
+    }
+
+    /// Records how long a command waited for the matching task and how long it took to apply
+    pub fn observe_command(&self, command: &str, waited: Duration, applied: Duration) {
+        self.queue_wait.observe(waited.as_secs_f64());
+        self.apply_duration
+            .with_label_values(&[command])
+            .observe(applied.as_secs_f64());
+    }
+
+    /// Counts an order in `market` and, if it was accepted, its matches
+    pub fn observe_order(&self, market: &str, outcome: &Result<Outcome, ApplicationError>) {
+        match outcome {
+            Ok(Outcome::Receipt(receipt)) => {
+                self.orders_accepted.with_label_values(&[market]).inc();
+                self.observe_fills(market, receipt);
+            }
+            Ok(_) => {}
+            Err(e) => self
+                .orders_rejected
+                .with_label_values(&[market, &e.code().to_string()])
+                .inc(),
+        }
+    }
+
+    fn observe_fills(&self, market: &str, receipt: &Receipt) {
+        for fill in &receipt.matches {
+            self.matches.with_label_values(&[market]).inc();
+            self.traded_quantity
+                .with_label_values(&[market])
+                .inc_by(fill.amount.0);
+            self.traded_notional
+                .with_label_values(&[market])
+                .inc_by(fill.price.0.saturating_mul(fill.amount.0));
+        }
+    }
+
+    /// Counts the fills of an uncrossed auction, each pair of orders being one match
+    pub fn observe_auction(&self, market: &str, outcome: &Result<Outcome, ApplicationError>) {
+        if let Ok(Outcome::Auction(Some(result))) = outcome {
+            let quantity = result.indication.volume.0;
+            self.matches
+                .with_label_values(&[market])
+                .inc_by(result.fills.len() as u64);
+            self.traded_quantity
+                .with_label_values(&[market])
+                .inc_by(quantity);
+            self.traded_notional
+                .with_label_values(&[market])
+                .inc_by(result.indication.price.0.saturating_mul(quantity));
+        }
+    }
+
+    /// Sets the book gauges of `market` to the published `book`
+    pub fn observe_book(&self, market: &str, book: &BookView) {
+        for (side, levels) in [("buy", &book.bids), ("sell", &book.asks)] {
+            let quantity: u64 = levels
+                .values()
+                .flat_map(|level| level.iter())
+                .map(|order| order.remaining.0)
+                .sum();
+            self.book_quantity
+                .with_label_values(&[market, side])
+                .set(quantity as i64);
+            self.book_levels
+                .with_label_values(&[market, side])
+                .set(levels.len() as i64);
+        }
+    }
+
+    pub fn observe_queue_depth(&self, depth: usize) {
+        self.queue_depth.set(depth as i64);
+    }
+
+    /// Records an HTTP request to the route `template`, e.g. `/v1/account/{signer}`
+    pub fn observe_request(&self, template: &str, method: &str, status: u16, elapsed: Duration) {
+        self.request_duration
+            .with_label_values(&[template, method, &status.to_string()])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    /// All metrics in the Prometheus text format
+    pub fn render(&self) -> String {
+        let mut buffer = vec![];
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .expect("Metrics should be encodable");
+        String::from_utf8(buffer).expect("The text format should be UTF-8")
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Metrics::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use octopus_common::{
+        money::{Price, Quantity},
+        types::{PartialOrder, Side},
+    };
+
+    use super::*;
+
+    fn fill(price: u64, amount: u64) -> PartialOrder {
+        PartialOrder {
+            price: Price(price),
+            amount: Quantity(amount),
+            remaining: Quantity(0),
+            side: Side::Sell,
+            signer: "ALICE".to_string(),
+            ordinal: 1,
+        }
+    }
+
+    #[test]
+    fn test_orders_are_counted_by_outcome() {
+        let metrics = Metrics::new();
+        let receipt = Receipt {
+            ordinal: 2,
+            matches: vec![fill(100, 2), fill(101, 1)],
+            fees: vec![],
+        };
+        metrics.observe_order("OCTO", &Ok(Outcome::Receipt(receipt)));
+        metrics.observe_order("OCTO", &Err(ApplicationError::MarketHalted));
+        let text = metrics.render();
+
+        assert!(text.contains("octopus_orders_accepted_total{market=\"OCTO\"} 1"));
+        assert!(text
+            .contains("octopus_orders_rejected_total{market=\"OCTO\",reason=\"MARKET_HALTED\"} 1"));
+        assert!(text.contains("octopus_matches_total{market=\"OCTO\"} 2"));
+        assert!(text.contains("octopus_traded_quantity_total{market=\"OCTO\"} 3"));
+        assert!(text.contains("octopus_traded_notional_total{market=\"OCTO\"} 301"));
+    }
+}
@@ -0,0 +1,1102 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+    time::Instant,
+};
+
+use octopus_common::{
+    errors::ApplicationError,
+    fees::FeeSchedule,
+    instrument::InstrumentSpec,
+    ledger::TrialBalance,
+    money::Price,
+    tx::{Statement, StatementQuery, Tx},
+    types::{
+        AccountMetadata, AccountView, AuctionIndication, AuctionResult, MarketState, Order,
+        PartialOrder, Receipt,
+    },
+};
+use serde::{ser::SerializeSeq, Deserialize, Serialize, Serializer};
+use tokio::sync::{mpsc, oneshot, watch};
+
+use crate::{
+    audit::AuditLog,
+    book::BookSide,
+    journal::{Journal, JournalEntry},
+    matching::MatchingEngine,
+    metrics::Metrics,
+    trading_platform::{Clock, TradingPlatform},
+};
+
+/// Number of commands that may wait for the matching task before senders have to wait as well
+pub const DEFAULT_QUEUE_CAPACITY: usize = 1024;
+
+/// Maximum number of commands applied before a new [`Snapshot`] is published
+const MAX_BATCH: usize = 256;
+
+/// Number of entries per shared chunk of a [`SharedLog`]
+const CHUNK_LEN: usize = 64;
+
+/// A request to the [`TradingPlatform`]. Commands are applied one at a time in the order the matching task receives them.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    OpenAccount {
+        signer: String,
+        owner: String,
+    },
+    FreezeAccount {
+        signer: String,
+    },
+    UnfreezeAccount {
+        signer: String,
+    },
+    CloseAccount {
+        signer: String,
+    },
+    Deposit {
+        signer: String,
+        amount: u64,
+    },
+    Withdraw {
+        signer: String,
+        amount: u64,
+    },
+    Send {
+        sender: String,
+        recipient: String,
+        amount: u64,
+    },
+    Order(Order),
+    Cancel {
+        signer: String,
+        ordinal: u64,
+    },
+    Halt,
+    Resume,
+    StartAuction,
+    Uncross {
+        next: MarketState,
+    },
+    /// Reads an [`AccountView`] in sequence with the other commands
+    Account {
+        signer: String,
+    },
+    /// Reads a [`Statement`] in sequence with the other commands
+    Statement {
+        signer: String,
+        query: StatementQuery,
+    },
+    /// Reads the [`TrialBalance`] in sequence with the other commands
+    TrialBalance,
+}
+
+/// The result of a successfully applied [`Command`]. Serializes to the bare result, e.g. a [`Receipt`] for an order.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum Outcome {
+    Account(AccountMetadata),
+    AccountView(AccountView),
+    Statement(Statement),
+    Tx(Tx),
+    Transfer((Tx, Tx)),
+    Receipt(Receipt),
+    Cancelled(PartialOrder),
+    MarketState(MarketState),
+    Auction(Option<AuctionResult>),
+    TrialBalance(TrialBalance),
+}
+
+impl Command {
+    /// Name of the command's kind, as used in logs and metrics
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Command::OpenAccount { .. } => "open_account",
+            Command::FreezeAccount { .. } => "freeze_account",
+            Command::UnfreezeAccount { .. } => "unfreeze_account",
+            Command::CloseAccount { .. } => "close_account",
+            Command::Deposit { .. } => "deposit",
+            Command::Withdraw { .. } => "withdraw",
+            Command::Send { .. } => "send",
+            Command::Order(_) => "order",
+            Command::Cancel { .. } => "cancel",
+            Command::Halt => "halt",
+            Command::Resume => "resume",
+            Command::StartAuction => "start_auction",
+            Command::Uncross { .. } => "uncross",
+            Command::Account { .. } => "account",
+            Command::Statement { .. } => "statement",
+            Command::TrialBalance => "trial_balance",
+        }
+    }
+
+    /// Whether the command only reads state
+    pub fn is_query(&self) -> bool {
+        matches!(
+            self,
+            Command::Account { .. } | Command::Statement { .. } | Command::TrialBalance
+        )
+    }
+
+    /// Applies the command to a [`TradingPlatform`].
+    ///
+    /// # Errors
+    /// Whatever the corresponding [`TradingPlatform`] method returns
+    pub fn apply(self, platform: &mut TradingPlatform) -> Result<Outcome, ApplicationError> {
+        let outcome = match self {
+            Command::OpenAccount { signer, owner } => {
+                Outcome::Account(platform.open_account(&signer, &owner)?)
+            }
+            Command::FreezeAccount { signer } => {
+                Outcome::Account(platform.freeze_account(&signer)?)
+            }
+            Command::UnfreezeAccount { signer } => {
+                Outcome::Account(platform.unfreeze_account(&signer)?)
+            }
+            Command::CloseAccount { signer } => Outcome::Account(platform.close_account(&signer)?),
+            Command::Deposit { signer, amount } => Outcome::Tx(platform.deposit(&signer, amount)?),
+            Command::Withdraw { signer, amount } => {
+                Outcome::Tx(platform.withdraw(&signer, amount)?)
+            }
+            Command::Send {
+                sender,
+                recipient,
+                amount,
+            } => Outcome::Transfer(platform.send(&sender, &recipient, amount)?),
+            Command::Order(order) => Outcome::Receipt(platform.order(order)?),
+            Command::Cancel { signer, ordinal } => {
+                Outcome::Cancelled(platform.cancel(&signer, ordinal)?)
+            }
+            Command::Halt => Outcome::MarketState(platform.halt()?),
+            Command::Resume => Outcome::MarketState(platform.resume()?),
+            Command::StartAuction => Outcome::MarketState(platform.start_auction()?),
+            Command::Uncross { next } => Outcome::Auction(platform.uncross(next)?),
+            Command::Account { signer } => Outcome::AccountView(platform.account(&signer)?),
+            Command::Statement { signer, query } => {
+                Outcome::Statement(platform.statement(&signer, &query)?)
+            }
+            Command::TrialBalance => Outcome::TrialBalance(platform.trial_balance()),
+        };
+        Ok(outcome)
+    }
+}
+
+/// An append-only log that can be published cheaply: published copies share all full chunks and only copy the tail.
+#[derive(Debug, Default)]
+pub struct SharedLog<T> {
+    chunks: Vec<Arc<[T]>>,
+    tail: Vec<T>,
+}
+
+impl<T: Clone> SharedLog<T> {
+    /// Creates an empty log
+    pub fn new() -> Self {
+        SharedLog {
+            chunks: vec![],
+            tail: vec![],
+        }
+    }
+
+    /// Number of entries in the log
+    pub fn len(&self) -> usize {
+        self.chunks.len() * CHUNK_LEN + self.tail.len()
+    }
+
+    /// Whether the log has no entries
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Appends entries, freezing the tail into a shared chunk whenever it is full
+    pub fn extend_from_slice(&mut self, entries: &[T]) {
+        for entry in entries {
+            self.tail.push(entry.clone());
+            if self.tail.len() == CHUNK_LEN {
+                self.chunks.push(std::mem::take(&mut self.tail).into());
+            }
+        }
+    }
+
+    /// An immutable copy of the log's current state
+    pub fn publish(&self) -> LogView<T> {
+        LogView {
+            chunks: self.chunks.clone(),
+            tail: self.tail.as_slice().into(),
+        }
+    }
+}
+
+/// A published, immutable copy of a [`SharedLog`]. Serializes as a flat sequence.
+#[derive(Debug, Clone)]
+pub struct LogView<T> {
+    chunks: Vec<Arc<[T]>>,
+    tail: Arc<[T]>,
+}
+
+impl<T> Default for LogView<T> {
+    fn default() -> Self {
+        LogView {
+            chunks: vec![],
+            tail: Arc::new([]),
+        }
+    }
+}
+
+impl<T> LogView<T> {
+    /// Iterates over all entries, oldest first
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.chunks
+            .iter()
+            .flat_map(|c| c.iter())
+            .chain(self.tail.iter())
+    }
+
+    /// Iterates over the entries from index `start` on, skipping whole chunks
+    pub fn iter_from(&self, start: usize) -> impl Iterator<Item = &T> {
+        let first = (start / CHUNK_LEN).min(self.chunks.len());
+        self.chunks[first..]
+            .iter()
+            .flat_map(|c| c.iter())
+            .chain(self.tail.iter())
+            .skip(start - first * CHUNK_LEN)
+    }
+
+    /// Number of entries in the log
+    pub fn len(&self) -> usize {
+        self.chunks.len() * CHUNK_LEN + self.tail.len()
+    }
+
+    /// Whether the log has no entries
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T: Serialize> Serialize for LogView<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.len()))?;
+        for entry in self.iter() {
+            seq.serialize_element(entry)?;
+        }
+        seq.end()
+    }
+}
+
+/// A published, immutable copy of the order book. Levels that didn't change are shared between snapshots.
+/// Serializes as a flat sequence of asks followed by bids, like [`TradingPlatform::orderbook`].
+#[derive(Debug, Clone, Default)]
+pub struct BookView {
+    pub asks: BTreeMap<Price, Arc<[PartialOrder]>>,
+    pub bids: BTreeMap<Price, Arc<[PartialOrder]>>,
+}
+
+impl BookView {
+    /// Copies all levels of the `matching_engine`
+    fn new(matching_engine: &MatchingEngine) -> Self {
+        let copy = |side: &BookSide| {
+            side.levels()
+                .map(|(price, level)| (price, level.cloned().collect()))
+                .collect()
+        };
+        BookView {
+            asks: copy(&matching_engine.asks),
+            bids: copy(&matching_engine.bids),
+        }
+    }
+
+    /// Copies the levels at `price` again, after orders there were added or matched
+    fn refresh(&mut self, matching_engine: &MatchingEngine, price: Price) {
+        for (view, side) in [
+            (&mut self.asks, &matching_engine.asks),
+            (&mut self.bids, &matching_engine.bids),
+        ] {
+            let level: Vec<PartialOrder> = side.level(price).cloned().collect();
+            if level.is_empty() {
+                view.remove(&price);
+            } else {
+                view.insert(price, level.into());
+            }
+        }
+    }
+
+    /// Iterates over all resting orders, asks first
+    pub fn iter(&self) -> impl Iterator<Item = &PartialOrder> {
+        self.asks
+            .values()
+            .chain(self.bids.values())
+            .flat_map(|level| level.iter())
+    }
+
+    /// Number of resting orders
+    pub fn len(&self) -> usize {
+        self.asks
+            .values()
+            .chain(self.bids.values())
+            .map(|level| level.len())
+            .sum()
+    }
+
+    /// Whether the book is empty
+    pub fn is_empty(&self) -> bool {
+        self.asks.is_empty() && self.bids.is_empty()
+    }
+}
+
+impl Serialize for BookView {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.len()))?;
+        for order in self.iter() {
+            seq.serialize_element(order)?;
+        }
+        seq.end()
+    }
+}
+
+/// Public market data as of a sequence number, published by the matching task after applying commands
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    /// Sequence number of the last applied command
+    pub sequence: u64,
+    pub market_state: MarketState,
+    pub orderbook: BookView,
+    pub history: LogView<Receipt>,
+    /// The price and volume of the running auction, if any
+    pub indication: Option<AuctionIndication>,
+    pub instrument: InstrumentSpec,
+    pub fees: FeeSchedule,
+}
+
+/// Who sent a [`Command`], as recorded in the audit log
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Caller {
+    /// The interface the command arrived through, e.g. `http` or `fix`
+    pub interface: String,
+    /// Remote address of the connection
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    /// The identity the client logged on with, e.g. its FIX SenderCompID
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub identity: Option<String>,
+}
+
+impl Caller {
+    pub fn new(interface: &str, address: Option<String>, identity: Option<String>) -> Self {
+        Caller {
+            interface: interface.to_string(),
+            address,
+            identity,
+        }
+    }
+}
+
+/// Commands sent by the service itself, e.g. by tests or tools
+impl Default for Caller {
+    fn default() -> Self {
+        Caller::new("internal", None, None)
+    }
+}
+
+/// A [`Command`] on its way to the matching task
+struct Envelope {
+    command: Command,
+    caller: Arc<Caller>,
+    /// Channel for the result
+    reply: oneshot::Sender<Result<Outcome, ApplicationError>>,
+    /// When the command was sent
+    sent: Instant,
+}
+
+/// Handle to the single writer of a [`TradingPlatform`]. State changes are sent as [`Command`]s to a dedicated matching task
+/// that sequences them, while reads are served from the latest published [`Snapshot`] without waiting for the matching task.
+#[derive(Clone)]
+pub struct Pipeline {
+    commands: mpsc::Sender<Envelope>,
+    snapshots: watch::Receiver<Arc<Snapshot>>,
+    metrics: Arc<Metrics>,
+    /// Who the commands sent through this handle are recorded as
+    caller: Arc<Caller>,
+    /// Set once the pipeline is shut down
+    closing: Arc<watch::Sender<bool>>,
+}
+
+impl Pipeline {
+    /// Moves the `platform` into a new matching task. Must be called within a tokio runtime.
+    pub fn spawn(platform: TradingPlatform, capacity: usize) -> Self {
+        Pipeline::spawn_with_journal(platform, capacity, None)
+    }
+
+    /// Like [`Pipeline::spawn`], additionally recording every state changing command in the `journal` before its result
+    /// is sent, so the journal can be replayed later.
+    pub fn spawn_with_journal(
+        platform: TradingPlatform,
+        capacity: usize,
+        journal: Option<Journal>,
+    ) -> Self {
+        Pipeline::spawn_with_logs(platform, capacity, journal, None)
+    }
+
+    /// Like [`Pipeline::spawn_with_journal`], additionally recording every state changing command together with its
+    /// [`Caller`] and outcome in the `audit` log before its result is sent. The matching task stops once either log
+    /// can't be written. Commands are numbered after the last entry of the `journal`, so a journal reopened with
+    /// [`Journal::open`] keeps increasing sequence numbers.
+    pub fn spawn_with_logs(
+        platform: TradingPlatform,
+        capacity: usize,
+        journal: Option<Journal>,
+        audit: Option<AuditLog>,
+    ) -> Self {
+        let (commands, receiver) = mpsc::channel(capacity);
+        let metrics = Arc::new(Metrics::new());
+        let mut writer = Writer::new(platform);
+        writer.sequence = journal.as_ref().map_or(0, Journal::last_sequence);
+        writer.journal = journal;
+        writer.audit = audit;
+        writer.metrics = Arc::clone(&metrics);
+        let (publisher, snapshots) = watch::channel(Arc::new(writer.snapshot()));
+        let (closing, closed) = watch::channel(false);
+        tokio::spawn(writer.run(receiver, publisher, closed));
+        Pipeline {
+            commands,
+            snapshots,
+            metrics,
+            caller: Arc::default(),
+            closing: Arc::new(closing),
+        }
+    }
+
+    /// Sends a [`Command`] to the matching task and waits until it has been applied and published.
+    ///
+    /// # Errors
+    /// - The command failed
+    /// - The matching task stopped
+    pub async fn execute(&self, command: Command) -> Result<Outcome, ApplicationError> {
+        let (reply, outcome) = oneshot::channel();
+        let envelope = Envelope {
+            command,
+            caller: Arc::clone(&self.caller),
+            reply,
+            // Waiting for room in a full queue counts as queue wait as well
+            sent: Instant::now(),
+        };
+        self.commands
+            .send(envelope)
+            .await
+            .map_err(|_| ApplicationError::EngineUnavailable)?;
+        outcome
+            .await
+            .map_err(|_| ApplicationError::EngineUnavailable)?
+    }
+
+    /// Stops accepting commands and waits until the queued ones are applied, their results sent and the logs flushed.
+    /// Afterwards [`Pipeline::execute`] fails with [`ApplicationError::EngineUnavailable`] and subscribers of
+    /// [`Pipeline::updates`] see the channel closed, while the final [`Snapshot`] can still be read.
+    pub async fn shutdown(&self) {
+        self.closing.send_replace(true);
+        let mut updates = self.updates();
+        // The matching task drops the publisher once it's done
+        while updates.changed().await.is_ok() {}
+    }
+
+    /// A handle to the same matching task that records its commands as sent by `caller`
+    pub fn with_caller(&self, caller: Caller) -> Self {
+        Pipeline {
+            caller: Arc::new(caller),
+            ..self.clone()
+        }
+    }
+
+    /// The most recently published [`Snapshot`]
+    pub fn snapshot(&self) -> Arc<Snapshot> {
+        Arc::clone(&self.snapshots.borrow())
+    }
+
+    /// A receiver that is notified whenever a new [`Snapshot`] is published
+    pub fn updates(&self) -> watch::Receiver<Arc<Snapshot>> {
+        self.snapshots.clone()
+    }
+
+    /// The pipeline's [`Metrics`]
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// All metrics in the Prometheus text format, with the book and queue gauges taken from the current state
+    pub fn render_metrics(&self) -> String {
+        let snapshot = self.snapshot();
+        self.metrics
+            .observe_book(&snapshot.instrument.symbol, &snapshot.orderbook);
+        self.metrics
+            .observe_queue_depth(self.commands.max_capacity() - self.commands.capacity());
+        self.metrics.render()
+    }
+}
+
+/// The matching task's state: the only owner of the [`TradingPlatform`]
+struct Writer {
+    platform: TradingPlatform,
+    sequence: u64,
+    history: SharedLog<Receipt>,
+    book: BookView,
+    /// Price levels changed since the last snapshot
+    touched: BTreeSet<Price>,
+    /// Whether the whole book has to be copied for the next snapshot
+    book_stale: bool,
+    journal: Option<Journal>,
+    audit: Option<AuditLog>,
+    /// Set once the journal or the audit log couldn't be written. They may end in a partial line then, so no further
+    /// command is applied.
+    failed: bool,
+    metrics: Arc<Metrics>,
+}
+
+impl Writer {
+    fn new(platform: TradingPlatform) -> Self {
+        let mut history = SharedLog::new();
+        history.extend_from_slice(&platform.matching_engine.history);
+        Writer {
+            book: BookView::new(&platform.matching_engine),
+            platform,
+            sequence: 0,
+            history,
+            touched: BTreeSet::new(),
+            book_stale: false,
+            journal: None,
+            audit: None,
+            failed: false,
+            metrics: Arc::default(),
+        }
+    }
+
+    /// Applies a single command and remembers which parts of the book it changed
+    fn apply(&mut self, command: Command, caller: &Caller) -> Result<Outcome, ApplicationError> {
+        self.sequence += 1;
+        let kind = command.kind();
+        let _span = tracing::debug_span!("command", sequence = self.sequence, kind).entered();
+        // All records of a command share one timestamp, the one that is journaled
+        let clock = self.platform.clock;
+        let timestamp = clock.now();
+        if let Some(journal) = &mut self.journal {
+            if !command.is_query() {
+                let entry = JournalEntry {
+                    sequence: self.sequence,
+                    timestamp,
+                    command: command.clone(),
+                };
+                // Commands that aren't journaled aren't applied
+                if let Err(e) = journal.append(&entry) {
+                    tracing::error!(
+                        sequence = self.sequence,
+                        "couldn't journal the command: {}",
+                        e
+                    );
+                    self.failed = true;
+                    return Err(ApplicationError::EngineUnavailable);
+                }
+            }
+        }
+        let price = match &command {
+            Command::Order(order) => Some(order.price),
+            _ => None,
+        };
+        // These remove orders all over the book
+        self.book_stale |= matches!(
+            command,
+            Command::FreezeAccount { .. } | Command::CloseAccount { .. } | Command::Uncross { .. }
+        );
+        let audited = match &self.audit {
+            Some(_) if !command.is_query() => Some(command.clone()),
+            _ => None,
+        };
+        self.platform.clock = Clock::Fixed(timestamp);
+        let outcome = command.apply(&mut self.platform);
+        self.platform.clock = clock;
+        match (&outcome, price) {
+            (Ok(Outcome::Receipt(receipt)), Some(price)) => {
+                self.touched.insert(price);
+                self.touched.extend(receipt.matches.iter().map(|m| m.price));
+            }
+            // The order may have been matched before it failed
+            (Err(_), Some(_)) => self.book_stale = true,
+            (Ok(Outcome::Cancelled(order)), _) => {
+                self.touched.insert(order.price);
+            }
+            _ => {}
+        }
+
+        let market = &self.platform.instrument.symbol;
+        match kind {
+            "order" => self.metrics.observe_order(market, &outcome),
+            "uncross" => self.metrics.observe_auction(market, &outcome),
+            _ => {}
+        }
+        if let (Some(audit), Some(command)) = (&mut self.audit, audited) {
+            if let Err(e) = audit.append(self.sequence, timestamp, caller, command, &outcome) {
+                tracing::error!(
+                    sequence = self.sequence,
+                    "couldn't audit the command: {}",
+                    e
+                );
+                self.failed = true;
+            }
+        }
+        if let Err(e) = &outcome {
+            tracing::debug!(code = %e.code(), "command rejected: {}", e);
+        }
+        outcome
+    }
+
+    /// Applies commands in the order they arrive. Commands that are already queued are applied as a batch, followed by a
+    /// single snapshot. Results are only sent after the snapshot is published, so callers always read their own writes.
+    /// Once `closing` is set, the commands queued so far are still applied, then the task stops.
+    ///
+    /// If the journal or the audit log can't be written, the task stops right away: none of the batch's results is
+    /// published, its commands fail with [`ApplicationError::EngineUnavailable`], and so do the queued ones.
+    async fn run(
+        mut self,
+        mut receiver: mpsc::Receiver<Envelope>,
+        publisher: watch::Sender<Arc<Snapshot>>,
+        mut closing: watch::Receiver<bool>,
+    ) {
+        let mut replies = Vec::with_capacity(MAX_BATCH);
+        let mut open = true;
+        loop {
+            let first = tokio::select! {
+                first = receiver.recv() => match first {
+                    Some(first) => first,
+                    // Every handle is gone, or the queue is drained after closing
+                    None => break,
+                },
+                // `closing` only ever changes to true, or every handle is gone
+                _ = closing.changed(), if open => {
+                    open = false;
+                    receiver.close();
+                    continue;
+                }
+            };
+            let mut changed = false;
+            let mut next = Some(first);
+            while let Some(envelope) = next.take() {
+                changed |= !envelope.command.is_query();
+                let kind = envelope.command.kind();
+                let started = Instant::now();
+                let outcome = self.apply(envelope.command, &envelope.caller);
+                self.metrics
+                    .observe_command(kind, started - envelope.sent, started.elapsed());
+                replies.push((envelope.reply, outcome));
+                if replies.len() < MAX_BATCH && !self.failed {
+                    next = receiver.try_recv().ok();
+                }
+            }
+            if changed && !self.failed {
+                if let Some(Err(e)) = self.journal.as_mut().map(Journal::flush) {
+                    tracing::error!("couldn't flush the journal: {}", e);
+                    self.failed = true;
+                }
+                if let Some(Err(e)) = self.audit.as_mut().map(AuditLog::flush) {
+                    tracing::error!("couldn't flush the audit log: {}", e);
+                    self.failed = true;
+                }
+            }
+            if self.failed {
+                for (reply, _) in replies.drain(..) {
+                    let _ = reply.send(Err(ApplicationError::EngineUnavailable));
+                }
+                // Dropping the queue fails the commands still in it
+                break;
+            }
+            if changed {
+                publisher.send_replace(Arc::new(self.snapshot()));
+            }
+            for (reply, outcome) in replies.drain(..) {
+                // The caller may have given up waiting, the command counts anyway
+                let _ = reply.send(outcome);
+            }
+        }
+    }
+
+    fn snapshot(&mut self) -> Snapshot {
+        let history = &self.platform.matching_engine.history;
+        self.history
+            .extend_from_slice(&history[self.history.len().min(history.len())..]);
+        let matching_engine = &self.platform.matching_engine;
+        if self.book_stale {
+            self.book = BookView::new(matching_engine);
+        } else {
+            for price in &self.touched {
+                self.book.refresh(matching_engine, *price);
+            }
+        }
+        self.book_stale = false;
+        self.touched.clear();
+        Snapshot {
+            sequence: self.sequence,
+            market_state: self.platform.market_state(),
+            orderbook: self.book.clone(),
+            history: self.history.publish(),
+            indication: self.platform.auction_indication(),
+            instrument: self.platform.instrument.clone(),
+            fees: self.platform.fees.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // reduce the warnings for naming tests
+    #![allow(non_snake_case)]
+
+    use super::*;
+    use crate::testing::Failing;
+    use octopus_common::{
+        errors::ErrorCode,
+        money::{Price, Quantity},
+        types::Side,
+    };
+
+    fn order(side: Side, price: u64, amount: u64, signer: &str) -> Command {
+        Command::Order(Order {
+            price: Price(price),
+            amount: Quantity(amount),
+            side,
+            signer: signer.to_string(),
+        })
+    }
+
+    async fn funded(signers: &[&str]) -> Pipeline {
+        let pipeline = Pipeline::spawn(TradingPlatform::new(), DEFAULT_QUEUE_CAPACITY);
+        for signer in signers {
+            let open = Command::OpenAccount {
+                signer: signer.to_string(),
+                owner: "owner".to_string(),
+            };
+            let deposit = Command::Deposit {
+                signer: signer.to_string(),
+                amount: 1_000_000,
+            };
+            assert!(pipeline.execute(open).await.is_ok());
+            assert!(pipeline.execute(deposit).await.is_ok());
+        }
+        pipeline
+    }
+
+    #[tokio::test]
+    async fn test_Pipeline_execute_publishes_snapshot_before_replying() {
+        let pipeline = funded(&["ALICE", "BOB"]).await;
+        assert_eq!(pipeline.snapshot().sequence, 4);
+
+        let outcome = pipeline.execute(order(Side::Sell, 10, 2, "ALICE")).await;
+        assert!(matches!(outcome, Ok(Outcome::Receipt(ref r)) if r.matches.is_empty()));
+        let snapshot = pipeline.snapshot();
+        assert_eq!(snapshot.sequence, 5);
+        assert_eq!(snapshot.orderbook.len(), 1);
+
+        let outcome = pipeline.execute(order(Side::Buy, 10, 2, "BOB")).await;
+        assert!(matches!(outcome, Ok(Outcome::Receipt(ref r)) if r.matches.len() == 1));
+        let snapshot = pipeline.snapshot();
+        assert!(snapshot.orderbook.is_empty());
+        assert_eq!(snapshot.history.len(), 2);
+
+        assert_eq!(
+            pipeline.execute(Command::Halt).await,
+            Ok(Outcome::MarketState(MarketState::Halted))
+        );
+        assert_eq!(pipeline.snapshot().market_state, MarketState::Halted);
+    }
+
+    #[tokio::test]
+    async fn test_Pipeline_execute_returns_errors_and_sequences_queries() {
+        let pipeline = funded(&["ALICE"]).await;
+
+        assert_eq!(
+            pipeline
+                .execute(Command::Withdraw {
+                    signer: "ALICE".to_string(),
+                    amount: 2_000_000,
+                })
+                .await,
+            Err(ApplicationError::AccountUnderFunded(
+                "ALICE".to_string(),
+                2_000_000
+            ))
+        );
+        let view = pipeline
+            .execute(Command::Account {
+                signer: "ALICE".to_string(),
+            })
+            .await;
+        assert!(matches!(view, Ok(Outcome::AccountView(ref v)) if v.available == 1_000_000));
+
+        // Failed commands and queries are sequenced as well
+        assert_eq!(pipeline.execute(Command::Halt).await.map(|_| ()), Ok(()));
+        assert_eq!(pipeline.snapshot().sequence, 5);
+    }
+
+    #[tokio::test]
+    async fn test_Pipeline_stops_once_a_log_cannot_be_written() {
+        let open = |signer: &str| Command::OpenAccount {
+            signer: signer.to_string(),
+            owner: "owner".to_string(),
+        };
+
+        // The journal only fails when the batch is flushed
+        let pipeline = Pipeline::spawn_with_journal(
+            TradingPlatform::new(),
+            DEFAULT_QUEUE_CAPACITY,
+            Some(Journal::new(Failing)),
+        );
+        assert_eq!(
+            pipeline.execute(open("ALICE")).await,
+            Err(ApplicationError::EngineUnavailable)
+        );
+        assert_eq!(
+            pipeline.execute(Command::TrialBalance).await,
+            Err(ApplicationError::EngineUnavailable)
+        );
+        assert_eq!(pipeline.snapshot().sequence, 0);
+
+        // A record larger than the buffer fails right away
+        let pipeline = Pipeline::spawn_with_logs(
+            TradingPlatform::new(),
+            DEFAULT_QUEUE_CAPACITY,
+            None,
+            Some(AuditLog::new(Failing)),
+        );
+        assert_eq!(
+            pipeline.execute(open(&"A".repeat(10_000))).await,
+            Err(ApplicationError::EngineUnavailable)
+        );
+        assert_eq!(
+            pipeline.execute(open("BOB")).await,
+            Err(ApplicationError::EngineUnavailable)
+        );
+        assert_eq!(pipeline.snapshot().sequence, 0);
+    }
+
+    #[tokio::test]
+    async fn test_Pipeline_with_caller_audits_state_changes() {
+        let path =
+            std::env::temp_dir().join(format!("octopus-pipeline-{}.audit", std::process::id()));
+        let audit = AuditLog::open(&path).unwrap();
+        let pipeline = Pipeline::spawn_with_logs(
+            TradingPlatform::new(),
+            DEFAULT_QUEUE_CAPACITY,
+            None,
+            Some(audit),
+        );
+        let caller = Caller::new(
+            "fix",
+            Some("10.0.0.1:5000".to_string()),
+            Some("CLIENT".to_string()),
+        );
+        let client = pipeline.with_caller(caller.clone());
+        let open = Command::OpenAccount {
+            signer: "ALICE".to_string(),
+            owner: "owner".to_string(),
+        };
+        assert!(client.execute(open).await.is_ok());
+        let deposit = Command::Deposit {
+            signer: "ALICE".to_string(),
+            amount: 1_000,
+        };
+        assert!(pipeline.execute(deposit).await.is_ok());
+        let query = Command::Account {
+            signer: "ALICE".to_string(),
+        };
+        assert!(client.execute(query).await.is_ok());
+        let receipt = client.execute(order(Side::Buy, 10, 1_000, "ALICE")).await;
+        assert!(receipt.is_err());
+        let Ok(Outcome::Receipt(receipt)) = client.execute(order(Side::Buy, 10, 1, "ALICE")).await
+        else {
+            panic!("the order should be accepted");
+        };
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(crate::audit::verify(text.as_bytes()).unwrap().records, 4);
+        let records: Vec<crate::audit::AuditRecord> = text
+            .lines()
+            .map(|line| {
+                let line: serde_json::Value = serde_json::from_str(line).unwrap();
+                serde_json::from_value(line["record"].clone()).unwrap()
+            })
+            .collect();
+        // Queries aren't audited, rejected commands are
+        let sequences: Vec<u64> = records.iter().map(|r| r.sequence).collect();
+        assert_eq!(sequences, vec![1, 2, 4, 5]);
+        assert_eq!(records[0].caller, caller);
+        assert_eq!(records[1].caller, Caller::default());
+        assert!(matches!(
+            records[2].outcome,
+            crate::audit::AuditOutcome::Rejected {
+                code: ErrorCode::AccountUnderFunded,
+                ..
+            }
+        ));
+        assert_eq!(records[3].ordinal, Some(receipt.ordinal));
+    }
+
+    #[tokio::test]
+    async fn test_Pipeline_shutdown_applies_queued_commands_then_refuses() {
+        let pipeline = funded(&["ALICE"]).await;
+        let queued: Vec<_> = (0..5)
+            .map(|i| {
+                let pipeline = pipeline.clone();
+                tokio::spawn(async move {
+                    pipeline
+                        .execute(order(Side::Sell, 10 + i, 1, "ALICE"))
+                        .await
+                })
+            })
+            .collect();
+        // Lets the tasks queue their commands
+        tokio::task::yield_now().await;
+
+        pipeline.shutdown().await;
+        for task in queued {
+            assert!(matches!(task.await.unwrap(), Ok(Outcome::Receipt(_))));
+        }
+        assert_eq!(pipeline.snapshot().orderbook.len(), 5);
+        assert!(pipeline.updates().has_changed().is_err());
+        assert_eq!(
+            pipeline.execute(order(Side::Sell, 20, 1, "ALICE")).await,
+            Err(ApplicationError::EngineUnavailable)
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_Pipeline_execute_sequences_concurrent_commands() {
+        let signers: Vec<String> = (0..8).map(|i| format!("SIGNER{}", i)).collect();
+        let pipeline = funded(&signers.iter().map(|s| s.as_str()).collect::<Vec<_>>()).await;
+
+        let tasks: Vec<_> = signers
+            .iter()
+            .enumerate()
+            .map(|(i, signer)| {
+                let pipeline = pipeline.clone();
+                let side = if i.is_multiple_of(2) {
+                    Side::Buy
+                } else {
+                    Side::Sell
+                };
+                let signer = signer.clone();
+                tokio::spawn(async move {
+                    let mut ordinals = vec![];
+                    for _ in 0..50 {
+                        match pipeline.execute(order(side.clone(), 10, 1, &signer)).await {
+                            Ok(Outcome::Receipt(receipt)) => ordinals.push(receipt.ordinal),
+                            other => panic!("Unexpected outcome {:?}", other),
+                        }
+                    }
+                    ordinals
+                })
+            })
+            .collect();
+
+        let mut ordinals = vec![];
+        for task in tasks {
+            let own = task.await.unwrap();
+            // Each sender's commands are applied in the order they were sent
+            assert!(own.windows(2).all(|w| w[0] < w[1]));
+            ordinals.extend(own);
+        }
+        ordinals.sort_unstable();
+        assert_eq!(ordinals, (1..=400).collect::<Vec<u64>>());
+
+        let trial_balance = pipeline.execute(Command::TrialBalance).await;
+        assert!(matches!(trial_balance, Ok(Outcome::TrialBalance(ref t)) if t.balanced));
+        assert_eq!(pipeline.snapshot().sequence, 16 + 400);
+    }
+
+    #[test]
+    fn test_Writer_snapshot_refreshes_touched_levels_only() {
+        let mut writer = Writer::new(TradingPlatform::new());
+        let internal = Caller::default();
+        for signer in ["ALICE", "BOB", "CAROL"] {
+            let open = Command::OpenAccount {
+                signer: signer.to_string(),
+                owner: "owner".to_string(),
+            };
+            let deposit = Command::Deposit {
+                signer: signer.to_string(),
+                amount: 10_000,
+            };
+            assert!(writer.apply(open, &internal).is_ok());
+            assert!(writer.apply(deposit, &internal).is_ok());
+        }
+        for (side, price, amount, signer) in [
+            (Side::Sell, 12, 5, "ALICE"),
+            (Side::Sell, 11, 5, "ALICE"),
+            (Side::Buy, 9, 5, "BOB"),
+            (Side::Buy, 8, 5, "CAROL"),
+        ] {
+            assert!(writer
+                .apply(order(side, price, amount, signer), &internal)
+                .is_ok());
+        }
+        let first = writer.snapshot();
+        assert_eq!(
+            first.orderbook.iter().cloned().collect::<Vec<_>>(),
+            writer.platform.orderbook()
+        );
+
+        // Sweeps the level at 11 and partially fills the one at 12
+        assert!(writer
+            .apply(order(Side::Buy, 12, 7, "CAROL"), &internal)
+            .is_ok());
+        let second = writer.snapshot();
+        assert_eq!(
+            second.orderbook.iter().cloned().collect::<Vec<_>>(),
+            writer.platform.orderbook()
+        );
+        assert!(Arc::ptr_eq(
+            &first.orderbook.bids[&Price(9)],
+            &second.orderbook.bids[&Price(9)]
+        ));
+        assert!(!second.orderbook.asks.contains_key(&Price(11)));
+
+        // Freezing an account pulls its orders from every level
+        assert!(writer
+            .apply(
+                Command::FreezeAccount {
+                    signer: "CAROL".to_string()
+                },
+                &internal
+            )
+            .is_ok());
+        let third = writer.snapshot();
+        assert_eq!(
+            third.orderbook.iter().cloned().collect::<Vec<_>>(),
+            writer.platform.orderbook()
+        );
+        assert_eq!(third.orderbook.len(), 2);
+        assert_eq!(
+            serde_json::to_string(&third.orderbook).unwrap(),
+            serde_json::to_string(&writer.platform.orderbook()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_SharedLog_publish_shares_full_chunks() {
+        let mut log = SharedLog::new();
+        let entries: Vec<u64> = (0..(2 * CHUNK_LEN as u64 + 3)).collect();
+        log.extend_from_slice(&entries[..CHUNK_LEN + 1]);
+        let first = log.publish();
+        log.extend_from_slice(&entries[CHUNK_LEN + 1..]);
+        let second = log.publish();
+
+        assert_eq!(first.len(), CHUNK_LEN + 1);
+        assert_eq!(second.len(), entries.len());
+        assert!(Arc::ptr_eq(&first.chunks[0], &second.chunks[0]));
+        assert_eq!(second.iter().copied().collect::<Vec<_>>(), entries);
+        for start in [
+            0,
+            1,
+            CHUNK_LEN,
+            CHUNK_LEN + 2,
+            entries.len(),
+            entries.len() + 5,
+        ] {
+            let from: Vec<u64> = second.iter_from(start).copied().collect();
+            assert_eq!(from, entries[start.min(entries.len())..]);
+        }
+    }
+}
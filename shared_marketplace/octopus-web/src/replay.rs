@@ -0,0 +1,420 @@
+use std::collections::BTreeMap;
+
+use octopus_common::{
+    errors::ApplicationError,
+    ledger::TrialBalance,
+    money::{Price, Quantity},
+    types::{AccountView, MarketState, PartialOrder, Side},
+};
+use serde::Serialize;
+
+use crate::{
+    journal::JournalEntry,
+    pipeline::{Command, Outcome},
+    trading_platform::{Clock, TradingPlatform},
+};
+
+/// Maximum number of commands a [`Strategy`] may submit in reaction to a single recorded command. Stops strategies
+/// that keep reacting to their own orders.
+pub const MAX_REACTIONS: usize = 10_000;
+
+/// Who submitted a replayed command
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    /// The command was recorded in the replayed stream
+    Recorded,
+    /// The [`Strategy`] submitted the command
+    Strategy,
+}
+
+/// A command that was just applied during a [`Replay`], together with its result
+#[derive(Debug)]
+pub struct Event<'a> {
+    /// Unix timestamp in milliseconds of the recorded command the event belongs to
+    pub timestamp: u64,
+    pub source: Source,
+    pub command: &'a Command,
+    pub outcome: &'a Result<Outcome, ApplicationError>,
+}
+
+/// Reacts to the events of a [`Replay`] by submitting commands of its own, e.g. orders of the strategy under test
+pub trait Strategy {
+    /// Called after every applied command, including the strategy's own. The returned commands are applied right
+    /// away, before the next recorded command and at the same time.
+    fn on_event(&mut self, event: &Event<'_>, platform: &TradingPlatform) -> Vec<Command>;
+}
+
+impl<F> Strategy for F
+where
+    F: FnMut(&Event<'_>, &TradingPlatform) -> Vec<Command>,
+{
+    fn on_event(&mut self, event: &Event<'_>, platform: &TradingPlatform) -> Vec<Command> {
+        self(event, platform)
+    }
+}
+
+/// A strategy that never submits anything, for replaying a stream as it was recorded
+pub struct Passive;
+
+impl Strategy for Passive {
+    fn on_event(&mut self, _event: &Event<'_>, _platform: &TradingPlatform) -> Vec<Command> {
+        vec![]
+    }
+}
+
+/// A single fill between a buy and a sell order
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Fill {
+    /// Unix timestamp in milliseconds
+    pub timestamp: u64,
+    pub price: Price,
+    pub amount: Quantity,
+    pub buyer: String,
+    pub buy_ordinal: u64,
+    pub seller: String,
+    pub sell_ordinal: u64,
+    /// Side of the incoming order, `None` for auction fills
+    pub aggressor: Option<Side>,
+}
+
+/// The outcome of a [`Replay`]
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ReplayReport {
+    /// Number of recorded commands that were applied
+    pub recorded: usize,
+    /// Number of commands the strategy submitted
+    pub submitted: usize,
+    /// Number of commands (of both sources) that failed
+    pub rejected: usize,
+    /// All fills in the order they happened
+    pub fills: Vec<Fill>,
+    /// The final state of every account
+    pub accounts: BTreeMap<String, AccountView>,
+    /// The final order book, asks first
+    pub orderbook: Vec<PartialOrder>,
+    pub market_state: MarketState,
+    pub trial_balance: TrialBalance,
+}
+
+/// Replays recorded commands through a [`TradingPlatform`], applying them in sequence at the time they were recorded.
+/// The result only depends on the platform, the commands and the strategy, so the same input always produces the same
+/// fills, balances and book.
+pub struct Replay<S: Strategy> {
+    platform: TradingPlatform,
+    strategy: S,
+    recorded: usize,
+    submitted: usize,
+    rejected: usize,
+    fills: Vec<Fill>,
+}
+
+impl<S: Strategy> Replay<S> {
+    /// Replays onto `platform`, e.g. an empty [`TradingPlatform`] configured like the recorded market
+    pub fn new(platform: TradingPlatform, strategy: S) -> Self {
+        Replay {
+            platform,
+            strategy,
+            recorded: 0,
+            submitted: 0,
+            rejected: 0,
+            fills: vec![],
+        }
+    }
+
+    /// The platform in its current state
+    pub fn platform(&self) -> &TradingPlatform {
+        &self.platform
+    }
+
+    /// Applies a recorded command followed by everything the strategy submits in reaction.
+    /// Failing commands are counted and passed on to the strategy like in the recorded stream.
+    ///
+    /// # Errors
+    /// The strategy submitted more than [`MAX_REACTIONS`] commands
+    pub fn step(&mut self, entry: JournalEntry) -> Result<(), String> {
+        let timestamp = entry.timestamp;
+        self.platform.clock = Clock::Fixed(timestamp);
+        self.recorded += 1;
+        let mut pending = self.apply(entry.command, Source::Recorded, timestamp);
+        let mut reactions = 0;
+        while !pending.is_empty() {
+            let mut next = vec![];
+            for command in pending {
+                reactions += 1;
+                if reactions > MAX_REACTIONS {
+                    return Err(format!(
+                        "The strategy submitted more than {} commands after command {}",
+                        MAX_REACTIONS, entry.sequence
+                    ));
+                }
+                self.submitted += 1;
+                next.extend(self.apply(command, Source::Strategy, timestamp));
+            }
+            pending = next;
+        }
+        Ok(())
+    }
+
+    /// Replays all `entries` in order and reports the final state
+    ///
+    /// # Errors
+    /// The strategy submitted more than [`MAX_REACTIONS`] commands in reaction to a recorded command
+    pub fn run(
+        mut self,
+        entries: impl IntoIterator<Item = JournalEntry>,
+    ) -> Result<ReplayReport, String> {
+        for entry in entries {
+            self.step(entry)?;
+        }
+        Ok(self.report())
+    }
+
+    /// Summarizes the replay so far
+    pub fn report(&self) -> ReplayReport {
+        let mut signers: Vec<&str> = self.platform.accounts.signers().collect();
+        signers.sort_unstable();
+        ReplayReport {
+            recorded: self.recorded,
+            submitted: self.submitted,
+            rejected: self.rejected,
+            fills: self.fills.clone(),
+            accounts: signers
+                .into_iter()
+                .filter_map(|signer| {
+                    let view = self.platform.account(signer).ok()?;
+                    Some((signer.to_string(), view))
+                })
+                .collect(),
+            orderbook: self.platform.orderbook(),
+            market_state: self.platform.market_state(),
+            trial_balance: self.platform.trial_balance(),
+        }
+    }
+
+    /// Applies a single command, records its fills and returns the strategy's reaction
+    fn apply(&mut self, command: Command, source: Source, timestamp: u64) -> Vec<Command> {
+        let outcome = command.clone().apply(&mut self.platform);
+        match (&command, &outcome) {
+            (Command::Order(order), Ok(Outcome::Receipt(receipt))) => {
+                self.fills.extend(receipt.matches.iter().map(|m| {
+                    let (buyer, buy_ordinal, seller, sell_ordinal) = match order.side {
+                        Side::Buy => (&order.signer, receipt.ordinal, &m.signer, m.ordinal),
+                        Side::Sell => (&m.signer, m.ordinal, &order.signer, receipt.ordinal),
+                    };
+                    Fill {
+                        timestamp,
+                        price: m.price,
+                        amount: m.amount,
+                        buyer: buyer.clone(),
+                        buy_ordinal,
+                        seller: seller.clone(),
+                        sell_ordinal,
+                        aggressor: Some(order.side.clone()),
+                    }
+                }));
+            }
+            (_, Ok(Outcome::Auction(Some(result)))) => {
+                self.fills
+                    .extend(result.fills.iter().map(|(buy, sell)| Fill {
+                        timestamp,
+                        price: buy.price,
+                        amount: buy.amount,
+                        buyer: buy.signer.clone(),
+                        buy_ordinal: buy.ordinal,
+                        seller: sell.signer.clone(),
+                        sell_ordinal: sell.ordinal,
+                        aggressor: None,
+                    }));
+            }
+            (_, Err(_)) => self.rejected += 1,
+            _ => {}
+        }
+        let event = Event {
+            timestamp,
+            source,
+            command: &command,
+            outcome: &outcome,
+        };
+        self.strategy.on_event(&event, &self.platform)
+    }
+}
+
+/// Rebuilds the state a journal leads to by applying its `entries` to `platform` at the time they were recorded, e.g.
+/// when the server restarts. The platform keeps its own clock afterwards.
+pub fn restore(
+    mut platform: TradingPlatform,
+    entries: impl IntoIterator<Item = JournalEntry>,
+) -> TradingPlatform {
+    let clock = platform.clock;
+    for entry in entries {
+        platform.clock = Clock::Fixed(entry.timestamp);
+        // Failed commands were journaled as well and fail the same way again
+        let _ = entry.command.apply(&mut platform);
+    }
+    platform.clock = clock;
+    platform
+}
+
+#[cfg(test)]
+mod tests {
+    // reduce the warnings for naming tests
+    #![allow(non_snake_case)]
+
+    use super::*;
+    use crate::{
+        journal::{read_csv, Journal},
+        pipeline::{Pipeline, DEFAULT_QUEUE_CAPACITY},
+        testing::Shared,
+    };
+    use octopus_common::types::Order;
+
+    const ORDERS: &str = "timestamp,signer,side,price,amount\n\
+                          1000,ALICE,sell,10,5\n\
+                          1001,BOB,buy,9,2\n\
+                          1002,CAROL,buy,10,3\n\
+                          1003,BOB,sell,11,1\n\
+                          1004,CAROL,buy,11,3\n";
+
+    fn replay<S: Strategy>(strategy: S) -> ReplayReport {
+        let entries = read_csv(ORDERS.as_bytes(), 10_000).unwrap();
+        Replay::new(TradingPlatform::new(), strategy)
+            .run(entries)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_Replay_run_is_deterministic() {
+        let report = replay(Passive);
+        assert_eq!(report, replay(Passive));
+
+        assert_eq!(report.recorded, 11);
+        assert_eq!(report.rejected, 0);
+        assert_eq!(
+            report
+                .fills
+                .iter()
+                .map(|f| (
+                    f.timestamp,
+                    f.price,
+                    f.amount,
+                    f.buyer.as_str(),
+                    f.seller.as_str()
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                (1002, Price(1_000), Quantity(3), "CAROL", "ALICE"),
+                (1004, Price(1_000), Quantity(2), "CAROL", "ALICE"),
+                (1004, Price(1_100), Quantity(1), "CAROL", "BOB"),
+            ]
+        );
+        assert_eq!(report.accounts["ALICE"].available, 15_000);
+        assert_eq!(report.accounts["CAROL"].metadata.created_at, 1002);
+        // CAROL's order is filled completely, BOB's bid is left
+        assert_eq!(report.orderbook.len(), 1);
+        assert_eq!(report.orderbook[0].signer, "BOB");
+        assert!(report.trial_balance.balanced);
+    }
+
+    #[test]
+    fn test_Replay_step_applies_strategy_commands_after_each_event() {
+        // Opens an account and joins the best bid whenever BOB bids
+        let strategy = |event: &Event<'_>, platform: &TradingPlatform| match event.command {
+            Command::OpenAccount { signer, .. } if signer == "ALICE" => vec![
+                Command::OpenAccount {
+                    signer: "STRATEGY".to_string(),
+                    owner: "test".to_string(),
+                },
+                Command::Deposit {
+                    signer: "STRATEGY".to_string(),
+                    amount: 10_000,
+                },
+            ],
+            Command::Order(order) if order.side == Side::Buy && order.signer == "BOB" => {
+                match platform.matching_engine.bids.prices().next_back() {
+                    Some(price) => vec![Command::Order(Order {
+                        price,
+                        amount: Quantity(1),
+                        side: Side::Buy,
+                        signer: "STRATEGY".to_string(),
+                    })],
+                    None => vec![],
+                }
+            }
+            _ => vec![],
+        };
+        let report = replay(strategy);
+
+        assert_eq!(report.recorded, 11);
+        assert_eq!(report.submitted, 3);
+        assert_eq!(report.accounts["STRATEGY"].open_orders, 1);
+        // The strategy's bid at 9 was placed right after BOB's, so it's second in the queue
+        let bids: Vec<&str> = report
+            .orderbook
+            .iter()
+            .filter(|o| o.side == Side::Buy)
+            .map(|o| o.signer.as_str())
+            .collect();
+        assert_eq!(bids, vec!["BOB", "STRATEGY"]);
+    }
+
+    #[test]
+    fn test_Replay_step_stops_runaway_strategies() {
+        let strategy = |_: &Event<'_>, _: &TradingPlatform| vec![Command::Halt];
+        let entries = read_csv(ORDERS.as_bytes(), 10_000).unwrap();
+        let result = Replay::new(TradingPlatform::new(), strategy).run(entries);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_restore_reaches_the_replayed_state_with_the_platform_clock() {
+        let entries = read_csv(ORDERS.as_bytes(), 10_000).unwrap();
+        let platform = restore(TradingPlatform::new(), entries);
+        let report = replay(Passive);
+
+        assert_eq!(platform.clock, Clock::System);
+        assert_eq!(platform.account("CAROL").unwrap(), report.accounts["CAROL"]);
+        assert_eq!(platform.orderbook(), report.orderbook);
+        assert_eq!(platform.matching_engine.history.len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_Replay_run_reproduces_journaled_pipeline() {
+        let sink = Shared::default();
+        let pipeline = Pipeline::spawn_with_journal(
+            TradingPlatform::new(),
+            DEFAULT_QUEUE_CAPACITY,
+            Some(Journal::new(sink.clone())),
+        );
+        for entry in read_csv(ORDERS.as_bytes(), 10_000).unwrap() {
+            let _ = pipeline.execute(entry.command).await;
+            // Queries aren't journaled
+            let _ = pipeline.execute(Command::TrialBalance).await;
+        }
+        let live = pipeline
+            .execute(Command::Account {
+                signer: "CAROL".to_string(),
+            })
+            .await;
+
+        let journal = sink.bytes();
+        let entries = crate::journal::read_journal(&journal[..]).unwrap();
+        assert_eq!(entries.len(), 11);
+        assert_eq!(entries[1].sequence, 3);
+        let report = Replay::new(TradingPlatform::new(), Passive)
+            .run(entries)
+            .unwrap();
+        assert_eq!(
+            live,
+            Ok(Outcome::AccountView(report.accounts["CAROL"].clone()))
+        );
+        assert_eq!(
+            report.orderbook,
+            pipeline
+                .snapshot()
+                .orderbook
+                .iter()
+                .cloned()
+                .collect::<Vec<_>>()
+        );
+    }
+}
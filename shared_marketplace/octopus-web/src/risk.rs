@@ -0,0 +1,370 @@
+use octopus_common::{
+    errors::ApplicationError,
+    fees::BPS_PER_UNIT,
+    money::Price,
+    risk::{RiskConfig, RiskLimits},
+    types::{Order, Side},
+};
+use std::collections::{HashMap, VecDeque};
+
+/// Market and account state a [`RiskCheck`] can base its decision on
+#[derive(Debug, Clone, Default)]
+pub struct RiskContext {
+    /// Current unix timestamp in milliseconds
+    pub now: u64,
+    /// Number of the signer's orders in the book
+    pub open_orders: u64,
+    /// Price of the last fill in the market
+    pub last_trade: Option<Price>,
+    /// Highest price on the buy side of the book
+    pub best_bid: Option<Price>,
+    /// Lowest price on the sell side of the book
+    pub best_ask: Option<Price>,
+}
+
+/// A single pre-trade rule. Implement this to plug additional rules into the [`RiskEngine`].
+pub trait RiskCheck: Send + Sync {
+    /// Accepts or rejects an order based on the signer's limits
+    ///
+    /// # Errors
+    /// The rule rejects the order
+    fn check(
+        &self,
+        order: &Order,
+        limits: &RiskLimits,
+        context: &RiskContext,
+    ) -> Result<(), ApplicationError>;
+}
+
+/// Rejects orders above `max_order_quantity`
+pub struct MaxOrderSize;
+
+impl RiskCheck for MaxOrderSize {
+    fn check(
+        &self,
+        order: &Order,
+        limits: &RiskLimits,
+        _: &RiskContext,
+    ) -> Result<(), ApplicationError> {
+        match limits.max_order_quantity {
+            Some(max) if order.amount > max => Err(ApplicationError::OrderSizeLimitExceeded(
+                order.amount.0,
+                max.0,
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Rejects orders with a value above `max_order_notional`
+pub struct MaxNotional;
+
+impl RiskCheck for MaxNotional {
+    fn check(
+        &self,
+        order: &Order,
+        limits: &RiskLimits,
+        _: &RiskContext,
+    ) -> Result<(), ApplicationError> {
+        match limits.max_order_notional {
+            Some(max) => {
+                let notional = order.price.notional(order.amount)?;
+                if notional > max {
+                    Err(ApplicationError::NotionalLimitExceeded(notional.0, max.0))
+                } else {
+                    Ok(())
+                }
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// Rejects orders if the signer already has `max_open_orders` in the book
+pub struct MaxOpenOrders;
+
+impl RiskCheck for MaxOpenOrders {
+    fn check(
+        &self,
+        _: &Order,
+        limits: &RiskLimits,
+        context: &RiskContext,
+    ) -> Result<(), ApplicationError> {
+        match limits.max_open_orders {
+            Some(max) if context.open_orders >= max => Err(
+                ApplicationError::OpenOrderLimitExceeded(context.open_orders, max),
+            ),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Fat-finger protection: rejects prices more than `price_collar_bps` away from the last trade or, without trades, the best opposite price
+pub struct PriceCollar;
+
+impl RiskCheck for PriceCollar {
+    fn check(
+        &self,
+        order: &Order,
+        limits: &RiskLimits,
+        context: &RiskContext,
+    ) -> Result<(), ApplicationError> {
+        let opposite = match order.side {
+            Side::Buy => context.best_ask,
+            Side::Sell => context.best_bid,
+        };
+        match (limits.price_collar_bps, context.last_trade.or(opposite)) {
+            (Some(bps), Some(reference)) => {
+                let distance = (reference.0 as u128 * bps as u128 / BPS_PER_UNIT as u128) as u64;
+                let low = reference.0.saturating_sub(distance);
+                let high = reference.0.saturating_add(distance);
+                if order.price.0 < low || order.price.0 > high {
+                    Err(ApplicationError::PriceOutsideCollar(
+                        order.price.0,
+                        low,
+                        high,
+                    ))
+                } else {
+                    Ok(())
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Runs every registered [`RiskCheck`] against incoming orders and limits each signer to `max_orders_per_window`
+/// accepted orders within their rate window
+pub struct RiskEngine {
+    /// Default and per-signer limits
+    pub config: RiskConfig,
+    checks: Vec<Box<dyn RiskCheck>>,
+    order_times: HashMap<String, VecDeque<u64>>,
+}
+
+impl Default for RiskEngine {
+    fn default() -> Self {
+        RiskEngine::new(RiskConfig::default())
+    }
+}
+
+impl std::fmt::Debug for RiskEngine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RiskEngine")
+            .field("config", &self.config)
+            .field("checks", &self.checks.len())
+            .finish()
+    }
+}
+
+impl RiskEngine {
+    /// Creates a [`RiskEngine`] with all built-in checks
+    pub fn new(config: RiskConfig) -> Self {
+        RiskEngine {
+            config,
+            checks: vec![
+                Box::new(MaxOrderSize),
+                Box::new(MaxNotional),
+                Box::new(MaxOpenOrders),
+                Box::new(PriceCollar),
+            ],
+            order_times: HashMap::new(),
+        }
+    }
+
+    /// Adds a custom check that runs after the existing ones
+    pub fn register(&mut self, check: Box<dyn RiskCheck>) {
+        self.checks.push(check);
+    }
+
+    /// Number of accepted orders `signer` sent within their rate window before `now`
+    pub fn recent_orders(&self, signer: &str, now: u64) -> u64 {
+        let window = self.config.limits_for(signer).rate_window_millis;
+        self.order_times.get(signer).map_or(0, |times| {
+            times
+                .iter()
+                .filter(|t| now.saturating_sub(**t) < window)
+                .count() as u64
+        })
+    }
+
+    /// Runs all checks and then the signer's rate limit. Only [`RiskEngine::record`] counts an order towards the rate.
+    ///
+    /// # Errors
+    /// The first check that rejected the order, or the rate limit
+    pub fn check(&self, order: &Order, context: &RiskContext) -> Result<(), ApplicationError> {
+        let limits = self.config.limits_for(&order.signer);
+        for check in &self.checks {
+            check.check(order, limits, context)?;
+        }
+        match limits.max_orders_per_window {
+            Some(max) if self.recent_orders(&order.signer, context.now) >= max => Err(
+                ApplicationError::RateLimitExceeded(order.signer.clone(), max),
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    /// Counts an order of `signer` accepted at `now` towards their rate and forgets the ones that left the window
+    pub fn record(&mut self, signer: &str, now: u64) {
+        let window = self.config.limits_for(signer).rate_window_millis;
+        let times = self.order_times.entry(signer.to_string()).or_default();
+        times.push_back(now);
+        while times
+            .front()
+            .is_some_and(|t| now.saturating_sub(*t) >= window)
+        {
+            times.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // reduce the warnings for naming tests
+    #![allow(non_snake_case)]
+
+    use super::*;
+    use octopus_common::money::{Notional, Quantity};
+
+    fn order(price: u64, amount: u64, side: Side) -> Order {
+        Order {
+            price: Price(price),
+            amount: Quantity(amount),
+            side,
+            signer: "ALICE".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_RiskEngine_check_enforces_size_notional_and_open_orders() {
+        let risk = RiskEngine::new(RiskConfig {
+            default: RiskLimits {
+                max_order_quantity: Some(Quantity(10)),
+                max_order_notional: Some(Notional(500)),
+                max_open_orders: Some(2),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        let context = RiskContext::default();
+
+        assert_eq!(
+            risk.check(&order(1, 11, Side::Buy), &context),
+            Err(ApplicationError::OrderSizeLimitExceeded(11, 10))
+        );
+        assert_eq!(
+            risk.check(&order(60, 10, Side::Buy), &context),
+            Err(ApplicationError::NotionalLimitExceeded(600, 500))
+        );
+        assert_eq!(
+            risk.check(
+                &order(50, 10, Side::Buy),
+                &RiskContext {
+                    open_orders: 2,
+                    ..Default::default()
+                }
+            ),
+            Err(ApplicationError::OpenOrderLimitExceeded(2, 2))
+        );
+        assert_eq!(risk.check(&order(50, 10, Side::Buy), &context), Ok(()));
+    }
+
+    #[test]
+    fn test_RiskEngine_check_price_collar_uses_last_trade_then_best_price() {
+        let risk = RiskEngine::new(RiskConfig {
+            default: RiskLimits {
+                price_collar_bps: Some(1_000),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+
+        let with_trade = RiskContext {
+            last_trade: Some(Price(100)),
+            best_ask: Some(Price(200)),
+            ..Default::default()
+        };
+        assert_eq!(
+            risk.check(&order(111, 1, Side::Buy), &with_trade),
+            Err(ApplicationError::PriceOutsideCollar(111, 90, 110))
+        );
+        assert_eq!(risk.check(&order(90, 1, Side::Buy), &with_trade), Ok(()));
+
+        let without_trade = RiskContext {
+            best_ask: Some(Price(200)),
+            ..Default::default()
+        };
+        assert_eq!(
+            risk.check(&order(100, 1, Side::Buy), &without_trade),
+            Err(ApplicationError::PriceOutsideCollar(100, 180, 220))
+        );
+        // Without any reference price there is no collar
+        assert_eq!(
+            risk.check(&order(100, 1, Side::Sell), &without_trade),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_RiskEngine_check_rate_limits_per_signer() {
+        let mut risk = RiskEngine::new(RiskConfig {
+            default: RiskLimits {
+                max_orders_per_window: Some(2),
+                rate_window_millis: 1_000,
+                ..Default::default()
+            },
+            signers: [("BOB".to_string(), RiskLimits::default())]
+                .into_iter()
+                .collect(),
+        });
+        let at = |now| RiskContext {
+            now,
+            ..Default::default()
+        };
+
+        let mut accept = |order: &Order, now| {
+            risk.check(order, &at(now))?;
+            risk.record(&order.signer, now);
+            Ok::<_, ApplicationError>(())
+        };
+        assert_eq!(accept(&order(1, 1, Side::Buy), 0), Ok(()));
+        assert_eq!(accept(&order(1, 1, Side::Buy), 500), Ok(()));
+        assert_eq!(
+            accept(&order(1, 1, Side::Buy), 900),
+            Err(ApplicationError::RateLimitExceeded("ALICE".to_string(), 2))
+        );
+
+        // BOB has no limits
+        let mut bob = order(1, 1, Side::Buy);
+        bob.signer = "BOB".to_string();
+        for now in 0..5 {
+            assert_eq!(accept(&bob, now), Ok(()));
+        }
+
+        // The first order left the window, the rejected one never counted
+        assert_eq!(risk.recent_orders("ALICE", 1_000), 1);
+        assert_eq!(risk.check(&order(1, 1, Side::Buy), &at(1_000)), Ok(()));
+    }
+
+    #[test]
+    fn test_RiskEngine_check_only_counts_recorded_orders() {
+        let risk = RiskEngine::new(RiskConfig {
+            default: RiskLimits {
+                max_orders_per_window: Some(1),
+                rate_window_millis: 1_000,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+
+        // Orders rejected later on, e.g. for lack of funds, don't use up the rate
+        for _ in 0..3 {
+            assert_eq!(
+                risk.check(&order(1, 1, Side::Buy), &RiskContext::default()),
+                Ok(())
+            );
+        }
+        assert_eq!(risk.recent_orders("ALICE", 0), 0);
+    }
+}
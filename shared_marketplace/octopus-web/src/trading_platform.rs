@@ -1,16 +1,64 @@
-use octopus_common::types::{Order, PartialOrder, Receipt, Side};
-use octopus_common::{errors::ApplicationError, tx::Tx};
+use octopus_common::money::{Notional, Price};
+use octopus_common::types::{
+    AccountMetadata, AccountView, AuctionIndication, AuctionResult, MarketState, Order,
+    PartialOrder, Receipt, Side, CASH_ASSET,
+};
+use octopus_common::{
+    errors::ApplicationError,
+    fees::{FeeSchedule, FillFee},
+    instrument::InstrumentSpec,
+    ledger::{LedgerAccount, SystemAccount, TrialBalance},
+    tx::{Statement, StatementQuery, Tx, TxRecord},
+};
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use crate::accounting::Accounts;
+use crate::circuit_breaker::CircuitBreaker;
+use crate::fees::VolumeTracker;
 use crate::matching::MatchingEngine;
+use crate::risk::{RiskContext, RiskEngine};
 
 /// The core of the core: the [`TradingPlatform`]. Manages accounts, validates-, and orchestrates the processing of each order.
 ///
 ///
+#[derive(Default)]
 pub struct TradingPlatform {
     pub matching_engine: MatchingEngine,
     pub accounts: Accounts,
-    pub transactions: Vec<Tx>,
+    pub transactions: Vec<TxRecord>,
+    /// Trading rules of the market
+    pub instrument: InstrumentSpec,
+    /// Maker and taker fees charged on every fill
+    pub fees: FeeSchedule,
+    /// Traded notional per signer for the fee tiers
+    pub volumes: VolumeTracker,
+    /// Pre-trade risk checks
+    pub risk: RiskEngine,
+    /// Halts the market on large price moves
+    pub circuit_breaker: CircuitBreaker,
+    /// Source of all timestamps
+    pub clock: Clock,
+}
+
+/// Where the [`TradingPlatform`] takes the current time from
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Clock {
+    /// The system's wall clock
+    #[default]
+    System,
+    /// A fixed unix timestamp in milliseconds, e.g. the time a replayed command was recorded at
+    Fixed(u64),
+}
+
+impl Clock {
+    /// The current time as unix timestamp in milliseconds
+    pub fn now(&self) -> u64 {
+        match self {
+            Clock::System => unix_millis(),
+            Clock::Fixed(timestamp) => *timestamp,
+        }
+    }
 }
 
 impl TradingPlatform {
@@ -20,40 +68,309 @@ impl TradingPlatform {
             matching_engine: MatchingEngine::new(),
             accounts: Accounts::new(),
             transactions: vec![],
+            instrument: InstrumentSpec::default(),
+            fees: FeeSchedule::default(),
+            volumes: VolumeTracker::new(),
+            risk: RiskEngine::default(),
+            circuit_breaker: CircuitBreaker::default(),
+            clock: Clock::System,
         }
     }
 
+    /// Creates a new instance without any data for a market with the provided rules.
+    ///
+    /// # Errors
+    /// The spec is inconsistent
+    pub fn with_instrument(instrument: InstrumentSpec) -> Result<Self, String> {
+        instrument.check()?;
+        Ok(TradingPlatform {
+            instrument,
+            ..TradingPlatform::new()
+        })
+    }
+
     /// Fetches the complete order book at this time
     pub fn orderbook(&self) -> Vec<PartialOrder> {
         self.matching_engine
             .asks
-            .values()
+            .orders()
+            .chain(self.matching_engine.bids.orders())
             .cloned()
-            .into_iter()
-            .chain(self.matching_engine.bids.values().cloned().into_iter())
-            .flatten()
             .collect()
     }
 
-    /// Withdraw funds
-    pub fn balance_of(&mut self, signer: &str) -> Result<&u64, ApplicationError> {
+    /// The current trading phase of the market
+    pub fn market_state(&self) -> MarketState {
+        self.matching_engine.state
+    }
+
+    /// Stops trading until [`TradingPlatform::resume`] is called. Resting orders stay in the book.
+    ///
+    /// # Errors
+    /// The market isn't open: an auction has to be uncrossed and a closed market opened first
+    pub fn halt(&mut self) -> Result<MarketState, ApplicationError> {
+        self.transition(
+            &[MarketState::Open, MarketState::Halted],
+            MarketState::Halted,
+        )
+    }
+
+    /// Continues trading after a halt and restarts the circuit breaker's window
+    ///
+    /// # Errors
+    /// The market isn't halted
+    pub fn resume(&mut self) -> Result<MarketState, ApplicationError> {
+        self.transition(&[MarketState::Halted], MarketState::Open)?;
+        self.circuit_breaker.reset();
+        Ok(MarketState::Open)
+    }
+
+    /// Starts a call auction: orders are collected without matching until [`TradingPlatform::uncross`] is called
+    ///
+    /// # Errors
+    /// The market is halted or already in an auction
+    pub fn start_auction(&mut self) -> Result<MarketState, ApplicationError> {
+        self.transition(
+            &[MarketState::Open, MarketState::Closed],
+            MarketState::Auction,
+        )
+    }
+
+    /// Moves the market to `to` if it's currently in one of the states in `from`. Orders only cross during an auction,
+    /// so [`TradingPlatform::uncross`] is the only way out of one.
+    fn transition(
+        &mut self,
+        from: &[MarketState],
+        to: MarketState,
+    ) -> Result<MarketState, ApplicationError> {
+        let current = self.matching_engine.state;
+        if !from.contains(&current) {
+            return Err(ApplicationError::InvalidMarketTransition(current, to));
+        }
+        self.matching_engine.state = to;
+        Ok(to)
+    }
+
+    /// The price and volume the running auction would currently uncross at, using the last trade as reference.
+    /// `None` outside of an auction.
+    pub fn auction_indication(&self) -> Option<AuctionIndication> {
+        if self.matching_engine.state != MarketState::Auction {
+            return None;
+        }
+        self.matching_engine.indicative(self.last_trade_price())
+    }
+
+    /// Ends the running auction by executing all crossing orders at a single price and moves the market to `next`.
+    /// Both sides of an auction fill pay the maker fee.
+    ///
+    /// # Errors
+    /// - The market isn't in an auction, or `next` is an auction again
+    /// - A fill's notional overflows, or a buyer can't pay for its fills any more. The auction keeps running unchanged then.
+    pub fn uncross(
+        &mut self,
+        next: MarketState,
+    ) -> Result<Option<AuctionResult>, ApplicationError> {
+        if self.matching_engine.state != MarketState::Auction {
+            return Err(ApplicationError::NoAuction);
+        }
+        if next == MarketState::Auction {
+            return Err(ApplicationError::InvalidMarketTransition(
+                MarketState::Auction,
+                next,
+            ));
+        }
+        let reference = self.last_trade_price();
+        let result = self.matching_engine.auction(reference);
+        let now = self.clock.now();
+        let fills = result.as_ref().map_or(&[][..], |r| &r.fills[..]);
+
+        // Every fill has to settle before the book changes, otherwise the auction keeps running untouched
+        let mut traded: HashMap<&str, Notional> = HashMap::new();
+        let mut settlements = Vec::with_capacity(fills.len());
+        let mut postings = Vec::with_capacity(3 * fills.len());
+        for (buy, sell) in fills {
+            let notional = buy.price.notional(buy.amount)?;
+            // Earlier fills of the auction count towards the fee tiers of later ones
+            let [buy_fee, sell_fee] = [&buy.signer, &sell.signer].map(|signer| {
+                let volume = traded.entry(signer.as_str()).or_insert(Notional::ZERO);
+                let trailing = self.volumes.trailing(signer, now);
+                let (maker_bps, _) = self
+                    .fees
+                    .rates_for(trailing.checked_add(*volume).unwrap_or(Notional(u64::MAX)));
+                *volume = volume.checked_add(notional).unwrap_or(Notional(u64::MAX));
+                FeeSchedule::fee(notional, maker_bps)
+            });
+            postings.push((
+                LedgerAccount::Customer(buy.signer.clone()),
+                LedgerAccount::Customer(sell.signer.clone()),
+                notional.0,
+            ));
+            for (signer, fee) in [(&buy.signer, buy_fee), (&sell.signer, sell_fee)] {
+                if fee > Notional::ZERO {
+                    postings.push((
+                        LedgerAccount::Customer(signer.clone()),
+                        LedgerAccount::System(SystemAccount::Fees),
+                        fee.0,
+                    ));
+                }
+            }
+            settlements.push((buy, sell, notional, buy_fee, sell_fee));
+        }
+        self.accounts.check_postings(&postings)?;
+
+        self.matching_engine.close_auction(result.as_ref(), next);
+        for (buy, sell, notional, buy_fee, sell_fee) in settlements {
+            let ordinals = (Some(buy.ordinal), Some(sell.ordinal));
+            self.transfer(&buy.signer, &sell.signer, notional.0, ordinals)?;
+            self.charge_fee(&buy.signer, buy_fee, Some(buy.ordinal))?;
+            self.charge_fee(&sell.signer, sell_fee, Some(sell.ordinal))?;
+            self.volumes.record(&buy.signer, now, notional);
+            self.volumes.record(&sell.signer, now, notional);
+        }
+        // The auction price is the new reference for the circuit breaker
+        if let Some(result) = &result {
+            self.circuit_breaker.reset();
+            self.circuit_breaker.record(now, result.indication.price);
+        }
+        Ok(result)
+    }
+
+    /// The price of the most recent fill
+    pub fn last_trade_price(&self) -> Option<Price> {
+        self.matching_engine
+            .history
+            .iter()
+            .rev()
+            .find_map(|r| r.matches.last())
+            .map(|m| m.price)
+    }
+
+    /// Assembles an [`AccountView`] of the `signer` account, including the funds reserved by its resting buy orders
+    ///
+    /// # Errors
+    /// The account doesn't exist
+    pub fn account(&self, signer: &str) -> Result<AccountView, ApplicationError> {
+        let balance = *self.accounts.balance_of(signer)?;
+        let open_orders = self.open_orders(signer);
+        let reserved = self.reserved(signer);
+
+        Ok(AccountView {
+            signer: signer.to_string(),
+            available: balance.saturating_sub(reserved),
+            reserved: reserved.min(balance),
+            balances: [(CASH_ASSET.to_string(), balance)].into_iter().collect(),
+            open_orders,
+            metadata: self.accounts.metadata_of(signer)?.clone(),
+        })
+    }
+
+    /// Number of orders `signer` has resting on either side of the book
+    fn open_orders(&self, signer: &str) -> usize {
+        self.matching_engine.asks.order_count_of(signer)
+            + self.matching_engine.bids.order_count_of(signer)
+    }
+
+    /// Funds committed to the resting buy orders of `signer`: their value plus the highest maker fee they can be charged
+    fn reserved(&self, signer: &str) -> u64 {
+        let notional = Notional(
+            u64::try_from(self.matching_engine.bids.notional_of(signer)).unwrap_or(u64::MAX),
+        );
+        let fee = FeeSchedule::fee(notional, self.fees.max_maker_bps());
+        notional.0.saturating_add(fee.0)
+    }
+
+    /// Checks that `signer` can pay `amount` from funds that aren't committed to resting buy orders
+    ///
+    /// # Errors
+    /// The account doesn't exist or the available funds are too low
+    fn ensure_available(&self, signer: &str, amount: u64) -> Result<(), ApplicationError> {
+        let balance = *self.accounts.balance_of(signer)?;
+        if balance.saturating_sub(self.reserved(signer)) < amount {
+            return Err(ApplicationError::AccountUnderFunded(
+                signer.to_string(),
+                amount,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Opens a new account with a zero balance
+    ///
+    /// # Errors
+    /// The signer is taken
+    pub fn open_account(
+        &mut self,
+        signer: &str,
+        owner: &str,
+    ) -> Result<AccountMetadata, ApplicationError> {
+        self.accounts.open(signer, owner, self.clock.now()).cloned()
+    }
+
+    /// Freezes an account and pulls its resting orders from the book
+    ///
+    /// # Errors
+    /// The account doesn't exist or is closed
+    pub fn freeze_account(&mut self, signer: &str) -> Result<AccountMetadata, ApplicationError> {
+        let metadata = self.accounts.freeze(signer)?.clone();
+        self.matching_engine.remove_orders_of(signer);
+        Ok(metadata)
+    }
+
+    /// Lifts the freeze from an account
+    ///
+    /// # Errors
+    /// The account doesn't exist or is closed
+    pub fn unfreeze_account(&mut self, signer: &str) -> Result<AccountMetadata, ApplicationError> {
+        self.accounts.unfreeze(signer).cloned()
+    }
+
+    /// Closes an account with a zero balance and pulls its resting orders from the book
+    ///
+    /// # Errors
+    /// The account doesn't exist, is closed already or has a balance
+    pub fn close_account(&mut self, signer: &str) -> Result<AccountMetadata, ApplicationError> {
+        let metadata = self.accounts.close(signer)?.clone();
+        self.matching_engine.remove_orders_of(signer);
+        Ok(metadata)
+    }
+
+    /// Removes a resting order of `signer` from the book
+    ///
+    /// # Errors
+    /// The signer has no resting order with this ordinal
+    pub fn cancel(&mut self, signer: &str, ordinal: u64) -> Result<PartialOrder, ApplicationError> {
+        match self.matching_engine.get(ordinal) {
+            Some(order) if order.signer == signer => {}
+            _ => return Err(ApplicationError::OrderNotFound(ordinal)),
+        }
+        self.matching_engine
+            .cancel(ordinal)
+            .ok_or(ApplicationError::OrderNotFound(ordinal))
+    }
+
+    /// Retrieve the balance of an account
+    pub fn balance_of(&self, signer: &str) -> Result<&u64, ApplicationError> {
         self.accounts.balance_of(signer)
     }
 
+    /// Summarizes the double-entry journal behind the accounts
+    pub fn trial_balance(&self) -> TrialBalance {
+        self.accounts.trial_balance()
+    }
+
     /// Deposit funds
     pub fn deposit(&mut self, signer: &str, amount: u64) -> Result<Tx, ApplicationError> {
-        self.accounts.deposit(signer, amount).map(|tx| {
-            self.transactions.push(tx.clone());
-            tx
-        })
+        let tx = self.accounts.deposit(signer, amount)?;
+        self.record(tx.clone(), None);
+        Ok(tx)
     }
 
     /// Withdraw funds
     pub fn withdraw(&mut self, signer: &str, amount: u64) -> Result<Tx, ApplicationError> {
-        self.accounts.withdraw(signer, amount).map(|tx| {
-            self.transactions.push(tx.clone());
-            tx
-        })
+        self.ensure_available(signer, amount)?;
+        let tx = self.accounts.withdraw(signer, amount)?;
+        self.record(tx.clone(), None);
+        Ok(tx)
     }
 
     /// Transfer funds between sender and recipient
@@ -63,53 +380,235 @@ impl TradingPlatform {
         recipient: &str,
         amount: u64,
     ) -> Result<(Tx, Tx), ApplicationError> {
-        self.accounts.send(sender, recipient, amount).map(|tx| {
-            self.transactions.push(tx.0.clone());
-            self.transactions.push(tx.1.clone());
-            tx
+        self.ensure_available(sender, amount)?;
+        self.transfer(sender, recipient, amount, (None, None))
+    }
+
+    /// Fetches a page of the `signer` account's transaction records, oldest first
+    ///
+    /// # Errors
+    /// The account doesn't exist
+    pub fn statement(
+        &self,
+        signer: &str,
+        query: &StatementQuery,
+    ) -> Result<Statement, ApplicationError> {
+        self.accounts.balance_of(signer)?;
+        let matching: Vec<&TxRecord> = self
+            .transactions
+            .iter()
+            .filter(|r| r.tx.account() == signer && query.matches(r))
+            .collect();
+
+        Ok(Statement {
+            signer: signer.to_string(),
+            total: matching.len(),
+            entries: matching
+                .into_iter()
+                .skip(query.offset.unwrap_or(0))
+                .take(query.limit.unwrap_or(StatementQuery::DEFAULT_LIMIT))
+                .cloned()
+                .collect(),
         })
     }
 
+    /// Transfers funds and records both sides with the ordinals of the orders that caused them
+    fn transfer(
+        &mut self,
+        sender: &str,
+        recipient: &str,
+        amount: u64,
+        ordinals: (Option<u64>, Option<u64>),
+    ) -> Result<(Tx, Tx), ApplicationError> {
+        let (tx_withdraw, tx_deposit) = self.accounts.send(sender, recipient, amount)?;
+        self.record(tx_withdraw.clone(), ordinals.0);
+        self.record(tx_deposit.clone(), ordinals.1);
+        Ok((tx_withdraw, tx_deposit))
+    }
+
+    /// Charges a non-zero trading fee and records it
+    fn charge_fee(
+        &mut self,
+        signer: &str,
+        fee: Notional,
+        ordinal: Option<u64>,
+    ) -> Result<(), ApplicationError> {
+        if fee > Notional::ZERO {
+            let tx = self.accounts.charge_fee(signer, fee.0)?;
+            self.record(tx, ordinal);
+        }
+        Ok(())
+    }
+
+    /// Appends a [`TxRecord`] for an applied [`Tx`] to the transaction log
+    fn record(&mut self, tx: Tx, ordinal: Option<u64>) {
+        let balance = self.accounts.balance_of(tx.account()).copied().unwrap_or(0);
+        self.transactions.push(TxRecord {
+            id: self.transactions.len() as u64 + 1,
+            timestamp: self.clock.now(),
+            tx,
+            balance,
+            ordinal,
+        });
+    }
+
     /// Process a given order and apply the outcome to the accounts involved. Note that there are very few safeguards in place.
     ///
     /// # Errors
+    /// - Account doesn't exist, is frozen or closed
+    /// - The market is halted or closed
+    /// - The order violates the market's [`InstrumentSpec`]
+    /// - The order violates the signer's risk limits
     /// - Account has insufficient funds
+    /// - The order's notional overflows
+    /// - A fill can't settle, e.g. because the seller's balance would overflow. The book and the accounts stay unchanged
+    ///   then.
     pub fn order(&mut self, order: Order) -> Result<Receipt, ApplicationError> {
-        let total_amount = order.amount * order.price;
-        // Make sure the account has a deposit
-        match self.balance_of(&order.signer) {
-            Ok(balance) if &order.side == &Side::Buy && balance < &total_amount => {
-                return Err(ApplicationError::AccountUnderFunded(
-                    order.signer.clone(),
-                    total_amount,
-                ))
+        self.accounts.ensure_active(&order.signer)?;
+        self.matching_engine.ensure_accepting()?;
+        self.instrument.validate(&order)?;
+        let total_amount = order.price.notional(order.amount)?;
+        // A sell order may be filled above its limit, so every possible fill has to fit as well
+        if order.side == Side::Sell {
+            if let Some(best_bid) = self.matching_engine.bids.prices().next_back() {
+                best_bid.notional(order.amount)?;
             }
-            Ok(_) => {}
-            Err(e) => return Err(e),
+        }
+        let now = self.clock.now();
+        let context = RiskContext {
+            now,
+            open_orders: self.open_orders(&order.signer) as u64,
+            last_trade: self.last_trade_price(),
+            best_bid: self.matching_engine.bids.prices().next_back(),
+            best_ask: self.matching_engine.asks.prices().next(),
+        };
+        self.risk.check(&order, &context)?;
+
+        let (_, taker_bps) = self
+            .fees
+            .rates_for(self.volumes.trailing(&order.signer, now));
+        // Whatever doesn't match rests and may be charged the maker rate later
+        let fee_bps = taker_bps.max(self.fees.max_maker_bps());
+        let required = total_amount
+            .checked_add(FeeSchedule::fee(total_amount, fee_bps))
+            .ok_or(ApplicationError::NotionalOverflow(
+                order.price.0,
+                order.amount.0,
+            ))?;
+        // Make sure the account has funds, beyond those its resting bids need, that cover the order and its fees
+        if order.side == Side::Buy {
+            self.ensure_available(&order.signer, required.0)?;
         }
         let signer = order.signer.clone();
         let side = order.side.clone();
+
+        // Every fill has to settle before the book changes, otherwise the order is rejected without a trace
+        let matches = self.matching_engine.matches(&order);
+        let mut traded: HashMap<&str, Notional> = HashMap::new();
+        let mut settlements = Vec::with_capacity(matches.len());
+        let mut postings = Vec::with_capacity(3 * matches.len());
+        for m in &matches {
+            let notional = m.price.notional(m.amount)?;
+            // Earlier fills of the order count towards the maker's fee tier
+            let volume = traded.entry(m.signer.as_str()).or_insert(Notional::ZERO);
+            let trailing = self.volumes.trailing(&m.signer, now);
+            let (maker_bps, _) = self
+                .fees
+                .rates_for(trailing.checked_add(*volume).unwrap_or(Notional(u64::MAX)));
+            *volume = volume.checked_add(notional).unwrap_or(Notional(u64::MAX));
+            let fill_fee = FillFee {
+                ordinal: m.ordinal,
+                maker_fee: FeeSchedule::fee(notional, maker_bps),
+                taker_fee: FeeSchedule::fee(notional, taker_bps),
+            };
+            let (buyer, seller) = match side {
+                Side::Buy => (&signer, &m.signer),
+                Side::Sell => (&m.signer, &signer),
+            };
+            postings.push((
+                LedgerAccount::Customer(buyer.clone()),
+                LedgerAccount::Customer(seller.clone()),
+                notional.0,
+            ));
+            for (payer, fee) in [
+                (&m.signer, fill_fee.maker_fee),
+                (&signer, fill_fee.taker_fee),
+            ] {
+                if fee > Notional::ZERO {
+                    postings.push((
+                        LedgerAccount::Customer(payer.clone()),
+                        LedgerAccount::System(SystemAccount::Fees),
+                        fee.0,
+                    ));
+                }
+            }
+            settlements.push((notional, fill_fee));
+        }
+        self.accounts.check_postings(&postings)?;
+
         // Do the actual matching
-        let receipt = self.matching_engine.process(order)?;
+        let mut receipt = self.matching_engine.process(order)?;
+        self.risk.record(&signer, now);
+        debug_assert_eq!(receipt.matches, matches, "the planned matches are executed");
 
-        receipt
-            .matches
-            .iter()
-            .map(|m| match side {
-                Side::Buy => self.send(&signer, &m.signer, m.amount * m.price),
-                Side::Sell => self.send(&m.signer, &signer, m.amount * m.price),
-            })
-            .collect::<Result<Vec<_>, ApplicationError>>()?;
+        // Each side's transactions are linked to its own order
+        let ordinal = Some(receipt.ordinal);
+        for (m, (notional, fill_fee)) in receipt.matches.iter().zip(&settlements) {
+            match side {
+                Side::Buy => {
+                    self.transfer(&signer, &m.signer, notional.0, (ordinal, Some(m.ordinal)))?
+                }
+                Side::Sell => {
+                    self.transfer(&m.signer, &signer, notional.0, (Some(m.ordinal), ordinal))?
+                }
+            };
+            self.charge_fee(&m.signer, fill_fee.maker_fee, Some(m.ordinal))?;
+            self.charge_fee(&signer, fill_fee.taker_fee, ordinal)?;
+            self.volumes.record(&m.signer, now, *notional);
+            self.volumes.record(&signer, now, *notional);
+        }
+        receipt.fees = settlements.into_iter().map(|(_, fee)| fee).collect();
+
+        // Large price moves halt the market after this order
+        let mut tripped = false;
+        for m in &receipt.matches {
+            tripped |= self.circuit_breaker.record(now, m.price);
+        }
+        // Orders only match while the market is open, so it can always be halted here
+        if tripped {
+            self.matching_engine.state = MarketState::Halted;
+        }
+
+        // Keep the fees in the matching history as well
+        if let Some(recorded) = self.matching_engine.history.last_mut() {
+            recorded.fees = receipt.fees.clone();
+        }
         Ok(receipt)
     }
 }
 
+/// The current time as unix timestamp in milliseconds
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
 #[cfg(test)]
 mod tests {
     // reduce the warnings for naming tests
     #![allow(non_snake_case)]
 
     use super::*;
+    use octopus_common::{
+        fees::FeeTier,
+        ledger::SystemAccount,
+        money::Quantity,
+        risk::{RiskConfig, RiskLimits},
+        tx::TxKind,
+        types::{AccountStatus, CircuitBreakerConfig},
+    };
 
     #[test]
     fn test_TradingPlatform_order_requires_deposit_to_order() {
@@ -117,8 +616,8 @@ mod tests {
 
         assert_eq!(
             trading_platform.order(Order {
-                price: 10,
-                amount: 1,
+                price: Price(10),
+                amount: Quantity(1),
                 side: Side::Sell,
                 signer: "ALICE".to_string(),
             }),
@@ -133,13 +632,15 @@ mod tests {
         let mut trading_platform = TradingPlatform::new();
 
         // Set up accounts
+        assert!(trading_platform.open_account("ALICE", "owner").is_ok());
         assert!(trading_platform.accounts.deposit("ALICE", 100).is_ok());
+        assert!(trading_platform.open_account("BOB", "owner").is_ok());
         assert!(trading_platform.accounts.deposit("BOB", 100).is_ok());
 
         let alice_receipt = trading_platform
             .order(Order {
-                price: 10,
-                amount: 1,
+                price: Price(10),
+                amount: Quantity(1),
                 side: Side::Sell,
                 signer: "ALICE".to_string(),
             })
@@ -149,8 +650,8 @@ mod tests {
 
         let bob_receipt = trading_platform
             .order(Order {
-                price: 10,
-                amount: 2,
+                price: Price(10),
+                amount: Quantity(2),
                 side: Side::Buy,
                 signer: "BOB".to_string(),
             })
@@ -159,9 +660,9 @@ mod tests {
         assert_eq!(
             bob_receipt.matches,
             vec![PartialOrder {
-                price: 10,
-                amount: 1,
-                remaining: 0,
+                price: Price(10),
+                amount: Quantity(1),
+                remaining: Quantity(0),
                 side: Side::Sell,
                 signer: "ALICE".to_string(),
                 ordinal: 1
@@ -180,13 +681,15 @@ mod tests {
         let mut trading_platform = TradingPlatform::new();
 
         // Set up accounts
+        assert!(trading_platform.open_account("ALICE", "owner").is_ok());
         assert!(trading_platform.accounts.deposit("ALICE", 100).is_ok());
+        assert!(trading_platform.open_account("BOB", "owner").is_ok());
         assert!(trading_platform.accounts.deposit("BOB", 100).is_ok());
 
         let alice_receipt = trading_platform
             .order(Order {
-                price: 10,
-                amount: 2,
+                price: Price(10),
+                amount: Quantity(2),
                 side: Side::Sell,
                 signer: "ALICE".to_string(),
             })
@@ -196,8 +699,8 @@ mod tests {
 
         let bob_receipt = trading_platform
             .order(Order {
-                price: 10,
-                amount: 2,
+                price: Price(10),
+                amount: Quantity(2),
                 side: Side::Buy,
                 signer: "BOB".to_string(),
             })
@@ -206,9 +709,9 @@ mod tests {
         assert_eq!(
             bob_receipt.matches,
             vec![PartialOrder {
-                price: 10,
-                amount: 2,
-                remaining: 0,
+                price: Price(10),
+                amount: Quantity(2),
+                remaining: Quantity(0),
                 side: Side::Sell,
                 signer: "ALICE".to_string(),
                 ordinal: 1
@@ -229,14 +732,17 @@ mod tests {
         let mut trading_platform = TradingPlatform::new();
 
         // Set up accounts
+        assert!(trading_platform.open_account("ALICE", "owner").is_ok());
         assert!(trading_platform.accounts.deposit("ALICE", 100).is_ok());
+        assert!(trading_platform.open_account("BOB", "owner").is_ok());
         assert!(trading_platform.accounts.deposit("BOB", 100).is_ok());
+        assert!(trading_platform.open_account("CHARLIE", "owner").is_ok());
         assert!(trading_platform.accounts.deposit("CHARLIE", 100).is_ok());
 
         let alice_receipt = trading_platform
             .order(Order {
-                price: 10,
-                amount: 1,
+                price: Price(10),
+                amount: Quantity(1),
                 side: Side::Sell,
                 signer: "ALICE".to_string(),
             })
@@ -246,8 +752,8 @@ mod tests {
 
         let charlie_receipt = trading_platform
             .order(Order {
-                price: 10,
-                amount: 1,
+                price: Price(10),
+                amount: Quantity(1),
                 side: Side::Sell,
                 signer: "CHARLIE".to_string(),
             })
@@ -257,8 +763,8 @@ mod tests {
 
         let bob_receipt = trading_platform
             .order(Order {
-                price: 10,
-                amount: 2,
+                price: Price(10),
+                amount: Quantity(2),
                 side: Side::Buy,
                 signer: "BOB".to_string(),
             })
@@ -268,17 +774,17 @@ mod tests {
             bob_receipt.matches,
             vec![
                 PartialOrder {
-                    price: 10,
-                    amount: 1,
-                    remaining: 0,
+                    price: Price(10),
+                    amount: Quantity(1),
+                    remaining: Quantity(0),
                     side: Side::Sell,
                     signer: "ALICE".to_string(),
                     ordinal: 1
                 },
                 PartialOrder {
-                    price: 10,
-                    amount: 1,
-                    remaining: 0,
+                    price: Price(10),
+                    amount: Quantity(1),
+                    remaining: Quantity(0),
                     side: Side::Sell,
                     signer: "CHARLIE".to_string(),
                     ordinal: 2
@@ -300,13 +806,15 @@ mod tests {
         let mut trading_platform = TradingPlatform::new();
 
         // Set up accounts
+        assert!(trading_platform.open_account("ALICE", "owner").is_ok());
         assert!(trading_platform.accounts.deposit("ALICE", 100).is_ok());
+        assert!(trading_platform.open_account("CHARLIE", "owner").is_ok());
         assert!(trading_platform.accounts.deposit("CHARLIE", 100).is_ok());
 
         let alice_receipt = trading_platform
             .order(Order {
-                price: 10,
-                amount: 1,
+                price: Price(10),
+                amount: Quantity(1),
                 side: Side::Sell,
                 signer: "ALICE".to_string(),
             })
@@ -316,8 +824,8 @@ mod tests {
 
         let charlie_receipt = trading_platform
             .order(Order {
-                price: 10,
-                amount: 1,
+                price: Price(10),
+                amount: Quantity(1),
                 side: Side::Sell,
                 signer: "CHARLIE".to_string(),
             })
@@ -327,8 +835,8 @@ mod tests {
 
         let bob_receipt = trading_platform
             .order(Order {
-                price: 10,
-                amount: 2,
+                price: Price(10),
+                amount: Quantity(2),
                 side: Side::Buy,
                 signer: "ALICE".to_string(),
             })
@@ -337,9 +845,9 @@ mod tests {
         assert_eq!(
             bob_receipt.matches,
             vec![PartialOrder {
-                price: 10,
-                amount: 1,
-                remaining: 0,
+                price: Price(10),
+                amount: Quantity(1),
+                remaining: Quantity(0),
                 side: Side::Sell,
                 signer: "CHARLIE".to_string(),
                 ordinal: 2
@@ -358,13 +866,15 @@ mod tests {
         let mut trading_platform = TradingPlatform::new();
 
         // Set up accounts
+        assert!(trading_platform.open_account("ALICE", "owner").is_ok());
         assert!(trading_platform.accounts.deposit("ALICE", 100).is_ok());
+        assert!(trading_platform.open_account("BOB", "owner").is_ok());
         assert!(trading_platform.accounts.deposit("BOB", 100).is_ok());
 
         let alice_receipt = trading_platform
             .order(Order {
-                price: 10,
-                amount: 2,
+                price: Price(10),
+                amount: Quantity(2),
                 side: Side::Sell,
                 signer: "ALICE".to_string(),
             })
@@ -374,8 +884,8 @@ mod tests {
 
         let bob_receipt = trading_platform
             .order(Order {
-                price: 11,
-                amount: 2,
+                price: Price(11),
+                amount: Quantity(2),
                 side: Side::Sell,
                 signer: "BOB".to_string(),
             })
@@ -388,4 +898,908 @@ mod tests {
         assert_eq!(trading_platform.accounts.balance_of("ALICE"), Ok(&100));
         assert_eq!(trading_platform.accounts.balance_of("BOB"), Ok(&100));
     }
+
+    #[test]
+    fn test_TradingPlatform_account_reports_reserved_funds_and_open_orders() {
+        let mut trading_platform = TradingPlatform::new();
+
+        // Set up accounts
+        assert!(trading_platform.open_account("ALICE", "owner").is_ok());
+        assert!(trading_platform.accounts.deposit("ALICE", 100).is_ok());
+        assert!(trading_platform.open_account("BOB", "owner").is_ok());
+        assert!(trading_platform.accounts.deposit("BOB", 100).is_ok());
+
+        trading_platform
+            .order(Order {
+                price: Price(10),
+                amount: Quantity(3),
+                side: Side::Buy,
+                signer: "ALICE".to_string(),
+            })
+            .unwrap();
+        trading_platform
+            .order(Order {
+                price: Price(20),
+                amount: Quantity(1),
+                side: Side::Sell,
+                signer: "ALICE".to_string(),
+            })
+            .unwrap();
+
+        let metadata = trading_platform
+            .accounts
+            .metadata_of("ALICE")
+            .unwrap()
+            .clone();
+        assert_eq!(
+            trading_platform.account("ALICE"),
+            Ok(AccountView {
+                signer: "ALICE".to_string(),
+                available: 70,
+                reserved: 30,
+                balances: [(CASH_ASSET.to_string(), 100)].into_iter().collect(),
+                open_orders: 2,
+                metadata,
+            })
+        );
+        assert_eq!(trading_platform.account("BOB").unwrap().open_orders, 0);
+        assert_eq!(
+            trading_platform.account("CHARLIE"),
+            Err(ApplicationError::AccountNotFound("CHARLIE".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_TradingPlatform_statement_records_running_balances_and_ordinals() {
+        let mut trading_platform = TradingPlatform::new();
+
+        assert!(trading_platform.open_account("ALICE", "owner").is_ok());
+        assert!(trading_platform.deposit("ALICE", 100).is_ok());
+        assert!(trading_platform.open_account("BOB", "owner").is_ok());
+        assert!(trading_platform.deposit("BOB", 100).is_ok());
+        assert!(trading_platform.withdraw("ALICE", 10).is_ok());
+
+        trading_platform
+            .order(Order {
+                price: Price(10),
+                amount: Quantity(2),
+                side: Side::Sell,
+                signer: "ALICE".to_string(),
+            })
+            .unwrap();
+        trading_platform
+            .order(Order {
+                price: Price(10),
+                amount: Quantity(2),
+                side: Side::Buy,
+                signer: "BOB".to_string(),
+            })
+            .unwrap();
+
+        let statement = trading_platform
+            .statement("ALICE", &StatementQuery::default())
+            .unwrap();
+        assert_eq!(statement.total, 3);
+        let summary: Vec<_> = statement
+            .entries
+            .iter()
+            .map(|r| (r.id, r.tx.kind(), r.balance, r.ordinal))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (1, TxKind::Deposit, 100, None),
+                (3, TxKind::Withdraw, 90, None),
+                (5, TxKind::Deposit, 110, Some(1)),
+            ]
+        );
+
+        let statement = trading_platform
+            .statement("BOB", &StatementQuery::default())
+            .unwrap();
+        assert_eq!(statement.entries[1].tx.kind(), TxKind::Withdraw);
+        assert_eq!(statement.entries[1].balance, 80);
+        assert_eq!(statement.entries[1].ordinal, Some(2));
+    }
+
+    #[test]
+    fn test_TradingPlatform_statement_filters_and_paginates() {
+        let mut trading_platform = TradingPlatform::new();
+
+        assert!(trading_platform.open_account("ALICE", "owner").is_ok());
+        for _ in 0..5 {
+            assert!(trading_platform.deposit("ALICE", 10).is_ok());
+        }
+        assert!(trading_platform.withdraw("ALICE", 10).is_ok());
+
+        let deposits = trading_platform
+            .statement(
+                "ALICE",
+                &StatementQuery {
+                    kind: Some(TxKind::Deposit),
+                    offset: Some(1),
+                    limit: Some(2),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(deposits.total, 5);
+        assert_eq!(
+            deposits.entries.iter().map(|r| r.id).collect::<Vec<_>>(),
+            vec![2, 3]
+        );
+
+        let future = trading_platform
+            .statement(
+                "ALICE",
+                &StatementQuery {
+                    from: Some(u64::MAX),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(future.total, 0);
+        assert!(future.entries.is_empty());
+
+        assert_eq!(
+            trading_platform.statement("BOB", &StatementQuery::default()),
+            Err(ApplicationError::AccountNotFound("BOB".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_TradingPlatform_frozen_account_cannot_trade_and_loses_resting_orders() {
+        let mut trading_platform = TradingPlatform::new();
+
+        assert!(trading_platform.open_account("ALICE", "Alice").is_ok());
+        assert!(trading_platform.deposit("ALICE", 100).is_ok());
+        trading_platform
+            .order(Order {
+                price: Price(10),
+                amount: Quantity(1),
+                side: Side::Buy,
+                signer: "ALICE".to_string(),
+            })
+            .unwrap();
+        assert_eq!(trading_platform.orderbook().len(), 1);
+
+        let metadata = trading_platform.freeze_account("ALICE").unwrap();
+        assert_eq!(metadata.status, AccountStatus::Frozen);
+        assert!(trading_platform.orderbook().is_empty());
+        assert_eq!(
+            trading_platform.order(Order {
+                price: Price(10),
+                amount: Quantity(1),
+                side: Side::Buy,
+                signer: "ALICE".to_string(),
+            }),
+            Err(ApplicationError::AccountFrozen("ALICE".to_string()))
+        );
+
+        // Frozen accounts still receive funds
+        assert!(trading_platform.deposit("ALICE", 1).is_ok());
+        assert_eq!(
+            trading_platform.account("ALICE").unwrap().metadata.status,
+            AccountStatus::Frozen
+        );
+
+        assert!(trading_platform.unfreeze_account("ALICE").is_ok());
+        assert!(trading_platform.withdraw("ALICE", 101).is_ok());
+        assert_eq!(
+            trading_platform.close_account("ALICE").map(|m| m.status),
+            Ok(AccountStatus::Closed)
+        );
+    }
+
+    #[test]
+    fn test_TradingPlatform_order_notional_overflow_is_rejected() {
+        let mut trading_platform = TradingPlatform::new();
+
+        assert!(trading_platform.open_account("ALICE", "owner").is_ok());
+        assert!(trading_platform.open_account("BOB", "owner").is_ok());
+        assert!(trading_platform.deposit("ALICE", u64::MAX).is_ok());
+
+        assert_eq!(
+            trading_platform.order(Order {
+                price: Price(u64::MAX),
+                amount: Quantity(2),
+                side: Side::Buy,
+                signer: "ALICE".to_string(),
+            }),
+            Err(ApplicationError::NotionalOverflow(u64::MAX, 2))
+        );
+
+        // A sell order could be filled at the best bid, which would overflow
+        trading_platform
+            .order(Order {
+                price: Price(u64::MAX / 2),
+                amount: Quantity(1),
+                side: Side::Buy,
+                signer: "ALICE".to_string(),
+            })
+            .unwrap();
+        assert_eq!(
+            trading_platform.order(Order {
+                price: Price(1),
+                amount: Quantity(3),
+                side: Side::Sell,
+                signer: "BOB".to_string(),
+            }),
+            Err(ApplicationError::NotionalOverflow(u64::MAX / 2, 3))
+        );
+        assert_eq!(trading_platform.orderbook().len(), 1);
+        assert_eq!(trading_platform.accounts.balance_of("BOB"), Ok(&0));
+    }
+
+    #[test]
+    fn test_TradingPlatform_order_is_validated_against_instrument() {
+        let mut trading_platform = TradingPlatform::with_instrument(InstrumentSpec {
+            symbol: "TEST".to_string(),
+            tick_size: Price(20),
+            lot_size: Quantity(5),
+            min_quantity: Quantity(10),
+            max_quantity: Quantity(100),
+            min_notional: Notional(1_000),
+        })
+        .unwrap();
+        assert!(trading_platform.open_account("ALICE", "owner").is_ok());
+        assert!(trading_platform.deposit("ALICE", 100_000).is_ok());
+
+        let order = |price: &str, amount| Order {
+            price: price.parse().unwrap(),
+            amount: Quantity(amount),
+            side: Side::Buy,
+            signer: "ALICE".to_string(),
+        };
+
+        assert_eq!(
+            trading_platform.order(order("1.30", 10)),
+            Err(ApplicationError::InvalidPrice(130, 20))
+        );
+        assert_eq!(
+            trading_platform.order(order("0", 10)),
+            Err(ApplicationError::InvalidPrice(0, 20))
+        );
+        assert_eq!(
+            trading_platform.order(order("1.20", 12)),
+            Err(ApplicationError::InvalidLotSize(12, 5))
+        );
+        assert_eq!(
+            trading_platform.order(order("1.20", 5)),
+            Err(ApplicationError::QuantityOutOfRange(5, 10, 100))
+        );
+        assert_eq!(
+            trading_platform.order(order("1.20", 105)),
+            Err(ApplicationError::QuantityOutOfRange(105, 10, 100))
+        );
+        assert_eq!(
+            trading_platform.order(order("0.80", 10)),
+            Err(ApplicationError::NotionalTooSmall(800, 1_000))
+        );
+        assert!(trading_platform.order(order("1.20", 10)).is_ok());
+        assert!(trading_platform.orderbook().len() == 1);
+    }
+
+    #[test]
+    fn test_TradingPlatform_with_instrument_rejects_inconsistent_spec() {
+        assert!(TradingPlatform::with_instrument(InstrumentSpec {
+            tick_size: Price(0),
+            ..Default::default()
+        })
+        .is_err());
+        assert!(TradingPlatform::with_instrument(InstrumentSpec {
+            min_quantity: Quantity(10),
+            max_quantity: Quantity(5),
+            ..Default::default()
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn test_TradingPlatform_order_leaves_everything_untouched_if_a_fill_cannot_settle() {
+        let mut trading_platform = TradingPlatform::new();
+        assert!(trading_platform.open_account("ALICE", "owner").is_ok());
+        assert!(trading_platform.open_account("BOB", "owner").is_ok());
+        assert!(trading_platform.deposit("ALICE", u64::MAX - 5).is_ok());
+        assert!(trading_platform.deposit("BOB", 1_000).is_ok());
+        trading_platform
+            .order(Order {
+                price: Price(10),
+                amount: Quantity(2),
+                side: Side::Buy,
+                signer: "BOB".to_string(),
+            })
+            .unwrap();
+        let orderbook = trading_platform.orderbook();
+        let history = trading_platform.matching_engine.history.clone();
+        let transactions = trading_platform.transactions.clone();
+
+        // ALICE can't be credited the 20 the fill is worth
+        assert_eq!(
+            trading_platform.order(Order {
+                price: Price(10),
+                amount: Quantity(2),
+                side: Side::Sell,
+                signer: "ALICE".to_string(),
+            }),
+            Err(ApplicationError::AccountOverFunded("ALICE".to_string(), 20))
+        );
+        assert_eq!(trading_platform.orderbook(), orderbook);
+        assert_eq!(trading_platform.matching_engine.history, history);
+        assert_eq!(trading_platform.transactions, transactions);
+        assert_eq!(trading_platform.balance_of("ALICE"), Ok(&(u64::MAX - 5)));
+        assert_eq!(trading_platform.balance_of("BOB"), Ok(&1_000));
+        assert!(trading_platform.trial_balance().balanced);
+    }
+
+    #[test]
+    fn test_TradingPlatform_order_resting_bids_reserve_funds() {
+        let mut trading_platform = TradingPlatform::new();
+
+        assert!(trading_platform.open_account("ALICE", "owner").is_ok());
+        assert!(trading_platform.open_account("BOB", "owner").is_ok());
+        assert!(trading_platform.deposit("ALICE", 100).is_ok());
+
+        let bid = |price: u64| Order {
+            price: Price(price),
+            amount: Quantity(1),
+            side: Side::Buy,
+            signer: "ALICE".to_string(),
+        };
+        assert!(trading_platform.order(bid(60)).is_ok());
+        // Only 40 are left once the first bid's funds are set aside
+        assert_eq!(
+            trading_platform.order(bid(60)),
+            Err(ApplicationError::AccountUnderFunded(
+                "ALICE".to_string(),
+                60
+            ))
+        );
+        assert_eq!(
+            trading_platform.withdraw("ALICE", 50),
+            Err(ApplicationError::AccountUnderFunded(
+                "ALICE".to_string(),
+                50
+            ))
+        );
+        assert_eq!(
+            trading_platform.send("ALICE", "BOB", 41),
+            Err(ApplicationError::AccountUnderFunded(
+                "ALICE".to_string(),
+                41
+            ))
+        );
+        assert!(trading_platform.order(bid(40)).is_ok());
+
+        // Both bids can be filled
+        let receipt = trading_platform
+            .order(Order {
+                price: Price(40),
+                amount: Quantity(2),
+                side: Side::Sell,
+                signer: "BOB".to_string(),
+            })
+            .unwrap();
+        assert_eq!(receipt.matches.len(), 2);
+        assert_eq!(trading_platform.balance_of("ALICE"), Ok(&0));
+        assert_eq!(trading_platform.balance_of("BOB"), Ok(&100));
+    }
+
+    #[test]
+    fn test_TradingPlatform_order_charges_maker_and_taker_fees() {
+        let mut trading_platform = TradingPlatform::new();
+        trading_platform.fees = FeeSchedule {
+            maker_bps: 10,
+            taker_bps: 50,
+            tiers: vec![],
+        };
+
+        assert!(trading_platform.open_account("ALICE", "owner").is_ok());
+        assert!(trading_platform.open_account("BOB", "owner").is_ok());
+        assert!(trading_platform.deposit("ALICE", 100_000).is_ok());
+        assert!(trading_platform.deposit("BOB", 100_000).is_ok());
+
+        trading_platform
+            .order(Order {
+                price: Price(1_000),
+                amount: Quantity(10),
+                side: Side::Sell,
+                signer: "ALICE".to_string(),
+            })
+            .unwrap();
+
+        // The buyer needs to cover the taker fee as well
+        assert_eq!(
+            trading_platform.order(Order {
+                price: Price(10_000),
+                amount: Quantity(10),
+                side: Side::Buy,
+                signer: "BOB".to_string(),
+            }),
+            Err(ApplicationError::AccountUnderFunded(
+                "BOB".to_string(),
+                100_500
+            ))
+        );
+
+        let receipt = trading_platform
+            .order(Order {
+                price: Price(1_000),
+                amount: Quantity(10),
+                side: Side::Buy,
+                signer: "BOB".to_string(),
+            })
+            .unwrap();
+        assert_eq!(
+            receipt.fees,
+            vec![FillFee {
+                ordinal: 1,
+                maker_fee: Notional(10),
+                taker_fee: Notional(50),
+            }]
+        );
+        assert_eq!(
+            trading_platform.matching_engine.history[1].fees,
+            receipt.fees
+        );
+
+        assert_eq!(trading_platform.accounts.balance_of("ALICE"), Ok(&109_990));
+        assert_eq!(trading_platform.accounts.balance_of("BOB"), Ok(&89_950));
+        assert_eq!(
+            trading_platform
+                .accounts
+                .system_balance(SystemAccount::Fees),
+            60
+        );
+        assert_eq!(trading_platform.accounts.verify(), Ok(()));
+
+        let statement = trading_platform
+            .statement(
+                "BOB",
+                &StatementQuery {
+                    kind: Some(TxKind::Fee),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(statement.entries.len(), 1);
+        assert_eq!(
+            statement.entries[0].tx,
+            Tx::Fee {
+                account: "BOB".to_string(),
+                amount: 50
+            }
+        );
+        assert_eq!(statement.entries[0].ordinal, Some(2));
+    }
+
+    #[test]
+    fn test_TradingPlatform_order_fee_tiers_follow_volume() {
+        let mut trading_platform = TradingPlatform::new();
+        trading_platform.fees = FeeSchedule {
+            maker_bps: 0,
+            taker_bps: 100,
+            tiers: vec![FeeTier {
+                min_volume: Notional(1_000),
+                maker_bps: 0,
+                taker_bps: 10,
+            }],
+        };
+
+        assert!(trading_platform.open_account("ALICE", "owner").is_ok());
+        assert!(trading_platform.open_account("BOB", "owner").is_ok());
+        assert!(trading_platform.deposit("ALICE", 100_000).is_ok());
+        assert!(trading_platform.deposit("BOB", 100_000).is_ok());
+
+        let mut taker_fees = vec![];
+        for _ in 0..2 {
+            trading_platform
+                .order(Order {
+                    price: Price(100),
+                    amount: Quantity(10),
+                    side: Side::Sell,
+                    signer: "ALICE".to_string(),
+                })
+                .unwrap();
+            let receipt = trading_platform
+                .order(Order {
+                    price: Price(100),
+                    amount: Quantity(10),
+                    side: Side::Buy,
+                    signer: "BOB".to_string(),
+                })
+                .unwrap();
+            taker_fees.push(receipt.fees[0].taker_fee);
+        }
+
+        // The first fill moves BOB into the cheaper tier
+        assert_eq!(taker_fees, vec![Notional(10), Notional(1)]);
+    }
+
+    #[test]
+    fn test_TradingPlatform_order_runs_risk_checks() {
+        let mut trading_platform = TradingPlatform::new();
+        trading_platform.risk = RiskEngine::new(RiskConfig {
+            default: RiskLimits {
+                max_open_orders: Some(1),
+                price_collar_bps: Some(1_000),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+
+        assert!(trading_platform.open_account("ALICE", "owner").is_ok());
+        assert!(trading_platform.open_account("BOB", "owner").is_ok());
+        assert!(trading_platform.deposit("ALICE", 10_000).is_ok());
+        assert!(trading_platform.deposit("BOB", 10_000).is_ok());
+
+        trading_platform
+            .order(Order {
+                price: Price(100),
+                amount: Quantity(1),
+                side: Side::Sell,
+                signer: "ALICE".to_string(),
+            })
+            .unwrap();
+        assert_eq!(
+            trading_platform.order(Order {
+                price: Price(100),
+                amount: Quantity(1),
+                side: Side::Sell,
+                signer: "ALICE".to_string(),
+            }),
+            Err(ApplicationError::OpenOrderLimitExceeded(1, 1))
+        );
+        assert_eq!(
+            trading_platform.order(Order {
+                price: Price(150),
+                amount: Quantity(1),
+                side: Side::Buy,
+                signer: "BOB".to_string(),
+            }),
+            Err(ApplicationError::PriceOutsideCollar(150, 90, 110))
+        );
+        trading_platform
+            .order(Order {
+                price: Price(100),
+                amount: Quantity(1),
+                side: Side::Buy,
+                signer: "BOB".to_string(),
+            })
+            .unwrap();
+        assert_eq!(trading_platform.last_trade_price(), Some(Price(100)));
+        assert!(trading_platform.orderbook().is_empty());
+    }
+
+    #[test]
+    fn test_TradingPlatform_order_rate_limit_only_counts_accepted_orders() {
+        let mut trading_platform = TradingPlatform::new();
+        trading_platform.risk = RiskEngine::new(RiskConfig {
+            default: RiskLimits {
+                max_orders_per_window: Some(1),
+                rate_window_millis: 60_000,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        assert!(trading_platform.open_account("ALICE", "owner").is_ok());
+        let bid = |price| Order {
+            price: Price(price),
+            amount: Quantity(1),
+            side: Side::Buy,
+            signer: "ALICE".to_string(),
+        };
+
+        // Bids ALICE can't pay for don't use up her rate
+        for _ in 0..3 {
+            assert_eq!(
+                trading_platform.order(bid(10)),
+                Err(ApplicationError::AccountUnderFunded(
+                    "ALICE".to_string(),
+                    10
+                ))
+            );
+        }
+        assert!(trading_platform.deposit("ALICE", 100).is_ok());
+        assert!(trading_platform.order(bid(10)).is_ok());
+        assert_eq!(
+            trading_platform.order(bid(10)),
+            Err(ApplicationError::RateLimitExceeded("ALICE".to_string(), 1))
+        );
+    }
+
+    #[test]
+    fn test_TradingPlatform_circuit_breaker_halts_market_until_resumed() {
+        let mut trading_platform = TradingPlatform::new();
+        trading_platform.circuit_breaker = CircuitBreaker::new(Some(CircuitBreakerConfig {
+            max_move_bps: 1_000,
+            window_millis: 60_000,
+        }));
+
+        assert!(trading_platform.open_account("ALICE", "owner").is_ok());
+        assert!(trading_platform.open_account("BOB", "owner").is_ok());
+        assert!(trading_platform.deposit("ALICE", 10_000).is_ok());
+        assert!(trading_platform.deposit("BOB", 10_000).is_ok());
+
+        let trade = |trading_platform: &mut TradingPlatform, price| {
+            trading_platform
+                .order(Order {
+                    price: Price(price),
+                    amount: Quantity(1),
+                    side: Side::Sell,
+                    signer: "ALICE".to_string(),
+                })
+                .and_then(|_| {
+                    trading_platform.order(Order {
+                        price: Price(price),
+                        amount: Quantity(1),
+                        side: Side::Buy,
+                        signer: "BOB".to_string(),
+                    })
+                })
+        };
+
+        assert!(trade(&mut trading_platform, 100).is_ok());
+        assert_eq!(trading_platform.market_state(), MarketState::Open);
+
+        // The fill that moves the price too far still happens but halts the market
+        assert_eq!(trade(&mut trading_platform, 120).unwrap().matches.len(), 1);
+        assert_eq!(trading_platform.market_state(), MarketState::Halted);
+        assert_eq!(
+            trade(&mut trading_platform, 120).map(|_| ()),
+            Err(ApplicationError::MarketHalted)
+        );
+
+        assert_eq!(trading_platform.resume(), Ok(MarketState::Open));
+        assert!(trade(&mut trading_platform, 120).is_ok());
+        assert_eq!(trading_platform.market_state(), MarketState::Open);
+    }
+
+    #[test]
+    fn test_TradingPlatform_market_state_changes_only_along_valid_transitions() {
+        let mut trading_platform = TradingPlatform::new();
+        for signer in ["ALICE", "BOB"] {
+            assert!(trading_platform.open_account(signer, "owner").is_ok());
+            assert!(trading_platform.accounts.deposit(signer, 10_000).is_ok());
+        }
+        assert_eq!(
+            trading_platform.resume(),
+            Err(ApplicationError::InvalidMarketTransition(
+                MarketState::Open,
+                MarketState::Open
+            ))
+        );
+
+        // A crossed auction book can only be left by uncrossing it
+        assert_eq!(trading_platform.start_auction(), Ok(MarketState::Auction));
+        for (side, price, signer) in [(Side::Sell, 8, "ALICE"), (Side::Buy, 12, "BOB")] {
+            assert!(trading_platform
+                .order(Order {
+                    price: Price(price),
+                    amount: Quantity(1),
+                    side,
+                    signer: signer.to_string(),
+                })
+                .is_ok());
+        }
+        assert_eq!(
+            trading_platform.resume(),
+            Err(ApplicationError::InvalidMarketTransition(
+                MarketState::Auction,
+                MarketState::Open
+            ))
+        );
+        assert_eq!(
+            trading_platform.halt(),
+            Err(ApplicationError::InvalidMarketTransition(
+                MarketState::Auction,
+                MarketState::Halted
+            ))
+        );
+        assert_eq!(
+            trading_platform.start_auction(),
+            Err(ApplicationError::InvalidMarketTransition(
+                MarketState::Auction,
+                MarketState::Auction
+            ))
+        );
+        assert_eq!(
+            trading_platform.uncross(MarketState::Auction),
+            Err(ApplicationError::InvalidMarketTransition(
+                MarketState::Auction,
+                MarketState::Auction
+            ))
+        );
+        assert_eq!(trading_platform.market_state(), MarketState::Auction);
+        assert_eq!(trading_platform.orderbook().len(), 2);
+
+        assert!(trading_platform.uncross(MarketState::Closed).is_ok());
+        assert!(trading_platform.orderbook().is_empty());
+        assert_eq!(
+            trading_platform.halt(),
+            Err(ApplicationError::InvalidMarketTransition(
+                MarketState::Closed,
+                MarketState::Halted
+            ))
+        );
+
+        // A halted market resumes continuous trading, it can't start an auction
+        assert_eq!(trading_platform.start_auction(), Ok(MarketState::Auction));
+        assert!(trading_platform.uncross(MarketState::Open).is_ok());
+        assert_eq!(trading_platform.halt(), Ok(MarketState::Halted));
+        assert_eq!(
+            trading_platform.start_auction(),
+            Err(ApplicationError::InvalidMarketTransition(
+                MarketState::Halted,
+                MarketState::Auction
+            ))
+        );
+        assert_eq!(trading_platform.resume(), Ok(MarketState::Open));
+    }
+
+    #[test]
+    fn test_TradingPlatform_uncross_settles_auction_fills_at_auction_price() {
+        let mut trading_platform = TradingPlatform::new();
+        trading_platform.fees = FeeSchedule {
+            maker_bps: 100,
+            taker_bps: 200,
+            tiers: vec![],
+        };
+        for signer in ["ALICE", "BOB", "CAROL"] {
+            assert!(trading_platform.open_account(signer, "owner").is_ok());
+            assert!(trading_platform.accounts.deposit(signer, 10_000).is_ok());
+        }
+        assert_eq!(
+            trading_platform.uncross(MarketState::Open),
+            Err(ApplicationError::NoAuction)
+        );
+        assert_eq!(trading_platform.start_auction(), Ok(MarketState::Auction));
+
+        let orders = [
+            (Side::Buy, 102, 10, "ALICE"),
+            (Side::Buy, 100, 10, "ALICE"),
+            (Side::Sell, 99, 5, "BOB"),
+            (Side::Sell, 101, 10, "CAROL"),
+        ];
+        for (side, price, amount, signer) in orders {
+            let receipt = trading_platform
+                .order(Order {
+                    price: Price(price),
+                    amount: Quantity(amount),
+                    side,
+                    signer: signer.to_string(),
+                })
+                .unwrap();
+            assert_eq!(receipt.matches, vec![]);
+        }
+        assert_eq!(trading_platform.accounts.balance_of("ALICE"), Ok(&10_000));
+        assert_eq!(
+            trading_platform.auction_indication(),
+            Some(AuctionIndication {
+                price: Price(101),
+                volume: Quantity(10),
+                buy_surplus: Quantity(0),
+                sell_surplus: Quantity(5),
+            })
+        );
+
+        let result = trading_platform
+            .uncross(MarketState::Open)
+            .unwrap()
+            .unwrap();
+        assert_eq!(result.fills.len(), 2);
+        assert_eq!(trading_platform.market_state(), MarketState::Open);
+        assert_eq!(trading_platform.last_trade_price(), Some(Price(101)));
+
+        // Both sides pay the 1% maker fee on 10 * 101
+        assert_eq!(
+            trading_platform.accounts.balance_of("ALICE"),
+            Ok(&(10_000 - 1010 - 5 - 5))
+        );
+        assert_eq!(
+            trading_platform.accounts.balance_of("BOB"),
+            Ok(&(10_000 + 505 - 5))
+        );
+        assert_eq!(
+            trading_platform.accounts.balance_of("CAROL"),
+            Ok(&(10_000 + 505 - 5))
+        );
+        assert!(trading_platform.trial_balance().balanced);
+
+        // Continuous trading picks up the remaining book
+        let receipt = trading_platform
+            .order(Order {
+                price: Price(101),
+                amount: Quantity(5),
+                side: Side::Buy,
+                signer: "ALICE".to_string(),
+            })
+            .unwrap();
+        assert_eq!(receipt.matches.len(), 1);
+        assert_eq!(receipt.matches[0].signer, "CAROL");
+    }
+
+    #[test]
+    fn test_TradingPlatform_uncross_leaves_auction_untouched_if_a_fill_cannot_settle() {
+        let mut trading_platform = TradingPlatform::new();
+        for signer in ["ALICE", "BOB", "CAROL"] {
+            assert!(trading_platform.open_account(signer, "owner").is_ok());
+            assert!(trading_platform.accounts.deposit(signer, 1_000).is_ok());
+        }
+        assert_eq!(trading_platform.start_auction(), Ok(MarketState::Auction));
+        let orders = [
+            (Side::Buy, 100, 5, "ALICE"),
+            (Side::Buy, 100, 5, "CAROL"),
+            (Side::Sell, 100, 10, "BOB"),
+        ];
+        for (side, price, amount, signer) in orders {
+            assert!(trading_platform
+                .order(Order {
+                    price: Price(price),
+                    amount: Quantity(amount),
+                    side,
+                    signer: signer.to_string(),
+                })
+                .is_ok());
+        }
+
+        // CAROL's funds are gone by the time the auction ends, ALICE's fill comes first but mustn't settle alone
+        assert!(trading_platform.accounts.withdraw("CAROL", 600).is_ok());
+        let book = trading_platform.orderbook();
+        let transactions = trading_platform.transactions.len();
+        let history = trading_platform.matching_engine.history.len();
+        assert_eq!(
+            trading_platform.uncross(MarketState::Open),
+            Err(ApplicationError::AccountUnderFunded(
+                "CAROL".to_string(),
+                500
+            ))
+        );
+        assert_eq!(trading_platform.market_state(), MarketState::Auction);
+        assert_eq!(trading_platform.orderbook(), book);
+        assert_eq!(trading_platform.transactions.len(), transactions);
+        assert_eq!(trading_platform.matching_engine.history.len(), history);
+        assert_eq!(trading_platform.accounts.balance_of("ALICE"), Ok(&1_000));
+        assert_eq!(trading_platform.accounts.balance_of("BOB"), Ok(&1_000));
+
+        assert!(trading_platform.accounts.deposit("CAROL", 600).is_ok());
+        let result = trading_platform
+            .uncross(MarketState::Open)
+            .unwrap()
+            .unwrap();
+        assert_eq!(result.fills.len(), 2);
+        assert_eq!(trading_platform.accounts.balance_of("BOB"), Ok(&2_000));
+        assert!(trading_platform.orderbook().is_empty());
+        assert!(trading_platform.trial_balance().balanced);
+    }
+
+    #[test]
+    fn test_TradingPlatform_cancel_only_removes_own_orders() {
+        let mut trading_platform = TradingPlatform::new();
+        assert!(trading_platform.open_account("ALICE", "owner").is_ok());
+        assert!(trading_platform.accounts.deposit("ALICE", 100).is_ok());
+
+        let receipt = trading_platform
+            .order(Order {
+                price: Price(10),
+                amount: Quantity(5),
+                side: Side::Buy,
+                signer: "ALICE".to_string(),
+            })
+            .unwrap();
+        assert_eq!(trading_platform.account("ALICE").unwrap().reserved, 50);
+
+        assert_eq!(
+            trading_platform.cancel("BOB", receipt.ordinal),
+            Err(ApplicationError::OrderNotFound(receipt.ordinal))
+        );
+        let cancelled = trading_platform.cancel("ALICE", receipt.ordinal).unwrap();
+        assert_eq!(cancelled.remaining, Quantity(5));
+        assert_eq!(
+            trading_platform.cancel("ALICE", receipt.ordinal),
+            Err(ApplicationError::OrderNotFound(receipt.ordinal))
+        );
+        assert!(trading_platform.matching_engine.bids.is_empty());
+        assert_eq!(trading_platform.account("ALICE").unwrap().reserved, 0);
+    }
 }
@@ -0,0 +1,272 @@
+//! Serves the binary order entry protocol of [`octopus_common::wire`] over TCP.
+//!
+//! A connection has to log on as a configured [`Counterparty`] first and may then trade for the accounts it is entitled
+//! to. Each connection's requests are executed one after another through the [`Pipeline`] and answered in order. Fills
+//! of the orders a connection placed are reported to it for as long as it stays connected, whoever caused them.
+
+use std::{collections::BTreeMap, io, sync::Arc};
+
+use octopus_common::{
+    types::Order,
+    wire::{frame_len, RejectCode, Request, Response},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+use crate::{
+    gateway::{self, Counterparty, FillTracker},
+    pipeline::{Caller, Command, Outcome, Pipeline, Snapshot},
+};
+
+/// Accepts connections on `listener` and serves each of them in its own task. Only the `counterparties`, by user, can
+/// log on.
+pub async fn serve(
+    pipeline: Pipeline,
+    listener: TcpListener,
+    counterparties: BTreeMap<String, Counterparty>,
+) {
+    let counterparties = Arc::new(counterparties);
+    gateway::accept(listener, "order entry", move |stream, address| {
+        // Small frames shouldn't wait for more data
+        if let Err(e) = stream.set_nodelay(true) {
+            tracing::warn!(%address, "couldn't disable Nagle's algorithm: {}", e);
+        }
+        Connection::new(pipeline.clone(), Arc::clone(&counterparties), stream).run()
+    })
+    .await
+}
+
+struct Connection {
+    pipeline: Pipeline,
+    counterparties: Arc<BTreeMap<String, Counterparty>>,
+    /// Remote address of the connection
+    address: Option<String>,
+    /// The user the connection logged on as
+    user: Option<String>,
+    stream: TcpStream,
+    /// Bytes received but not decoded yet
+    input: Vec<u8>,
+    /// Frames to send
+    output: Vec<u8>,
+    /// Fills of the connection's orders
+    fills: FillTracker,
+}
+
+impl Connection {
+    fn new(
+        pipeline: Pipeline,
+        counterparties: Arc<BTreeMap<String, Counterparty>>,
+        stream: TcpStream,
+    ) -> Self {
+        let address = stream.peer_addr().ok().map(|address| address.to_string());
+        let pipeline = pipeline.with_caller(Caller::new("wire", address.clone(), None));
+        Connection {
+            fills: FillTracker::new(&pipeline.snapshot()),
+            pipeline,
+            counterparties,
+            address,
+            user: None,
+            stream,
+            input: vec![],
+            output: vec![],
+        }
+    }
+
+    async fn run(mut self) -> io::Result<()> {
+        let mut updates = self.pipeline.updates();
+        let mut chunk = [0u8; 4096];
+        loop {
+            tokio::select! {
+                read = self.stream.read(&mut chunk) => {
+                    let read = read?;
+                    if read == 0 {
+                        return Ok(());
+                    }
+                    self.input.extend_from_slice(&chunk[..read]);
+                    self.handle_input().await?;
+                }
+                changed = updates.changed() => {
+                    // The matching task stopped
+                    if changed.is_err() {
+                        return Ok(());
+                    }
+                    let snapshot = updates.borrow_and_update().clone();
+                    self.collect_fills(&snapshot);
+                    self.flush().await?;
+                }
+            }
+        }
+    }
+
+    /// Executes and answers every complete request that was received
+    async fn handle_input(&mut self) -> io::Result<()> {
+        loop {
+            match Request::decode(&self.input) {
+                Ok(Some((request, len))) => {
+                    self.input.drain(..len);
+                    self.execute(request).await;
+                }
+                Ok(None) => return Ok(()),
+                Err(e) => {
+                    let len = frame_len(&self.input).expect("only complete frames are decoded");
+                    self.input.drain(..len);
+                    let reject = Response::Reject {
+                        request_id: 0,
+                        code: RejectCode::Malformed,
+                        message: e.to_string(),
+                    };
+                    reject.encode(&mut self.output);
+                }
+            }
+            self.flush().await?;
+        }
+    }
+
+    /// Binds the connection to `user` if the `password` is theirs
+    fn logon(&mut self, user: String, password: &str) -> Result<(), String> {
+        if self.user.is_some() {
+            return Err("Already logged on".to_string());
+        }
+        if !self
+            .counterparties
+            .get(&user)
+            .is_some_and(|counterparty| counterparty.accepts(password))
+        {
+            tracing::warn!("refused an order entry logon of {}", user);
+            return Err("Unknown user or wrong password".to_string());
+        }
+        let caller = Caller::new("wire", self.address.clone(), Some(user.clone()));
+        self.pipeline = self.pipeline.with_caller(caller);
+        self.user = Some(user);
+        Ok(())
+    }
+
+    /// Checks that the connection logged on as a user who may trade for `signer`
+    fn authorize(&self, signer: &str) -> Result<(), String> {
+        match &self.user {
+            None => Err("Log on first".to_string()),
+            Some(user) if !gateway::is_entitled(&self.counterparties, user, signer) => {
+                Err(format!("{} may not trade for {}", user, signer))
+            }
+            Some(_) => Ok(()),
+        }
+    }
+
+    async fn execute(&mut self, request: Request) {
+        let request_id = request.request_id();
+        let authorized = match &request {
+            Request::Logon { .. } => Ok(()),
+            Request::NewOrder { signer, .. } | Request::Cancel { signer, .. } => {
+                self.authorize(signer)
+            }
+        };
+        if let Err(message) = authorized {
+            let reject = Response::Reject {
+                request_id,
+                code: RejectCode::Unauthorized,
+                message,
+            };
+            reject.encode(&mut self.output);
+            return;
+        }
+        let result = match request {
+            Request::Logon { user, password, .. } => {
+                match self.logon(user, &password) {
+                    Ok(()) => Response::Ack {
+                        request_id,
+                        ordinal: 0,
+                    },
+                    Err(message) => Response::Reject {
+                        request_id,
+                        code: RejectCode::Unauthorized,
+                        message,
+                    },
+                }
+                .encode(&mut self.output);
+                Ok(())
+            }
+            Request::NewOrder {
+                side,
+                price,
+                quantity,
+                signer,
+                ..
+            } => {
+                let order = Order {
+                    price,
+                    amount: quantity,
+                    side,
+                    signer,
+                };
+                match self.pipeline.execute(Command::Order(order)).await {
+                    Ok(Outcome::Receipt(receipt)) => {
+                        self.fills.track(receipt.ordinal, quantity);
+                        Response::Ack {
+                            request_id,
+                            ordinal: receipt.ordinal,
+                        }
+                        .encode(&mut self.output);
+                        // The order's own fills
+                        let snapshot = self.pipeline.snapshot();
+                        self.collect_fills(&snapshot);
+                        Ok(())
+                    }
+                    Ok(outcome) => unreachable!("orders result in receipts, not {:?}", outcome),
+                    Err(e) => Err(e),
+                }
+            }
+            Request::Cancel {
+                ordinal, signer, ..
+            } => match self
+                .pipeline
+                .execute(Command::Cancel { signer, ordinal })
+                .await
+            {
+                Ok(_) => {
+                    // Fills that happened before the cancel come first
+                    let snapshot = self.pipeline.snapshot();
+                    self.collect_fills(&snapshot);
+                    self.fills.forget(ordinal);
+                    Response::Ack {
+                        request_id,
+                        ordinal,
+                    }
+                    .encode(&mut self.output);
+                    Ok(())
+                }
+                Err(e) => Err(e),
+            },
+        };
+        if let Err(e) = result {
+            let reject = Response::Reject {
+                request_id,
+                code: RejectCode::from(&e),
+                message: e.to_string(),
+            };
+            reject.encode(&mut self.output);
+        }
+    }
+
+    /// Adds a fill for every match of an open order of the connection in the history entries that weren't checked yet
+    fn collect_fills(&mut self, snapshot: &Snapshot) {
+        for fill in self.fills.fills(snapshot) {
+            Response::Fill {
+                ordinal: fill.ordinal,
+                price: fill.price,
+                quantity: fill.quantity,
+                leaves: fill.leaves,
+            }
+            .encode(&mut self.output);
+        }
+    }
+
+    async fn flush(&mut self) -> io::Result<()> {
+        if !self.output.is_empty() {
+            self.stream.write_all(&self.output).await?;
+            self.output.clear();
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,462 @@
+//! Drives the FIX acceptor over TCP with a minimal local initiator
+
+use std::{collections::BTreeMap, net::SocketAddr, time::Duration};
+
+use octopus_web::{
+    fix::{
+        message::{msg_type, tag, DecodeError, Message},
+        Acceptor, SeqStore,
+    },
+    gateway::Counterparty,
+    pipeline::{Command, Outcome, Pipeline, DEFAULT_QUEUE_CAPACITY},
+    trading_platform::TradingPlatform,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    time,
+};
+
+const ACCEPTOR: &str = "OCTOPUS";
+
+/// Longest wait for a message from the acceptor
+const RECV_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The client side of a FIX session
+struct Initiator {
+    stream: TcpStream,
+    buffer: Vec<u8>,
+    comp_id: String,
+    next_seq_num: u64,
+}
+
+impl Initiator {
+    async fn connect(address: SocketAddr, comp_id: &str) -> Self {
+        Initiator {
+            stream: TcpStream::connect(address).await.unwrap(),
+            buffer: vec![],
+            comp_id: comp_id.to_string(),
+            next_seq_num: 1,
+        }
+    }
+
+    /// Sends a message with the next sequence number
+    async fn send(&mut self, message: Message) {
+        let seq_num = self.next_seq_num;
+        self.next_seq_num += 1;
+        self.send_as(message, seq_num).await;
+    }
+
+    /// Sends a message with a standard header and any sequence number
+    async fn send_as(&mut self, message: Message, seq_num: u64) {
+        let mut stamped = Message::new(message.msg_type())
+            .with(tag::SENDER_COMP_ID, &self.comp_id)
+            .with(tag::TARGET_COMP_ID, ACCEPTOR)
+            .with(tag::MSG_SEQ_NUM, seq_num)
+            .with(tag::SENDING_TIME, "20240131-09:30:00.000");
+        for (tag, value) in &message.fields()[1..] {
+            stamped = stamped.with(*tag, value);
+        }
+        self.stream.write_all(&stamped.encode()).await.unwrap();
+    }
+
+    /// The next message, or `None` if the acceptor closed the connection
+    async fn recv(&mut self) -> Option<Message> {
+        loop {
+            match Message::decode(&self.buffer) {
+                Ok(Some((message, len))) => {
+                    self.buffer.drain(..len);
+                    return Some(message);
+                }
+                Ok(None) => {}
+                Err(e) => panic!("the acceptor sent an invalid message: {}", e),
+            }
+            let mut chunk = [0u8; 4096];
+            let read = time::timeout(RECV_TIMEOUT, self.stream.read(&mut chunk))
+                .await
+                .expect("the acceptor should answer")
+                .unwrap_or(0);
+            if read == 0 {
+                return None;
+            }
+            self.buffer.extend_from_slice(&chunk[..read]);
+        }
+    }
+
+    /// The next message apart from heartbeats the acceptor sends on its own, which has to be of `msg_type`
+    async fn expect(&mut self, msg_type: &str) -> Message {
+        loop {
+            let message = self.recv().await.expect("the connection should be open");
+            if message.msg_type() == msg_type::HEARTBEAT && message.get(tag::TEST_REQ_ID).is_none()
+            {
+                continue;
+            }
+            assert_eq!(message.msg_type(), msg_type, "{:?}", message);
+            return message;
+        }
+    }
+
+    /// A Logon with the initiator's password
+    fn logon_message(&self, heartbeat: u64) -> Message {
+        Message::new(msg_type::LOGON)
+            .with(tag::ENCRYPT_METHOD, 0)
+            .with(tag::HEART_BT_INT, heartbeat)
+            .with(tag::USERNAME, &self.comp_id)
+            .with(tag::PASSWORD, password(&self.comp_id))
+    }
+
+    async fn logon(&mut self, heartbeat: u64) -> Message {
+        self.send(self.logon_message(heartbeat)).await;
+        self.expect(msg_type::LOGON).await
+    }
+
+    async fn logout(&mut self) {
+        self.send(Message::new(msg_type::LOGOUT)).await;
+        self.expect(msg_type::LOGOUT).await;
+        assert_eq!(self.recv().await, None);
+    }
+}
+
+fn new_order(cl_ord_id: &str, side: &str, quantity: u64, price: &str) -> Message {
+    Message::new(msg_type::NEW_ORDER_SINGLE)
+        .with(tag::CL_ORD_ID, cl_ord_id)
+        .with(tag::SYMBOL, "OCTO")
+        .with(tag::SIDE, side)
+        .with(tag::ORDER_QTY, quantity)
+        .with(tag::ORD_TYPE, 2)
+        .with(tag::PRICE, price)
+        .with(tag::TRANSACT_TIME, "20240131-09:30:00.000")
+}
+
+fn cancel(cl_ord_id: &str, orig_cl_ord_id: &str) -> Message {
+    Message::new(msg_type::ORDER_CANCEL_REQUEST)
+        .with(tag::ORIG_CL_ORD_ID, orig_cl_ord_id)
+        .with(tag::CL_ORD_ID, cl_ord_id)
+        .with(tag::SYMBOL, "OCTO")
+        .with(tag::SIDE, 2)
+        .with(tag::TRANSACT_TIME, "20240131-09:30:00.000")
+}
+
+/// The password of each counterparty
+fn password(comp_id: &str) -> String {
+    format!("{}-secret", comp_id.to_lowercase())
+}
+
+/// Starts an acceptor for a platform where ALICE and BOB have 1_000.00 each, and BOB may also trade for ALICE
+async fn start(store: SeqStore) -> (Pipeline, SocketAddr) {
+    let pipeline = Pipeline::spawn(TradingPlatform::new(), DEFAULT_QUEUE_CAPACITY);
+    for signer in ["ALICE", "BOB"] {
+        let open = Command::OpenAccount {
+            signer: signer.to_string(),
+            owner: "fix".to_string(),
+        };
+        let deposit = Command::Deposit {
+            signer: signer.to_string(),
+            amount: 100_000,
+        };
+        pipeline.execute(open).await.unwrap();
+        pipeline.execute(deposit).await.unwrap();
+    }
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let counterparty = |comp_id: &str, accounts: &[&str]| {
+        let counterparty = Counterparty {
+            password: password(comp_id),
+            accounts: accounts.iter().map(|a| a.to_string()).collect(),
+        };
+        (comp_id.to_string(), counterparty)
+    };
+    let counterparties =
+        BTreeMap::from([counterparty("ALICE", &[]), counterparty("BOB", &["ALICE"])]);
+    let acceptor =
+        Acceptor::new(pipeline.clone(), ACCEPTOR, store).with_counterparties(counterparties);
+    tokio::spawn(acceptor.serve(listener));
+    (pipeline, address)
+}
+
+#[tokio::test]
+async fn fix_session_logs_on_keeps_alive_and_logs_out() {
+    let (_, address) = start(SeqStore::memory()).await;
+
+    // Only a Logon opens a session
+    let mut stranger = Initiator::connect(address, "ALICE").await;
+    stranger.send(Message::new(msg_type::HEARTBEAT)).await;
+    assert_eq!(stranger.recv().await, None);
+
+    let mut alice = Initiator::connect(address, "ALICE").await;
+    let logon = alice.logon(1).await;
+    assert_eq!(logon.get(tag::HEART_BT_INT), Some("1"));
+    assert_eq!(logon.get(tag::SENDER_COMP_ID), Some(ACCEPTOR));
+    assert_eq!(logon.seq_num(), Some(1));
+
+    // Unknown CompIDs and wrong passwords get a Logout
+    for (comp_id, password) in [("MALLORY", "mallory-secret"), ("BOB", "alice-secret")] {
+        let mut impostor = Initiator::connect(address, comp_id).await;
+        impostor
+            .send(
+                Message::new(msg_type::LOGON)
+                    .with(tag::HEART_BT_INT, 1)
+                    .with(tag::PASSWORD, password),
+            )
+            .await;
+        let logout = impostor.expect(msg_type::LOGOUT).await;
+        assert_eq!(
+            logout.get(tag::TEXT),
+            Some("Unknown SenderCompID or wrong password")
+        );
+        assert_eq!(logout.get(tag::TARGET_COMP_ID), Some(comp_id));
+        assert_eq!(impostor.recv().await, None);
+    }
+    // The Username has to match the SenderCompID
+    let mut impostor = Initiator::connect(address, "BOB").await;
+    let mut logon = impostor.logon_message(1);
+    logon.set(tag::USERNAME, "ALICE");
+    impostor.send(logon).await;
+    impostor.expect(msg_type::LOGOUT).await;
+
+    // A second connection for the same session is turned away
+    let mut twin = Initiator::connect(address, "ALICE").await;
+    twin.send(twin.logon_message(1)).await;
+    assert_eq!(twin.recv().await, None);
+
+    alice
+        .send(Message::new(msg_type::TEST_REQUEST).with(tag::TEST_REQ_ID, "ping"))
+        .await;
+    let heartbeat = alice.expect(msg_type::HEARTBEAT).await;
+    assert_eq!(heartbeat.get(tag::TEST_REQ_ID), Some("ping"));
+
+    // Without traffic the acceptor sends heartbeats, then checks whether the initiator is still there
+    let test_request = loop {
+        let message = alice.recv().await.expect("the session should stay up");
+        match message.msg_type() {
+            msg_type::HEARTBEAT => continue,
+            msg_type::TEST_REQUEST => break message,
+            _ => panic!("unexpected {:?}", message),
+        }
+    };
+    let id = test_request.get(tag::TEST_REQ_ID).unwrap();
+    alice
+        .send(Message::new(msg_type::HEARTBEAT).with(tag::TEST_REQ_ID, id))
+        .await;
+
+    // Unknown messages are rejected without ending the session
+    alice.send(Message::new("ZZ")).await;
+    let reject = alice.expect(msg_type::REJECT).await;
+    assert_eq!(reject.get(tag::SESSION_REJECT_REASON), Some("11"));
+
+    alice.logout().await;
+}
+
+#[tokio::test]
+async fn fix_orders_are_filled_reported_and_cancelled() {
+    let (pipeline, address) = start(SeqStore::memory()).await;
+    let mut alice = Initiator::connect(address, "ALICE").await;
+    let mut bob = Initiator::connect(address, "BOB").await;
+    alice.logon(30).await;
+    bob.logon(30).await;
+
+    alice.send(new_order("A1", "2", 10, "10.50")).await;
+    let new = alice.expect(msg_type::EXECUTION_REPORT).await;
+    assert_eq!(new.get(tag::EXEC_TYPE), Some("0"));
+    assert_eq!(new.get(tag::ORD_STATUS), Some("0"));
+    assert_eq!(new.get(tag::LEAVES_QTY), Some("10"));
+    let order_id = new.get(tag::ORDER_ID).unwrap().to_string();
+
+    // BOB takes part of ALICE's order, both sides get a fill
+    bob.send(new_order("B1", "1", 4, "11")).await;
+    let new = bob.expect(msg_type::EXECUTION_REPORT).await;
+    assert_eq!(new.get(tag::EXEC_TYPE), Some("0"));
+    let fill = bob.expect(msg_type::EXECUTION_REPORT).await;
+    assert_eq!(fill.get(tag::EXEC_TYPE), Some("F"));
+    assert_eq!(fill.get(tag::ORD_STATUS), Some("2"));
+    assert_eq!(fill.get(tag::LAST_PX), Some("10.50"));
+    assert_eq!(fill.get(tag::LAST_QTY), Some("4"));
+    assert_eq!(fill.get(tag::AVG_PX), Some("10.50"));
+
+    let fill = alice.expect(msg_type::EXECUTION_REPORT).await;
+    assert_eq!(fill.get(tag::CL_ORD_ID), Some("A1"));
+    assert_eq!(fill.get(tag::EXEC_TYPE), Some("F"));
+    assert_eq!(fill.get(tag::ORD_STATUS), Some("1"));
+    assert_eq!(fill.get(tag::CUM_QTY), Some("4"));
+    assert_eq!(fill.get(tag::LEAVES_QTY), Some("6"));
+
+    let account = Command::Account {
+        signer: "ALICE".to_string(),
+    };
+    match pipeline.execute(account).await {
+        Ok(Outcome::AccountView(view)) => assert_eq!(view.available, 104_200),
+        outcome => panic!("unexpected {:?}", outcome),
+    }
+
+    alice.send(cancel("A2", "A1")).await;
+    let cancelled = alice.expect(msg_type::EXECUTION_REPORT).await;
+    assert_eq!(cancelled.get(tag::EXEC_TYPE), Some("4"));
+    assert_eq!(cancelled.get(tag::ORD_STATUS), Some("4"));
+    assert_eq!(cancelled.get(tag::CL_ORD_ID), Some("A2"));
+    assert_eq!(cancelled.get(tag::ORIG_CL_ORD_ID), Some("A1"));
+    assert_eq!(cancelled.get(tag::ORDER_ID), Some(order_id.as_str()));
+    assert_eq!(cancelled.get(tag::LEAVES_QTY), Some("0"));
+    assert!(pipeline.snapshot().orderbook.is_empty());
+
+    alice.send(cancel("A3", "A1")).await;
+    let too_late = alice.expect(msg_type::ORDER_CANCEL_REJECT).await;
+    assert_eq!(too_late.get(tag::CXL_REJ_REASON), Some("0"));
+    alice.send(cancel("A4", "nope")).await;
+    let unknown = alice.expect(msg_type::ORDER_CANCEL_REJECT).await;
+    assert_eq!(unknown.get(tag::CXL_REJ_REASON), Some("1"));
+
+    // Orders the platform or the gateway don't accept are rejected with a reason
+    let with = |mut order: Message, tag: u32, value: &str| {
+        order.set(tag, value);
+        order
+    };
+    let rejections = [
+        (new_order("B1", "1", 1, "10"), "6"),
+        (with(new_order("B2", "1", 1, "10"), tag::SYMBOL, "XYZ"), "1"),
+        (new_order("B3", "1", 1_000, "10"), "3"),
+        (
+            with(new_order("B4", "1", 1, "10"), tag::ORD_TYPE, "1"),
+            "11",
+        ),
+        (
+            with(new_order("B5", "1", 1, "10"), tag::ACCOUNT, "NOBODY"),
+            "15",
+        ),
+    ];
+    for (order, reason) in rejections {
+        bob.send(order).await;
+        let rejected = bob.expect(msg_type::EXECUTION_REPORT).await;
+        assert_eq!(rejected.get(tag::EXEC_TYPE), Some("8"));
+        assert_eq!(rejected.get(tag::ORD_STATUS), Some("8"));
+        assert_eq!(
+            rejected.get(tag::ORD_REJ_REASON),
+            Some(reason),
+            "{:?}",
+            rejected
+        );
+    }
+
+    // Only the accounts a session is entitled to can be named
+    alice
+        .send(with(new_order("A5", "1", 1, "10"), tag::ACCOUNT, "BOB"))
+        .await;
+    let rejected = alice.expect(msg_type::EXECUTION_REPORT).await;
+    assert_eq!(rejected.get(tag::EXEC_TYPE), Some("8"));
+    assert_eq!(rejected.get(tag::ORD_REJ_REASON), Some("15"));
+    assert_eq!(rejected.get(tag::TEXT), Some("Not entitled to account BOB"));
+    bob.send(with(new_order("B7", "1", 1, "10"), tag::ACCOUNT, "ALICE"))
+        .await;
+    let new = bob.expect(msg_type::EXECUTION_REPORT).await;
+    assert_eq!(new.get(tag::EXEC_TYPE), Some("0"));
+    let snapshot = pipeline.snapshot();
+    let signers: Vec<&str> = snapshot
+        .orderbook
+        .iter()
+        .map(|o| o.signer.as_str())
+        .collect();
+    assert_eq!(signers, vec!["ALICE"]);
+
+    // Malformed fields are rejected at the session level
+    bob.send(new_order("B6", "1", 1, "ten")).await;
+    let reject = bob.expect(msg_type::REJECT).await;
+    assert_eq!(reject.get(tag::REF_TAG_ID), Some("44"));
+
+    alice.logout().await;
+    bob.logout().await;
+}
+
+#[tokio::test]
+async fn fix_fills_while_disconnected_are_reported_on_logon() {
+    let (_, address) = start(SeqStore::memory()).await;
+    let mut alice = Initiator::connect(address, "ALICE").await;
+    alice.logon(30).await;
+    alice.send(new_order("A1", "2", 5, "10")).await;
+    alice.expect(msg_type::EXECUTION_REPORT).await;
+    alice.logout().await;
+
+    let mut bob = Initiator::connect(address, "BOB").await;
+    bob.logon(30).await;
+    bob.send(new_order("B1", "1", 5, "10")).await;
+    bob.expect(msg_type::EXECUTION_REPORT).await;
+    bob.expect(msg_type::EXECUTION_REPORT).await;
+
+    let mut alice = Initiator::connect(address, "ALICE").await;
+    alice.next_seq_num = 4;
+    let logon = alice.logon(30).await;
+    assert_eq!(logon.seq_num(), Some(4));
+    let fill = alice.expect(msg_type::EXECUTION_REPORT).await;
+    assert_eq!(fill.get(tag::CL_ORD_ID), Some("A1"));
+    assert_eq!(fill.get(tag::ORD_STATUS), Some("2"));
+}
+
+#[tokio::test]
+async fn fix_sequence_numbers_are_checked_and_persisted() {
+    let dir = std::env::temp_dir().join(format!("octopus-fix-{}", std::process::id()));
+    let (_, address) = start(SeqStore::directory(&dir).unwrap()).await;
+
+    let mut alice = Initiator::connect(address, "ALICE").await;
+    alice.logon(30).await;
+    alice
+        .send(Message::new(msg_type::TEST_REQUEST).with(tag::TEST_REQ_ID, "1"))
+        .await;
+    alice.expect(msg_type::HEARTBEAT).await;
+
+    // Messages with a wrong checksum are ignored
+    let mut corrupt = Message::new(msg_type::TEST_REQUEST)
+        .with(tag::SENDER_COMP_ID, "ALICE")
+        .encode();
+    let len = corrupt.len();
+    corrupt[len - 2] = if corrupt[len - 2] == b'0' { b'1' } else { b'0' };
+    assert!(matches!(
+        Message::decode(&corrupt),
+        Err(DecodeError::CheckSum { .. })
+    ));
+    alice.stream.write_all(&corrupt).await.unwrap();
+
+    // A gap is answered with a ResendRequest, and the gap can be filled
+    alice.send_as(Message::new(msg_type::HEARTBEAT), 5).await;
+    let resend = alice.expect(msg_type::RESEND_REQUEST).await;
+    assert_eq!(resend.get(tag::BEGIN_SEQ_NO), Some("3"));
+    assert_eq!(resend.get(tag::END_SEQ_NO), Some("0"));
+    let gap_fill = Message::new(msg_type::SEQUENCE_RESET)
+        .with(tag::GAP_FILL_FLAG, "Y")
+        .with(tag::NEW_SEQ_NO, 6);
+    alice.send_as(gap_fill, 3).await;
+    alice.next_seq_num = 6;
+
+    // The acceptor fills gaps in what it sent the same way
+    alice
+        .send(
+            Message::new(msg_type::RESEND_REQUEST)
+                .with(tag::BEGIN_SEQ_NO, 2)
+                .with(tag::END_SEQ_NO, 0),
+        )
+        .await;
+    let gap_fill = alice.expect(msg_type::SEQUENCE_RESET).await;
+    assert_eq!(gap_fill.seq_num(), Some(2));
+    assert_eq!(gap_fill.get(tag::GAP_FILL_FLAG), Some("Y"));
+    assert_eq!(gap_fill.get(tag::POSS_DUP_FLAG), Some("Y"));
+    assert_eq!(gap_fill.get(tag::NEW_SEQ_NO), Some("4"));
+    alice.logout().await;
+
+    // After a restart of the acceptor the session continues with the stored numbers
+    let (_, address) = start(SeqStore::directory(&dir).unwrap()).await;
+    let mut alice = Initiator::connect(address, "ALICE").await;
+    alice.next_seq_num = 7;
+    alice.send(alice.logon_message(30)).await;
+    let logout = alice.expect(msg_type::LOGOUT).await;
+    assert_eq!(
+        logout.get(tag::TEXT),
+        Some("MsgSeqNum too low, expecting 8 but received 7")
+    );
+    assert_eq!(logout.seq_num(), Some(5));
+    assert_eq!(alice.recv().await, None);
+
+    let mut alice = Initiator::connect(address, "ALICE").await;
+    alice.next_seq_num = 8;
+    let logon = alice.logon(30).await;
+    assert_eq!(logon.seq_num(), Some(6));
+    alice.logout().await;
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
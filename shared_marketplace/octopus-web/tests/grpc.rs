@@ -0,0 +1,229 @@
+//! Drives the gRPC interface with the generated client
+
+use std::time::Duration;
+
+use octopus_web::{
+    grpc::{
+        self,
+        proto::{
+            octopus_client::OctopusClient, AccountRequest, AccountStatus, AmountRequest,
+            CancelRequest, HistoryRequest, MarketData, MarketDataRequest, OpenAccountRequest,
+            OrderBookRequest, OrderRequest, PriceLevel, SendRequest, Side, StatementRequest, Trade,
+            TransactionKind,
+        },
+    },
+    pipeline::{Pipeline, DEFAULT_QUEUE_CAPACITY},
+    trading_platform::TradingPlatform,
+};
+use tokio::{net::TcpListener, time};
+use tonic::{transport::Channel, Code, Streaming};
+
+async fn start() -> OctopusClient<Channel> {
+    let pipeline = Pipeline::spawn(TradingPlatform::new(), DEFAULT_QUEUE_CAPACITY);
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(grpc::serve(pipeline, listener));
+    let mut client = OctopusClient::connect(format!("http://{}", address))
+        .await
+        .unwrap();
+    for signer in ["ALICE", "BOB"] {
+        let open = OpenAccountRequest {
+            signer: signer.to_string(),
+            owner: "grpc".to_string(),
+        };
+        let deposit = AmountRequest {
+            signer: signer.to_string(),
+            amount: 10_000,
+        };
+        client.open_account(open).await.unwrap();
+        client.deposit(deposit).await.unwrap();
+    }
+    client
+}
+
+fn order(side: Side, price: u64, amount: u64, signer: &str) -> OrderRequest {
+    OrderRequest {
+        price,
+        amount,
+        side: side as i32,
+        signer: signer.to_string(),
+    }
+}
+
+async fn next(stream: &mut Streaming<MarketData>) -> MarketData {
+    time::timeout(Duration::from_secs(5), stream.message())
+        .await
+        .expect("the server should publish market data")
+        .unwrap()
+        .expect("the stream should stay open")
+}
+
+#[tokio::test]
+async fn grpc_accounts_can_be_managed() {
+    let mut client = start().await;
+
+    let sent = client
+        .send(SendRequest {
+            sender: "ALICE".to_string(),
+            recipient: "BOB".to_string(),
+            amount: 2_500,
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(sent.withdraw.unwrap().kind(), TransactionKind::Withdraw);
+    assert_eq!(sent.deposit.unwrap().amount, 2_500);
+
+    let bob = AccountRequest {
+        signer: "BOB".to_string(),
+    };
+    let view = client.get_account(bob.clone()).await.unwrap().into_inner();
+    assert_eq!(view.available, 12_500);
+    assert_eq!(view.metadata.unwrap().owner, "grpc");
+
+    let statement = client
+        .get_statement(StatementRequest {
+            signer: "BOB".to_string(),
+            kind: TransactionKind::Deposit as i32,
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(statement.total, 2);
+    assert_eq!(statement.entries[1].balance, 12_500);
+
+    let frozen = client
+        .freeze_account(bob.clone())
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(frozen.status(), AccountStatus::Frozen);
+    let withdraw = AmountRequest {
+        signer: "BOB".to_string(),
+        amount: 1,
+    };
+    let error = client.withdraw(withdraw).await.unwrap_err();
+    assert_eq!(error.code(), Code::PermissionDenied);
+
+    let error = client.get_account(AccountRequest {
+        signer: "NOBODY".to_string(),
+    });
+    assert_eq!(error.await.unwrap_err().code(), Code::NotFound);
+}
+
+#[tokio::test]
+async fn grpc_orders_are_matched() {
+    let mut client = start().await;
+
+    let ask = client
+        .place_order(order(Side::Sell, 100, 5, "ALICE"))
+        .await
+        .unwrap()
+        .into_inner();
+    client
+        .place_order(order(Side::Buy, 90, 1, "BOB"))
+        .await
+        .unwrap();
+    let book = client
+        .get_order_book(OrderBookRequest {})
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(book.asks.len(), 1);
+    assert_eq!(book.bids[0].price, 90);
+
+    let bid = client
+        .place_order(order(Side::Buy, 100, 2, "BOB"))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(bid.matches.len(), 1);
+    assert_eq!(bid.matches[0].ordinal, ask.ordinal);
+    assert_eq!(bid.matches[0].amount, 2);
+
+    let history = client
+        .get_history(HistoryRequest {
+            offset: 2,
+            limit: Some(5),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(history.total, 3);
+    assert_eq!(history.receipts, vec![bid.clone()]);
+
+    let cancelled = client
+        .cancel_order(CancelRequest {
+            signer: "ALICE".to_string(),
+            ordinal: ask.ordinal,
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(cancelled.remaining, 3);
+
+    let error = client
+        .place_order(order(Side::Unspecified, 100, 1, "BOB"))
+        .await
+        .unwrap_err();
+    assert_eq!(error.code(), Code::InvalidArgument);
+    let error = client
+        .place_order(order(Side::Buy, 100, 1_000, "BOB"))
+        .await
+        .unwrap_err();
+    assert_eq!(error.code(), Code::FailedPrecondition);
+}
+
+#[tokio::test]
+async fn grpc_market_data_is_streamed() {
+    let mut client = start().await;
+    client
+        .place_order(order(Side::Sell, 110, 4, "ALICE"))
+        .await
+        .unwrap();
+
+    let mut stream = client
+        .stream_market_data(MarketDataRequest { depth: 1 })
+        .await
+        .unwrap()
+        .into_inner();
+    let current = next(&mut stream).await;
+    assert_eq!(
+        current.asks,
+        vec![PriceLevel {
+            price: 110,
+            quantity: 4,
+            orders: 1
+        }]
+    );
+    assert!(current.trades.is_empty());
+
+    // Only the best level of each side is published
+    client
+        .place_order(order(Side::Sell, 120, 1, "ALICE"))
+        .await
+        .unwrap();
+    client
+        .place_order(order(Side::Buy, 110, 3, "BOB"))
+        .await
+        .unwrap();
+    let mut trades = vec![];
+    let mut latest = current;
+    while trades.is_empty() {
+        latest = next(&mut stream).await;
+        trades.extend(latest.trades.clone());
+    }
+    assert_eq!(
+        trades,
+        vec![Trade {
+            ordinal: 3,
+            maker_ordinal: 1,
+            price: 110,
+            quantity: 3,
+        }]
+    );
+    assert_eq!(latest.asks[0].quantity, 1);
+    assert_eq!(latest.asks.len(), 1);
+    assert!(latest.bids.is_empty());
+}
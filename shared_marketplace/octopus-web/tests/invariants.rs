@@ -0,0 +1,247 @@
+//! Property-based tests: random sequences of deposits, orders and cancels are applied to a [`TradingPlatform`] and the
+//! invariants of matching and accounting are checked after every step.
+
+use std::collections::BTreeMap;
+
+use octopus_common::{
+    fees::FeeSchedule,
+    ledger::SystemAccount,
+    money::{Price, Quantity},
+    types::{Order, PartialOrder, Receipt, Side},
+};
+use octopus_web::trading_platform::TradingPlatform;
+use proptest::prelude::*;
+
+const SIGNERS: [&str; 4] = ["ALICE", "BOB", "CAROL", "DAVE"];
+
+#[derive(Debug, Clone)]
+enum Op {
+    Deposit {
+        signer: usize,
+        amount: u64,
+    },
+    Order {
+        signer: usize,
+        side: Side,
+        price: u64,
+        amount: u64,
+    },
+    /// Cancels the `pick`th order the model knows about, which may be filled or belong to someone else
+    Cancel {
+        signer: usize,
+        pick: usize,
+    },
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    let signer = 0..SIGNERS.len();
+    prop_oneof![
+        1 => (signer.clone(), 1..5_000u64).prop_map(|(signer, amount)| Op::Deposit { signer, amount }),
+        4 => (signer.clone(), any::<bool>(), 1..=20u64, 1..=10u64).prop_map(|(signer, buy, price, amount)| {
+            let side = if buy { Side::Buy } else { Side::Sell };
+            Op::Order { signer, side, price, amount }
+        }),
+        1 => (signer, any::<usize>()).prop_map(|(signer, pick)| Op::Cancel { signer, pick }),
+    ]
+}
+
+/// What happened to an accepted order according to the receipts
+#[derive(Debug, Default)]
+struct Tracked {
+    signer: String,
+    submitted: u64,
+    filled: u64,
+    cancelled: u64,
+}
+
+/// Expected state, built from the inputs and the platform's responses only
+#[derive(Debug, Default)]
+struct Model {
+    deposited: u64,
+    orders: BTreeMap<u64, Tracked>,
+    last_ordinal: u64,
+}
+
+impl Model {
+    /// Tracks an accepted order and the fills of the resting orders it matched
+    fn record(
+        &mut self,
+        signer: &str,
+        amount: u64,
+        receipt: &Receipt,
+    ) -> Result<(), TestCaseError> {
+        prop_assert!(receipt.ordinal > self.last_ordinal);
+        self.last_ordinal = receipt.ordinal;
+        let mut taken = 0;
+        for m in &receipt.matches {
+            prop_assert!(m.amount > Quantity(0));
+            prop_assert_ne!(&m.signer, signer, "self-match");
+            let maker = self.orders.get_mut(&m.ordinal);
+            let maker = maker.ok_or_else(|| TestCaseError::fail("matched an unknown order"))?;
+            maker.filled += m.amount.0;
+            taken += m.amount.0;
+        }
+        let tracked = Tracked {
+            signer: signer.to_string(),
+            submitted: amount,
+            filled: taken,
+            cancelled: 0,
+        };
+        self.orders.insert(receipt.ordinal, tracked);
+        Ok(())
+    }
+}
+
+/// Everything a rejected operation must leave untouched
+#[derive(Debug, PartialEq)]
+struct Observed {
+    book: Vec<PartialOrder>,
+    balances: Vec<u64>,
+    history: usize,
+    transactions: usize,
+}
+
+fn observe(platform: &TradingPlatform) -> Observed {
+    Observed {
+        book: platform.orderbook(),
+        balances: SIGNERS
+            .iter()
+            .map(|s| *platform.balance_of(s).unwrap())
+            .collect(),
+        history: platform.matching_engine.history.len(),
+        transactions: platform.transactions.len(),
+    }
+}
+
+fn platform(fees: FeeSchedule) -> TradingPlatform {
+    let mut platform = TradingPlatform::new();
+    platform.fees = fees;
+    for signer in SIGNERS {
+        platform.open_account(signer, "owner").unwrap();
+    }
+    platform
+}
+
+fn apply(platform: &mut TradingPlatform, model: &mut Model, op: Op) -> Result<(), TestCaseError> {
+    let before = observe(platform);
+    let result = match op {
+        Op::Deposit { signer, amount } => platform
+            .deposit(SIGNERS[signer], amount)
+            .map(|_| model.deposited += amount),
+        Op::Order {
+            signer,
+            side,
+            price,
+            amount,
+        } => {
+            let order = Order {
+                price: Price(price),
+                amount: Quantity(amount),
+                side,
+                signer: SIGNERS[signer].to_string(),
+            };
+            match platform.order(order) {
+                Ok(receipt) => {
+                    model.record(SIGNERS[signer], amount, &receipt)?;
+                    Ok(())
+                }
+                Err(e) => Err(e),
+            }
+        }
+        Op::Cancel { signer, pick } => {
+            let ordinal = match model.orders.len() {
+                0 => 1,
+                n => *model.orders.keys().nth(pick % n).expect("within bounds"),
+            };
+            platform.cancel(SIGNERS[signer], ordinal).map(|order| {
+                if let Some(tracked) = model.orders.get_mut(&ordinal) {
+                    tracked.cancelled += order.remaining.0;
+                }
+            })
+        }
+    };
+    if let Err(e) = result {
+        // Rejected operations have no effect at all
+        prop_assert_eq!(observe(platform), before, "{:?} changed the state", e);
+    }
+    check(platform, model)
+}
+
+fn check(platform: &TradingPlatform, model: &Model) -> Result<(), TestCaseError> {
+    // Currency is conserved: whatever was deposited is held by the signers or was collected as fees
+    prop_assert!(platform.trial_balance().balanced);
+    let held: u64 = SIGNERS
+        .iter()
+        .map(|s| *platform.balance_of(s).unwrap())
+        .sum();
+    let fees = platform.accounts.system_balance(SystemAccount::Fees);
+    prop_assert_eq!(held as i128 + fees, model.deposited as i128);
+
+    // The book is never crossed between different signers. A signer's own bid and ask may cross, which is the
+    // self-match prevention rule documented on `MatchingEngine::process`, not a matching failure.
+    let book = platform.orderbook();
+    let (bids, asks): (Vec<&PartialOrder>, Vec<&PartialOrder>) =
+        book.iter().partition(|o| o.side == Side::Buy);
+    for bid in &bids {
+        for ask in asks.iter().filter(|a| a.price <= bid.price) {
+            prop_assert_eq!(
+                &bid.signer,
+                &ask.signer,
+                "crossed book: {:?} / {:?}",
+                bid,
+                ask
+            );
+        }
+    }
+
+    // Resting orders have a positive remainder, and every order's quantity is accounted for
+    let mut resting = BTreeMap::new();
+    for order in &book {
+        prop_assert!(order.remaining > Quantity(0));
+        prop_assert!(order.remaining <= order.amount);
+        resting.insert(order.ordinal, order);
+    }
+    for (ordinal, tracked) in &model.orders {
+        let remaining = resting.get(ordinal).map_or(0, |o| o.remaining.0);
+        prop_assert_eq!(
+            tracked.filled + tracked.cancelled + remaining,
+            tracked.submitted,
+            "order {} of {}",
+            ordinal,
+            tracked.signer
+        );
+    }
+
+    // Ordinals in the history are strictly increasing
+    let history = &platform.matching_engine.history;
+    prop_assert!(history.windows(2).all(|w| w[0].ordinal < w[1].ordinal));
+    Ok(())
+}
+
+fn run(fees: FeeSchedule, ops: Vec<Op>) -> Result<(), TestCaseError> {
+    let mut platform = platform(fees);
+    let mut model = Model::default();
+    for op in ops {
+        apply(&mut platform, &mut model, op)?;
+    }
+    Ok(())
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(512))]
+
+    #[test]
+    fn invariants_hold_without_fees(ops in prop::collection::vec(op(), 1..120)) {
+        run(FeeSchedule::default(), ops)?;
+    }
+
+    #[test]
+    fn invariants_hold_with_fees(ops in prop::collection::vec(op(), 1..120)) {
+        let fees = FeeSchedule {
+            maker_bps: 10,
+            taker_bps: 30,
+            tiers: vec![],
+        };
+        run(fees, ops)?;
+    }
+}
@@ -0,0 +1,103 @@
+//! Scrapes the metrics of a running HTTP server after trading on it
+
+use std::net::SocketAddr;
+
+use octopus_web::{
+    api,
+    pipeline::{Pipeline, DEFAULT_QUEUE_CAPACITY},
+    trading_platform::TradingPlatform,
+};
+use serde_json::{json, Value};
+
+async fn post(client: &reqwest::Client, url: String, body: Value) -> u16 {
+    client
+        .post(url)
+        .json(&body)
+        .send()
+        .await
+        .unwrap()
+        .status()
+        .as_u16()
+}
+
+#[tokio::test]
+async fn metrics_count_orders_matches_and_requests() {
+    let pipeline = Pipeline::spawn(TradingPlatform::new(), DEFAULT_QUEUE_CAPACITY);
+    let (address, server) =
+        warp::serve(api::routes(pipeline)).bind_ephemeral(SocketAddr::from(([127, 0, 0, 1], 0)));
+    tokio::spawn(server);
+    let client = reqwest::Client::new();
+    let base = format!("http://{}", address);
+
+    for signer in ["ALICE", "BOB"] {
+        let open = json!({ "signer": signer, "owner": "metrics" });
+        let deposit = json!({ "signer": signer, "amount": "100.00" });
+        assert_eq!(
+            post(&client, format!("{}/v1/account/open", base), open).await,
+            200
+        );
+        assert_eq!(
+            post(&client, format!("{}/v1/account/deposit", base), deposit).await,
+            200
+        );
+    }
+    let orders = [
+        (
+            json!({ "price": "1.00", "amount": 5, "side": "Sell", "signer": "ALICE" }),
+            200,
+        ),
+        (
+            json!({ "price": "1.00", "amount": 2, "side": "Buy", "signer": "BOB" }),
+            200,
+        ),
+        (
+            json!({ "price": "0.90", "amount": 1, "side": "Buy", "signer": "BOB" }),
+            200,
+        ),
+        (
+            json!({ "price": "1.00", "amount": 1_000, "side": "Buy", "signer": "BOB" }),
+            422,
+        ),
+    ];
+    for (order, status) in orders {
+        assert_eq!(
+            post(&client, format!("{}/v1/order", base), order).await,
+            status
+        );
+    }
+    let account = client
+        .get(format!("{}/v1/account/ALICE", base))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(account.status().as_u16(), 200);
+
+    let response = client
+        .get(format!("{}/metrics", base))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.headers()["content-type"]
+        .to_str()
+        .unwrap()
+        .starts_with("text/plain"));
+    let text = response.text().await.unwrap();
+    for line in [
+        "octopus_orders_accepted_total{market=\"OCTO\"} 3",
+        "octopus_orders_rejected_total{market=\"OCTO\",reason=\"ACCOUNT_UNDER_FUNDED\"} 1",
+        "octopus_matches_total{market=\"OCTO\"} 1",
+        "octopus_traded_quantity_total{market=\"OCTO\"} 2",
+        "octopus_traded_notional_total{market=\"OCTO\"} 200",
+        "octopus_book_quantity{market=\"OCTO\",side=\"sell\"} 3",
+        "octopus_book_levels{market=\"OCTO\",side=\"buy\"} 1",
+        "octopus_command_queue_depth 0",
+        "octopus_command_apply_seconds_count{command=\"order\"} 4",
+        "octopus_http_request_duration_seconds_count{method=\"POST\",route=\"/v1/order\",status=\"200\"} 3",
+        "octopus_http_request_duration_seconds_count{method=\"GET\",route=\"/v1/account/{signer}\",status=\"200\"} 1",
+    ] {
+        assert!(text.contains(line), "{} is missing from\n{}", line, text);
+    }
+    // Signers never end up in labels
+    assert!(!text.contains("ALICE"));
+}
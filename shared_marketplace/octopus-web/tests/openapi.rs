@@ -0,0 +1,343 @@
+//! Checks the running HTTP server against the OpenAPI document it serves
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    net::SocketAddr,
+};
+
+use octopus_web::{
+    api::{self, Operator},
+    pipeline::{Pipeline, DEFAULT_QUEUE_CAPACITY},
+    trading_platform::TradingPlatform,
+};
+use reqwest::Method;
+use serde_json::{json, Value};
+
+struct Contract {
+    client: reqwest::Client,
+    base: String,
+    doc: Value,
+    /// Name and password of the operator sending the requests, if any
+    operator: Option<(String, String)>,
+    /// Operations that were called, as (method, path template)
+    covered: BTreeSet<(String, String)>,
+}
+
+impl Contract {
+    /// The public and the admin routes of a fresh platform, each on an ephemeral port. Requests to the admin routes carry
+    /// the credentials of an operator.
+    async fn start() -> (Self, Self) {
+        let pipeline = Pipeline::spawn(TradingPlatform::new(), DEFAULT_QUEUE_CAPACITY);
+        let (address, server) = warp::serve(api::routes(pipeline.clone()))
+            .bind_ephemeral(SocketAddr::from(([127, 0, 0, 1], 0)));
+        tokio::spawn(server);
+        let operators = BTreeMap::from([(
+            "ops".to_string(),
+            Operator {
+                password: "0p3rate".to_string(),
+            },
+        )]);
+        let (admin_address, server) = warp::serve(api::admin_routes(pipeline, operators))
+            .bind_ephemeral(SocketAddr::from(([127, 0, 0, 1], 0)));
+        tokio::spawn(server);
+        let operator = ("ops".to_string(), "0p3rate".to_string());
+        (
+            Contract::connect(address, None).await,
+            Contract::connect(admin_address, Some(operator)).await,
+        )
+    }
+
+    async fn connect(address: SocketAddr, operator: Option<(String, String)>) -> Self {
+        let client = reqwest::Client::new();
+        let base = format!("http://{}", address);
+        let doc = client
+            .get(format!("{}/openapi.json", base))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        Contract {
+            client,
+            base,
+            doc,
+            operator,
+            covered: BTreeSet::new(),
+        }
+    }
+
+    /// Checks `instance` against `schema`, resolving references into the document's components
+    fn matches(&self, schema: &Value, instance: &Value) -> Result<(), String> {
+        let mut schema = schema.clone();
+        schema["components"] = self.doc["components"].clone();
+        jsonschema::validate(&schema, instance).map_err(|e| e.to_string())
+    }
+
+    /// Calls the operation at `template` with the concrete `path` and checks the request and the response against its
+    /// description. Requests with a body that doesn't match the schema have to be rejected with a 400. Returns the status
+    /// and the response body.
+    async fn call(
+        &mut self,
+        method: Method,
+        template: &str,
+        path: &str,
+        body: Option<Value>,
+    ) -> (u16, Value) {
+        let key = method.as_str().to_lowercase();
+        let operation = self.doc["paths"][template][&key].clone();
+        assert!(
+            operation.is_object(),
+            "{} {} is not documented",
+            method,
+            template
+        );
+        self.covered.insert((key, template.to_string()));
+
+        let mut request = self
+            .client
+            .request(method.clone(), format!("{}{}", self.base, path));
+        if let Some((name, password)) = &self.operator {
+            request = request.basic_auth(name, Some(password));
+        }
+        let mut body_valid = true;
+        if let Some(body) = &body {
+            let schema = &operation["requestBody"]["content"]["application/json"]["schema"];
+            assert!(
+                schema.is_object(),
+                "{} {} has no request body",
+                method,
+                template
+            );
+            body_valid = self.matches(schema, body).is_ok();
+            request = request.json(body);
+        }
+        let response = request.send().await.unwrap();
+        let status = response.status().as_u16();
+        let content: Value = response.json().await.unwrap();
+        if !body_valid {
+            assert_eq!(
+                status,
+                400,
+                "{} {} accepted {}",
+                method,
+                path,
+                body.unwrap()
+            );
+        }
+
+        let documented = &operation["responses"][status.to_string()];
+        assert!(
+            documented.is_object(),
+            "{} {} answered with the undocumented status {}: {}",
+            method,
+            path,
+            status,
+            content
+        );
+        let schema = &documented["content"]["application/json"]["schema"];
+        if let Err(e) = self.matches(schema, &content) {
+            panic!(
+                "{} {} answered {} with {} which doesn't match the document: {}",
+                method, path, status, content, e
+            );
+        }
+        (status, content)
+    }
+
+    async fn get(&mut self, template: &str, path: &str) -> (u16, Value) {
+        self.call(Method::GET, template, path, None).await
+    }
+
+    async fn post(&mut self, template: &str, body: Option<Value>) -> (u16, Value) {
+        self.call(Method::POST, template, template, body).await
+    }
+
+    /// Operations of the document that weren't called
+    fn uncovered(&self) -> Vec<(String, String)> {
+        let mut uncovered = vec![];
+        for (path, item) in self.doc["paths"].as_object().unwrap() {
+            for method in item.as_object().unwrap().keys() {
+                let operation = (method.clone(), path.clone());
+                if !self.covered.contains(&operation) {
+                    uncovered.push(operation);
+                }
+            }
+        }
+        uncovered
+    }
+}
+
+#[tokio::test]
+async fn openapi_document_describes_every_route() {
+    let (mut api, mut admin) = Contract::start().await;
+    assert_eq!(api.doc["info"]["title"], "Octopus");
+    assert!(api.doc["openapi"].as_str().unwrap().starts_with("3."));
+    assert_eq!(admin.doc["info"]["title"], "Octopus operations");
+
+    // Accounts
+    for signer in ["ALICE", "BOB", "CAROL"] {
+        let open = json!({ "signer": signer, "owner": "contract" });
+        assert_eq!(api.post("/v1/account/open", Some(open)).await.0, 200);
+    }
+    let open = json!({ "signer": "ALICE", "owner": "contract" });
+    assert_eq!(api.post("/v1/account/open", Some(open)).await.0, 409);
+    for signer in ["ALICE", "BOB"] {
+        let deposit = json!({ "signer": signer, "amount": "100.00" });
+        assert_eq!(api.post("/v1/account/deposit", Some(deposit)).await.0, 200);
+    }
+    let deposit = json!({ "signer": "ALICE", "amount": "all of it" });
+    let (status, error) = api.post("/v1/account/deposit", Some(deposit)).await;
+    assert_eq!(status, 400);
+    assert_eq!(error["error"], "INVALID_BODY");
+    assert_eq!(error["details"][0]["field"], "amount");
+    let deposit = json!({ "signer": "ALICE" });
+    let (status, error) = api.post("/v1/account/deposit", Some(deposit)).await;
+    assert_eq!(status, 400);
+    assert_eq!(error["details"][0]["field"], "amount");
+    assert_eq!(error["details"][0]["message"], "missing field `amount`");
+    let send = json!({ "sender": "ALICE", "recipient": "BOB", "amount": "1.00" });
+    assert_eq!(api.post("/v1/account/send", Some(send)).await.0, 200);
+    let withdraw = json!({ "signer": "BOB", "amount": "0.50" });
+    assert_eq!(
+        api.post("/v1/account/withdraw", Some(withdraw)).await.0,
+        200
+    );
+    let withdraw = json!({ "signer": "BOB", "amount": "10000.00" });
+    let (status, error) = api.post("/v1/account/withdraw", Some(withdraw)).await;
+    assert_eq!(status, 422);
+    assert_eq!(error["error"], "ACCOUNT_UNDER_FUNDED");
+
+    let carol = json!({ "signer": "CAROL" });
+    assert_eq!(
+        admin
+            .post("/v1/account/freeze", Some(carol.clone()))
+            .await
+            .0,
+        200
+    );
+    assert_eq!(
+        admin
+            .post("/v1/account/unfreeze", Some(carol.clone()))
+            .await
+            .0,
+        200
+    );
+    assert_eq!(
+        admin.post("/v1/account/close", Some(carol.clone())).await.0,
+        200
+    );
+    let (status, error) = admin.post("/v1/account/freeze", Some(carol)).await;
+    assert_eq!(status, 410);
+    assert_eq!(error["error"], "ACCOUNT_CLOSED");
+
+    let (status, view) = api.get("/v1/account/{signer}", "/v1/account/BOB").await;
+    assert_eq!(status, 200);
+    assert_eq!(view["available"], "100.50");
+    assert_eq!(
+        api.get("/v1/account/{signer}", "/v1/account/NOBODY")
+            .await
+            .0,
+        404
+    );
+    let (status, statement) = api
+        .get(
+            "/v1/account/{signer}/transactions",
+            "/v1/account/ALICE/transactions?kind=withdraw",
+        )
+        .await;
+    assert_eq!(status, 200);
+    assert_eq!(statement["total"], 1);
+    let (status, error) = api
+        .get(
+            "/v1/account/{signer}/transactions",
+            "/v1/account/ALICE/transactions?kind=gift",
+        )
+        .await;
+    assert_eq!(status, 400);
+    assert_eq!(error["error"], "INVALID_QUERY");
+
+    // Orders
+    let ask = json!({ "price": "1.00", "amount": 5, "side": "Sell", "signer": "ALICE" });
+    let (status, receipt) = api.post("/v1/order", Some(ask)).await;
+    assert_eq!(status, 200);
+    let bid = json!({ "price": "1.00", "amount": 2, "side": "Buy", "signer": "BOB" });
+    let (status, matched) = api.post("/v1/order", Some(bid)).await;
+    assert_eq!(status, 200);
+    assert_eq!(matched["matches"][0]["ordinal"], receipt["ordinal"]);
+    let bid = json!({ "price": "1.005", "amount": 2, "side": "Buy", "signer": "BOB" });
+    let (status, error) = api.post("/v1/order", Some(bid)).await;
+    assert_eq!(status, 400);
+    assert_eq!(error["details"][0]["field"], "price");
+
+    let cancel = json!({ "signer": "ALICE", "ordinal": receipt["ordinal"] });
+    let (status, cancelled) = api.post("/v1/order/cancel", Some(cancel.clone())).await;
+    assert_eq!(status, 200);
+    assert_eq!(cancelled["remaining"], 3);
+    let (status, error) = api.post("/v1/order/cancel", Some(cancel)).await;
+    assert_eq!(status, 404);
+    assert_eq!(error["error"], "ORDER_NOT_FOUND");
+
+    // Market data and operations
+    for path in [
+        "/v1/order/history",
+        "/v1/orderbook",
+        "/v1/market",
+        "/v1/market/fees",
+        "/v1/market/state",
+        "/v1/market/auction",
+        "/v1/ledger/trial-balance",
+    ] {
+        assert_eq!(api.get(path, path).await.0, 200);
+    }
+    let uncross = json!({ "next": "Open" });
+    assert_eq!(
+        admin
+            .post("/v1/market/auction/uncross", Some(uncross.clone()))
+            .await
+            .0,
+        409
+    );
+    assert_eq!(admin.post("/v1/market/auction/start", None).await.0, 200);
+    assert_eq!(
+        admin
+            .post("/v1/market/auction/uncross", Some(uncross))
+            .await
+            .0,
+        200
+    );
+    let (status, state) = admin.post("/v1/market/halt", None).await;
+    assert_eq!((status, state), (200, json!("Halted")));
+    let bid = json!({ "price": "1.00", "amount": 1, "side": "Buy", "signer": "BOB" });
+    let (status, error) = api.post("/v1/order", Some(bid)).await;
+    assert_eq!(status, 409);
+    assert_eq!(error["error"], "MARKET_HALTED");
+    // Only operators may change the trading phase
+    let operator = admin.operator.take();
+    let (status, error) = admin.post("/v1/market/resume", None).await;
+    assert_eq!(status, 401);
+    assert_eq!(error["error"], "UNAUTHORIZED");
+    admin.operator = operator;
+    assert_eq!(admin.post("/v1/market/resume", None).await.0, 200);
+
+    assert_eq!(api.uncovered(), vec![]);
+    assert_eq!(admin.uncovered(), vec![]);
+}
+
+#[tokio::test]
+async fn openapi_routes_are_versioned() {
+    let (api, admin) = Contract::start().await;
+    for doc in [&api.doc, &admin.doc] {
+        for path in doc["paths"].as_object().unwrap().keys() {
+            assert!(path.starts_with("/v1/"), "{} isn't versioned", path);
+        }
+    }
+
+    let unversioned = api
+        .client
+        .get(format!("{}/orderbook", api.base))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(unversioned.status().as_u16(), 404);
+}
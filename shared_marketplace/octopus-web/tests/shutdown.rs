@@ -0,0 +1,253 @@
+//! Terminates a running `octopus-web` while clients are trading on it
+
+use std::{
+    collections::BTreeSet,
+    fs::{self, File},
+    io::BufReader,
+    net::{SocketAddr, TcpListener},
+    path::Path,
+    process::{Child, Command, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use octopus_web::{
+    audit::{self, AuditRecord},
+    grpc::proto::{octopus_client::OctopusClient, MarketDataRequest},
+    journal::read_journal,
+    pipeline::Command as PipelineCommand,
+    replay::restore,
+    trading_platform::TradingPlatform,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::time;
+use tonic::Code;
+
+/// A port nothing listens on right now
+fn free_port() -> SocketAddr {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+}
+
+fn start(dir: &Path, http: SocketAddr, grpc: SocketAddr) -> Child {
+    Command::new(env!("CARGO_BIN_EXE_octopus-web"))
+        .args(["--http-addr", &http.to_string()])
+        .args(["--grpc-addr", &grpc.to_string()])
+        .args(["--data-dir", dir.to_str().unwrap()])
+        .env_remove("OCTOPUS_CONFIG")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap()
+}
+
+async fn wait_until_serving(client: &reqwest::Client, base: &str) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while client
+        .get(format!("{}/v1/orderbook", base))
+        .send()
+        .await
+        .is_err()
+    {
+        assert!(Instant::now() < deadline, "the server didn't start");
+        time::sleep(Duration::from_millis(20)).await;
+    }
+}
+
+/// What a trader saw of the orders it sent
+#[derive(Default)]
+struct Seen {
+    /// Ordinals of accepted orders
+    accepted: Vec<u64>,
+    rejected: usize,
+    /// Orders refused with 503 or by a closed connection
+    refused: usize,
+}
+
+/// Places orders until the server refuses them, or until `running` is unset
+async fn trade(
+    base: String,
+    signer: &'static str,
+    side: &'static str,
+    running: Arc<AtomicBool>,
+) -> Seen {
+    let client = reqwest::Client::new();
+    let mut seen = Seen::default();
+    let mut price = 0;
+    while running.load(Ordering::Relaxed) {
+        price = (price + 1) % 10;
+        let order = json!({
+            "price": format!("1.{}0", price),
+            "amount": 1,
+            "side": side,
+            "signer": signer,
+        });
+        let response = client
+            .post(format!("{}/v1/order", base))
+            .json(&order)
+            .send()
+            .await;
+        match response.map(|r| (r.status().as_u16(), r)) {
+            Ok((200, response)) => {
+                let receipt: Value = response.json().await.unwrap();
+                seen.accepted.push(receipt["ordinal"].as_u64().unwrap());
+            }
+            Ok((422, _)) => seen.rejected += 1,
+            Ok((503, _)) | Err(_) => {
+                seen.refused += 1;
+                return seen;
+            }
+            Ok((status, _)) => panic!("unexpected status {}", status),
+        }
+    }
+    seen
+}
+
+#[derive(Deserialize)]
+struct Line {
+    record: AuditRecord,
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn sigterm_drains_commands_and_flushes_logs() {
+    let dir = std::env::temp_dir().join(format!("octopus-shutdown-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (http, grpc) = (free_port(), free_port());
+    let mut server = start(&dir, http, grpc);
+    let base = format!("http://{}", http);
+    let client = reqwest::Client::new();
+    wait_until_serving(&client, &base).await;
+
+    for signer in ["ALICE", "BOB"] {
+        let open = json!({ "signer": signer, "owner": "shutdown" });
+        let deposit = json!({ "signer": signer, "amount": "10000000.00" });
+        for (path, body) in [("open", open), ("deposit", deposit)] {
+            let response = client
+                .post(format!("{}/v1/account/{}", base, path))
+                .json(&body)
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status().as_u16(), 200);
+        }
+    }
+    let mut market_data = OctopusClient::connect(format!("http://{}", grpc))
+        .await
+        .unwrap()
+        .stream_market_data(MarketDataRequest { depth: 1 })
+        .await
+        .unwrap()
+        .into_inner();
+
+    let running = Arc::new(AtomicBool::new(true));
+    let traders: Vec<_> = (0..8)
+        .map(|i| {
+            let (signer, side) = if i % 2 == 0 {
+                ("ALICE", "Sell")
+            } else {
+                ("BOB", "Buy")
+            };
+            tokio::spawn(trade(base.clone(), signer, side, Arc::clone(&running)))
+        })
+        .collect();
+    time::sleep(Duration::from_millis(300)).await;
+    let killed = Command::new("kill")
+        .args(["-TERM", &server.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(killed.success());
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let status = loop {
+        if let Some(status) = server.try_wait().unwrap() {
+            break status;
+        }
+        assert!(Instant::now() < deadline, "the server didn't exit");
+        time::sleep(Duration::from_millis(20)).await;
+    };
+    assert!(status.success(), "{}", status);
+    running.store(false, Ordering::Relaxed);
+
+    let mut accepted = BTreeSet::new();
+    let mut answered = 0;
+    for trader in traders {
+        let seen = trader.await.unwrap();
+        answered += seen.accepted.len() + seen.rejected;
+        accepted.extend(seen.accepted);
+    }
+    assert!(!accepted.is_empty());
+
+    // Subscribers are told the stream ended instead of waiting forever
+    loop {
+        match time::timeout(Duration::from_secs(5), market_data.message()).await {
+            Ok(Ok(Some(_))) => continue,
+            Ok(Err(status)) => {
+                assert_eq!(status.code(), Code::Unavailable);
+                break;
+            }
+            other => panic!("expected the stream to end as unavailable, got {:?}", other),
+        }
+    }
+
+    // Every answered order is in both logs, and nothing else is
+    let audit = BufReader::new(File::open(dir.join("audit.jsonl")).unwrap());
+    audit::verify(audit).unwrap();
+    let audit = fs::read_to_string(dir.join("audit.jsonl")).unwrap();
+    let orders: Vec<AuditRecord> = audit
+        .lines()
+        .map(|line| serde_json::from_str::<Line>(line).unwrap().record)
+        .filter(|record| matches!(record.command, PipelineCommand::Order(_)))
+        .collect();
+    assert_eq!(orders.len(), answered);
+    let audited: BTreeSet<u64> = orders.iter().filter_map(|record| record.ordinal).collect();
+    assert_eq!(audited, accepted);
+
+    let journal = BufReader::new(File::open(dir.join("journal.jsonl")).unwrap());
+    let entries = read_journal(journal).unwrap();
+    let journaled = entries
+        .iter()
+        .filter(|entry| matches!(entry.command, PipelineCommand::Order(_)))
+        .count();
+    assert_eq!(journaled, answered);
+
+    // A restarted server replays the journal and continues its sequence
+    let last = entries.last().unwrap().sequence;
+    let expected = restore(TradingPlatform::new(), entries)
+        .account("ALICE")
+        .unwrap();
+    let mut server = start(&dir, http, grpc);
+    wait_until_serving(&client, &base).await;
+    let account: Value = client
+        .get(format!("{}/v1/account/ALICE", base))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(account, serde_json::to_value(expected).unwrap());
+    let response = client
+        .post(format!("{}/v1/account/deposit", base))
+        .json(&json!({ "signer": "ALICE", "amount": "0.01" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let killed = Command::new("kill")
+        .args(["-TERM", &server.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(killed.success());
+    assert!(server.wait().unwrap().success());
+    let journal = BufReader::new(File::open(dir.join("journal.jsonl")).unwrap());
+    let entries = read_journal(journal).unwrap();
+    assert!(entries.windows(2).all(|w| w[0].sequence < w[1].sequence));
+    assert!(entries.last().unwrap().sequence > last);
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,245 @@
+//! Serves the HTTP API over TLS with certificates issued by a throwaway CA
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
+
+use octopus_web::{
+    api::{self, Operator},
+    config::TlsConfig,
+    pipeline::{Pipeline, DEFAULT_QUEUE_CAPACITY},
+    trading_platform::TradingPlatform,
+};
+use rcgen::{
+    BasicConstraints, Certificate, CertificateParams, ExtendedKeyUsagePurpose, IsCa, KeyPair,
+};
+use reqwest::Identity;
+
+struct Authority {
+    certificate: Certificate,
+    key: KeyPair,
+}
+
+impl Authority {
+    fn new() -> Authority {
+        let key = KeyPair::generate().unwrap();
+        let mut params = CertificateParams::new(Vec::<String>::new()).unwrap();
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let certificate = params.self_signed(&key).unwrap();
+        Authority { certificate, key }
+    }
+
+    /// PEM certificate and key for `localhost`
+    fn issue(&self, usage: ExtendedKeyUsagePurpose) -> (String, String) {
+        let key = KeyPair::generate().unwrap();
+        let mut params = CertificateParams::new(vec!["localhost".to_string()]).unwrap();
+        params.extended_key_usages = vec![usage];
+        let certificate = params
+            .signed_by(&key, &self.certificate, &self.key)
+            .unwrap();
+        (certificate.pem(), key.serialize_pem())
+    }
+}
+
+fn write(dir: &Path, name: &str, contents: &str) -> PathBuf {
+    let path = dir.join(name);
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+/// Serves a fresh platform on an ephemeral port
+fn serve(tls: &TlsConfig) -> SocketAddr {
+    let pipeline = Pipeline::spawn(TradingPlatform::new(), DEFAULT_QUEUE_CAPACITY);
+    let (address, server) = api::serve(
+        pipeline,
+        SocketAddr::from(([127, 0, 0, 1], 0)),
+        tls,
+        std::future::pending(),
+    )
+    .unwrap();
+    tokio::spawn(server);
+    address
+}
+
+async fn order_book(
+    address: SocketAddr,
+    ca: Option<&str>,
+    identity: Option<(&str, &str)>,
+) -> reqwest::Result<u16> {
+    let mut builder = reqwest::Client::builder()
+        .use_rustls_tls()
+        .resolve("localhost", address);
+    if let Some(ca) = ca {
+        builder = builder.add_root_certificate(reqwest::Certificate::from_pem(ca.as_bytes())?);
+    }
+    if let Some((cert, key)) = identity {
+        builder = builder.identity(Identity::from_pem(format!("{}{}", cert, key).as_bytes())?);
+    }
+    let response = builder
+        .build()?
+        .get(format!("https://localhost:{}/v1/orderbook", address.port()))
+        .send()
+        .await?;
+    Ok(response.status().as_u16())
+}
+
+#[tokio::test]
+async fn https_needs_the_certificate_to_be_trusted() {
+    let dir = std::env::temp_dir().join(format!("octopus-tls-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let authority = Authority::new();
+    let ca = authority.certificate.pem();
+    let (cert, key) = authority.issue(ExtendedKeyUsagePurpose::ServerAuth);
+    let address = serve(&TlsConfig {
+        cert: Some(write(&dir, "server.pem", &cert)),
+        key: Some(write(&dir, "server.key", &key)),
+        client_ca: None,
+    });
+
+    assert_eq!(order_book(address, Some(&ca), None).await.unwrap(), 200);
+    // The CA isn't one of the built-in roots
+    assert!(order_book(address, None, None).await.is_err());
+    // Nothing is served in plaintext
+    assert!(reqwest::get(format!("http://{}/v1/orderbook", address))
+        .await
+        .is_err());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn mutual_tls_needs_a_client_certificate_of_the_client_ca() {
+    let dir = std::env::temp_dir().join(format!("octopus-mtls-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let servers = Authority::new();
+    let clients = Authority::new();
+    let (cert, key) = servers.issue(ExtendedKeyUsagePurpose::ServerAuth);
+    let address = serve(&TlsConfig {
+        cert: Some(write(&dir, "server.pem", &cert)),
+        key: Some(write(&dir, "server.key", &key)),
+        client_ca: Some(write(&dir, "clients.pem", &clients.certificate.pem())),
+    });
+    let ca = servers.certificate.pem();
+
+    let (client_cert, client_key) = clients.issue(ExtendedKeyUsagePurpose::ClientAuth);
+    assert_eq!(
+        order_book(address, Some(&ca), Some((&client_cert, &client_key)))
+            .await
+            .unwrap(),
+        200
+    );
+    assert!(order_book(address, Some(&ca), None).await.is_err());
+    // Issued by a CA the server doesn't trust for clients
+    let (stranger_cert, stranger_key) = servers.issue(ExtendedKeyUsagePurpose::ClientAuth);
+    assert!(
+        order_book(address, Some(&ca), Some((&stranger_cert, &stranger_key)))
+            .await
+            .is_err()
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn unusable_certificates_are_reported() {
+    let dir = std::env::temp_dir().join(format!("octopus-badtls-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let pipeline = Pipeline::spawn(TradingPlatform::new(), DEFAULT_QUEUE_CAPACITY);
+    let tls = TlsConfig {
+        cert: Some(write(&dir, "server.pem", "not a certificate")),
+        key: Some(write(&dir, "server.key", "not a key")),
+        client_ca: None,
+    };
+    let error = api::serve(
+        pipeline,
+        SocketAddr::from(([127, 0, 0, 1], 0)),
+        &tls,
+        std::future::pending(),
+    )
+    .err()
+    .unwrap();
+    assert!(
+        error.starts_with("Couldn't serve HTTPS on 127.0.0.1:0: "),
+        "{}",
+        error
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn the_admin_interface_needs_a_client_certificate_and_an_operator() {
+    let dir = std::env::temp_dir().join(format!("octopus-admin-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let servers = Authority::new();
+    let clients = Authority::new();
+    let (cert, key) = servers.issue(ExtendedKeyUsagePurpose::ServerAuth);
+    let mut tls = TlsConfig {
+        cert: Some(write(&dir, "server.pem", &cert)),
+        key: Some(write(&dir, "server.key", &key)),
+        client_ca: None,
+    };
+    let operators = BTreeMap::from([(
+        "ops".to_string(),
+        Operator {
+            password: "0p3rate".to_string(),
+        },
+    )]);
+    let serve = |tls: &TlsConfig| {
+        let pipeline = Pipeline::spawn(TradingPlatform::new(), DEFAULT_QUEUE_CAPACITY);
+        api::serve_admin(
+            pipeline,
+            SocketAddr::from(([127, 0, 0, 1], 0)),
+            tls,
+            operators.clone(),
+            std::future::pending(),
+        )
+    };
+    assert_eq!(
+        serve(&tls).err().unwrap(),
+        "The admin interface requires tls.cert, tls.key and tls.client_ca"
+    );
+    tls.client_ca = Some(write(&dir, "clients.pem", &clients.certificate.pem()));
+    let (address, server) = serve(&tls).unwrap();
+    tokio::spawn(server);
+
+    let ca = reqwest::Certificate::from_pem(servers.certificate.pem().as_bytes()).unwrap();
+    let (client_cert, client_key) = clients.issue(ExtendedKeyUsagePurpose::ClientAuth);
+    let identity = Identity::from_pem(format!("{}{}", client_cert, client_key).as_bytes()).unwrap();
+    let halt = |identity: Option<&Identity>, password: &str| {
+        let mut builder = reqwest::Client::builder()
+            .use_rustls_tls()
+            .resolve("localhost", address)
+            .add_root_certificate(ca.clone());
+        if let Some(identity) = identity {
+            builder = builder.identity(identity.clone());
+        }
+        builder
+            .build()
+            .unwrap()
+            .post(format!(
+                "https://localhost:{}/v1/market/halt",
+                address.port()
+            ))
+            .basic_auth("ops", Some(password))
+            .send()
+    };
+    assert!(halt(None, "0p3rate").await.is_err());
+    assert_eq!(
+        halt(Some(&identity), "wrong")
+            .await
+            .unwrap()
+            .status()
+            .as_u16(),
+        401
+    );
+    assert_eq!(
+        halt(Some(&identity), "0p3rate")
+            .await
+            .unwrap()
+            .status()
+            .as_u16(),
+        200
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,295 @@
+//! Drives the binary order entry listener over TCP
+
+use std::{collections::BTreeMap, net::SocketAddr, time::Duration};
+
+use octopus_common::{
+    money::{Price, Quantity},
+    types::Side,
+    wire::{RejectCode, Request, Response},
+};
+use octopus_web::{
+    gateway::Counterparty,
+    pipeline::{Command, Pipeline, DEFAULT_QUEUE_CAPACITY},
+    trading_platform::TradingPlatform,
+    wire,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    time,
+};
+
+struct Client {
+    stream: TcpStream,
+    buffer: Vec<u8>,
+}
+
+impl Client {
+    async fn connect(address: SocketAddr) -> Self {
+        Client {
+            stream: TcpStream::connect(address).await.unwrap(),
+            buffer: vec![],
+        }
+    }
+
+    /// Connects and logs on as `user`
+    async fn logon(address: SocketAddr, user: &str) -> Self {
+        let mut client = Client::connect(address).await;
+        client.send(logon(0, user, &password(user))).await;
+        assert_eq!(
+            client.recv().await,
+            Response::Ack {
+                request_id: 0,
+                ordinal: 0
+            }
+        );
+        client
+    }
+
+    async fn send(&mut self, request: Request) {
+        let mut frame = vec![];
+        request.encode(&mut frame).unwrap();
+        self.stream.write_all(&frame).await.unwrap();
+    }
+
+    async fn recv(&mut self) -> Response {
+        loop {
+            if let Some((response, len)) = Response::decode(&self.buffer).unwrap() {
+                self.buffer.drain(..len);
+                return response;
+            }
+            let mut chunk = [0u8; 1024];
+            let read = time::timeout(Duration::from_secs(5), self.stream.read(&mut chunk))
+                .await
+                .expect("the server should answer")
+                .unwrap();
+            assert!(read > 0, "the server closed the connection");
+            self.buffer.extend_from_slice(&chunk[..read]);
+        }
+    }
+}
+
+fn logon(request_id: u64, user: &str, password: &str) -> Request {
+    Request::Logon {
+        request_id,
+        user: user.to_string(),
+        password: password.to_string(),
+    }
+}
+
+/// The password of each user
+fn password(user: &str) -> String {
+    format!("{}-secret", user.to_lowercase())
+}
+
+fn order(request_id: u64, side: Side, price: u64, quantity: u64, signer: &str) -> Request {
+    Request::NewOrder {
+        request_id,
+        side,
+        price: Price(price),
+        quantity: Quantity(quantity),
+        signer: signer.to_string(),
+    }
+}
+
+async fn start() -> SocketAddr {
+    let pipeline = Pipeline::spawn(TradingPlatform::new(), DEFAULT_QUEUE_CAPACITY);
+    for signer in ["ALICE", "BOB"] {
+        let open = Command::OpenAccount {
+            signer: signer.to_string(),
+            owner: "wire".to_string(),
+        };
+        let deposit = Command::Deposit {
+            signer: signer.to_string(),
+            amount: 10_000,
+        };
+        pipeline.execute(open).await.unwrap();
+        pipeline.execute(deposit).await.unwrap();
+    }
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    // BOB may also trade for an account that doesn't exist
+    let counterparty = |user: &str, accounts: &[&str]| {
+        let counterparty = Counterparty {
+            password: password(user),
+            accounts: accounts.iter().map(|a| a.to_string()).collect(),
+        };
+        (user.to_string(), counterparty)
+    };
+    let counterparties =
+        BTreeMap::from([counterparty("ALICE", &[]), counterparty("BOB", &["NOBODY"])]);
+    tokio::spawn(wire::serve(pipeline, listener, counterparties));
+    address
+}
+
+#[tokio::test]
+async fn wire_orders_are_acknowledged_and_filled() {
+    let address = start().await;
+    let mut alice = Client::logon(address, "ALICE").await;
+    let mut bob = Client::logon(address, "BOB").await;
+
+    alice.send(order(1, Side::Sell, 100, 5, "ALICE")).await;
+    assert_eq!(
+        alice.recv().await,
+        Response::Ack {
+            request_id: 1,
+            ordinal: 1
+        }
+    );
+
+    bob.send(order(9, Side::Buy, 100, 2, "BOB")).await;
+    assert_eq!(
+        bob.recv().await,
+        Response::Ack {
+            request_id: 9,
+            ordinal: 2
+        }
+    );
+    let fill = Response::Fill {
+        ordinal: 2,
+        price: Price(100),
+        quantity: Quantity(2),
+        leaves: Quantity(0),
+    };
+    assert_eq!(bob.recv().await, fill);
+    // The resting order's fill reaches its connection as well
+    assert_eq!(
+        alice.recv().await,
+        Response::Fill {
+            ordinal: 1,
+            price: Price(100),
+            quantity: Quantity(2),
+            leaves: Quantity(3),
+        }
+    );
+
+    // Requests may be sent without waiting for the answers
+    alice
+        .send(Request::Cancel {
+            request_id: 2,
+            ordinal: 1,
+            signer: "ALICE".to_string(),
+        })
+        .await;
+    alice
+        .send(Request::Cancel {
+            request_id: 3,
+            ordinal: 1,
+            signer: "ALICE".to_string(),
+        })
+        .await;
+    assert_eq!(
+        alice.recv().await,
+        Response::Ack {
+            request_id: 2,
+            ordinal: 1
+        }
+    );
+    assert!(matches!(
+        alice.recv().await,
+        Response::Reject {
+            request_id: 3,
+            code: RejectCode::OrderNotFound,
+            ..
+        }
+    ));
+}
+
+#[tokio::test]
+async fn wire_invalid_requests_are_rejected() {
+    let address = start().await;
+    let mut bob = Client::logon(address, "BOB").await;
+
+    bob.send(order(1, Side::Buy, 100, 1_000, "BOB")).await;
+    assert!(matches!(
+        bob.recv().await,
+        Response::Reject {
+            request_id: 1,
+            code: RejectCode::UnderFunded,
+            ..
+        }
+    ));
+    bob.send(order(2, Side::Buy, 100, 1, "NOBODY")).await;
+    assert!(matches!(
+        bob.recv().await,
+        Response::Reject {
+            request_id: 2,
+            code: RejectCode::Account,
+            ..
+        }
+    ));
+
+    // A frame that isn't a request is skipped and the connection stays usable
+    bob.stream.write_all(&[0, 3, 0x7f, 1, 2]).await.unwrap();
+    assert!(matches!(
+        bob.recv().await,
+        Response::Reject {
+            request_id: 0,
+            code: RejectCode::Malformed,
+            ..
+        }
+    ));
+    bob.send(order(3, Side::Buy, 100, 1, "BOB")).await;
+    assert_eq!(
+        bob.recv().await,
+        Response::Ack {
+            request_id: 3,
+            ordinal: 1
+        }
+    );
+}
+
+#[tokio::test]
+async fn wire_connections_only_trade_for_their_accounts_after_logging_on() {
+    let address = start().await;
+    let mut client = Client::connect(address).await;
+    let unauthorized = |request_id| {
+        move |response| {
+            matches!(
+                response,
+                Response::Reject {
+                    request_id: id,
+                    code: RejectCode::Unauthorized,
+                    ..
+                } if id == request_id
+            )
+        }
+    };
+
+    client.send(order(1, Side::Buy, 100, 1, "ALICE")).await;
+    assert!(unauthorized(1)(client.recv().await));
+    client.send(logon(2, "MALLORY", "mallory-secret")).await;
+    assert!(unauthorized(2)(client.recv().await));
+    client.send(logon(3, "ALICE", "bob-secret")).await;
+    assert!(unauthorized(3)(client.recv().await));
+
+    client.send(logon(4, "ALICE", "alice-secret")).await;
+    assert_eq!(
+        client.recv().await,
+        Response::Ack {
+            request_id: 4,
+            ordinal: 0
+        }
+    );
+    // The connection stays bound to ALICE
+    client.send(logon(5, "BOB", "bob-secret")).await;
+    assert!(unauthorized(5)(client.recv().await));
+    client.send(order(6, Side::Buy, 100, 1, "BOB")).await;
+    assert!(unauthorized(6)(client.recv().await));
+    client
+        .send(Request::Cancel {
+            request_id: 7,
+            ordinal: 1,
+            signer: "BOB".to_string(),
+        })
+        .await;
+    assert!(unauthorized(7)(client.recv().await));
+    client.send(order(8, Side::Buy, 100, 1, "ALICE")).await;
+    assert_eq!(
+        client.recv().await,
+        Response::Ack {
+            request_id: 8,
+            ordinal: 1
+        }
+    );
+}